@@ -55,6 +55,9 @@ pub struct Atomic {
     #[serde(default)]
     /// Constant operand
     constant: Option<Const>,
+    /// What the KPI tells the participants (shown in their reports)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
 }
 
 impl Atomic {
@@ -67,6 +70,7 @@ impl Atomic {
             op: AdditionConst,
             var: Vec::new(),
             constant: Some(0_f64),
+            description: None,
         }
     }
 
@@ -100,6 +104,22 @@ impl Atomic {
         &self.constant
     }
 
+    /// Returns the description of the KPI (if any)
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Rename the atomic and its dependencies (namespacing and macro expansion)
+    pub(crate) fn map_names<F: Fn(&str) -> VariableID>(&mut self, rename: F) {
+        self.name = rename(&self.name);
+        self.var = self.var.iter().map(|var| rename(var)).collect();
+    }
+
+    /// Set whether the atomic is a kpi
+    pub(crate) fn set_kpi(&mut self, is_kpi: bool) {
+        self.is_kpi = is_kpi;
+    }
+
     // For parsing
     pub fn new(
         name: VariableID,
@@ -114,6 +134,7 @@ impl Atomic {
             op,
             var,
             constant,
+            description: None,
         }
     }
 
@@ -134,12 +155,7 @@ impl Atomic {
         let input = self.get_resolved_for_op(resolved, false)?;
         let input_op = self.get_map_op();
 
-        let _input_cpy: OperationInput;
-
-        #[cfg(not(feature = "evaluation"))]
-        {
-            _input_cpy = input.clone();
-        }
+        log::trace!("[Atomic: {}] [Type: {}]", self.name(), self.op.to_string());
 
         // Exhaustive list of operations and their constraints that mitigate panics on runtime
         // When returning, the result is a variable
@@ -264,17 +280,6 @@ impl Atomic {
             },
         };
 
-        #[cfg(not(feature = "evaluation"))]
-        {
-            log::info!(
-                "[Atomic: {}] [Type: {}] [Input: {:?}] [Output: {:?}]",
-                self.name(),
-                self.op.to_string(),
-                _input_cpy,
-                var_res
-            );
-        }
-
         // Insert into resolved
         resolved.insert(self.name().to_string(), var_res)?;
 
@@ -299,18 +304,15 @@ impl Atomic {
             // NARY OPERATIONS
             //
             Addition | Subtraction | Multiplication | Minima | Maxima => {
-                #[cfg(not(feature = "evaluation"))]
-                {
-                    // Sanity checks: at least one var and no const required
-                    if self.var.len() == 0 {
-                        return Err(BenchmarkingError::from((self.clone(), "has no variables")));
-                    }
-                    if let Some(constant) = self.constant {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("has unused constant {:?}", constant),
-                        )));
-                    }
+                // Sanity checks: at least one var and no const required
+                if self.var.len() == 0 {
+                    return Err(BenchmarkingError::from((self.clone(), "has no variables")));
+                }
+                if let Some(constant) = self.constant {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("has unused constant {:?}", constant),
+                    )));
                 }
 
                 // Extract resolved nary values for further computation
@@ -326,22 +328,19 @@ impl Atomic {
             // BINARY OPERATIONS WITH 2 VARIABLES
             //
             Division | Power => {
-                #[cfg(not(feature = "evaluation"))]
-                {
-                    // need exactly 2 operands
-                    let len = self.var.len();
-                    if len != 2 {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("binary op expected 2 vars but received {}", len),
-                        )));
-                    }
-                    if let Some(constant) = self.constant {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("has unused constant {:?}", constant),
-                        )));
-                    }
+                // need exactly 2 operands
+                let len = self.var.len();
+                if len != 2 {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("binary op expected 2 vars but received {}", len),
+                    )));
+                }
+                if let Some(constant) = self.constant {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("has unused constant {:?}", constant),
+                    )));
                 }
 
                 // Extract resolved binary values for further computation
@@ -351,19 +350,16 @@ impl Atomic {
                     resolved_values.get(var_names.1)?,
                 );
 
-                #[cfg(not(feature = "evaluation"))]
+                // Verify against 0-divisions
+                if operands.1.vector().iter().find(|&&x| x == 0_f64).is_some()
+                    && self.op == Division
                 {
-                    // Verify against 0-divisions
-                    if operands.1.vector().iter().find(|&&x| x == 0_f64).is_some()
-                        && self.op == Division
-                    {
-                        //operands.1 = Variable::new(vec![1.0]);
-                        //log::error!("0-Division for {}", var_names.1);
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("has 0-Division for value {}", var_names.1),
-                        )));
-                    }
+                    //operands.1 = Variable::new(vec![1.0]);
+                    //log::error!("0-Division for {}", var_names.1);
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("has 0-Division for value {}", var_names.1),
+                    )));
                 }
 
                 Ok(OperationInput::Binary(
@@ -377,25 +373,22 @@ impl Atomic {
             //
             AdditionConst | SubtractionVarConst | MultiplicationConst | DivisionVarConst
             | PowerConst | PowerBaseConst => {
-                #[cfg(not(feature = "evaluation"))]
-                {
-                    // sanity check: expect one var and one const
-                    let len = self.var.len();
-                    if len != 1 {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!(
-                                "binary op with constant expected 1 var but received {}",
-                                len
-                            ),
-                        )));
-                    }
-                    if self.constant.is_none() {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            "is binary op with constant but no constant is provided!",
-                        )));
-                    }
+                // sanity check: expect one var and one const
+                let len = self.var.len();
+                if len != 1 {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!(
+                            "binary op with constant expected 1 var but received {}",
+                            len
+                        ),
+                    )));
+                }
+                if self.constant.is_none() {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        "is binary op with constant but no constant is provided!",
+                    )));
                 }
 
                 // Extract computed values → The constant is always the second operand
@@ -403,17 +396,14 @@ impl Atomic {
                 let operand0 = resolved_values.get(var_name)?;
                 let operand1 = Variable::new(vec![self.constant.unwrap_or_default()]);
 
-                #[cfg(not(feature = "evaluation"))]
+                // Verify against 0-divisions
+                if operand1.vector().iter().find(|&&x| x == 0_f64).is_some()
+                    && (self.op == DivisionVarConst || self.op == SubtractionVarConst)
                 {
-                    // Verify against 0-divisions
-                    if operand1.vector().iter().find(|&&x| x == 0_f64).is_some()
-                        && (self.op == DivisionVarConst || self.op == SubtractionVarConst)
-                    {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            "has 0-Division for provided constant",
-                        )));
-                    }
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        "has 0-Division for provided constant",
+                    )));
                 }
 
                 Ok(OperationInput::Binary(operand0.clone(), operand1))
@@ -423,25 +413,22 @@ impl Atomic {
             // BINARY OPERATIONS WITH ONE VAR AND ONE CONSTANT ON FIRST POSITION
             //
             SubtractionConstVar | DivisionConstVar => {
-                #[cfg(not(feature = "evaluation"))]
-                {
-                    // sanity check: expect one var and one const
-                    let len = self.var.len();
-                    if len != 1 {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!(
-                                "binary op with constant expected 1 var but received {}",
-                                len
-                            ),
-                        )));
-                    }
-                    if self.constant.is_none() {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            "is binary op with constant but no constant is provided!",
-                        )));
-                    }
+                // sanity check: expect one var and one const
+                let len = self.var.len();
+                if len != 1 {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!(
+                            "binary op with constant expected 1 var but received {}",
+                            len
+                        ),
+                    )));
+                }
+                if self.constant.is_none() {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        "is binary op with constant but no constant is provided!",
+                    )));
                 }
 
                 // Extract computed values → The constant is always the second operand
@@ -450,22 +437,19 @@ impl Atomic {
                 let operand0 = resolved_values.get(var_name)?;
                 let operand1 = Variable::new(vec![self.constant.unwrap_or_default()]);
 
-                #[cfg(not(feature = "evaluation"))]
+                // Verify against 0-divisions
+                if operand0.vector().iter().find(|&&x| x == 0_f64).is_some()
+                    && (self.op == DivisionVarConst || self.op == SubtractionVarConst)
                 {
-                    // Verify against 0-divisions
-                    if operand0.vector().iter().find(|&&x| x == 0_f64).is_some()
-                        && (self.op == DivisionVarConst || self.op == SubtractionVarConst)
-                    {
-                        //operand1 = Variable::new(vec![1.0]);
-                        //log::error!("0-Division for {:?}", operand0);
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!(
-                                "has 0-Division for provided variable operand: {:?}",
-                                operand0
-                            ),
-                        )));
-                    }
+                    //operand1 = Variable::new(vec![1.0]);
+                    //log::error!("0-Division for {:?}", operand0);
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!(
+                            "has 0-Division for provided variable operand: {:?}",
+                            operand0
+                        ),
+                    )));
                 }
 
                 Ok(OperationInput::Binary(operand0.clone(), operand1))
@@ -475,22 +459,19 @@ impl Atomic {
             // UNARY OPERATION WITH NO CONSTANT
             //
             Squareroot | Absolute | AdditionOverN | MinimaOverN | MaximaOverN => {
-                #[cfg(not(feature = "evaluation"))]
-                {
-                    // sanity checks: one operand only
-                    let len = self.var.len().clone();
-                    if len != 1 {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("binary op expected 2 vars but received {}", len).as_str(),
-                        )));
-                    }
-                    if let Some(constant) = self.constant {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("has unused constant {:?}", constant),
-                        )));
-                    }
+                // sanity checks: one operand only
+                let len = self.var.len().clone();
+                if len != 1 {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("binary op expected 2 vars but received {}", len).as_str(),
+                    )));
+                }
+                if let Some(constant) = self.constant {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("has unused constant {:?}", constant),
+                    )));
                 }
 
                 // Extract resolved values
@@ -503,23 +484,20 @@ impl Atomic {
             // DEFINITION OF CONSTANTS
             //
             DefConst => {
-                #[cfg(not(feature = "evaluation"))]
-                {
-                    // sanity checks: no variable should exist
-                    let len = self.var.len().clone();
-                    if len != 0 {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("constant definition expected 0 vars but received {}", len)
-                                .as_str(),
-                        )));
-                    }
-                    if self.constant.is_none() {
-                        return Err(BenchmarkingError::from((
-                            self.clone(),
-                            format!("has no constant"),
-                        )));
-                    }
+                // sanity checks: no variable should exist
+                let len = self.var.len().clone();
+                if len != 0 {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("constant definition expected 0 vars but received {}", len)
+                            .as_str(),
+                    )));
+                }
+                if self.constant.is_none() {
+                    return Err(BenchmarkingError::from((
+                        self.clone(),
+                        format!("has no constant"),
+                    )));
                 }
 
                 // Extract resolved values
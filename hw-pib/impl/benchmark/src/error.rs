@@ -4,10 +4,13 @@
 //!     - Atomic errors: in case computation is incorrect (dimensionality or similar)
 //!     - Operation errors: In case computatoin fails on a specific operation type
 //!     - Algorithm: General error: In case Input parsing, algorithm parsing,.. fail
+//!     - Missing variables / cycles: Carry their details for the error code
 
 use std::error::Error;
 use std::fmt;
 
+use types::message::error::ErrorCode;
+
 use super::atomic::Atomic;
 use super::operation::OperationInput;
 
@@ -22,6 +25,29 @@ enum BenchmarkingErrorCause {
     Atomic(Atomic, String),
     Operation(OperationInput, String, OperationInput),
     Algorithm(String),
+    MissingVariables(Vec<String>),
+    Cycle(Vec<String>),
+}
+
+impl BenchmarkingError {
+    /// Input lacks the required `variables`
+    pub fn missing_variables(variables: Vec<String>) -> Self {
+        BenchmarkingError { cause: BenchmarkingErrorCause::MissingVariables(variables) }
+    }
+
+    /// Operations depend on each other, the first one is repeated at the end of the `cycle`
+    pub fn cycle(cycle: Vec<String>) -> Self {
+        BenchmarkingError { cause: BenchmarkingErrorCause::Cycle(cycle) }
+    }
+
+    /// Machine-readable code of the error
+    pub fn code(&self) -> ErrorCode {
+        match &self.cause {
+            BenchmarkingErrorCause::MissingVariables(variables) => ErrorCode::InputMissingVariables { variables: variables.clone() },
+            BenchmarkingErrorCause::Cycle(cycle) => ErrorCode::AlgorithmCycle { cycle: cycle.clone() },
+            _ => ErrorCode::InvalidAlgorithm,
+        }
+    }
 }
 
 impl fmt::Display for BenchmarkingError {
@@ -36,6 +62,12 @@ impl fmt::Display for BenchmarkingError {
             BenchmarkingErrorCause::Algorithm(reason) => {
                 write!(f, "Algorithm computation failed with reason: {}", reason)
             }
+            BenchmarkingErrorCause::MissingVariables(variables) => {
+                write!(f, "Missing input variables: {:?}", variables)
+            }
+            BenchmarkingErrorCause::Cycle(cycle) => {
+                write!(f, "Cyclic dependency of the operations: {}", cycle.join(" -> "))
+            }
         }
         
     }
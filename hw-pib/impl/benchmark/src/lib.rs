@@ -14,12 +14,16 @@
 //! 
 //! `run` performs the computation of [`Company`] 
 //! input data and returns all KPI variables.
+//! 
+//! Parsing and sorting are traced as `parse` and `topo_sort`
+//! spans (see [`tracing`]), the server measures them.
 
 pub mod atomic;
 pub mod variable;
 pub mod operation;
 pub mod resolved;
 pub mod error;
+pub mod library;
 
 use serde::{Deserialize, Serialize};
 use strum::Display;
@@ -27,8 +31,7 @@ use types::input::Input;
 use types::output::{OutputVariable, Output};
 use variable::Variable;
 use std::collections::HashSet;
-use std::{fs::OpenOptions, collections::HashMap};
-use std::io::BufReader;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use self::{
@@ -69,6 +72,12 @@ impl Default for AlgoHelper {
     }
 }
 
+/// Serialization format of a bundled algorithm
+#[derive(Serialize)]
+struct BundleFmt<'a> {
+    operations: &'a Vec<Atomic>,
+}
+
 #[derive(Clone, PartialEq, Display)]
 enum DepState {
     Unresolved,
@@ -78,38 +87,46 @@ enum DepState {
 
 impl Algorithm {
     /// Pareses the algorithm file
-    pub fn load(path: &PathBuf) -> Result<(Self, u128, u128), BenchmarkingError> {
-        let file = OpenOptions::new()
-            .read(true)
-            .open(path)
-            .expect("Could not open ATOMIC_FILE");
+    ///
+    /// Includes of other algorithm files and macro calls are resolved
+    /// (see [`library`]), such that only plain atomics remain.
+    pub fn load(path: &PathBuf) -> Result<Self, BenchmarkingError> {
+        Self::load_with(path, true)
+    }
 
-        #[cfg(feature="evaluation")]
-        let now = std::time::SystemTime::now();
+    /// Parses an algorithm file that must not include other files
+    ///
+    /// This is used for uploaded algorithms, which are bundled by the
+    /// analyst in advance (see [`Algorithm::bundle`]).
+    pub fn load_bundle(path: &PathBuf) -> Result<Self, BenchmarkingError> {
+        Self::load_with(path, false)
+    }
 
-        let buffer = BufReader::new(file);
-        let mut res: Self = serde_yaml::from_reader(buffer).map_err(|err| BenchmarkingError::from(format!("Could not parse algorithm: {}", err.to_string())))?;
+    /// Resolves the algorithm file with all includes and macros into one self-contained file
+    pub fn bundle(path: &PathBuf) -> Result<String, BenchmarkingError> {
+        let operations = library::resolve(path, true)?;
+        serde_yaml::to_string(&BundleFmt { operations: &operations })
+            .map_err(|err| BenchmarkingError::from(format!("Could not serialize algorithm: {}", err)))
+    }
 
-        #[cfg(feature="evaluation")]
-        let parse_time = now.elapsed().unwrap().as_nanos();
-        
-        #[cfg(not(feature="evaluation"))]
-        {
-            // Sanity check: is not empty and operations are unique
-            let mut unique_elems: std::collections::HashSet<String> = std::collections::HashSet::new();
-            let has_unique_elems = res.operations.iter().all(move |x| unique_elems.insert(x.name().to_string()));
-
-            if res.len() == 0 ||  !has_unique_elems {
-                return Err(
-                    BenchmarkingError::from(format!("The provided algorihm is malformed, because {}{}", 
-                    if !has_unique_elems {"it has duplicate definitions"} else {""}, 
-                    if res.len() == 0 {"it is empty"} else {""}))
-                );
-            }
+    /// Parses the algorithm file and builds the lookup tables and execution order
+    fn load_with(path: &PathBuf, allow_includes: bool) -> Result<Self, BenchmarkingError> {
+        let operations = tracing::info_span!("parse").in_scope(|| library::resolve(path, allow_includes))?;
+        let mut res = Algorithm { operations, required: HashSet::new(), algohelper: AlgoHelper::default() };
+
+        // Sanity check: is not empty and operations are unique
+        let mut unique_elems: std::collections::HashSet<String> = std::collections::HashSet::new();
+        let has_unique_elems = res.operations.iter().all(move |x| unique_elems.insert(x.name().to_string()));
+
+        if res.len() == 0 ||  !has_unique_elems {
+            return Err(
+                BenchmarkingError::from(format!("The provided algorihm is malformed, because {}{}", 
+                if !has_unique_elems {"it has duplicate definitions"} else {""}, 
+                if res.len() == 0 {"it is empty"} else {""}))
+            );
         }
 
-        #[cfg(feature="evaluation")]
-        let now2 = std::time::SystemTime::now();
+        let _topo_sort = tracing::info_span!("topo_sort").entered();
 
         // Create lookup tables and results
         for i in &res.operations {
@@ -144,18 +161,8 @@ impl Algorithm {
 
         // Overwrite operations: Now they are ordered by topological execution
         res.operations = res.topological_op_sort()?;
-        
-        #[cfg(feature="evaluation")]
-        {
-            let topo_time =  now2.elapsed().unwrap().as_nanos();
-            Ok((res, parse_time, topo_time))
-        }
-        
-
 
-
-        #[cfg(not(feature="evaluation"))]
-        Ok((res, 0, 0))
+        Ok(res)
     }
 
     /// Resolve dependencies and verify the computability of the algorithm
@@ -202,24 +209,31 @@ impl Algorithm {
         
         for i in self.operations.clone() {
             if topo.get(i.name()).unwrap().1 == DepState::Unresolved {
-                self.dfs_topo_sort_inner(topo, i.name(), topo_num)?;
+                self.dfs_topo_sort_inner(topo, i.name(), topo_num, &mut Vec::new())?;
             }
         }
         Ok(())
     }
 
     /// Handle topological sorting on one tree
-    fn dfs_topo_sort_inner(&self, topo: &mut HashMap<&str, (usize, DepState)>, curr_op: &str, topo_num: &mut usize) -> Result<(), BenchmarkingError> {
+    /// (`path` holds the operations in visit, i.e. the cycle on a dependency hell)
+    fn dfs_topo_sort_inner(&self, topo: &mut HashMap<&str, (usize, DepState)>, curr_op: &str, topo_num: &mut usize, path: &mut Vec<String>) -> Result<(), BenchmarkingError> {
     
         let tuple = topo.get_mut(curr_op).unwrap();
         tuple.1 = DepState::InVisit;
+        path.push(curr_op.to_string());
         log::debug!("children: {:?}", self.find_atomic_by_name(curr_op).unwrap().var_ids());
 
         for i in self.find_atomic_by_name(curr_op).unwrap().var_ids() {
             log::debug!("{}", i.clone());
             match topo.get(i.as_str()).unwrap().1 {
-                DepState::InVisit => { return Err(BenchmarkingError::from("Cyclic Dependency Hell detected - what kind of an analyst are you?".to_string())); }, // Cyclic dependency
-                DepState::Unresolved => { self.dfs_topo_sort_inner(topo, &i, topo_num)?; }, // Go one step deeper
+                DepState::InVisit => { // Cyclic dependency
+                    let start = path.iter().position(|op| op == i).unwrap_or_default();
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(i.clone());
+                    return Err(BenchmarkingError::cycle(cycle));
+                },
+                DepState::Unresolved => { self.dfs_topo_sort_inner(topo, &i, topo_num, path)?; }, // Go one step deeper
                 DepState::Resolved => {}, // Ok, dependency served
             }
 
@@ -229,6 +243,7 @@ impl Algorithm {
         topo.get_mut(curr_op).unwrap().0 = *topo_num;
         topo.get_mut(curr_op).unwrap().1 = DepState::Resolved;
         *topo_num += 1;
+        path.pop();
         
         Ok(())
     }
@@ -248,16 +263,23 @@ impl Algorithm {
         &self.required
     }
 
+    /// Returns the required input variables that are missing in `input` (sorted by name)
+    pub fn missing_input_vars(&self, input: &Input) -> Vec<String> {
+        let required: &HashSet<String> = self.required_input_atomics();
+        let mut missing_vars: Vec<String> = required.iter().filter(|&req_atom| !input.has_input_var(req_atom)).cloned().collect();
+        missing_vars.sort();
+        missing_vars
+    }
+
     // Verifies the input of a company and throws error in case of missing fields
-    pub fn verify_input(&self, input: Input) -> Result<(), BenchmarkingError> {
+    pub fn verify_input(&self, input: &Input) -> Result<(), BenchmarkingError> {
         
-        let required: &HashSet<String> = self.required_input_atomics();
-        let missing_vars: Vec<&String> = required.iter().filter(|&req_atom| !input.has_input_var(req_atom)).collect();
+        let missing_vars = self.missing_input_vars(input);
 
         // At least one variable is missing
         if missing_vars.len() != 0 {
             log::error!("Missing: {:?}", missing_vars);
-            Err(BenchmarkingError::from(format!("Missing input variables: {:?}", missing_vars)))
+            Err(BenchmarkingError::missing_variables(missing_vars))
         }else{
             Ok(())
         }
@@ -289,7 +311,7 @@ impl Algorithm {
     /// in sequential order and computes all formula values. Especially are
     /// "resolved values" computed, that are not KPIs. They get removed in 
     /// the end as they are not required as results. 
-    pub fn run(&self, company: &Company) -> Result<(Output, u128), BenchmarkingError> {
+    pub fn run(&self, company: &Company) -> Result<Output, BenchmarkingError> {
 
         let mut resolved_vals = ResolvedValues::new();
 
@@ -302,9 +324,6 @@ impl Algorithm {
             resolved_vals.insert(i.to_string(), var)?;
         }
 
-        #[cfg(feature="evaluation")]
-        let mut ops_time: u128 = 0;
-
         // Compute every atomic in given order
         //
         // Computation will NOT fail because of missing 
@@ -313,28 +332,15 @@ impl Algorithm {
         // unused constants.. that get reported! 
         for atom in self.operations.iter() {
             log::info!("Computing op: {}", atom.name());
-
-            #[cfg(feature="evaluation")]
-            let now = std::time::SystemTime::now();
-
             atom.calc(&mut resolved_vals)?;
-
-            #[cfg(feature="evaluation")]
-            {
-                ops_time +=  now.elapsed().unwrap().as_nanos();
-            }
-            
         }
 
         // Return only relevant data - rest is discarded
         resolved_vals.filter_atomics_by_name(self.get_non_kpis());
         resolved_vals.filter_atomics_by_name(&self.required);
 
-        #[cfg(not(feature="evaluation"))]
-        {
-            // Debug: print the values
-            resolved_vals.print_plain();
-        }
+        // Debug: print the values
+        resolved_vals.print_plain();
 
         // Add output variables and append them to the company
         let mut output_vars = Output::new_empty();
@@ -342,16 +348,8 @@ impl Algorithm {
             let out_var = OutputVariable::new_result_only(i.0.clone(), (*i.1.vector()).to_vec());
             output_vars.add_var(out_var);
         }
-        
 
-        #[cfg(feature="evaluation")]
-        {
-            Ok((output_vars, ops_time / self.operations.len() as u128))
-        }
-        
-            
-        #[cfg(not(feature="evaluation"))]
-        Ok((output_vars, 0))
+        Ok(output_vars)
     }
 
     /// Aggregate one variable which was evaluated among many companies
@@ -360,23 +358,20 @@ impl Algorithm {
     /// from all company results, that were computed.
     pub fn aggregate_atomic_var(&self, company_results: &mut Vec<&Vec<f64>>) -> Result<OutputVariable, BenchmarkingError> {
 
-        log::info!("Aggregation on KPI with input {:?} started", company_results);
+        log::info!("Aggregation on KPI with {} company results started", company_results.len());
 
-        #[cfg(not(feature="evaluation"))]
-        {
-            // sanity check: no nans and infs
-            for company_result in company_results.iter() {
-                for entry in company_result.iter(){
-                    if (*entry).is_infinite(){
-                        log::error!("Invalid computation result - return empty results!");
-                        return Ok(OutputVariable::new("".to_string(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()))
-                    }
+        // sanity check: no nans and infs
+        for company_result in company_results.iter() {
+            for entry in company_result.iter(){
+                if (*entry).is_infinite(){
+                    log::error!("Invalid computation result - return empty results!");
+                    return Ok(OutputVariable::new("".to_string(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()))
                 }
-            }    
-        }
+            }
+        }    
         
-        // Statistical measurements that rely on ranks
-        company_results.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+        // Statistical measurements that rely on ranks (only the first dimension is ranked)
+        company_results.sort_by(|a, b| a[0].total_cmp(&b[0]));
         
         let median = if company_results.len() % 2 == 0 {
             let s0 = company_results[(company_results.len() / 2) as usize - 1 ].clone();
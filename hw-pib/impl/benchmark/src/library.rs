@@ -0,0 +1,310 @@
+//! Algorithm Library
+//!
+//! Algorithm files can reuse definitions of other algorithm
+//! files and parameterised sub-formulas (macros). Both are
+//! resolved at load time so that the resulting [`Algorithm`]
+//! only consists of plain [`Atomic`] operations.
+//!
+//! A template of an algorithm file that uses the library features:
+//!
+//! ```yaml
+//! include:
+//!   - path: common/ratios.yaml    # relative to this file
+//!     namespace: common
+//!
+//! macros:
+//!   - name: ratio
+//!     params: [x, y]
+//!     operations:
+//!       - name: ratio              # the op named like the macro is its result
+//!         op: Division
+//!         is_kpi: false
+//!         var: [x, y]
+//!
+//! operations:
+//!   - name: margin
+//!     is_kpi: true
+//!     call: ratio                  # or `common.ratio` from the include
+//!     args: [profit, revenue]
+//! ```
+//!
+//! Namespacing: every operation and macro of an included file is
+//! prefixed with `<namespace>.`, and so are all references to them
+//! within that file. Names that are not defined in a file refer to
+//! input variables of the companies and stay global.
+//!
+//! Macros: a call expands the macro body. The result operation
+//! receives the name (and `is_kpi`) of the call, intermediate
+//! operations are named `<call>.<op>`. Parameters are replaced by
+//! the call arguments. A macro body may only refer to its parameters,
+//! its own operations, other macros and input variables.
+//!
+//! [`Algorithm`]: crate::Algorithm
+
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{atomic::Atomic, error::BenchmarkingError, variable::VariableID};
+
+/// Separator between namespace and name
+pub const NAMESPACE_SEPARATOR: &str = ".";
+
+/// Representation of one algorithm file
+#[derive(Debug, Deserialize)]
+struct AlgorithmFile {
+    #[serde(default)]
+    include: Vec<Include>,
+    #[serde(default)]
+    macros: Vec<Macro>,
+    #[serde(default)]
+    operations: Vec<OperationDef>,
+}
+
+/// Include of another algorithm file
+#[derive(Debug, Deserialize)]
+struct Include {
+    /// Path relative to the including file
+    path: PathBuf,
+    /// Prefix for all definitions of the included file
+    namespace: String,
+}
+
+/// Parameterised sub-formula
+#[derive(Debug, Clone, Deserialize)]
+struct Macro {
+    name: String,
+    #[serde(default)]
+    params: Vec<VariableID>,
+    operations: Vec<OperationDef>,
+}
+
+/// Invocation of a [`Macro`]
+#[derive(Debug, Clone, Deserialize)]
+struct MacroCall {
+    name: VariableID,
+    #[serde(default)]
+    is_kpi: bool,
+    call: String,
+    #[serde(default)]
+    args: Vec<VariableID>,
+}
+
+/// One entry of an `operations` list
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum OperationDef {
+    Call(MacroCall),
+    Atomic(Atomic),
+}
+
+/// Resolved content of one file: expanded operations and visible macros
+#[derive(Debug, Default)]
+struct Library {
+    operations: Vec<Atomic>,
+    macros: HashMap<String, Macro>,
+}
+
+/// Parse the algorithm file at `path` and resolve all includes and macro calls
+///
+/// Includes are rejected if `allow_includes` is false. This is required whenever
+/// the file stems from an untrusted location (e.g. an upload), since includes
+/// could otherwise reference arbitrary files.
+pub(crate) fn resolve(path: &Path, allow_includes: bool) -> Result<Vec<Atomic>, BenchmarkingError> {
+    let mut stack = Vec::new();
+    Ok(resolve_file(path, allow_includes, &mut stack)?.operations)
+}
+
+/// Resolve one file recursively, `stack` holds the files that are currently resolved
+fn resolve_file(path: &Path, allow_includes: bool, stack: &mut Vec<PathBuf>) -> Result<Library, BenchmarkingError> {
+    let canonical = path.canonicalize().map_err(|err| BenchmarkingError::from(format!("Could not open algorithm file {}: {}", path.display(), err)))?;
+    if stack.contains(&canonical) {
+        return Err(BenchmarkingError::from(format!("Cyclic include of algorithm file {}", path.display())));
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .open(&canonical)
+        .map_err(|err| BenchmarkingError::from(format!("Could not open algorithm file {}: {}", path.display(), err)))?;
+    let file: AlgorithmFile = serde_yaml::from_reader(BufReader::new(file)).map_err(|err| BenchmarkingError::from(format!("Could not parse algorithm: {}", err)))?;
+
+    if !allow_includes && !file.include.is_empty() {
+        return Err(BenchmarkingError::from("Includes are not allowed in this algorithm, bundle it first".to_string()));
+    }
+
+    stack.push(canonical.clone());
+    let mut lib = Library::default();
+
+    // Includes are namespaced and merged into the current library
+    for include in &file.include {
+        if include.namespace.is_empty() || include.namespace.contains(NAMESPACE_SEPARATOR) {
+            return Err(BenchmarkingError::from(format!("Invalid namespace \"{}\" for include {}", include.namespace, include.path.display())));
+        }
+        let include_path = canonical.parent().unwrap_or_else(|| Path::new(".")).join(&include.path);
+        let included = resolve_file(&include_path, allow_includes, stack)?;
+        lib.merge_namespaced(included, &include.namespace)?;
+    }
+    stack.pop();
+
+    // Macros are expanded within the scope of the defining file
+    for mac in file.macros {
+        if !mac.operations.iter().any(|op| op.name() == mac.name) {
+            return Err(BenchmarkingError::from(format!("Macro {} has no operation named {} that yields its result", mac.name, mac.name)));
+        }
+        let operations = lib.expand_all(&mac.operations)?;
+        let mac = Macro {
+            operations: operations.into_iter().map(OperationDef::Atomic).collect(),
+            ..mac
+        };
+        if lib.macros.insert(mac.name.clone(), mac.clone()).is_some() {
+            return Err(BenchmarkingError::from(format!("Duplicate definition of macro {}", mac.name)));
+        }
+    }
+
+    let mut operations = lib.expand_all(&file.operations)?;
+    lib.operations.append(&mut operations);
+
+    Ok(lib)
+}
+
+impl OperationDef {
+    fn name(&self) -> &str {
+        match self {
+            OperationDef::Call(call) => &call.name,
+            OperationDef::Atomic(atom) => atom.name(),
+        }
+    }
+}
+
+impl Library {
+    /// Merge an included library with all its definitions prefixed by `namespace`
+    fn merge_namespaced(&mut self, included: Library, namespace: &str) -> Result<(), BenchmarkingError> {
+        let prefix = |name: &str| format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, name);
+        let defined: HashSet<String> = included.operations.iter().map(|op| op.name().to_string()).collect();
+
+        for mut op in included.operations {
+            op.map_names(|name| if defined.contains(name) { prefix(name) } else { name.to_string() });
+            self.operations.push(op);
+        }
+
+        for (name, mac) in included.macros {
+            let name = prefix(&name);
+            if self.macros.contains_key(&name) {
+                return Err(BenchmarkingError::from(format!("Duplicate definition of macro {}", name)));
+            }
+            self.macros.insert(name, mac);
+        }
+        Ok(())
+    }
+
+    /// Expand all macro calls of an operation list
+    fn expand_all(&self, operations: &[OperationDef]) -> Result<Vec<Atomic>, BenchmarkingError> {
+        let mut expanded = Vec::with_capacity(operations.len());
+        for op in operations {
+            match op {
+                OperationDef::Atomic(atom) => expanded.push(atom.clone()),
+                OperationDef::Call(call) => expanded.append(&mut self.expand(call)?),
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Expand one macro call into plain atomics
+    fn expand(&self, call: &MacroCall) -> Result<Vec<Atomic>, BenchmarkingError> {
+        let mac = self.macros.get(&call.call).ok_or_else(|| BenchmarkingError::from(format!("Operation {} calls unknown macro {}", call.name, call.call)))?;
+
+        if mac.params.len() != call.args.len() {
+            return Err(BenchmarkingError::from(format!(
+                "Operation {} calls macro {} with {} arguments, but it expects {}",
+                call.name, call.call, call.args.len(), mac.params.len()
+            )));
+        }
+
+        let args: HashMap<&str, &VariableID> = mac.params.iter().map(String::as_str).zip(call.args.iter()).collect();
+        let internal: HashSet<&str> = mac.operations.iter().map(OperationDef::name).collect();
+        let rename = |name: &str| {
+            if name == mac.name {
+                call.name.clone()
+            } else if internal.contains(name) {
+                format!("{}{}{}", call.name, NAMESPACE_SEPARATOR, name)
+            } else if let Some(arg) = args.get(name) {
+                (*arg).clone()
+            } else {
+                name.to_string()
+            }
+        };
+
+        let mut expanded = Vec::with_capacity(mac.operations.len());
+        for op in &mac.operations {
+            // Macro bodies are expanded on definition
+            if let OperationDef::Atomic(atom) = op {
+                let mut atom = atom.clone();
+                let is_result = atom.name() == mac.name;
+                atom.map_names(rename);
+                atom.set_kpi(is_result && call.is_kpi);
+                expanded.push(atom);
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn resolve_include_and_macro() {
+        let dir = std::env::temp_dir().join(format!("teebench-library-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        write(&dir, "common.yaml", "
+macros:
+  - name: ratio
+    params: [x, y]
+    operations:
+      - { name: ratio, op: Division, is_kpi: false, var: [x, y] }
+operations:
+  - { name: total, op: Addition, is_kpi: false, var: [cost, profit] }
+");
+        let main = write(&dir, "main.yaml", "
+include:
+  - { path: common.yaml, namespace: common }
+operations:
+  - { name: margin, is_kpi: true, call: common.ratio, args: [profit, common.total] }
+");
+
+        let ops = resolve(&main, true).unwrap();
+        let names: Vec<&str> = ops.iter().map(Atomic::name).collect();
+        assert_eq!(names, vec!["common.total", "margin"]);
+        assert_eq!(ops[0].var_ids(), ["cost", "profit"]);
+        assert_eq!(ops[1].var_ids(), ["profit", "common.total"]);
+        assert!(ops[1].is_kpi());
+
+        assert!(resolve(&main, false).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reject_cyclic_include() {
+        let dir = std::env::temp_dir().join(format!("teebench-library-cycle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let a = write(&dir, "a.yaml", "include:\n  - { path: b.yaml, namespace: b }\n");
+        write(&dir, "b.yaml", "include:\n  - { path: a.yaml, namespace: a }\n");
+
+        assert!(resolve(&a, true).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
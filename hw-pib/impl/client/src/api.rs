@@ -1,4 +1,4 @@
-use types::consts::*;
+use types::{consts::*, message::response::BenchmarkID};
 #[derive(Debug, Clone)]
 pub struct TeebenchHttpAPI {
     base: String,
@@ -27,67 +27,110 @@ impl TeebenchHttpAPI {
 #[derive(Debug, Clone)]
 pub struct TeebenchHttpsAPI {
     base: String,
+    benchmark_id: BenchmarkID,
 }
 
 impl TeebenchHttpsAPI {
     /// Host example: `teebench.xyz`
     /// HTTP_port example: `8443`
-    pub fn new(host: String, https_port: String) -> Self {
+    /// Benchmark example: `0` (routes of companies and the analyst address this benchmark)
+    pub fn new(host: String, https_port: String, benchmark_id: BenchmarkID) -> Self {
         let base = "https://".to_string() + &host + &":".to_string() + &https_port;
         TeebenchHttpsAPI {
             base,
+            benchmark_id,
         }
     }
 
+    pub fn benchmark_id(&self) -> BenchmarkID {
+        self.benchmark_id
+    }
+
+    /// Address the benchmark that the server assigned instead
+    pub fn set_benchmark_id(&mut self, benchmark_id: BenchmarkID) {
+        self.benchmark_id = benchmark_id;
+    }
+
+    /// Base of all routes of the benchmark
+    fn benchmark_base(&self) -> String {
+        self.base.clone() + C_ROUTE_BENCHMARKS + &self.benchmark_id.to_string()
+    }
+
     #[allow(unused)]
     pub fn setup(&self) -> String {
         self.base.clone() + C_ROUTE_SETUP
     }
 
-    #[allow(unused)]
     pub fn attest(&self) -> String {
         self.base.clone() + C_ROUTE_ATTEST
     }
 
+    pub fn key_exchange(&self) -> String {
+        self.base.clone() + C_ROUTE_KEY_EXCHANGE
+    }
+
     pub fn shutdown(&self) -> String {
         self.base.clone() + C_ROUTE_SHUTDOWN
     }
-    pub fn company_register(&self, uuid: u128) -> String {
-        self.base.clone() + C_ROUTE_COMPANY_EXT_REGISTER_ID + &uuid.to_string()
+    pub fn analyst_benchmarks(&self) -> String {
+        self.base.clone() + C_ROUTE_ANALYST_EXT_BENCHMARKS
     }
-    pub fn company_input_data(&self, uuid: u128) -> String {
-        self.base.clone() + C_ROUTE_COMPANY_EXT_INPUT_DATA_ID + &uuid.to_string()
+    pub fn analyst_key_exchange(&self) -> String {
+        self.base.clone() + C_ROUTE_ANALYST_EXT_KEY_EXCHANGE
     }
-    #[allow(unused)]
-    pub fn company_results(&self, uuid: u128) -> String {
-        self.base.clone() + C_ROUTE_COMPANY_EXT_RESULTS_ID + &uuid.to_string()
+    pub fn company_register(&self) -> String {
+        self.benchmark_base() + C_ROUTE_COMPANY_EXT_REGISTER
+    }
+    pub fn company_input_data(&self) -> String {
+        self.benchmark_base() + C_ROUTE_COMPANY_EXT_INPUT_DATA
+    }
+    pub fn company_results(&self) -> String {
+        self.benchmark_base() + C_ROUTE_COMPANY_EXT_RESULTS
+    }
+    pub fn company_algorithms(&self) -> String {
+        self.benchmark_base() + C_ROUTE_COMPANY_EXT_ALGORITHMS
     }
     pub fn get_events(&self) -> String {
-        self.base.clone() + C_ROUTE_ENROLL_EVENTS
+        self.benchmark_base() + C_ROUTE_ENROLL_EVENTS
+    }
+    pub fn company_events(&self) -> String {
+        self.benchmark_base() + C_ROUTE_COMPANY_EXT_EVENTS
+    }
+    #[allow(unused)]
+    pub fn get_phase(&self) -> String {
+        self.benchmark_base() + C_ROUTE_PHASE
     }
 
     #[allow(unused)]
     pub fn analyst_benchmark_config(&self) -> String {
-        self.base.clone() + C_ROUTE_ANALYST_EXT_BENCHMARK_CONFIG
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_BENCHMARK_CONFIG
     }
 
     #[allow(unused)]
     pub fn analyst_company_status(&self, uuid: u128) -> String {
-        self.base.clone() + C_ROUTE_ANALYST_EXT_COMPANY_STATUS_ID + &uuid.to_string()
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_COMPANY_STATUS_ID + &uuid.to_string()
     }
 
     pub fn analyst_enroll_company(&self) -> String {
-        self.base.clone() + C_ROUTE_ANALYST_EXT_ENROLL_COMPANY
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_ENROLL_COMPANY
     }
     
     pub fn analyst_algorithms(&self) -> String {
-        self.base.clone() + C_ROUTE_ANALYST_EXT_ALGORITHMS
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_ALGORITHMS
     }
     pub fn analyst_benchmark(&self) -> String {
-        self.base.clone() + C_ROUTE_ANALYST_EXT_BENCHMARK
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_BENCHMARK
     }
     
     pub fn analyst_send_event(&self) -> String {
-        self.base.clone() + C_ROUTE_ANALYST_EXT_EVENT
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_EVENT
+    }
+
+    pub fn analyst_phase(&self) -> String {
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_PHASE
+    }
+
+    pub fn analyst_events(&self) -> String {
+        self.benchmark_base() + C_ROUTE_ANALYST_EXT_EVENTS
     }
 }
\ No newline at end of file
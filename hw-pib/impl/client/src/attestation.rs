@@ -0,0 +1,246 @@
+//! Verification of the server's attestation evidence
+//!
+//! Before any data is handed over, the evidence of the server is
+//! fetched with a fresh nonce and checked:
+//!
+//! - The evidence is signed by the attestation key that the
+//!   [`AttestationPolicy`] pins (a server cannot sign with a key of its own).
+//! - The nonce matches (no replay of old evidence).
+//! - The hash of the TLS certificate matches the certificate of the
+//!   connection (no relay of the evidence by another server).
+//! - The benchmark has algorithms that the evidence reports.
+//!
+//! Additionally, the server status has to satisfy the remaining fields of
+//! the policy (see `templates/yaml/attestation_policy.yaml`). Every company
+//! requires a policy, without one no data is handed over.
+
+use std::{fs::File, path::Path};
+
+use reqwest::{tls::TlsInfo, Client};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+    signature::{UnparsedPublicKey, RSA_PKCS1_2048_8192_SHA256},
+};
+use serde::Deserialize;
+use types::message::{
+    request::AttestMsg,
+    response::{AttestationEvidence, BenchmarkID, RspMsg, ServerStatus},
+};
+
+use crate::{
+    api::TeebenchHttpsAPI,
+    error::{AbstractClientErrorType, ClientError},
+};
+
+/// Length of the nonce in bytes
+const NONCE_LEN: usize = 32;
+
+/// Expectations of a company on the attested server
+///
+/// All fields but the minimum k-anonymity are required.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct AttestationPolicy {
+    /// Trusted public key (DER, hex) that signs the evidence
+    attestation_key: String,
+    /// Expected measurement of the server code (hex)
+    measurement: String,
+    /// Hashes of the algorithms that the company accepts (hex), i.e. the
+    /// ones of the attestation and modifications it confirms
+    algorithm_hashes: Vec<String>,
+    /// Minimum k-anonymity of the benchmark
+    #[serde(default)]
+    min_k_anonymity: Option<u64>,
+}
+
+impl AttestationPolicy {
+    /// Load the policy from a YAML file
+    pub(crate) fn load(path: &Path) -> Result<Self, ClientError> {
+        let file = File::open(path).map_err(|e| ClientError::from((AbstractClientErrorType::NotFound, e.to_string())))?;
+        serde_yaml::from_reader(file).map_err(|e| ClientError::from((AbstractClientErrorType::BadRequest, format!("Invalid attestation policy: {}", e))))
+    }
+
+    /// The pinned attestation key
+    fn attestation_key(&self) -> Result<Vec<u8>, ClientError> {
+        hex::decode(&self.attestation_key).map_err(|e| ClientError::from((AbstractClientErrorType::BadRequest, format!("Invalid attestation key of the policy: {}", e))))
+    }
+
+    /// Whether the company accepts algorithms with this hash
+    pub(crate) fn accepts_algorithms(&self, hash: &str) -> bool {
+        self.algorithm_hashes.iter().any(|accepted| accepted.eq_ignore_ascii_case(hash))
+    }
+
+    /// Check the (verified) server status of the benchmark against the policy
+    pub(crate) fn check(&self, status: &ServerStatus, benchmark_id: BenchmarkID) -> Result<(), ClientError> {
+        let evidence = status.evidence();
+
+        if !evidence.measurement.eq_ignore_ascii_case(&self.measurement) {
+            return Err(unauthorized(format!("Unexpected server measurement {}", evidence.measurement)));
+        }
+
+        match evidence.algorithm_hashes.get(&benchmark_id) {
+            Some(hash) if self.accepts_algorithms(hash) => {}
+            hash => return Err(unauthorized(format!("Unexpected algorithm hash {:?}", hash))),
+        }
+
+        if let Some(min_k_anonymity) = self.min_k_anonymity {
+            let k_anonymity = status
+                .benchmarks()
+                .get(&benchmark_id)
+                .map(|config| config.k_anonymity())
+                .ok_or_else(|| unauthorized(format!("Benchmark {} is not reported", benchmark_id)))?;
+            if k_anonymity < min_k_anonymity {
+                return Err(unauthorized(format!("K-anonymity {} is below the required {}", k_anonymity, min_k_anonymity)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fetch the server status and verify its attestation evidence
+///
+/// The evidence has to be signed by the key that the policy pins.
+pub(crate) async fn attest(client: &Client, routes: &TeebenchHttpsAPI, policy: &AttestationPolicy) -> Result<ServerStatus, ClientError> {
+    let mut nonce = [0_u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| unauthorized("No randomness available for the nonce".to_string()))?;
+    let nonce = hex::encode(nonce);
+
+    let rsp = client
+        .get(routes.attest())
+        .query(&AttestMsg { nonce: nonce.clone() })
+        .send()
+        .await
+        .map_err(|e| ClientError::from((AbstractClientErrorType::NoConnection, e.to_string())))?;
+    if !rsp.status().is_success() {
+        return Err(ClientError::from_response(rsp).await);
+    }
+
+    // The certificate of this very connection
+    let tls_certificate = rsp
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .map(|cert| cert.to_vec())
+        .ok_or_else(|| unauthorized("No TLS certificate of the server available".to_string()))?;
+
+    let status = rsp
+        .json::<RspMsg<ServerStatus>>()
+        .await
+        .map_err(|e| unauthorized(format!("Attestation response could not be decoded: {}", e)))?
+        .content;
+
+    verify_evidence(status.evidence(), &policy.attestation_key()?, &tls_certificate, &nonce)?;
+
+    if !status.evidence().algorithm_hashes.contains_key(&routes.benchmark_id()) {
+        return Err(unauthorized(format!("Evidence holds no algorithms of benchmark {}", routes.benchmark_id())));
+    }
+    if status.evidence().attester == "simulated" {
+        log::warn!("Server uses a simulated attester - the evidence does not prove execution in an enclave!");
+    }
+
+    log::info!("Server attestation verified! Measurement: {}", status.evidence().measurement);
+    Ok(status)
+}
+
+/// Check the signature with the pinned key, the nonce and the binding to the TLS certificate
+fn verify_evidence(evidence: &AttestationEvidence, pinned_key: &[u8], tls_certificate: &[u8], nonce: &str) -> Result<(), ClientError> {
+    // The key in the evidence is reported by the server, only the pinned one is trusted
+    let attestation_key = hex::decode(&evidence.attestation_key).map_err(|e| unauthorized(e.to_string()))?;
+    if attestation_key != pinned_key {
+        return Err(unauthorized(format!("Evidence is signed by the untrusted attestation key {}", evidence.attestation_key)));
+    }
+
+    let signature = hex::decode(&evidence.signature).map_err(|e| unauthorized(e.to_string()))?;
+    UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, pinned_key)
+        .verify(&evidence.report_data(), &signature)
+        .map_err(|_| unauthorized("Invalid signature of the attestation evidence".to_string()))?;
+
+    if evidence.nonce != nonce {
+        return Err(unauthorized("Evidence does not hold the requested nonce".to_string()));
+    }
+
+    if evidence.tls_certificate_hash != hex::encode(digest(&SHA256, tls_certificate)) {
+        return Err(unauthorized("Evidence is not bound to the TLS certificate of the server".to_string()));
+    }
+
+    Ok(())
+}
+
+fn unauthorized(reason: String) -> ClientError {
+    ClientError::from((AbstractClientErrorType::Unauthorized, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::*;
+
+    const MEASUREMENT: &str = "4f1717be9b801834df82b8f9f483050c862d620b28e2d2e0bd727d23abc3c77d";
+    const ALGORITHM_HASH: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+    const ATTESTATION_KEY: &str = "3082010a0282010100e96feecd";
+
+    fn policy() -> AttestationPolicy {
+        AttestationPolicy {
+            attestation_key: ATTESTATION_KEY.to_string(),
+            measurement: MEASUREMENT.to_string(),
+            algorithm_hashes: vec![ALGORITHM_HASH.to_string()],
+            min_k_anonymity: None,
+        }
+    }
+
+    fn status(measurement: &str, algorithm_hash: &str) -> ServerStatus {
+        let evidence = AttestationEvidence {
+            attester: "simulated".to_string(),
+            measurement: measurement.to_string(),
+            tls_certificate_hash: String::new(),
+            algorithm_hashes: BTreeMap::from([(0, algorithm_hash.to_string())]),
+            nonce: String::new(),
+            attestation_key: ATTESTATION_KEY.to_string(),
+            signature: String::new(),
+        };
+        ServerStatus::new(true, evidence, HashMap::new())
+    }
+
+    #[test]
+    pub fn policy_accepts_expected_server() {
+        assert!(policy().check(&status(MEASUREMENT, ALGORITHM_HASH), 0).is_ok());
+    }
+
+    #[test]
+    pub fn policy_rejects_other_measurement() {
+        let other = "0000000000000000000000000000000000000000000000000000000000000000";
+        assert!(policy().check(&status(other, ALGORITHM_HASH), 0).is_err());
+    }
+
+    #[test]
+    pub fn policy_rejects_other_algorithm_hash() {
+        let other = "1111111111111111111111111111111111111111111111111111111111111111";
+        assert!(policy().check(&status(MEASUREMENT, other), 0).is_err());
+    }
+
+    #[test]
+    pub fn policy_rejects_missing_algorithms() {
+        assert!(policy().check(&status(MEASUREMENT, ALGORITHM_HASH), 1).is_err());
+    }
+
+    #[test]
+    pub fn policy_accepts_listed_algorithms_only() {
+        assert!(policy().accepts_algorithms(&ALGORITHM_HASH.to_uppercase()));
+        assert!(!policy().accepts_algorithms(MEASUREMENT));
+    }
+
+    #[test]
+    pub fn policy_is_required_in_full() {
+        assert!(serde_yaml::from_str::<AttestationPolicy>("attestation_key: 00\nmeasurement: 00\n").is_err());
+    }
+
+    #[test]
+    pub fn evidence_of_untrusted_key_is_rejected() {
+        let evidence = status(MEASUREMENT, ALGORITHM_HASH).evidence().clone();
+        assert!(verify_evidence(&evidence, &[0x30, 0x00], &[], "").is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use client::generate_report;
+
+/// Offline report of exported results
+#[derive(Debug, Parser)]
+#[clap(name = "teebench-report")]
+#[clap(about = "HTML report with box plots per KPI from exported benchmark results.", long_about = None)]
+struct ReportArguments {
+    /// Results exported by the company client (CSV or JSON)
+    results_path: PathBuf,
+    /// Path of the HTML report
+    report_path: PathBuf,
+    /// Algorithm file with the descriptions of the KPIs
+    #[clap(long, short = 'a')]
+    algorithms: Option<PathBuf>,
+    /// Title of the report
+    #[clap(long, short = 't', default_value = "Benchmark report")]
+    title: String,
+}
+
+/// Writes the report without connecting to the server
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+
+    let arguments = ReportArguments::parse();
+    generate_report(&arguments.results_path, arguments.algorithms.as_deref(), &arguments.report_path, &arguments.title)?;
+    log::info!("Wrote the report to {:?}", arguments.report_path);
+
+    Ok(())
+}
@@ -13,8 +13,8 @@ use crate::connection::company::CompanyConnection;
 use crate::connection::connection::TeebenchClient;
 use crate::connection::spectator::SpectatorConnection;
 use crate::connection::state::ClientConnection;
-use reqwest::{header, Certificate, Client, ClientBuilder, Identity};
-use types::consts::{CC_CLIENT_PKCS12_KEY, CC_CLIENT_SERVER_CA_CERTIFICATE, X_APPLICATION_FIELD};
+use reqwest::{Certificate, Client, ClientBuilder, Identity};
+use types::consts::{CC_CLIENT_PKCS12_KEY, CC_CLIENT_SERVER_CA_CERTIFICATE};
 
 /// HTTP configuration server startup
 ///
@@ -68,6 +68,8 @@ fn req_client(config: &ClientConfiguration) -> Client {
         //.tcp_keepalive(Some(std::time::Duration::new(300, 0)))
         .add_root_certificate(server_root_ca_certificate)
         .pool_max_idle_per_host(0)
+        // Server certificate is required to verify the attestation
+        .tls_info(true)
         .identity(ident);
 
     match config.role() {
@@ -77,16 +79,9 @@ fn req_client(config: &ClientConfiguration) -> Client {
                 .build()
                 .expect("Could not build analyst client!")
         }
-        ClientType::Company(uuid) => {
-            // Put client UUID into all requests
-            let mut client_header = header::HeaderMap::new();
-            client_header.insert(
-                X_APPLICATION_FIELD,
-                header::HeaderValue::from_str(uuid.to_string().as_str()).expect("UUID is invalid!"),
-            );
-
+        ClientType::Company(_) => {
+            // The server takes the company UUID from the certificate
             client_build
-                .default_headers(client_header)
                 .build()
                 .expect("Could not build company client!")
         }
@@ -106,6 +101,7 @@ fn application_client(config: &ClientConfiguration, client: Client) -> TeebenchC
                 config.server_https_port().to_string(),
                 config.paths(),
                 None,
+                config.benchmark(),
                 config.eval_mode(),
             );
             TeebenchClient::Analyst(analyst_conn)
@@ -118,6 +114,7 @@ fn application_client(config: &ClientConfiguration, client: Client) -> TeebenchC
                 config.server_https_port().to_string(),
                 config.paths(),
                 Some(*uuid),
+                config.benchmark(),
                 config.eval_mode(),
             );
             TeebenchClient::Company(company_conn)
@@ -130,6 +127,7 @@ fn application_client(config: &ClientConfiguration, client: Client) -> TeebenchC
                 config.server_https_port().to_string(),
                 config.paths(),
                 None,
+                config.benchmark(),
                 config.eval_mode(),
             );
             TeebenchClient::Spectator(spec_conn)
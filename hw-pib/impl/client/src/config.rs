@@ -4,10 +4,16 @@ use benchmark::operation::OperationType;
 use clap::{Parser, Subcommand};
 
 use strum::{Display, EnumString};
-use types::consts::{
-    CC_ANALYST_ALGORITHMS_KEY, CC_ANALYST_BENCHMARK_CONFIG_KEY, CC_ANALYST_CA_CERTIFICATE_KEY,
-    CC_ANALYST_CERTIFICATE_KEY, CC_CLIENT_PKCS12_KEY, CC_CLIENT_SERVER_CA_CERTIFICATE,
-    CC_COMPANY_INPUT_DATA_PATH_KEY,
+
+use crate::export::ResultFormat;
+use types::{
+    consts::{
+        CC_ANALYST_ALGORITHMS_KEY, CC_ANALYST_AUDITOR_CERTIFICATES_KEY,
+        CC_ANALYST_BENCHMARK_CONFIG_KEY, CC_ANALYST_CA_CERTIFICATE_KEY, CC_ANALYST_CERTIFICATE_KEY,
+        CC_ANALYST_CO_ANALYST_CERTIFICATES_KEY, CC_CLIENT_PKCS12_KEY, CC_CLIENT_SERVER_CA_CERTIFICATE,
+        CC_COMPANY_ATTESTATION_POLICY_KEY, CC_COMPANY_INPUT_DATA_PATH_KEY, CC_COMPANY_RESULTS_PATH_KEY, CC_COMPANY_REPORT_PATH_KEY,
+    },
+    message::response::{BenchmarkID, DEFAULT_BENCHMARK_ID},
 };
 
 /// Server Configuration
@@ -35,6 +41,9 @@ struct CLIArguments {
     /// HTTPs port for all others
     #[clap(long, short = 's')]
     server_https: u16,
+    /// Benchmark of the server to participate in (the analyst creates it if it is not the first)
+    #[clap(long, short = 'b', default_value_t = DEFAULT_BENCHMARK_ID)]
+    benchmark: BenchmarkID,
     /// Crypto key for client certificate
     #[clap(long, short = 'c')]
     client_pkcs12_path: std::path::PathBuf,
@@ -62,14 +71,29 @@ pub(crate) enum TeeBenchCLISubcommands {
         algorithm_path: std::path::PathBuf,
         /// [For analyst] Data path for benchmarking config upload
         benchmarking_config_path: std::path::PathBuf,
+        /// [For analyst] Certificates (PEM) of co-analysts who share the administration
+        #[clap(long)]
+        co_analyst_certificates: Option<std::path::PathBuf>,
+        /// [For analyst] Certificates (PEM) of auditors with read-only access
+        #[clap(long)]
+        auditor_certificates: Option<std::path::PathBuf>,
     },
     /// Required for a company: input data and own UUID
     #[clap(arg_required_else_help = true)]
     Company {
-        /// [For companies] Data path for input uploads
+        /// [For companies] Data path for input uploads (YAML, JSON or CSV by the extension)
         input_data_path: std::path::PathBuf,
-        /// [For companies] UUID of specific company known from analyst
+        /// [For companies] UUID of specific company known from analyst (as in its certificate)
         uuid: u128,
+        /// [For companies] Policy that the server attestation has to satisfy (with the trusted attestation key)
+        #[clap(long)]
+        attestation_policy: std::path::PathBuf,
+        /// [For companies] Export of the results as table (CSV, JSON or XLSX by the extension)
+        #[clap(long)]
+        results: Option<std::path::PathBuf>,
+        /// [For companies] HTML report of the results with box plots per KPI
+        #[clap(long)]
+        report: Option<std::path::PathBuf>,
     },
     /// For other: keep it free: this is debug
     #[clap(arg_required_else_help = true)]
@@ -84,6 +108,8 @@ pub struct ClientConfiguration {
     server_http: u16,
     /// HTTPs port for all others
     server_https: u16,
+    /// Benchmark of the server
+    benchmark: BenchmarkID,
     /// Paths
     paths: HashMap<String, PathBuf>,
     /// Evaluation mode (whether offloading is used)
@@ -117,10 +143,12 @@ pub fn configure_client_application() -> ClientConfiguration {
 
 impl ClientConfiguration {
     /// For in-program start
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         server_host: String,
         server_http: u16,
         server_https: u16,
+        benchmark: BenchmarkID,
         paths: HashMap<String, PathBuf>,
         eval_mode: (bool, EvalMode),
         offload: Option<Vec<OperationType>>,
@@ -138,6 +166,7 @@ impl ClientConfiguration {
             server_host,
             server_http,
             server_https,
+            benchmark,
             paths,
             offload,
             role,
@@ -171,6 +200,9 @@ impl ClientConfiguration {
             TeeBenchCLISubcommands::Company {
                 input_data_path,
                 uuid,
+                attestation_policy,
+                results,
+                report,
             } => {
                 if !input_data_path.exists() {
                     panic!("Input data path is invalid!");
@@ -181,6 +213,24 @@ impl ClientConfiguration {
                     input_data_path.clone(),
                 );
 
+                if !attestation_policy.exists() {
+                    panic!("Attestation policy path is invalid!");
+                }
+                paths.insert(
+                    CC_COMPANY_ATTESTATION_POLICY_KEY.to_string(),
+                    attestation_policy,
+                );
+
+                if let Some(results) = results {
+                    if ResultFormat::from_path(&results).is_none() {
+                        panic!("Results path has to end with .csv, .json or .xlsx!");
+                    }
+                    paths.insert(CC_COMPANY_RESULTS_PATH_KEY.to_string(), results);
+                }
+                if let Some(report) = report {
+                    paths.insert(CC_COMPANY_REPORT_PATH_KEY.to_string(), report);
+                }
+
                 ClientType::Company(uuid)
             }
             TeeBenchCLISubcommands::Analyst {
@@ -188,6 +238,8 @@ impl ClientConfiguration {
                 analyst_certificate_path,
                 algorithm_path,
                 benchmarking_config_path,
+                co_analyst_certificates,
+                auditor_certificates,
             } => {
                 if !analyst_ca_cert_path.exists() {
                     panic!("Analyst CA Certificate path is invalid!");
@@ -219,6 +271,18 @@ impl ClientConfiguration {
                     benchmarking_config_path.clone(),
                 );
 
+                for (key, certificates) in [
+                    (CC_ANALYST_CO_ANALYST_CERTIFICATES_KEY, co_analyst_certificates),
+                    (CC_ANALYST_AUDITOR_CERTIFICATES_KEY, auditor_certificates),
+                ] {
+                    if let Some(certificates) = certificates {
+                        if !certificates.exists() {
+                            panic!("Team certificates path is invalid!");
+                        }
+                        paths.insert(key.to_string(), certificates);
+                    }
+                }
+
                 ClientType::Analyst
             }
             TeeBenchCLISubcommands::Other {} => {
@@ -233,6 +297,7 @@ impl ClientConfiguration {
             server_host: arguments.server_host,
             server_http: arguments.server_http,
             server_https: arguments.server_https,
+            benchmark: arguments.benchmark,
             eval_mode: arguments.eval_mode,
             offload: arguments.offload,
             role,
@@ -254,6 +319,10 @@ impl ClientConfiguration {
         self.server_https
     }
 
+    pub fn benchmark(&self) -> BenchmarkID {
+        self.benchmark
+    }
+
     pub fn paths(&self) -> &HashMap<String, PathBuf> {
         &self.paths
     }
@@ -1,9 +1,8 @@
 //! Analyst state machine and internal behaviour
 
 use async_trait::async_trait;
-use eventsource_stream::Eventsource;
-use futures_util::StreamExt;
-use reqwest::{multipart, Client};
+use benchmark::Algorithm;
+use reqwest::{header::CONTENT_TYPE, multipart, Client};
 use serde_json::json;
 use std::{
     collections::HashMap,
@@ -16,25 +15,39 @@ use crate::{
     api::{TeebenchHttpAPI, TeebenchHttpsAPI},
     config::EvalMode,
     error::{AbstractClientErrorType, ClientError},
+    events::EventStream,
+    integrity::{multipart_body, RequestSigner},
 };
 
 use types::{
     consts::{
         CC_ANALYST_ALGORITHMS_KEY, CC_ANALYST_BENCHMARK_CONFIG_KEY, CC_ANALYST_CA_CERTIFICATE_KEY,
-        CC_ANALYST_CERTIFICATE_KEY, FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME,
-        FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME, FORM_DATA_FIELD_01_CONFIGURATION_MIME,
+        CC_ANALYST_AUDITOR_CERTIFICATES_KEY, CC_ANALYST_CERTIFICATE_KEY,
+        CC_ANALYST_CO_ANALYST_CERTIFICATES_KEY, FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME,
+        FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME, FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_MIME,
+        FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_NAME, FORM_DATA_FIELD_01_CONFIGURATION_MIME,
+        FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_MIME,
+        FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_NAME,
         FORM_DATA_FIELD_01_CONFIGURATION_NAME, FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_MIME,
         FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_NAME, FORM_DATA_FIELD_02_ALGORIHTMS_NAME,
-        FORM_DATA_FIELD_02_ALGORITHMS_MIME,
+        FORM_DATA_FIELD_02_ALGORITHMS_MIME, FORM_DATA_FIELD_03_CONFIGURATION_MIME,
+        FORM_DATA_FIELD_03_CONFIGURATION_NAME,
+    },
+    entity::{BenchmarkPhase, BenchmarkingConfig},
+    message::{
+        event::ServerEvent,
+        io::CompanyUUIDs,
+        request::{AnalystEventMsg, AnalystPhaseMsg, AnalystSetupMsg},
+        response::{BenchmarkID, RspMsg, DEFAULT_BENCHMARK_ID},
     },
-    entity::BenchmarkingConfig,
-    message::{io::CompanyUUIDs, request::AnalystEventMsg, response::RspMsg},
 };
 
 use super::state::{ClientConnection, Event, StateMachine};
 
 static COMPANY_UUID_PATH: &str = "../data/client_data/uuids.yaml";
 static ANALYST_FINISHED_PATH: &str = "../data/client_data/finished";
+/// The HMAC key is set once per server, further benchmarks reuse it
+static ANALYST_HMAC_KEY_PATH: &str = "../data/client_data/analyst_hmac.key";
 
 /// Analyst Connection has connection Strings
 /// and a state which has to be initialized.
@@ -58,6 +71,7 @@ impl ClientConnection for AnalystConnection {
         https_port: String,
         paths: &HashMap<String, PathBuf>,
         _uuid: Option<u128>,
+        benchmark_id: BenchmarkID,
         _eval_mode: EvalMode,
     ) -> Self {
         // Get the paths for the analyst
@@ -66,6 +80,23 @@ impl ClientConnection for AnalystConnection {
         let analyst_cert_path = paths.get(CC_ANALYST_CERTIFICATE_KEY).unwrap().clone();
         let algorithms_path = paths.get(CC_ANALYST_ALGORITHMS_KEY).unwrap().clone();
 
+        // Certificates of co-analysts and auditors are optional
+        let team_certificate_paths = [
+            (
+                CC_ANALYST_CO_ANALYST_CERTIFICATES_KEY,
+                FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_NAME,
+                FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_MIME,
+            ),
+            (
+                CC_ANALYST_AUDITOR_CERTIFICATES_KEY,
+                FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_NAME,
+                FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_MIME,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, name, mime)| paths.get(key).map(|path| (name, mime, path.clone())))
+        .collect();
+
         // Get quantity, i.e. the number of participants from config file
         let f = std::fs::File::open(paths.get(CC_ANALYST_BENCHMARK_CONFIG_KEY).unwrap().clone())
             .expect("Could not read benchmarking configuration!");
@@ -78,10 +109,12 @@ impl ClientConnection for AnalystConnection {
             host,
             http_port,
             https_port,
+            benchmark_id,
             analyst_ca_cert_path,
             benchmark_config_path,
             analyst_cert_path,
             algorithms_path,
+            team_certificate_paths,
             benchmarking_config.k_anonymity(),
         );
 
@@ -94,7 +127,7 @@ impl ClientConnection for AnalystConnection {
     /// Start the state machine for the analyst
     async fn run(&mut self) {
         while !self.state.eq(&AnalystState::Shutdown) {
-            match self.state.run(&self.conn_info).await {
+            match self.state.run(&mut self.conn_info).await {
                 Ok(_) => {
                     log::debug!("[SUCCESS] Current State: {:?}", self.state);
                     self.state = self.state.next(Event::SuccessfulResponse);
@@ -121,10 +154,14 @@ struct AnalystConnectionInfo {
     analyst_ca_cert_path: PathBuf,
     benchmark_config_path: PathBuf,
     analyst_cert_path: PathBuf,
+    /// Form field, MIME type and path of the team certificates
+    team_certificate_paths: Vec<(&'static str, &'static str, PathBuf)>,
     /// Algorithm upload state
     algorithms_path: PathBuf,
     /// Company enroll state
     quantity: u64,
+    /// Signs the requests with the exchanged HMAC key
+    signer: RequestSigner,
 }
 
 impl AnalystConnectionInfo {
@@ -134,14 +171,16 @@ impl AnalystConnectionInfo {
         host: String,
         http_port: String,
         https_port: String,
+        benchmark_id: BenchmarkID,
         analyst_ca_cert_path: PathBuf,
         benchmark_config_path: PathBuf,
         analyst_cert_path: PathBuf,
         algorithms_path: PathBuf,
+        team_certificate_paths: Vec<(&'static str, &'static str, PathBuf)>,
         quantity: u64,
     ) -> Self {
         let routes_http = TeebenchHttpAPI::new(host.clone(), http_port);
-        let routes_https = TeebenchHttpsAPI::new(host, https_port);
+        let routes_https = TeebenchHttpsAPI::new(host, https_port, benchmark_id);
 
         AnalystConnectionInfo {
             algorithms_path,
@@ -152,14 +191,59 @@ impl AnalystConnectionInfo {
             quantity,
             routes_http,
             routes_https,
+            signer: RequestSigner::new(),
+            team_certificate_paths,
+        }
+    }
+
+    /// Exchange the HMAC key with the server or reuse the one of an earlier run
+    async fn ensure_hmac_key(&self) -> Result<(), ClientError> {
+        if self.signer.key_hex().is_some() {
+            return Ok(());
+        }
+        if let Ok(key) = std::fs::read_to_string(ANALYST_HMAC_KEY_PATH) {
+            log::debug!("Reusing the exchanged HMAC key!");
+            return self.signer.set_key_hex(&key);
         }
+
+        let encrypted_key = self.signer.exchange_key(&self.client, &self.routes_https).await?;
+        let setup_msg = AnalystSetupMsg {
+            exchange_key: encrypted_key.exchange_key,
+            public_key: encrypted_key.public_key,
+            encrypted_analyst_hmac: encrypted_key.encrypted_hmac,
+        };
+        self.signer
+            .send(&self.client, self.client.post(self.routes_https.analyst_key_exchange()).json(&setup_msg))
+            .await?;
+
+        let mut key_file = File::create(ANALYST_HMAC_KEY_PATH).expect("Could not create HMAC key file!");
+        key_file
+            .write_all(self.signer.key_hex().unwrap_or_default().as_bytes())
+            .expect("Could not write to HMAC key file!");
+        log::info!("HMAC key exchanged with the server!");
+        Ok(())
+    }
+
+    /// Move the benchmark into its next phase
+    async fn change_phase(&self, phase: BenchmarkPhase) -> Result<(), ClientError> {
+        self.signer
+            .send(&self.client, self.client.put(self.routes_https.analyst_phase()).json(&AnalystPhaseMsg { phase }))
+            .await
+            .map_err(|e| {
+                log::error!("Could not change to phase {}: {}", phase, e);
+                e
+            })?;
+
+        log::info!("Benchmark is now in phase {}", phase);
+        Ok(())
     }
 }
 
 #[derive(Debug, PartialEq)]
 /// This state machine describes the way a connecion
 /// is setup for an analyst. First he uploads the
-/// configuration for the server, then his algorithms.
+/// configuration for the server (or creates a further
+/// benchmark on a configured server), then his algorithms.
 /// After that he enrolls the given number of companies.
 /// Finally he waits until the server reports to have
 /// enough companies enrolled and starts the benchmark.
@@ -251,11 +335,61 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
 
     /// Suplementary operations that get perfromed depending on the state
     /// of the connection the analyst has.
-    async fn run(&self, conn_info: &AnalystConnectionInfo) -> Result<(), ClientError> {
+    async fn run(&self, conn_info: &mut AnalystConnectionInfo) -> Result<(), ClientError> {
         match *self {
+            AnalystState::ServerUnconfigured
+                if conn_info.routes_https.benchmark_id() != DEFAULT_BENCHMARK_ID =>
+            {
+                log::debug!("[ST] ServerUnconfigured - Creating a further benchmark!");
+
+                // The server is already set up, only the benchmarking config is sent
+                let mut config_buf = Vec::new();
+                File::open(conn_info.benchmark_config_path.clone())
+                    .unwrap()
+                    .read_to_end(&mut config_buf)
+                    .expect("Could not read Benchmark Config");
+
+                // Multipart in memory, such that the body is signed
+                let (content_type, form) = multipart_body(&[(
+                    FORM_DATA_FIELD_03_CONFIGURATION_NAME,
+                    FORM_DATA_FIELD_03_CONFIGURATION_MIME,
+                    config_buf,
+                )]);
+
+                conn_info.ensure_hmac_key().await?;
+                let rsp = conn_info
+                    .signer
+                    .send(
+                        &conn_info.client,
+                        conn_info
+                            .client
+                            .post(conn_info.routes_https.analyst_benchmarks())
+                            .header(CONTENT_TYPE, content_type)
+                            .body(form),
+                    )
+                    .await?;
+
+                // The server assigns the ID, all further routes address the created benchmark
+                let json_body = rsp.json::<RspMsg<BenchmarkID>>().await.map_err(|e| {
+                    ClientError::from((AbstractClientErrorType::BadRequest, e.to_string()))
+                })?;
+                if json_body.content != conn_info.routes_https.benchmark_id() {
+                    log::warn!(
+                        "Server created benchmark {} instead of {} - the companies have to join benchmark {}!",
+                        json_body.content,
+                        conn_info.routes_https.benchmark_id(),
+                        json_body.content
+                    );
+                    conn_info.routes_https.set_benchmark_id(json_body.content);
+                }
+                Ok(())
+            }
             AnalystState::ServerUnconfigured => {
                 log::debug!("[ST] ServerUnconfigured - Performing server configuration!");
 
+                // A fresh server does not know the HMAC key of an earlier one
+                std::fs::remove_file(ANALYST_HMAC_KEY_PATH).ok();
+
                 // Create multipart
                 let form = multipart::Form::new();
 
@@ -294,7 +428,7 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
                     .mime_str(FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME)
                     .expect("Could not create Root CA Certificate Part!");
 
-                let form = form
+                let mut form = form
                     .part(FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_NAME, root_ca_cert)
                     .part(FORM_DATA_FIELD_01_CONFIGURATION_NAME, benchmarking_config)
                     .part(
@@ -302,6 +436,17 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
                         analyst_certificate,
                     );
 
+                // Co-analysts and auditors get their roles with the setup
+                for (name, mime, path) in conn_info.team_certificate_paths.iter() {
+                    let team_certificates = std::fs::read(path)
+                        .expect("Could not read team certificates");
+                    let team_certificates = multipart::Part::bytes(team_certificates)
+                        .file_name(*name)
+                        .mime_str(mime)
+                        .expect("Could not create team certificates Part!");
+                    form = form.part(*name, team_certificates);
+                }
+
                 // Send post request
                 let rsp = conn_info
                     .client
@@ -312,10 +457,13 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
 
                 // Parse response
                 match rsp {
-                    Ok(e) => {
+                    Ok(e) if e.status().is_success() => {
                         log::debug!("Got rsp! {:?}", e);
                         return Ok(());
                     }
+                    Ok(e) => {
+                        return Err(ClientError::from_response(e).await);
+                    }
                     Err(e) => {
                         log::error!("Error! {:?}", e);
                         return Err(ClientError::from((
@@ -332,28 +480,32 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
                 log::info!("Waiting 1.3 seconds for the server to restart!");
                 tokio::time::sleep(tokio::time::Duration::from_millis(1300)).await;
 
-                // Create multipart
-                let form = multipart::Form::new();
+                // All further requests are authenticated by an HMAC
+                conn_info.ensure_hmac_key().await?;
 
-                let mut algorithms = Vec::new();
-                File::open(conn_info.algorithms_path.clone())
-                    .unwrap()
-                    .read_to_end(&mut algorithms)
-                    .expect("Could not read algorithms file!");
+                // Includes and macros are resolved locally, the server only accepts bundles
+                let algorithms = Algorithm::bundle(&conn_info.algorithms_path).map_err(|e| {
+                    ClientError::from((AbstractClientErrorType::BadRequest, e.to_string()))
+                })?;
 
-                let algorithms = multipart::Part::bytes(algorithms)
-                    .file_name(FORM_DATA_FIELD_02_ALGORIHTMS_NAME)
-                    .mime_str(FORM_DATA_FIELD_02_ALGORITHMS_MIME)
-                    .expect("Could not create algorithms Part!");
-
-                let form = form.part(FORM_DATA_FIELD_02_ALGORIHTMS_NAME, algorithms);
+                // Multipart in memory, such that the body is signed
+                let (content_type, form) = multipart_body(&[(
+                    FORM_DATA_FIELD_02_ALGORIHTMS_NAME,
+                    FORM_DATA_FIELD_02_ALGORITHMS_MIME,
+                    algorithms.into_bytes(),
+                )]);
 
                 // Send post request
                 let rsp = conn_info
-                    .client
-                    .post(conn_info.routes_https.analyst_algorithms())
-                    .multipart(form)
-                    .send()
+                    .signer
+                    .send(
+                        &conn_info.client,
+                        conn_info
+                            .client
+                            .post(conn_info.routes_https.analyst_algorithms())
+                            .header(CONTENT_TYPE, content_type)
+                            .body(form),
+                    )
                     .await;
 
                 // Parse response
@@ -364,10 +516,7 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
                     }
                     Err(e) => {
                         log::error!("Error! {:?}", e);
-                        return Err(ClientError::from((
-                            AbstractClientErrorType::NoConnection,
-                            e.to_string(),
-                        )));
+                        return Err(e);
                     }
                 }
             }
@@ -376,24 +525,27 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
                     "[ST] AlgorithmsUploaded - Registering the companies to upload their data!"
                 );
 
+                // Companies can only be enrolled once the enrollment is open
+                conn_info.change_phase(BenchmarkPhase::Enrollment).await?;
+
                 let mut uuids = Vec::<u128>::with_capacity(conn_info.quantity as usize);
                 // Enroll companies
                 for _ in 0..conn_info.quantity {
                     // Set request and save to vector
                     let enroll_req = conn_info
-                        .client
-                        .post(conn_info.routes_https.analyst_enroll_company())
-                        .send()
+                        .signer
+                        .send(&conn_info.client, conn_info.client.post(conn_info.routes_https.analyst_enroll_company()))
                         .await;
-                    if let Ok(rsp) = enroll_req {
-                        let json_body = rsp.json::<RspMsg<u128>>().await.unwrap();
-                        let uuid = json_body.content;
-                        uuids.push(uuid);
-                    } else {
-                        return Err(ClientError::from((
-                            AbstractClientErrorType::BadRequest,
-                            "Could not create company ID".to_string(),
-                        )));
+                    match enroll_req {
+                        Ok(rsp) => {
+                            let json_body = rsp.json::<RspMsg<u128>>().await.unwrap();
+                            let uuid = json_body.content;
+                            uuids.push(uuid);
+                        }
+                        Err(e) => {
+                            log::error!("Could not create company ID: {}", e);
+                            return Err(e);
+                        }
                     }
                 }
 
@@ -407,141 +559,60 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
                     .write_all(yaml_out_str.as_bytes())
                     .expect("Could not write to UUID file!");
 
-                Ok(())
+                // Companies register and upload their data from now on
+                conn_info.change_phase(BenchmarkPhase::Collection).await
             }
             AnalystState::CompaniesReady => {
                 log::debug!("[ST] CompaniesReady - Waiting for the required companies to enroll!");
                 // Wait for SSE to report that all participants are OK
-                let event_stream = conn_info
-                    .client
-                    .get(conn_info.routes_https.get_events())
-                    .send()
-                    .await;
-
-                match event_stream {
-                    Ok(rsp) => {
-                        // Get event stream
-                        let mut stream = rsp.bytes_stream().eventsource();
-
-                        // Listen for events and exit whenever the event is `Ready`
-                        while let Some(server_event) = stream.next().await {
-                            match server_event {
-                                Ok(event) => {
-                                    log::debug!(
-                                        "Event received: {:?} Type: {:?}",
-                                        event.data,
-                                        event.event
-                                    );
-
-                                    match event.data.as_str() {
-                                        "keep-alive" => {
-                                            log::debug!("Server keeps connection alive!");
-                                        }
-                                        "all-participants-enrolled" => {
-                                            log::debug!("All participants are enrolled! - Taking state transition!");
-                                            return Ok(());
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                                Err(e) => {
-                                    log::error!(
-                                        "Server event error during company enroll wait: {:?}",
-                                        e
-                                    );
-                                    return Err(ClientError::from((
-                                        AbstractClientErrorType::NotFound,
-                                        "Server did not respond correctly".to_string(),
-                                    )));
-                                }
-                            }
+                let mut events = EventStream::connect(&conn_info.client, Some(&conn_info.signer), conn_info.routes_https.analyst_events()).await?;
+                loop {
+                    match events.next().await? {
+                        ServerEvent::AllParticipantsEnrolled => {
+                            log::debug!("All participants are enrolled! - Taking state transition!");
+                            return Ok(());
                         }
-                        Ok(())
-                    }
-                    Err(e) => {
-                        log::error!("Could not establish connection for event stream: {}", e);
-                        Ok(())
+                        ServerEvent::ParticipantEnrolled { active, required } => {
+                            log::debug!("{} of {} participants enrolled!", active, required);
+                        }
+                        _ => {}
                     }
                 }
             }
             AnalystState::BenchmarkingStarted => {
                 log::debug!("[ST] BenchmarkingStarted - Waiting for the server to complete!");
 
+                // Subscribe first, such that no event of the computation is missed
+                let mut events = EventStream::connect(&conn_info.client, Some(&conn_info.signer), conn_info.routes_https.analyst_events()).await?;
+
                 let rsp = conn_info
-                    .client
-                    .post(conn_info.routes_https.analyst_benchmark())
-                    .json(&json!({ "selected_kpis": null }))
-                    .send()
+                    .signer
+                    .send(
+                        &conn_info.client,
+                        conn_info.client.post(conn_info.routes_https.analyst_benchmark()).json(&json!({ "selected_kpis": null })),
+                    )
                     .await;
+                if let Err(e) = rsp {
+                    log::error!("Error! {:?}", e);
+                    return Err(e);
+                }
 
-                // Parse response
-                match rsp {
-                    Ok(_) => {
-                        // Wait for SSE to report that the benchmark is finished - then kill process
-                        let event_stream = conn_info
-                            .client
-                            .get(conn_info.routes_https.get_events())
-                            .send()
-                            .await;
-
-                        match event_stream {
-                            Ok(rsp) => {
-                                // Get event stream
-                                let mut stream = rsp.bytes_stream().eventsource();
-
-                                while let Some(server_event) = stream.next().await {
-                                    match server_event {
-                                        Ok(event) => {
-                                            log::debug!(
-                                                "Event received: {:?} Type: {:?}",
-                                                event.data,
-                                                event.event
-                                            );
-
-                                            match event.data.as_str() {
-                                                // Wait for server to finish benchmark
-                                                "benchmarking-success" => {
-                                                    log::debug!("[SUCCESS] Server has computed all the results!");
-                                                    return Ok(());
-                                                }
-                                                "keep-alive" => {
-                                                    log::debug!("Server keeps connection alive!");
-                                                }
-                                                "connected" => {
-                                                    log::debug!(
-                                                        "Connection established to SSE Stream."
-                                                    );
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                        Err(e) => {
-                                            log::error!(
-                                                "Server event error during benchmark: {:?}",
-                                                e
-                                            );
-                                            return Err(ClientError::from((
-                                                AbstractClientErrorType::NotFound,
-                                                "Server did not respond correctly".to_string(),
-                                            )));
-                                        }
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("Could not establish server event stream connection during benchmark: {}", e);
-                            }
+                // Wait for SSE to report that the benchmark is finished - then kill process
+                loop {
+                    match events.next().await? {
+                        ServerEvent::BenchmarkingSuccess => {
+                            log::debug!("[SUCCESS] Server has computed all the results!");
+                            return Ok(());
                         }
-                    }
-                    Err(e) => {
-                        log::error!("Error! {:?}", e);
-                        return Err(ClientError::from((
-                            AbstractClientErrorType::NoConnection,
-                            e.to_string(),
-                        )));
+                        ServerEvent::Progress { step, steps, message } => {
+                            log::debug!("[{}/{}] {}", step, steps, message);
+                        }
+                        ServerEvent::ComputationFailed { reason } => {
+                            return Err(ClientError::from((AbstractClientErrorType::BadRequest, format!("The computation failed: {}", reason))));
+                        }
+                        _ => {}
                     }
                 }
-                return Ok(());
             }
             AnalystState::BenchmarkingComplete => {
                 let event = json!(AnalystEventMsg {
@@ -551,18 +622,15 @@ impl StateMachine<AnalystConnectionInfo> for AnalystState {
 
                 // Send additional message to all participants
                 conn_info
-                    .client
-                    .post(conn_info.routes_https.analyst_send_event())
-                    .json(&event)
-                    .send()
+                    .signer
+                    .send(&conn_info.client, conn_info.client.post(conn_info.routes_https.analyst_send_event()).json(&event))
                     .await
                     .ok();
 
                 // Shutdown the server
                 let rsp = conn_info
-                    .client
-                    .post(conn_info.routes_https.shutdown())
-                    .send()
+                    .signer
+                    .send(&conn_info.client, conn_info.client.post(conn_info.routes_https.shutdown()))
                     .await;
                 match rsp {
                     Ok(success) => {
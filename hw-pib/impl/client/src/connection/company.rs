@@ -1,21 +1,35 @@
 //! Company connection state machine and behaviour
 
 use async_trait::async_trait;
-use eventsource_stream::Eventsource;
-use futures_util::StreamExt;
-use reqwest::{multipart, Client};
+use benchmark::Algorithm;
+use reqwest::{header::CONTENT_TYPE, Client};
 use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 
 use crate::{
     api::TeebenchHttpsAPI,
+    attestation::{self, AttestationPolicy},
     config::EvalMode,
     connection::state::{ClientConnection, Event, StateMachine},
     error::{AbstractClientErrorType, ClientError},
+    events::EventStream,
+    export::{export_results, result_rows},
+    integrity::{multipart_body, RequestSigner},
+    report::{kpi_descriptions, write_report},
+    sealing::ResultKey,
 };
 
-use types::consts::{
-    CC_COMPANY_INPUT_DATA_PATH_KEY, FORM_DATA_FIELD_04_COMPANY_INPUT_MIME,
-    FORM_DATA_FIELD_04_COMPANY_INPUT_NAME,
+use types::{
+    consts::{
+        CC_COMPANY_ATTESTATION_POLICY_KEY, CC_COMPANY_INPUT_DATA_PATH_KEY, CC_COMPANY_RESULTS_PATH_KEY, CC_COMPANY_REPORT_PATH_KEY,
+        FORM_DATA_FIELD_04_COMPANY_INPUT_NAME,
+    },
+    input::{Input, InputFormat},
+    message::{
+        error::ErrorCode,
+        event::ServerEvent,
+        request::{CompanyConfirmMsg, CompanySetupMsg},
+        response::{AlgorithmsMsg, BenchmarkID, RspMsg, SealedResults},
+    },
 };
 
 /// Company Connection has connection information
@@ -42,6 +56,7 @@ impl ClientConnection for CompanyConnection {
         https_port: String,
         paths: &HashMap<String, PathBuf>,
         uuid: Option<u128>,
+        benchmark_id: BenchmarkID,
         _eval_mode: EvalMode,
     ) -> Self {
         // Get the paths for the analyst and check for UUID
@@ -50,12 +65,22 @@ impl ClientConnection for CompanyConnection {
             panic!("No uuid provided!");
         }
 
+        // No data is handed over without a policy (and its pinned key)
+        let attestation_policy = paths
+            .get(CC_COMPANY_ATTESTATION_POLICY_KEY)
+            .map(|path| AttestationPolicy::load(path).expect("Could not load attestation policy!"))
+            .expect("No attestation policy provided!");
+
         let conn_info = CompanyConnectionInfo::new(
             client,
             host,
             https_port,
+            benchmark_id,
             company_input_data_path,
+            paths.get(CC_COMPANY_RESULTS_PATH_KEY).cloned(),
+            paths.get(CC_COMPANY_REPORT_PATH_KEY).cloned(),
             uuid.unwrap(),
+            attestation_policy,
         );
 
         CompanyConnection {
@@ -68,7 +93,7 @@ impl ClientConnection for CompanyConnection {
     /// For understanding ist see [`CompanyState`].
     async fn run(&mut self) {
         while !self.state.eq(&CompanyState::BenchmarkingComplete) {
-            match self.state.run(&self.conn_info).await {
+            match self.state.run(&mut self.conn_info).await {
                 Ok(_) => {
                     log::debug!("[SUCCESS] Current State: {:?}", self.state);
                     self.state = self.state.next(Event::SuccessfulResponse);
@@ -92,37 +117,93 @@ struct CompanyConnectionInfo {
     routes_https: TeebenchHttpsAPI,
     /// Input data for upload
     company_input_data: PathBuf,
+    /// Export of the results (if any)
+    results_path: Option<PathBuf>,
+    /// HTML report of the results (if any)
+    report_path: Option<PathBuf>,
     /// Company UUID
     uuid: u128,
+    /// Expectations on the attested server
+    attestation_policy: AttestationPolicy,
+    /// Signs the requests with the exchanged HMAC key
+    signer: RequestSigner,
+    /// Opens the results that the server sealed to the company
+    result_key: ResultKey,
 }
 
 impl CompanyConnectionInfo {
     /// Create new analyst connection info
+    #[allow(clippy::too_many_arguments)]
     fn new(
         client: Client,
         host: String,
         https_port: String,
+        benchmark_id: BenchmarkID,
         company_input_data: PathBuf,
+        results_path: Option<PathBuf>,
+        report_path: Option<PathBuf>,
         uuid: u128,
+        attestation_policy: AttestationPolicy,
     ) -> Self {
         CompanyConnectionInfo {
             company_input_data,
+            results_path,
+            report_path,
             client,
             uuid,
-            routes_https: TeebenchHttpsAPI::new(host, https_port),
+            attestation_policy,
+            routes_https: TeebenchHttpsAPI::new(host, https_port, benchmark_id),
+            signer: RequestSigner::new(),
+            result_key: ResultKey::new(),
+        }
+    }
+
+    /// Confirm a proposed modification of the algorithms if the policy accepts it
+    ///
+    /// The modification only becomes active once every participant confirmed it.
+    async fn confirm_algorithms(&self, hash: &str) -> Result<(), ClientError> {
+        if !self.attestation_policy.accepts_algorithms(hash) {
+            return Err(ClientError::from((
+                AbstractClientErrorType::Unauthorized,
+                format!("The proposed algorithms (hash {}) are not accepted by the attestation policy", hash),
+            )));
         }
+
+        let confirm_msg = CompanyConfirmMsg { algorithm_hash: hash.to_string() };
+        self.signer.send(&self.client, self.client.post(self.routes_https.company_algorithms()).json(&confirm_msg)).await?;
+        log::info!("Confirmed the modified algorithms (hash {})!", hash);
+        Ok(())
+    }
+
+    /// The active and the pending algorithms
+    async fn algorithms(&self) -> Result<AlgorithmsMsg<Algorithm>, ClientError> {
+        let rsp = self.signer.send(&self.client, self.client.get(self.routes_https.company_algorithms())).await?;
+        let algorithms = rsp
+            .json::<RspMsg<AlgorithmsMsg<Algorithm>>>()
+            .await
+            .map_err(|e| ClientError::from((AbstractClientErrorType::BadRequest, e.to_string())))?
+            .content;
+
+        Ok(algorithms)
+    }
+
+    /// The algorithms that are run on the inputs (if any)
+    async fn active_algorithms(&self) -> Result<Option<Algorithm>, ClientError> {
+        Ok(self.algorithms().await?.active.map(|info| info.algorithm))
     }
 }
 
 #[derive(Debug, PartialEq)]
 /// This state machine describes the way a connecion
-/// is setup for a company. First it registers itself
-/// to make the certificate known to the server, then
-/// the input data is uploaded. The analyst does himself
+/// is setup for a company. First the server is attested,
+/// then the company registers itself to make the certificate
+/// and HMAC key known to the server and the input data is
+/// uploaded. The analyst does himself
 /// the benchmarking process and thus the result is
 /// received in the end, which is also downloaded and
 /// displayed.
 enum CompanyState {
+    Unattested,
     Unregistered,
     NotParticipating,
     DataUploaded,
@@ -135,11 +216,16 @@ enum CompanyState {
 #[async_trait]
 impl StateMachine<CompanyConnectionInfo> for CompanyState {
     fn init() -> CompanyState {
-        CompanyState::Unregistered
+        CompanyState::Unattested
     }
 
     fn next(&self, event: Event) -> CompanyState {
         match (self, event) {
+            (CompanyState::Unattested, Event::SuccessfulResponse) => CompanyState::Unregistered,
+            (CompanyState::Unattested, Event::ErrorResponse(reason)) => {
+                CompanyState::Failure("Attestation failed with reason: ".to_owned() + &reason)
+            }
+
             (CompanyState::Unregistered, Event::SuccessfulResponse) => {
                 CompanyState::NotParticipating
             }
@@ -179,24 +265,51 @@ impl StateMachine<CompanyConnectionInfo> for CompanyState {
         }
     }
 
-    async fn run(&self, conn_info: &CompanyConnectionInfo) -> Result<(), ClientError> {
+    async fn run(&self, conn_info: &mut CompanyConnectionInfo) -> Result<(), ClientError> {
         match *self {
+            CompanyState::Unattested => {
+                log::debug!("[ST] Unattested - Verifying the attestation of the server!");
+
+                // Only hand over the data to an attested server that meets the policy
+                let policy = &conn_info.attestation_policy;
+                let status = attestation::attest(&conn_info.client, &conn_info.routes_https, policy).await?;
+                policy.check(&status, conn_info.routes_https.benchmark_id())?;
+                log::info!("Server satisfies the attestation policy!");
+
+                return Ok(());
+            }
             CompanyState::Unregistered => {
                 log::debug!("[ST] Unregistered - Registering to the server!");
 
+                // The HMAC key is only handed to the attested server
+                let encrypted_key = conn_info
+                    .signer
+                    .exchange_key(&conn_info.client, &conn_info.routes_https)
+                    .await?;
+                let setup_msg = CompanySetupMsg {
+                    exchange_key: encrypted_key.exchange_key,
+                    public_key: encrypted_key.public_key,
+                    encrypted_company_hmac: encrypted_key.encrypted_hmac,
+                    result_public_key: conn_info.result_key.generate()?,
+                };
+
                 // Send registration request
                 let rsp = conn_info
                     .client
-                    .post(conn_info.routes_https.company_register(conn_info.uuid))
+                    .post(conn_info.routes_https.company_register())
+                    .json(&setup_msg)
                     .send()
                     .await;
 
                 // Parse response
                 match rsp {
-                    Ok(e) => {
+                    Ok(e) if e.status().is_success() => {
                         log::debug!("Got rsp! {:?}", e);
                         return Ok(());
                     }
+                    Ok(e) => {
+                        return Err(ClientError::from_response(e).await);
+                    }
                     Err(e) => {
                         log::error!("Error! {:?}", e);
                         return Err(ClientError::from((
@@ -209,9 +322,6 @@ impl StateMachine<CompanyConnectionInfo> for CompanyState {
             CompanyState::NotParticipating => {
                 log::debug!("[ST] NotParticipating - Uploading data to the server!");
 
-                // Create multipart
-                let form = multipart::Form::new();
-
                 // Byte streams of files to send and populate byte streams
                 let mut input_data_buf = Vec::new();
                 File::open(conn_info.company_input_data.clone())
@@ -219,21 +329,31 @@ impl StateMachine<CompanyConnectionInfo> for CompanyState {
                     .read_to_end(&mut input_data_buf)
                     .expect("Could not read Company input data");
 
-                // Add up streams to the form
-                let input_data = multipart::Part::bytes(input_data_buf)
-                    .file_name(FORM_DATA_FIELD_04_COMPANY_INPUT_NAME)
-                    .mime_str(FORM_DATA_FIELD_04_COMPANY_INPUT_MIME)
-                    .expect("Could not create Input data part part!");
+                // The extension decides about the format, which is checked before the upload
+                let format = InputFormat::from_path(&conn_info.company_input_data);
+                if let Err(e) = Input::from_slice(&input_data_buf, format) {
+                    log::error!("Input data is no valid {}: {}", format, e);
+                    return Err(ClientError::from((AbstractClientErrorType::BadRequest, e.to_string())));
+                }
 
-                // Append part to multipart request
-                let form = form.part(FORM_DATA_FIELD_04_COMPANY_INPUT_NAME, input_data);
+                // Multipart in memory, such that the body is signed
+                let (content_type, form) = multipart_body(&[(
+                    FORM_DATA_FIELD_04_COMPANY_INPUT_NAME,
+                    format.mime(),
+                    input_data_buf,
+                )]);
 
                 // Send post request
                 let rsp = conn_info
-                    .client
-                    .post(conn_info.routes_https.company_input_data(conn_info.uuid))
-                    .multipart(form)
-                    .send()
+                    .signer
+                    .send(
+                        &conn_info.client,
+                        conn_info
+                            .client
+                            .post(conn_info.routes_https.company_input_data())
+                            .header(CONTENT_TYPE, content_type)
+                            .body(form),
+                    )
                     .await;
 
                 // Parse response
@@ -243,91 +363,100 @@ impl StateMachine<CompanyConnectionInfo> for CompanyState {
                         return Ok(());
                     }
                     Err(e) => {
-                        log::error!("Error on upload: {:?}", e);
-                        return Err(ClientError::from((
-                            AbstractClientErrorType::NoConnection,
-                            e.to_string(),
-                        )));
+                        log::error!("Error on upload: {}", e);
+                        if let Some(ErrorCode::InputMissingVariables { variables }) = e.code() {
+                            log::error!("Add the variables {} to {}!", variables.join(", "), conn_info.company_input_data.display());
+                        }
+                        return Err(e);
                     }
                 }
             }
             CompanyState::DataUploaded => {
                 log::debug!("[ST] DataUploaded - Waiting for all the other participants to upload and the analyst to start!");
 
-                let event_stream = conn_info
-                    .client
-                    .get(conn_info.routes_https.get_events())
-                    .send()
-                    .await;
+                let mut events = EventStream::connect(&conn_info.client, Some(&conn_info.signer), conn_info.routes_https.company_events()).await?;
+
+                // A modification might have been proposed before we subscribed
+                if let Some(pending) = conn_info.algorithms().await?.pending {
+                    conn_info.confirm_algorithms(&pending.hash).await?;
+                }
 
-                match event_stream {
-                    Ok(rsp) => {
-                        // Get event stream
-                        let mut stream = rsp.bytes_stream().eventsource();
-
-                        // Listen for events and exit whenever the event is `Ready`
-                        while let Some(server_event) = stream.next().await {
-                            match server_event {
-                                Ok(event) => {
-                                    log::debug!(
-                                        "Event received: {:?} Type: {:?}",
-                                        event.data,
-                                        event.event
-                                    );
-
-                                    // Wait for finalizing the benchmark on server side
-                                    match event.data.as_str() {
-                                        "benchmarking-success" => {
-                                            log::debug!("Benchmarking complete! - Results are ready for retrieval!");
-                                            return Ok(());
-                                        }
-                                        "keep-alive" => {
-                                            log::debug!("Server keeps connection alive!");
-                                        }
-                                        _ => {}
-                                    };
-                                }
-                                Err(e) => {
-                                    log::error!("Server event error: {:?}", e);
-                                    return Err(ClientError::from((
-                                        AbstractClientErrorType::NotFound,
-                                        "Server did not respond correctly".to_string(),
-                                    )));
-                                }
-                            };
+                // Listen for events and exit whenever our results are ready
+                loop {
+                    match events.next().await? {
+                        // Sent privately once our results are sealed
+                        ServerEvent::ResultsReady => {
+                            log::debug!("Benchmarking complete! - Results are ready for retrieval!");
+                            return Ok(());
                         }
+                        // The modification only becomes active with our confirmation
+                        ServerEvent::AlgorithmsProposed { hash } => {
+                            log::info!("The analyst proposes modified algorithms (hash {})", hash);
+                            conn_info.confirm_algorithms(&hash).await?;
+                        }
+                        // The analyst modified the algorithms and our input lacks variables now
+                        ServerEvent::InputInvalid { company, missing } if company == conn_info.uuid => {
+                            log::error!("Input was discarded by the server, missing variables: {}", missing.join(","));
+                            return Err(ClientError::from((
+                                AbstractClientErrorType::BadRequest,
+                                format!("Input is invalid for the modified algorithms, missing variables: {}", missing.join(",")),
+                            )));
+                        }
+                        ServerEvent::CompanyRevoked { company } if company == conn_info.uuid => {
+                            return Err(ClientError::from((AbstractClientErrorType::Unauthorized, "The analyst removed us from the benchmark".to_string())));
+                        }
+                        ServerEvent::ComputationFailed { reason } => {
+                            log::warn!("The computation failed ({}) - waiting for the analyst to start again!", reason);
+                        }
+                        _ => {}
                     }
-                    Err(e) => {
-                        log::error!("Could not connect to server event stream {:?}!", e);
-                    }
-                };
-
-                return Ok(());
+                }
             }
             CompanyState::ResultsReady => {
                 log::debug!("[ST] ResultsReady - Getting the results from the server!");
 
-                // Do not ask for results as they are not important in eval
-                return Ok(());
+                let rsp = conn_info
+                    .signer
+                    .send(&conn_info.client, conn_info.client.get(conn_info.routes_https.company_results()))
+                    .await?;
+
+                // Only our result key opens the results
+                let sealed_results = rsp
+                    .json::<RspMsg<SealedResults>>()
+                    .await
+                    .map_err(|e| ClientError::from((AbstractClientErrorType::BadRequest, e.to_string())))?
+                    .content;
+                let results = conn_info.result_key.open(&sealed_results, conn_info.routes_https.benchmark_id(), conn_info.uuid)?;
+                log::debug!("Results for company benchmark: {:#?}", results);
+
+                // Written before the confirmation, which deletes the results on the server
+                if let Some(results_path) = &conn_info.results_path {
+                    export_results(&results, results_path)?;
+                    log::info!("Exported the results to {:?}", results_path);
+                }
+                if let Some(report_path) = &conn_info.report_path {
+                    // Without the algorithms the report lacks the descriptions only
+                    let descriptions = match conn_info.active_algorithms().await {
+                        Ok(algorithm) => algorithm.map(|algorithm| kpi_descriptions(&algorithm)).unwrap_or_default(),
+                        Err(e) => {
+                            log::warn!("Algorithms for the descriptions of the report are not available: {}", e);
+                            HashMap::new()
+                        }
+                    };
+                    write_report(report_path, &format!("Benchmark report of company {}", conn_info.uuid), &result_rows(&results), &descriptions)?;
+                    log::info!("Wrote the report to {:?}", report_path);
+                }
 
-                /*
-                let rsp = conn_info.client
-                    .get(conn_info.routes_https.company_results(conn_info.uuid))
-                    .send()
+                // The server deletes the results once their download is confirmed
+                let rsp = conn_info
+                    .signer
+                    .send(&conn_info.client, conn_info.client.delete(conn_info.routes_https.company_results()))
                     .await;
+                if let Err(e) = rsp {
+                    log::warn!("Download of the results could not be confirmed: {}", e);
+                }
 
-                // Parse response
-                match rsp {
-                    Ok(e) => {
-                        let result_msg = e.json::<RspMsg<Output>>().await.expect("Message could not be decoded");
-                        log::debug!("Results for company benchmark: {:#?}", result_msg.content);
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        log::error!("Error! {:?}", e);
-                        return Err(ClientError::from((AbstractClientErrorType::NotFound, e.to_string())));
-                    }
-                };*/
+                return Ok(());
             }
             CompanyState::BenchmarkingComplete => {
                 log::debug!("[FINISH]  I guess I die.");
@@ -1,15 +1,18 @@
 use std::{path::PathBuf, collections::HashMap, ops::Div, io::BufWriter};
 
 use async_trait::async_trait;
-use eventsource_stream::Eventsource;
-use futures_util::{StreamExt};
 use reqwest::Client;
-use types::consts::CC_SPECTATOR_EVAL_OUTPUT_KEY;
+use types::{consts::CC_SPECTATOR_EVAL_OUTPUT_KEY, message::{event::{EvalMetric, ServerEvent}, request::AnalystSetupMsg, response::BenchmarkID}};
 
-use crate::{api::TeebenchHttpsAPI, config::EvalMode};
+use crate::{api::TeebenchHttpsAPI, config::EvalMode, error::ClientError, events::EventStream, integrity::RequestSigner};
 
 use super::state::ClientConnection;
 
+/// Attempts of the key exchange while the analyst sets up the server
+static KEY_EXCHANGE_ATTEMPTS: u32 = 60;
+/// Wait between the attempts of the key exchange
+static KEY_EXCHANGE_RETRY_MS: u64 = 1000;
+
 // [AlgoUpload, AlgoParse, AlgoTopo, CompUpload, CompParse, BenchSingleAVG, BenchTotal, BenchClust, BenchAgg, BenchAssemb]
 #[derive(Debug, serde::Serialize)]
 struct EvalRecord {
@@ -29,6 +32,9 @@ struct EvalRecord {
 
 /// Analyst Connection has connection Strings
 /// and a state which has to be initialized.
+///
+/// The measurements are only sent to the analyst team, thus the spectator
+/// is an auditor of the team with its own certificate and HMAC key.
 pub struct SpectatorConnection {
     /// Conncetion information
     conn_info: SpectatorConnectionInfo,
@@ -45,20 +51,51 @@ struct SpectatorConnectionInfo {
     eval_output: PathBuf,
     /// Eval mode to write to logs
     eval_mode: EvalMode,
+    /// Signs the requests with the exchanged HMAC key
+    signer: RequestSigner,
 }
 
 
 impl SpectatorConnectionInfo {
     /// Create new analyst connection info 
-    fn new(client: Client, host: String, https_port: String, eval_output: PathBuf, eval_mode: EvalMode) -> Self {
+    fn new(client: Client, host: String, https_port: String, benchmark_id: BenchmarkID, eval_output: PathBuf, eval_mode: EvalMode) -> Self {
         
-        let routes_https = TeebenchHttpsAPI::new(host, https_port);
+        let routes_https = TeebenchHttpsAPI::new(host, https_port, benchmark_id);
 
         SpectatorConnectionInfo {
             client,
             routes_https,
             eval_output,
-            eval_mode
+            eval_mode,
+            signer: RequestSigner::new(),
+        }
+    }
+
+    /// Exchange the HMAC key once the analyst registered the spectator as auditor
+    async fn exchange_hmac_key(&self) -> Result<(), ClientError> {
+        let mut attempt = 1;
+        loop {
+            let result = async {
+                let encrypted_key = self.signer.exchange_key(&self.client, &self.routes_https).await?;
+                let setup_msg = AnalystSetupMsg {
+                    exchange_key: encrypted_key.exchange_key,
+                    public_key: encrypted_key.public_key,
+                    encrypted_analyst_hmac: encrypted_key.encrypted_hmac,
+                };
+                self.signer
+                    .send(&self.client, self.client.post(self.routes_https.analyst_key_exchange()).json(&setup_msg))
+                    .await
+            }.await;
+
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < KEY_EXCHANGE_ATTEMPTS => {
+                    log::debug!("[Spectator] Key exchange failed ({}), retrying!", e);
+                    attempt += 1;
+                    tokio::time::sleep(tokio::time::Duration::from_millis(KEY_EXCHANGE_RETRY_MS)).await;
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 }
@@ -70,9 +107,9 @@ impl ClientConnection for SpectatorConnection {
     
     /// Create an analyst connection from analyst configuration information 
     /// that is passed internally to create the client connection. 
-    fn new(client: Client, host: String, _http_port: String, https_port: String, paths: &HashMap<String, PathBuf>, _uuid: Option<u128>, eval_mode: EvalMode ) -> Self {
+    fn new(client: Client, host: String, _http_port: String, https_port: String, paths: &HashMap<String, PathBuf>, _uuid: Option<u128>, benchmark_id: BenchmarkID, eval_mode: EvalMode ) -> Self {
 
-        let conn_info = SpectatorConnectionInfo::new(client, host, https_port, paths.get(CC_SPECTATOR_EVAL_OUTPUT_KEY).unwrap().into(), eval_mode);
+        let conn_info = SpectatorConnectionInfo::new(client, host, https_port, benchmark_id, paths.get(CC_SPECTATOR_EVAL_OUTPUT_KEY).unwrap().into(), eval_mode);
         SpectatorConnection { conn_info }
     }
 
@@ -87,115 +124,88 @@ impl ClientConnection for SpectatorConnection {
 
         let mut comp_upload: Vec<u128> = vec![];
         let mut comp_parse: Vec<u128> = vec![];
-        let mut comp_bench_single: Vec<u128> = vec![];
 
         let mut bench_total: u128 = 0;
         let mut bench_clust: u128 = 0;
         let mut bench_agg: u128 = 0;
         let mut bench_assemb: u128 = 0;
 
-        // Get event stream
-        match self.conn_info.client.get(self.conn_info.routes_https.get_events()).send().await {
-            // Successful response
-            Ok(rsp) => {
-                let mut stream = rsp.bytes_stream().eventsource();
-
-                // Listen for events and exit whenever the event is `Ready`
-                while let Some(server_event) =  stream.next().await{
-                    match server_event {
-                        Ok(event) => {
-                            
-                            // LOG TO CSV
-                            match event.data.as_str() {
-                                "connected" |
-                                "keep-alive" | 
-                                "Company benchmark started!" |
-                                "[1/4] Processing Aggregation: Complete! Clustering data..." |
-                                "[2/4] Clustering KPI results: Complete! Aggregating..." |
-                                "[3/4] Processing Aggregation: Complete! Assembling information.." |
-                                "[4/4] Assembling Information: Complete! You can return your results now!" |
-                                "Benchmark sucessfully completed! Analyst is happy!" |
-                                "all-participants-enrolled" => {},
-                                
-                                msg => {
-                                    
-                                    
-                                    if msg.contains("EVAL") {
-
-                                        // We know that we have the eval format
-                                        let num: Vec<&str> = msg.clone().split_whitespace().collect();
-                                        let var = num[1].parse::<u128>().unwrap();
-
-                                        if msg.contains("ALGO") && msg.contains("UPLOAD") { algo_upload = var; }
-                                        if msg.contains("ALGO") && msg.contains("PARSE") { algo_parse = var; }
-                                        if msg.contains("ALGO") && msg.contains("TOPO") { algo_topo = var; }
-
-                                        if msg.contains("COMP") && msg.contains("UPLOAD") { comp_upload.push(var); }
-                                        if msg.contains("COMP") && msg.contains("PARSE") { comp_parse.push(var); }
-                                        
-                                        if msg.contains("BENCH") && msg.contains("COMP") && msg.contains("SINGLE") { comp_bench_single.push(var); }
-                                        if msg.contains("BENCH") && msg.contains("COMP") && msg.contains("TOTAL") { bench_total = var; }
-                                        if msg.contains("BENCH") && msg.contains("CLUST") { bench_clust = var; }
-                                        if msg.contains("BENCH") && msg.contains("AGG") { bench_agg = var; }
-                                        if msg.contains("BENCH") && msg.contains("ASSEMB") { bench_assemb = var; }
-
-                                    } else {
-                                        log::info!("{}", msg);
-                                    }
-                                },
-                            }
-                        },
-                        Err(_) => { 
-                            if comp_upload.is_empty() || comp_parse.is_empty() || comp_bench_single.is_empty(){ log::error!("FATAL: No companies were registered!"); }
-                            else {
-                                // Do CSV serialization
-
-                                let comp_bench_sum: u128 = comp_bench_single.iter().sum();
-                                let comp_upload_sum: u128 = comp_upload.iter().sum();
-                                let comp_parse_sum: u128 = comp_parse.iter().sum();
-
-                                let comp_bench_avg: u128 = comp_bench_sum.div(comp_bench_single.len() as u128);
-                                let comp_upload_avg: u128 = comp_upload_sum.div(comp_upload.len() as u128);
-                                let comp_parse_avg: u128 = comp_parse_sum.div(comp_parse.len() as u128);
-
-
-                                let record = EvalRecord {
-                                    algo_parse, 
-                                    algo_topo, 
-                                    algo_upload, 
-                                    bench_agg, 
-                                    bench_assemb, 
-                                    bench_clust, 
-                                    comp_bench_avg, 
-                                    bench_total, 
-                                    comp_parse_avg, 
-                                    comp_upload_avg,
-                                    eval_mode: self.conn_info.eval_mode.to_string()
-                                };
-
-                                // Append new headers only when file does not exist
-                                let exists = PathBuf::from(self.conn_info.eval_output.clone()).exists();
-                                let outfile = std::fs::OpenOptions::new()
-                                    .write(true)
-                                    .append(true)
-                                    .create(true)
-                                    .open(self.conn_info.eval_output.clone())
-                                    .unwrap();
-                                let outbuf = BufWriter::new(outfile);
-                                let mut wtr = csv::WriterBuilder::new()    
-                                    .has_headers(!exists)
-                                    .from_writer(outbuf);
-
-                                wtr.serialize(record).expect("Could not write benchmarking result to CSV file");
-                                wtr.flush().expect("Could not flush buffer to file");
-
-                            }
-                            log::warn!("Connection to server lost!"); break;}
-                    }
-                };                
-            },
+        // Get event stream of the analyst team
+        if let Err(e) = self.conn_info.exchange_hmac_key().await {
+            log::error!("FATAL {}", e); std::process::exit(-1);
+        }
+        let mut events = match EventStream::connect(&self.conn_info.client, Some(&self.conn_info.signer), self.conn_info.routes_https.analyst_events()).await {
+            Ok(events) => events,
             Err(e) => {log::error!("FATAL {}", e); std::process::exit(-1);}
+        };
+
+        // Listen for events until the server is gone
+        loop {
+            match events.next().await {
+                // LOG TO CSV
+                Ok(ServerEvent::Evaluation { metric, value }) => match metric {
+                    EvalMetric::AlgoUpload => algo_upload = value,
+                    EvalMetric::AlgoParse => algo_parse = value,
+                    EvalMetric::AlgoTopo => algo_topo = value,
+                    EvalMetric::CompUpload => comp_upload.push(value),
+                    EvalMetric::CompParse => comp_parse.push(value),
+                    EvalMetric::BenchCompTotal => bench_total = value,
+                    EvalMetric::BenchClust => bench_clust = value,
+                    EvalMetric::BenchAgg => bench_agg = value,
+                    EvalMetric::BenchAssemble => bench_assemb = value,
+                },
+                Ok(ServerEvent::Progress { .. }) |
+                Ok(ServerEvent::BenchmarkingSuccess) |
+                Ok(ServerEvent::AllParticipantsEnrolled) => {},
+                Ok(event) => log::info!("{:?}", event),
+                Err(_) => { 
+                    if comp_upload.is_empty() || comp_parse.is_empty() { log::error!("FATAL: No companies were registered!"); }
+                    else {
+                        // Do CSV serialization
+
+                        let comp_upload_sum: u128 = comp_upload.iter().sum();
+                        let comp_parse_sum: u128 = comp_parse.iter().sum();
+
+                        // Single companies are not measured, their average is taken from the whole computation
+                        let comp_bench_avg: u128 = bench_total.div(comp_parse.len() as u128);
+                        let comp_upload_avg: u128 = comp_upload_sum.div(comp_upload.len() as u128);
+                        let comp_parse_avg: u128 = comp_parse_sum.div(comp_parse.len() as u128);
+
+
+                        let record = EvalRecord {
+                            algo_parse, 
+                            algo_topo, 
+                            algo_upload, 
+                            bench_agg, 
+                            bench_assemb, 
+                            bench_clust, 
+                            comp_bench_avg, 
+                            bench_total, 
+                            comp_parse_avg, 
+                            comp_upload_avg,
+                            eval_mode: self.conn_info.eval_mode.to_string()
+                        };
+
+                        // Append new headers only when file does not exist
+                        let exists = PathBuf::from(self.conn_info.eval_output.clone()).exists();
+                        let outfile = std::fs::OpenOptions::new()
+                            .write(true)
+                            .append(true)
+                            .create(true)
+                            .open(self.conn_info.eval_output.clone())
+                            .unwrap();
+                        let outbuf = BufWriter::new(outfile);
+                        let mut wtr = csv::WriterBuilder::new()    
+                            .has_headers(!exists)
+                            .from_writer(outbuf);
+
+                        wtr.serialize(record).expect("Could not write benchmarking result to CSV file");
+                        wtr.flush().expect("Could not flush buffer to file");
+
+                    }
+                    log::warn!("Connection to server lost!"); break;}
+            }
         }
         
     }
-}
\ No newline at end of file
+}
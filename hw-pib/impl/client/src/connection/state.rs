@@ -3,6 +3,8 @@ use std::{collections::HashMap, path::PathBuf};
 
 use reqwest::Client;
 
+use types::message::response::BenchmarkID;
+
 use crate::{config::EvalMode, error::ClientError};
 
 #[derive(Debug, Clone)]
@@ -19,13 +21,15 @@ pub trait StateMachine<T> {
     fn init() -> Self;
     /// Take next state transition with event (alphabet literal)
     fn next(&self, event: Event) -> Self;
-    /// Run operation for one specific state
-    async fn run(&self, conn_info: &T) -> Result<(), ClientError>;
+    /// Run operation for one specific state (it may adopt what the server
+    /// assigned, e.g. the ID of a created benchmark)
+    async fn run(&self, conn_info: &mut T) -> Result<(), ClientError>;
 }
 
 #[async_trait]
 pub trait ClientConnection {
     /// Create a new client connection
+    #[allow(clippy::too_many_arguments)]
     fn new(
         client: Client,
         host: String,
@@ -33,6 +37,7 @@ pub trait ClientConnection {
         https_port: String,
         paths: &HashMap<String, PathBuf>,
         uuid: Option<u128>,
+        benchmark_id: BenchmarkID,
         eval_mode: EvalMode,
     ) -> Self;
     /// Run the client connection which hols a state machine in it
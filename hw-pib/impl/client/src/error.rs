@@ -1,6 +1,8 @@
 use std::{fmt, error::Error};
+use reqwest::{Response, StatusCode};
 use serde::Serialize;
 use serde_json::{Value as JsonValue};
+use types::message::error::{ErrorCode, ErrorMsg};
 
 #[derive(Debug)]
 pub struct ClientError {
@@ -25,6 +27,32 @@ enum ClientErrorCause {
     Unauthorized(JsonValue),
     BadRequest(JsonValue),
     NotFound(JsonValue),
+    /// Failed request with the error code of the server
+    Server(ErrorMsg),
+}
+
+impl ClientError {
+    /// Error code of the server (if the server rejected the request)
+    pub fn code(&self) -> Option<&ErrorCode> {
+        match &self.cause {
+            ClientErrorCause::Server(msg) => Some(&msg.code),
+            _ => None,
+        }
+    }
+
+    /// Error of a failed request, servers without error codes are told apart by the status
+    pub(crate) async fn from_response(rsp: Response) -> Self {
+        let status = rsp.status();
+        let body = rsp.text().await.unwrap_or_default();
+        if let Ok(msg) = serde_json::from_str::<ErrorMsg>(&body) {
+            return ClientError::from(msg);
+        }
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ClientError::from((AbstractClientErrorType::Unauthorized, body)),
+            StatusCode::NOT_FOUND => ClientError::from((AbstractClientErrorType::NotFound, body)),
+            _ => ClientError::from((AbstractClientErrorType::BadRequest, body)),
+        }
+    }
 }
 
 
@@ -35,6 +63,7 @@ impl fmt::Display for ClientError {
             ClientErrorCause::Unauthorized(error) => {write!(f, "Unauthorized: {}", error)},
             ClientErrorCause::BadRequest(error) => {write!(f, "BadRequest: {}", error)},
             ClientErrorCause::NotFound(error) => {write!(f, "NotFound: {}", error)},
+            ClientErrorCause::Server(error) => {write!(f, "Server: {}", error)},
         } 
     }
 }
@@ -68,6 +97,12 @@ impl From<(AbstractClientErrorType, String)> for ClientError {
     }
 }
 
+impl From<ErrorMsg> for ClientError {
+    fn from(error: ErrorMsg) -> Self {
+        ClientError { cause: ClientErrorCause::Server(error) }
+    }
+}
+
 impl From<ClientErrorCause> for ClientError {
     fn from(error: ClientErrorCause) -> Self {
         ClientError { cause: error }
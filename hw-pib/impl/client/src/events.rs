@@ -0,0 +1,121 @@
+//! Server sent events of a benchmark
+//!
+//! Events are JSON encoded [`ServerEvent`]s, the stream remembers the ID of
+//! the last one. When the connection drops, it reconnects with the
+//! `Last-Event-ID` header and the server replays the events that were
+//! missed meanwhile.
+//!
+//! Private channels of a company or the analyst team are authenticated,
+//! their (re-)connections are signed with the [`RequestSigner`].
+
+use std::{pin::Pin, time::Duration};
+
+use eventsource_stream::{Event, EventStreamError, Eventsource};
+use futures_util::{Stream, StreamExt};
+use reqwest::{header::CONTENT_TYPE, Client};
+use types::{consts::LAST_EVENT_ID_FIELD, message::event::ServerEvent};
+
+use crate::{
+    error::{AbstractClientErrorType, ClientError},
+    integrity::RequestSigner,
+};
+
+/// Attempts to reconnect before the stream is considered lost
+const MAX_RECONNECTS: u32 = 3;
+
+/// Pause before a reconnect
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+type SseStream = Pin<Box<dyn Stream<Item = Result<Event, EventStreamError<reqwest::Error>>> + Send>>;
+
+/// Typed event stream of one benchmark
+pub(crate) struct EventStream {
+    client: Client,
+    signer: Option<RequestSigner>,
+    url: String,
+    last_event_id: Option<String>,
+    stream: Option<SseStream>,
+    reconnects: u32,
+}
+
+impl EventStream {
+    /// Subscribe to the events at `url`, private channels require a `signer`
+    pub(crate) async fn connect(client: &Client, signer: Option<&RequestSigner>, url: String) -> Result<Self, ClientError> {
+        let mut events = EventStream { client: client.clone(), signer: signer.cloned(), url, last_event_id: None, stream: None, reconnects: 0 };
+        events.stream = Some(subscribe(&events.client, events.signer.as_ref(), &events.url, None).await?);
+        Ok(events)
+    }
+
+    /// Wait for the next event
+    ///
+    /// Fails once the connection could not be established again.
+    pub(crate) async fn next(&mut self) -> Result<ServerEvent, ClientError> {
+        loop {
+            let stream = match self.stream.as_mut() {
+                Some(stream) => stream,
+                None => {
+                    self.reconnects += 1;
+                    if self.reconnects > MAX_RECONNECTS {
+                        return Err(ClientError::from((AbstractClientErrorType::NoConnection, "The event stream of the server was lost".to_string())));
+                    }
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    log::debug!("Reconnecting to the event stream after event {:?}", self.last_event_id);
+                    match subscribe(&self.client, self.signer.as_ref(), &self.url, self.last_event_id.as_deref()).await {
+                        Ok(stream) => self.stream.insert(stream),
+                        Err(e) => {
+                            log::warn!("Could not reconnect to the event stream: {}", e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            match stream.next().await {
+                Some(Ok(event)) => {
+                    self.reconnects = 0;
+                    if !event.id.is_empty() {
+                        self.last_event_id = Some(event.id.clone());
+                    }
+                    log::debug!("Event received: {:?} Type: {:?}", event.data, event.event);
+
+                    // Events of newer servers are skipped
+                    match serde_json::from_str::<ServerEvent>(&event.data) {
+                        Ok(server_event) => return Ok(server_event),
+                        Err(e) => log::debug!("Skipping unknown event {:?}: {}", event.event, e),
+                    }
+                }
+                Some(Err(e)) => {
+                    log::warn!("Server event error: {:?}", e);
+                    self.stream = None;
+                }
+                None => {
+                    log::debug!("The server closed the event stream");
+                    self.stream = None;
+                }
+            }
+        }
+    }
+}
+
+/// Open the event stream, continuing after `last_event_id`
+async fn subscribe(client: &Client, signer: Option<&RequestSigner>, url: &str, last_event_id: Option<&str>) -> Result<SseStream, ClientError> {
+    let mut request = client.get(url);
+    if let Some(last_event_id) = last_event_id {
+        request = request.header(LAST_EVENT_ID_FIELD, last_event_id);
+    }
+
+    let rsp = match signer {
+        Some(signer) => signer.send(client, request).await?,
+        None => request.send().await.map_err(|e| ClientError::from((AbstractClientErrorType::NoConnection, e.to_string())))?,
+    };
+    if !rsp.status().is_success() {
+        return Err(ClientError::from_response(rsp).await);
+    }
+
+    // A rejected request verification answers with its reason instead
+    let is_event_stream = rsp.headers().get(CONTENT_TYPE).and_then(|mime| mime.to_str().ok()).is_some_and(|mime| mime.starts_with("text/event-stream"));
+    if !is_event_stream {
+        return Err(ClientError::from((AbstractClientErrorType::Unauthorized, rsp.text().await.unwrap_or_default())));
+    }
+    Ok(Box::pin(rsp.bytes_stream().eventsource()))
+}
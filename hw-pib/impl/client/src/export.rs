@@ -0,0 +1,285 @@
+//! Export of the results of a company
+//!
+//! The results become one table with a row per KPI and dimension: the own
+//! value of the company next to the statistics over all participants. The
+//! companies are ranked by the first dimension of a KPI, thus the minimum,
+//! maximum, median and quartiles are only exported for it (the values of
+//! further dimensions belong to single companies); the average is exported
+//! for every dimension. The table is written as CSV, pretty JSON or XLSX
+//! (chosen by the extension), such that it opens without knowledge of the
+//! YAML / JSON messages.
+//! Exported CSV and JSON files are read again for reports.
+//!
+//! Spreadsheets evaluate cells that start with `=`, `+`, `-` or `@` as
+//! formulas, such KPI names (and ones that start with `'` themselves) are
+//! prefixed with `'` in CSV and XLSX.
+
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use types::output::{Output, OutputVariable};
+
+use crate::error::{AbstractClientErrorType, ClientError};
+
+/// Formats of the exported results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFormat {
+    Csv,
+    Json,
+    Xlsx,
+}
+
+impl ResultFormat {
+    /// Format of a file by its extension (`.csv`, `.json` or `.xlsx`)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(ResultFormat::Csv),
+            "json" => Some(ResultFormat::Json),
+            "xlsx" => Some(ResultFormat::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+/// Column titles of CSV and XLSX (JSON uses the field names)
+const HEADER: [&str; 9] = ["KPI", "Dimension", "Own value", "Min", "Max", "Average", "Median", "Lower quartile", "Upper quartile"];
+
+/// First characters of a cell that spreadsheets evaluate as formula (or control characters that hide one)
+/// and the quote of the prefix itself, such that the text is read back unchanged
+const ESCAPED_PREFIXES: [char; 7] = ['=', '+', '-', '@', '\t', '\r', '\''];
+
+/// Text of a spreadsheet cell that is never evaluated
+fn text_cell(text: &str) -> String {
+    if text.starts_with(ESCAPED_PREFIXES) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Original text of a cell written by [`text_cell`]
+fn from_text_cell(cell: &str) -> &str {
+    match cell.strip_prefix('\'') {
+        Some(text) if text.starts_with(ESCAPED_PREFIXES) => text,
+        _ => cell,
+    }
+}
+
+/// One dimension of one KPI, statistics without values stay empty
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ResultRow {
+    pub(crate) kpi: String,
+    /// Starts at 1
+    pub(crate) dimension: usize,
+    pub(crate) own_value: Option<f64>,
+    pub(crate) min: Option<f64>,
+    pub(crate) max: Option<f64>,
+    pub(crate) average: Option<f64>,
+    pub(crate) median: Option<f64>,
+    pub(crate) lower_quartile: Option<f64>,
+    pub(crate) upper_quartile: Option<f64>,
+}
+
+impl ResultRow {
+    /// The values in the order of the [`HEADER`] (after KPI and dimension)
+    fn values(&self) -> [Option<f64>; 7] {
+        [self.own_value, self.min, self.max, self.average, self.median, self.lower_quartile, self.upper_quartile]
+    }
+}
+
+/// Rows of all KPIs sorted by their name
+pub(crate) fn result_rows(output: &Output) -> Vec<ResultRow> {
+    let vars: BTreeMap<&String, &OutputVariable> = output.vars().iter().collect();
+
+    vars.into_iter()
+        .flat_map(|(kpi, var)| {
+            // The sorted values are ascending: the worst in class is the minimum
+            let columns = [var.result(), var.worst_in_class(), var.best_in_class(), var.average(), var.median(), var.lower_quantile(), var.upper_quantile()];
+            let dimensions = columns.iter().map(|column| column.len()).max().unwrap_or_default();
+
+            (0..dimensions).map(move |i| {
+                let ranked = |column: &Vec<f64>| if i == 0 { column.get(i).copied() } else { None };
+                ResultRow {
+                    kpi: kpi.to_string(),
+                    dimension: i + 1,
+                    own_value: columns[0].get(i).copied(),
+                    min: ranked(columns[1]),
+                    max: ranked(columns[2]),
+                    average: columns[3].get(i).copied(),
+                    median: ranked(columns[4]),
+                    lower_quartile: ranked(columns[5]),
+                    upper_quartile: ranked(columns[6]),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Write the results to the path in the format of its extension
+pub(crate) fn export_results(output: &Output, path: &Path) -> Result<(), ClientError> {
+    let format = ResultFormat::from_path(path).ok_or_else(|| export_error(format!("Unknown format of {:?} (csv, json or xlsx)", path)))?;
+    let rows = result_rows(output);
+
+    match format {
+        ResultFormat::Csv => write_csv(&rows, path),
+        ResultFormat::Json => {
+            let file = File::create(path).map_err(|e| export_error(e.to_string()))?;
+            serde_json::to_writer_pretty(file, &rows).map_err(|e| export_error(e.to_string()))
+        }
+        ResultFormat::Xlsx => write_xlsx(&rows, path),
+    }
+}
+
+fn write_csv(rows: &[ResultRow], path: &Path) -> Result<(), ClientError> {
+    let mut writer = csv::Writer::from_path(path).map_err(|e| export_error(e.to_string()))?;
+    writer.write_record(HEADER).map_err(|e| export_error(e.to_string()))?;
+
+    for row in rows {
+        let mut record = vec![text_cell(&row.kpi), row.dimension.to_string()];
+        record.extend(row.values().iter().map(|value| value.map(|value| value.to_string()).unwrap_or_default()));
+        writer.write_record(&record).map_err(|e| export_error(e.to_string()))?;
+    }
+
+    writer.flush().map_err(|e| export_error(e.to_string()))
+}
+
+fn write_xlsx(rows: &[ResultRow], path: &Path) -> Result<(), ClientError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Results").map_err(|e| export_error(e.to_string()))?;
+
+    let bold = Format::new().set_bold();
+    for (col, title) in HEADER.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *title, &bold).map_err(|e| export_error(e.to_string()))?;
+    }
+
+    for (i, row) in rows.iter().enumerate() {
+        let line = i as u32 + 1;
+        worksheet.write_string(line, 0, text_cell(&row.kpi)).map_err(|e| export_error(e.to_string()))?;
+        worksheet.write_number(line, 1, row.dimension as f64).map_err(|e| export_error(e.to_string()))?;
+        for (col, value) in row.values().iter().enumerate() {
+            if let Some(value) = value {
+                worksheet.write_number(line, col as u16 + 2, *value).map_err(|e| export_error(e.to_string()))?;
+            }
+        }
+    }
+    worksheet.set_freeze_panes(1, 0).map_err(|e| export_error(e.to_string()))?;
+    worksheet.autofit();
+
+    workbook.save(path).map_err(|e| export_error(e.to_string()))
+}
+
+/// Read the rows of an exported CSV or JSON file
+pub(crate) fn read_results(path: &Path) -> Result<Vec<ResultRow>, ClientError> {
+    match ResultFormat::from_path(path) {
+        Some(ResultFormat::Json) => {
+            let file = File::open(path).map_err(|e| export_error(e.to_string()))?;
+            serde_json::from_reader(file).map_err(|e| export_error(e.to_string()))
+        }
+        Some(ResultFormat::Csv) => {
+            let mut reader = csv::Reader::from_path(path).map_err(|e| export_error(e.to_string()))?;
+            let mut rows = Vec::new();
+            for record in reader.records() {
+                let record = record.map_err(|e| export_error(e.to_string()))?;
+                let cell = |i: usize| -> Result<Option<f64>, ClientError> {
+                    match record.get(i).unwrap_or_default().trim() {
+                        "" => Ok(None),
+                        value => value.parse().map(Some).map_err(|_| export_error(format!("{:?} is not a number", value))),
+                    }
+                };
+                rows.push(ResultRow {
+                    kpi: from_text_cell(record.get(0).unwrap_or_default()).to_string(),
+                    dimension: cell(1)?.map_or(1, |dimension| dimension as usize),
+                    own_value: cell(2)?,
+                    min: cell(3)?,
+                    max: cell(4)?,
+                    average: cell(5)?,
+                    median: cell(6)?,
+                    lower_quartile: cell(7)?,
+                    upper_quartile: cell(8)?,
+                });
+            }
+            Ok(rows)
+        }
+        _ => Err(export_error(format!("Results are read from CSV or JSON exports, not {:?}", path))),
+    }
+}
+
+fn export_error(reason: String) -> ClientError {
+    ClientError::from((AbstractClientErrorType::BadRequest, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn output() -> Output {
+        let mut output = Output::new_empty();
+        output.add_var(OutputVariable::new(
+            "revenue".to_string(),
+            vec![3.0, 30.0],
+            vec![5.0, 50.0],
+            vec![1.0, 10.0],
+            vec![2.5, 22.5],
+            vec![3.0, 30.0],
+            vec![2.0, 20.0],
+            vec![4.0, 40.0],
+        ));
+        output.add_var(OutputVariable::new("=1+1".to_string(), vec![0.5], vec![1.0], vec![0.0], vec![0.5], vec![0.25], vec![0.75], vec![0.5]));
+        output
+    }
+
+    fn export_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("teebench-export-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    pub fn rows_rank_only_the_first_dimension() {
+        let rows = result_rows(&output());
+
+        // Sorted by the name of the KPI
+        assert_eq!(rows.iter().map(|row| (row.kpi.as_str(), row.dimension)).collect::<Vec<_>>(), vec![("=1+1", 1), ("revenue", 1), ("revenue", 2)]);
+        assert_eq!(rows[1].values(), [Some(3.0), Some(1.0), Some(5.0), Some(2.5), Some(3.0), Some(2.0), Some(4.0)]);
+        assert_eq!(rows[2].values(), [Some(30.0), None, None, Some(22.5), None, None, None]);
+    }
+
+    #[test]
+    pub fn csv_and_json_round_trip() {
+        let rows = result_rows(&output());
+
+        for name in ["results.csv", "results.json"] {
+            let path = export_path(name);
+            export_results(&output(), &path).unwrap();
+            assert_eq!(read_results(&path).unwrap(), rows);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    pub fn formulas_are_not_exported() {
+        let path = export_path("formula.csv");
+        export_results(&output(), &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(csv.lines().any(|line| line.starts_with("'=1+1,")));
+        assert!(!csv.lines().any(|line| line.starts_with("=1+1")));
+
+        for text in ["=SUM(A1)", "+1", "-1", "@x", "\tx", "'=x", "'quoted"] {
+            assert!(text_cell(text).starts_with('\''));
+            assert_eq!(from_text_cell(&text_cell(text)), text);
+        }
+        assert_eq!(text_cell("revenue"), "revenue");
+        assert_eq!(from_text_cell("'quoted"), "'quoted");
+    }
+
+    #[test]
+    pub fn unknown_formats_are_rejected() {
+        assert_eq!(ResultFormat::from_path(Path::new("results.XLSX")), Some(ResultFormat::Xlsx));
+        assert!(export_results(&output(), Path::new("results.txt")).is_err());
+        assert!(read_results(Path::new("results.xlsx")).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Integrity of the requests to the server
+//!
+//! The client chooses a random HMAC key and hands it over encrypted to an
+//! ephemeral key that the server offers: the X25519 agreement of both keys
+//! derives (HKDF-SHA256) an AES-256-GCM key that encrypts the HMAC key.
+//!
+//! Afterwards every request carries the HMAC over its method, path, nonce
+//! and body (see [`request_mac_data`]). The nonce is a counter that has to
+//! increase with every request. It starts at the current time, thus a
+//! restarted client continues above the counters of its previous run.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{header::HeaderValue, Client, RequestBuilder, Response};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf::{Salt, HKDF_SHA256},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use types::{
+    consts::{HMAC_KEY_EXCHANGE_INFO, HMAC_KEY_LEN, X_COUNTER_FIELD, X_HMAC_FIELD},
+    message::{
+        request::request_mac_data,
+        response::{KeyExchangeOffer, RspMsg},
+    },
+};
+
+use crate::{
+    api::TeebenchHttpsAPI,
+    error::{AbstractClientErrorType, ClientError},
+};
+
+/// HMAC key encrypted to an offered key of the server (all hex encoded)
+pub(crate) struct EncryptedHmacKey {
+    pub(crate) exchange_key: String,
+    pub(crate) public_key: String,
+    pub(crate) encrypted_hmac: String,
+}
+
+/// Signs the requests once an HMAC key was exchanged
+#[derive(Clone)]
+pub(crate) struct RequestSigner {
+    key: Arc<Mutex<Option<Vec<u8>>>>,
+    counter: Arc<AtomicU64>,
+}
+
+impl RequestSigner {
+    pub(crate) fn new() -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_micros() as u64).unwrap_or_default();
+        RequestSigner { key: Arc::new(Mutex::new(None)), counter: Arc::new(AtomicU64::new(now)) }
+    }
+
+    /// Hex encoded HMAC key (if already exchanged)
+    pub(crate) fn key_hex(&self) -> Option<String> {
+        self.key.lock().unwrap().as_ref().map(hex::encode)
+    }
+
+    /// Use the HMAC key of an earlier exchange
+    pub(crate) fn set_key_hex(&self, key: &str) -> Result<(), ClientError> {
+        let key = hex::decode(key.trim()).map_err(|e| unauthorized(e.to_string()))?;
+        if key.len() != HMAC_KEY_LEN {
+            return Err(unauthorized(format!("The HMAC key has to be {} bytes long", HMAC_KEY_LEN)));
+        }
+        *self.key.lock().unwrap() = Some(key);
+        Ok(())
+    }
+
+    /// Choose a new HMAC key and encrypt it to a key offered by the server
+    ///
+    /// All following requests are signed with the new key.
+    pub(crate) async fn exchange_key(&self, client: &Client, routes: &TeebenchHttpsAPI) -> Result<EncryptedHmacKey, ClientError> {
+        let offer = client
+            .get(routes.key_exchange())
+            .send()
+            .await
+            .map_err(|e| ClientError::from((AbstractClientErrorType::NoConnection, e.to_string())))?
+            .json::<RspMsg<KeyExchangeOffer>>()
+            .await
+            .map_err(|e| unauthorized(format!("Key exchange offer could not be decoded: {}", e)))?
+            .content;
+
+        let rng = SystemRandom::new();
+        let no_randomness = |_| unauthorized("No randomness available for the key exchange".to_string());
+        let mut hmac_key = vec![0_u8; HMAC_KEY_LEN];
+        rng.fill(&mut hmac_key).map_err(no_randomness)?;
+        let mut nonce = [0_u8; NONCE_LEN];
+        rng.fill(&mut nonce).map_err(no_randomness)?;
+
+        let private_key = EphemeralPrivateKey::generate(&X25519, &rng).map_err(no_randomness)?;
+        let public_key = private_key.compute_public_key().map_err(no_randomness)?;
+        let exchange_key = hex::decode(&offer.exchange_key).map_err(|e| unauthorized(e.to_string()))?;
+
+        let key = agreement::agree_ephemeral(private_key, &UnparsedPublicKey::new(&X25519, exchange_key), unauthorized("Key agreement failed".to_string()), |secret| {
+            let prk = Salt::new(HKDF_SHA256, &[]).extract(secret);
+            let okm = prk.expand(&[HMAC_KEY_EXCHANGE_INFO], &AES_256_GCM).map_err(|_| unauthorized("Key derivation failed".to_string()))?;
+            Ok(LessSafeKey::new(UnboundKey::from(okm)))
+        })?;
+
+        let mut ciphertext = hmac_key.clone();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut ciphertext)
+            .map_err(|_| unauthorized("Could not encrypt the HMAC key".to_string()))?;
+
+        *self.key.lock().unwrap() = Some(hmac_key);
+
+        Ok(EncryptedHmacKey {
+            exchange_key: offer.exchange_key,
+            public_key: hex::encode(public_key.as_ref()),
+            encrypted_hmac: hex::encode([&nonce[..], &ciphertext].concat()),
+        })
+    }
+
+    /// Send the request with its HMAC and counter
+    ///
+    /// The body has to be in memory, i.e. multipart forms are built with
+    /// [`multipart_body`] instead of streamed. Rejected requests fail with
+    /// the error code of the server.
+    pub(crate) async fn send(&self, client: &Client, request: RequestBuilder) -> Result<Response, ClientError> {
+        let mut request = request.build().map_err(|e| ClientError::from((AbstractClientErrorType::BadRequest, e.to_string())))?;
+
+        let key = self.key.lock().unwrap().clone();
+        if let Some(key) = key {
+            let counter = self.counter.fetch_add(1, Ordering::SeqCst) + 1;
+            let path = match request.url().query() {
+                Some(query) => format!("{}?{}", request.url().path(), query),
+                None => request.url().path().to_string(),
+            };
+            let body = request.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+
+            let data = request_mac_data(request.method().as_str(), &path, counter, body);
+            let mac = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), &data);
+
+            let headers = request.headers_mut();
+            headers.insert(X_HMAC_FIELD, HeaderValue::from_str(&hex::encode(mac.as_ref())).expect("Hex is a valid header"));
+            headers.insert(X_COUNTER_FIELD, HeaderValue::from(counter));
+        }
+
+        let rsp = client.execute(request).await.map_err(|e| ClientError::from((AbstractClientErrorType::NoConnection, e.to_string())))?;
+        if !rsp.status().is_success() {
+            return Err(ClientError::from_response(rsp).await);
+        }
+        Ok(rsp)
+    }
+}
+
+/// Encode a multipart form in memory such that it can be signed
+///
+/// Each part is given as `(name, mime, content)`, the file name equals the name.
+pub(crate) fn multipart_body(parts: &[(&str, &str, Vec<u8>)]) -> (HeaderValue, Vec<u8>) {
+    let mut boundary = [0_u8; 16];
+    SystemRandom::new().fill(&mut boundary).expect("No randomness available for the boundary");
+    let boundary = hex::encode(boundary);
+
+    let mut body = Vec::new();
+    for (name, mime, content) in parts {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n", boundary, name, name, mime).as_bytes(),
+        );
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let content_type = HeaderValue::from_str(&format!("multipart/form-data; boundary={}", boundary)).expect("Boundary is a valid header");
+    (content_type, body)
+}
+
+fn unauthorized(reason: String) -> ClientError {
+    ClientError::from((AbstractClientErrorType::Unauthorized, reason))
+}
@@ -1,19 +1,26 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::{Path, PathBuf}};
 
 use benchmark::operation::OperationType;
 use client::client_setup;
 use config::{ClientConfiguration, EvalMode};
 use types::consts::{
-    CC_ANALYST_ALGORITHMS_KEY, CC_ANALYST_BENCHMARK_CONFIG_KEY, CC_ANALYST_CA_CERTIFICATE_KEY,
+    CC_ANALYST_ALGORITHMS_KEY, CC_ANALYST_AUDITOR_CERTIFICATES_KEY, CC_ANALYST_BENCHMARK_CONFIG_KEY, CC_ANALYST_CA_CERTIFICATE_KEY,
     CC_ANALYST_CERTIFICATE_KEY, CC_CLIENT_PKCS12_KEY, CC_CLIENT_SERVER_CA_CERTIFICATE,
-    CC_COMPANY_INPUT_DATA_PATH_KEY, CC_SPECTATOR_EVAL_OUTPUT_KEY,
+    CC_COMPANY_ATTESTATION_POLICY_KEY, CC_COMPANY_INPUT_DATA_PATH_KEY, CC_SPECTATOR_EVAL_OUTPUT_KEY,
 };
+use types::message::response::DEFAULT_BENCHMARK_ID;
 
 mod api;
+mod attestation;
 mod client;
 pub mod config;
 mod connection;
 mod error;
+mod events;
+mod export;
+mod integrity;
+mod report;
+mod sealing;
 
 pub async fn execute_client(config: ClientConfiguration) -> Result<(), Box<dyn std::error::Error>> {
     // Start Client
@@ -25,7 +32,18 @@ pub async fn execute_client(config: ClientConfiguration) -> Result<(), Box<dyn s
     Ok(())
 }
 
+/// Write the HTML report of an exported results file (CSV or JSON) without connecting to the server
+///
+/// The descriptions of the KPIs are taken from the algorithm file (if given).
+pub fn generate_report(results_path: &Path, algorithm_path: Option<&Path>, report_path: &Path, title: &str) -> Result<(), Box<dyn std::error::Error>> {
+    report::report_from_files(results_path, algorithm_path, report_path, title)?;
+
+    Ok(())
+}
+
 /// Use same arguments as in CLI to create an analyst which is executed from within a program
+///
+/// The auditor certificates (e.g. of a spectator) are optional.
 pub async fn execute_client_analyst(
     server_host: String,
     server_http: u16,
@@ -36,6 +54,7 @@ pub async fn execute_client_analyst(
     analyst_certificate_path: PathBuf,
     algorithm_path: PathBuf,
     benchmarking_config_path: PathBuf,
+    auditor_certificates_path: Option<PathBuf>,
     offload: Option<Vec<OperationType>>,
 ) {
     // Verify that all paths indeed exist
@@ -78,12 +97,16 @@ pub async fn execute_client_analyst(
         CC_ANALYST_BENCHMARK_CONFIG_KEY.to_string(),
         benchmarking_config_path,
     );
+    if let Some(auditor_certificates_path) = auditor_certificates_path {
+        paths.insert(CC_ANALYST_AUDITOR_CERTIFICATES_KEY.to_string(), auditor_certificates_path);
+    }
 
     // Create client configuration and execute
     let cc = ClientConfiguration::new(
         server_host,
         server_http,
         server_https,
+        DEFAULT_BENCHMARK_ID,
         paths,
         (false, EvalMode::Unencrypted),
         offload,
@@ -95,6 +118,8 @@ pub async fn execute_client_analyst(
 }
 
 /// Use same arguments as in CLI to create a spectator for eval logging which is executed from within a program
+///
+/// The certificate of the spectator has to be registered as auditor by the analyst.
 pub async fn execute_client_spectator(
     server_host: String,
     server_http: u16,
@@ -129,6 +154,7 @@ pub async fn execute_client_spectator(
         server_host,
         server_http,
         server_https,
+        DEFAULT_BENCHMARK_ID,
         paths,
         eval_mode,
         offload,
@@ -147,6 +173,7 @@ pub async fn execute_client_company(
     server_cert_path: PathBuf,
     client_pkcs12_path: PathBuf,
     input_data_path: PathBuf,
+    attestation_policy_path: PathBuf,
     uuid: u128,
     offload: Option<Vec<OperationType>>,
 ) {
@@ -157,6 +184,9 @@ pub async fn execute_client_company(
     if !client_pkcs12_path.exists() {
         panic!("Client PFX does not exist!");
     }
+    if !attestation_policy_path.exists() {
+        panic!("Attestation policy does not exist!");
+    }
 
     // Push them into the map
     let mut paths: HashMap<String, PathBuf> = HashMap::new();
@@ -166,12 +196,14 @@ pub async fn execute_client_company(
     );
     paths.insert(CC_CLIENT_PKCS12_KEY.to_string(), client_pkcs12_path);
     paths.insert(CC_COMPANY_INPUT_DATA_PATH_KEY.to_string(), input_data_path);
+    paths.insert(CC_COMPANY_ATTESTATION_POLICY_KEY.to_string(), attestation_policy_path);
 
     // Create client configuration and execute
     let cc = ClientConfiguration::new(
         server_host,
         server_http,
         server_https,
+        DEFAULT_BENCHMARK_ID,
         paths,
         (false, EvalMode::Unencrypted),
         offload,
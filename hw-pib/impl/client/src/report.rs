@@ -0,0 +1,307 @@
+//! Benchmark report of a company
+//!
+//! A self-contained HTML page (no scripts, no external resources) with one
+//! SVG box plot per KPI and dimension: the whiskers span the minimum and the
+//! maximum of all participants, the box the quartiles with the median, and
+//! a marker shows the own value of the company. The descriptions of the KPIs
+//! are taken from the algorithms.
+//!
+//! Reports are written after the results were retrieved or offline from an
+//! exported results file (see [`crate::export`]).
+
+use std::{collections::HashMap, fs, path::Path};
+
+use benchmark::Algorithm;
+
+use crate::{
+    error::{AbstractClientErrorType, ClientError},
+    export::{read_results, ResultRow},
+};
+
+const PLOT_WIDTH: f64 = 640.0;
+const PLOT_HEIGHT: f64 = 96.0;
+/// Horizontal space for the labels at both ends
+const PLOT_MARGIN: f64 = 48.0;
+/// Vertical center of the box
+const PLOT_CENTER: f64 = 44.0;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 720px; color: #222; }
+section { border-top: 1px solid #ddd; padding: 0.5em 0 1em; }
+.description { color: #555; }
+.missing { color: #a00; }
+table { border-collapse: collapse; font-size: 0.9em; }
+th, td { padding: 0.2em 0.8em; text-align: right; }
+svg .whisker { stroke: #555; stroke-width: 1.5; }
+svg .box { fill: #dce6f2; stroke: #4a6fa5; stroke-width: 1.5; }
+svg .median { stroke: #1f3b64; stroke-width: 3; }
+svg .own { fill: #d9534f; stroke: #fff; stroke-width: 1.5; }
+svg text { font-size: 11px; fill: #333; }
+";
+
+/// Descriptions of the KPIs by their name
+pub(crate) fn kpi_descriptions(algorithm: &Algorithm) -> HashMap<String, String> {
+    algorithm
+        .operations()
+        .iter()
+        .filter(|atomic| atomic.is_kpi())
+        .filter_map(|atomic| Some((atomic.name().to_string(), atomic.description()?.to_string())))
+        .collect()
+}
+
+/// Write the report of an exported results file (descriptions from an algorithm file)
+pub(crate) fn report_from_files(results: &Path, algorithms: Option<&Path>, output: &Path, title: &str) -> Result<(), ClientError> {
+    let rows = read_results(results)?;
+    let descriptions = match algorithms {
+        Some(path) => {
+            let algorithm = Algorithm::load(&path.to_path_buf()).map_err(|e| report_error(e.to_string()))?;
+            kpi_descriptions(&algorithm)
+        }
+        None => HashMap::new(),
+    };
+
+    write_report(output, title, &rows, &descriptions)
+}
+
+/// Write the report as HTML file
+pub(crate) fn write_report(output: &Path, title: &str, rows: &[ResultRow], descriptions: &HashMap<String, String>) -> Result<(), ClientError> {
+    fs::write(output, render_report(title, rows, descriptions)).map_err(|e| report_error(e.to_string()))
+}
+
+/// The HTML page with a section per KPI (in the order of the rows)
+fn render_report(title: &str, rows: &[ResultRow], descriptions: &HashMap<String, String>) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = escape(title)
+    );
+    html.push_str("<p>Every plot shows the minimum and maximum (whiskers), the lower and upper quartile (box) and the median of all participants. The red marker is your own value.</p>\n");
+
+    let mut kpi: Option<&str> = None;
+    for row in rows {
+        if kpi != Some(row.kpi.as_str()) {
+            if kpi.is_some() {
+                html.push_str("</section>\n");
+            }
+            kpi = Some(&row.kpi);
+            html.push_str(&format!("<section>\n<h2>{}</h2>\n", escape(&row.kpi)));
+            if let Some(description) = descriptions.get(&row.kpi) {
+                html.push_str(&format!("<p class=\"description\">{}</p>\n", escape(description)));
+            }
+        }
+
+        // Dimensions are only labeled when the KPI has several
+        if rows.iter().any(|other| other.kpi == row.kpi && other.dimension > 1) {
+            html.push_str(&format!("<h3>Dimension {}</h3>\n", row.dimension));
+        }
+        html.push_str(&box_plot(row));
+        if let Some(position) = position(row) {
+            html.push_str(&format!("<p>Your value is {}.</p>\n", position));
+        }
+        html.push_str(&summary_table(row));
+    }
+    if kpi.is_some() {
+        html.push_str("</section>\n");
+    } else {
+        html.push_str("<p class=\"missing\">The results contain no KPIs.</p>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Horizontal box plot of one dimension
+fn box_plot(row: &ResultRow) -> String {
+    let (min, lower_quartile, median, upper_quartile, max) = match (row.min, row.lower_quartile, row.median, row.upper_quartile, row.max) {
+        (Some(min), Some(lower_quartile), Some(median), Some(upper_quartile), Some(max)) => (min, lower_quartile, median, upper_quartile, max),
+        // Only the first dimension of a KPI is ranked
+        _ if row.average.is_some() => return "<p class=\"missing\">Only the average of the participants is available for this dimension.</p>\n".to_string(),
+        _ => return "<p class=\"missing\">No statistics of the participants are available.</p>\n".to_string(),
+    };
+
+    // The own value may lie outside of the statistics (e.g. rounding)
+    let (low, high) = row.own_value.into_iter().fold((min, max), |(low, high), own| (low.min(own), high.max(own)));
+    let span = if high > low { high - low } else { 1.0 };
+    let x = |value: f64| PLOT_MARGIN + (value - low) / span * (PLOT_WIDTH - 2.0 * PLOT_MARGIN);
+    let (top, bottom) = (PLOT_CENTER - 14.0, PLOT_CENTER + 14.0);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" role=\"img\" aria-label=\"Box plot of {kpi}\">\n",
+        w = PLOT_WIDTH,
+        h = PLOT_HEIGHT,
+        kpi = escape(&row.kpi)
+    );
+    svg.push_str(&format!("<line class=\"whisker\" x1=\"{:.1}\" y1=\"{c}\" x2=\"{:.1}\" y2=\"{c}\"/>\n", x(min), x(max), c = PLOT_CENTER));
+    for end in [min, max] {
+        svg.push_str(&format!("<line class=\"whisker\" x1=\"{x:.1}\" y1=\"{:.1}\" x2=\"{x:.1}\" y2=\"{:.1}\"/>\n", top + 6.0, bottom - 6.0, x = x(end)));
+    }
+    svg.push_str(&format!(
+        "<rect class=\"box\" x=\"{:.1}\" y=\"{top}\" width=\"{:.1}\" height=\"{}\"/>\n",
+        x(lower_quartile),
+        (x(upper_quartile) - x(lower_quartile)).max(1.0),
+        bottom - top
+    ));
+    svg.push_str(&format!("<line class=\"median\" x1=\"{x:.1}\" y1=\"{top}\" x2=\"{x:.1}\" y2=\"{bottom}\"/>\n", x = x(median)));
+
+    // The median is labeled in its own line, it may be close to the ends
+    for (label, value, y) in [("Min", min, PLOT_HEIGHT - 6.0), ("Median", median, bottom + 14.0), ("Max", max, PLOT_HEIGHT - 6.0)] {
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{} {}</text>\n",
+            x(value),
+            y,
+            label,
+            number(value)
+        ));
+    }
+    if let Some(own) = row.own_value {
+        svg.push_str(&format!(
+            "<circle class=\"own\" cx=\"{x:.1}\" cy=\"{c}\" r=\"7\"><title>Your value: {v}</title></circle>\n<text x=\"{x:.1}\" y=\"12\" text-anchor=\"middle\">You: {v}</text>\n",
+            x = x(own),
+            c = PLOT_CENTER,
+            v = number(own)
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Position of the own value among the quartiles
+fn position(row: &ResultRow) -> Option<&'static str> {
+    let own = row.own_value?;
+    let (lower_quartile, median, upper_quartile) = (row.lower_quartile?, row.median?, row.upper_quartile?);
+
+    Some(if own < lower_quartile {
+        "below the lower quartile"
+    } else if own < median {
+        "between the lower quartile and the median"
+    } else if own <= upper_quartile {
+        "between the median and the upper quartile"
+    } else {
+        "above the upper quartile"
+    })
+}
+
+/// All values of one dimension
+fn summary_table(row: &ResultRow) -> String {
+    let cells = [
+        ("Your value", row.own_value),
+        ("Min", row.min),
+        ("Lower quartile", row.lower_quartile),
+        ("Median", row.median),
+        ("Average", row.average),
+        ("Upper quartile", row.upper_quartile),
+        ("Max", row.max),
+    ];
+
+    let header: String = cells.iter().map(|(title, _)| format!("<th>{}</th>", title)).collect();
+    let values: String = cells.iter().map(|(_, value)| format!("<td>{}</td>", value.map(number).unwrap_or_else(|| "-".to_string()))).collect();
+    format!("<table>\n<tr>{}</tr>\n<tr>{}</tr>\n</table>\n", header, values)
+}
+
+/// At most four decimals without trailing zeros
+fn number(value: f64) -> String {
+    let formatted = format!("{:.4}", value);
+    let formatted = if formatted.contains('.') { formatted.trim_end_matches('0').trim_end_matches('.') } else { &formatted };
+    if formatted == "-0" { "0".to_string() } else { formatted.to_string() }
+}
+
+/// Escape text for HTML content and attributes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn report_error(reason: String) -> ClientError {
+    ClientError::from((AbstractClientErrorType::BadRequest, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(own_value: Option<f64>) -> ResultRow {
+        ResultRow {
+            kpi: "<b>revenue</b> & \"costs\"".to_string(),
+            dimension: 1,
+            own_value,
+            min: Some(1.0),
+            max: Some(5.0),
+            average: Some(2.5),
+            median: Some(3.0),
+            lower_quartile: Some(2.0),
+            upper_quartile: Some(4.0),
+        }
+    }
+
+    /// Horizontal positions of the elements with the attribute in the SVG
+    fn positions(svg: &str, attribute: &str) -> Vec<f64> {
+        svg.split(&format!("{}=\"", attribute)).skip(1).map(|rest| rest.split('"').next().unwrap().parse().unwrap()).collect()
+    }
+
+    #[test]
+    pub fn escape_html() {
+        assert_eq!(escape("<b>revenue</b> & \"costs\""), "&lt;b&gt;revenue&lt;/b&gt; &amp; &quot;costs&quot;");
+        assert_eq!(escape("&lt;"), "&amp;lt;");
+
+        let html = render_report("Q1 <2024>", &[row(Some(3.0))], &HashMap::new());
+        assert!(html.contains("<title>Q1 &lt;2024&gt;</title>"));
+        assert!(html.contains("<h2>&lt;b&gt;revenue&lt;/b&gt; &amp; &quot;costs&quot;</h2>"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    pub fn number_format() {
+        assert_eq!(number(2.0), "2");
+        assert_eq!(number(2.5), "2.5");
+        assert_eq!(number(1.0 / 3.0), "0.3333");
+        assert_eq!(number(-0.00001), "0");
+        assert_eq!(number(-12.34), "-12.34");
+        assert_eq!(number(100.0), "100");
+    }
+
+    #[test]
+    pub fn position_among_quartiles() {
+        assert_eq!(position(&row(Some(1.5))), Some("below the lower quartile"));
+        assert_eq!(position(&row(Some(2.0))), Some("between the lower quartile and the median"));
+        assert_eq!(position(&row(Some(3.0))), Some("between the median and the upper quartile"));
+        assert_eq!(position(&row(Some(4.0))), Some("between the median and the upper quartile"));
+        assert_eq!(position(&row(Some(4.5))), Some("above the upper quartile"));
+        assert_eq!(position(&row(None)), None);
+
+        let missing = ResultRow { median: None, ..row(Some(3.0)) };
+        assert_eq!(position(&missing), None);
+    }
+
+    #[test]
+    pub fn box_plot_contains_the_own_value() {
+        // The plot spans from the minimum to the maximum
+        let svg = box_plot(&row(Some(3.0)));
+        assert_eq!(positions(&svg, "x1")[0], PLOT_MARGIN);
+        assert_eq!(positions(&svg, "x2")[0], PLOT_WIDTH - PLOT_MARGIN);
+        assert_eq!(positions(&svg, "cx"), vec![PLOT_WIDTH / 2.0]);
+
+        // An own value outside of the whiskers widens the plot
+        let svg = box_plot(&row(Some(9.0)));
+        assert_eq!(positions(&svg, "cx"), vec![PLOT_WIDTH - PLOT_MARGIN]);
+        assert_eq!(positions(&svg, "x1")[0], PLOT_MARGIN);
+        assert!(positions(&svg, "x2")[0] < PLOT_WIDTH - PLOT_MARGIN);
+        assert!(svg.contains("You: 9"));
+
+        // Without an own value there is no marker
+        assert!(!box_plot(&row(None)).contains("<circle"));
+    }
+
+    #[test]
+    pub fn box_plot_without_statistics() {
+        let ranked_average = ResultRow { dimension: 2, min: None, max: None, median: None, lower_quartile: None, upper_quartile: None, ..row(Some(3.0)) };
+        assert!(box_plot(&ranked_average).contains("Only the average"));
+        assert!(!box_plot(&ranked_average).contains("<svg"));
+
+        let empty = ResultRow { average: None, ..ranked_average };
+        assert!(box_plot(&empty).contains("No statistics"));
+
+        let html = render_report("Results", &[row(Some(3.0)), ResultRow { dimension: 2, ..empty }], &HashMap::new());
+        assert!(html.contains("<h3>Dimension 2</h3>"));
+        assert!(html.contains("<td>-</td>"));
+        assert!(render_report("Results", &[], &HashMap::new()).contains("The results contain no KPIs."));
+    }
+}
@@ -0,0 +1,151 @@
+//! Sealed results of a company
+//!
+//! On registration the company hands over a fresh X25519 key to which the
+//! server encrypts its results. The private key only lives in the memory of
+//! the client: the agreement with the ephemeral key of the server derives
+//! (HKDF-SHA256) the AES-256-GCM key that opens the results. The benchmark
+//! and company ID are authenticated as associated data.
+//!
+//! The private key is kept until the results were opened, thus a corrupt
+//! response (e.g. a wrong key of the server) does not cost the results and
+//! the download can be retried. The key is not stored though: results of an
+//! earlier run of the client (e.g. ones that the server restored from its
+//! snapshot after a restart) cannot be opened anymore, a company has to stay
+//! connected until it downloaded its results.
+
+use std::sync::{Arc, Mutex};
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hkdf::{Salt, HKDF_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use types::{
+    consts::RESULT_SEALING_INFO,
+    message::response::{result_sealing_aad, BenchmarkID, CompanyID, SealedResults},
+    output::Output,
+};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::error::{AbstractClientErrorType, ClientError};
+
+/// Length of an X25519 key in bytes
+const X25519_KEY_LEN: usize = 32;
+
+/// Private key to which the server seals the results
+#[derive(Clone)]
+pub(crate) struct ResultKey {
+    private_key: Arc<Mutex<Option<StaticSecret>>>,
+}
+
+impl ResultKey {
+    pub(crate) fn new() -> Self {
+        ResultKey { private_key: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Generate a new key and return its hex encoded public key for the registration
+    pub(crate) fn generate(&self) -> Result<String, ClientError> {
+        let mut secret = [0_u8; X25519_KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .map_err(|_| sealing_error("No randomness available for the result key".to_string()))?;
+        let private_key = StaticSecret::from(secret);
+        let public_key = hex::encode(PublicKey::from(&private_key).as_bytes());
+        *self.private_key.lock().unwrap() = Some(private_key);
+        Ok(public_key)
+    }
+
+    /// Decrypt the sealed results of the company in the benchmark (the key is consumed once they are opened)
+    pub(crate) fn open(&self, sealed_results: &SealedResults, benchmark_id: BenchmarkID, company_id: CompanyID) -> Result<Output, ClientError> {
+        let server_key = hex::decode(&sealed_results.public_key).map_err(|e| sealing_error(e.to_string()))?;
+        let server_key: [u8; X25519_KEY_LEN] = server_key
+            .try_into()
+            .map_err(|_| sealing_error(format!("The server key of the results has to be {} bytes long", X25519_KEY_LEN)))?;
+        let mut data = hex::decode(&sealed_results.sealed_output).map_err(|e| sealing_error(e.to_string()))?;
+        if data.len() < NONCE_LEN + AES_256_GCM.tag_len() {
+            return Err(sealing_error("The sealed results are truncated".to_string()));
+        }
+        let mut ciphertext = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data).map_err(|_| sealing_error("Invalid nonce of the sealed results".to_string()))?;
+
+        let mut private_key = self.private_key.lock().unwrap();
+        let secret = private_key
+            .as_ref()
+            .ok_or_else(|| sealing_error("The result key is not present (anymore)".to_string()))?
+            .diffie_hellman(&PublicKey::from(server_key));
+        if !secret.was_contributory() {
+            return Err(sealing_error("Key agreement failed".to_string()));
+        }
+        let prk = Salt::new(HKDF_SHA256, &[]).extract(secret.as_bytes());
+        let okm = prk.expand(&[RESULT_SEALING_INFO], &AES_256_GCM).map_err(|_| sealing_error("Key derivation failed".to_string()))?;
+        let key = LessSafeKey::new(UnboundKey::from(okm));
+
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(result_sealing_aad(benchmark_id, company_id)), &mut ciphertext)
+            .map_err(|_| sealing_error("The results could not be decrypted".to_string()))?;
+        let output = serde_json::from_slice(plaintext).map_err(|e| sealing_error(format!("Results could not be decoded: {}", e)))?;
+
+        // Only now the results are safe, a failed attempt above can be retried
+        *private_key = None;
+        Ok(output)
+    }
+}
+
+fn sealing_error(reason: String) -> ClientError {
+    ClientError::from((AbstractClientErrorType::BadRequest, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use server::sealing::seal_output;
+    use types::output::OutputVariable;
+
+    use super::*;
+
+    fn output() -> Output {
+        let mut output = Output::new_empty();
+        output.add_var(OutputVariable::new_result_only("revenue".to_string(), vec![1.5, 2.5]));
+        output
+    }
+
+    fn corrupt(hex_data: &str, index: usize) -> String {
+        let mut data = hex::decode(hex_data).unwrap();
+        data[index] ^= 0x01;
+        hex::encode(data)
+    }
+
+    #[test]
+    pub fn open_sealed_results_once() {
+        let result_key = ResultKey::new();
+        let public_key = result_key.generate().unwrap();
+        let sealed = seal_output(&public_key, 3, 7, &output()).unwrap();
+
+        let opened = result_key.open(&sealed, 3, 7).unwrap();
+        assert_eq!(opened.vars()["revenue"].result(), &vec![1.5, 2.5]);
+
+        // The key is gone once the results were opened
+        assert!(result_key.open(&sealed, 3, 7).is_err());
+    }
+
+    #[test]
+    pub fn corrupt_response_keeps_the_key() {
+        let result_key = ResultKey::new();
+        let public_key = result_key.generate().unwrap();
+        let sealed = seal_output(&public_key, 3, 7, &output()).unwrap();
+
+        let wrong_server_key = SealedResults { public_key: corrupt(&sealed.public_key, 0), sealed_output: sealed.sealed_output.clone() };
+        assert!(result_key.open(&wrong_server_key, 3, 7).is_err());
+
+        let wrong_ciphertext = SealedResults { public_key: sealed.public_key.clone(), sealed_output: corrupt(&sealed.sealed_output, NONCE_LEN) };
+        assert!(result_key.open(&wrong_ciphertext, 3, 7).is_err());
+
+        let truncated = SealedResults { public_key: sealed.public_key[2..].to_string(), sealed_output: sealed.sealed_output.clone() };
+        assert!(result_key.open(&truncated, 3, 7).is_err());
+
+        // Results of another benchmark or company are rejected as well
+        assert!(result_key.open(&sealed, 4, 7).is_err());
+        assert!(result_key.open(&sealed, 3, 8).is_err());
+
+        assert_eq!(result_key.open(&sealed, 3, 7).unwrap().vars()["revenue"].result(), &vec![1.5, 2.5]);
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 use client::{
     config::EvalMode, execute_client_analyst, execute_client_company, execute_client_spectator,
 };
-use server::execute_server;
+use server::{execute_server, AttestationReference};
 use types::message::io::CompanyUUIDs;
 
 use std::time::Duration;
@@ -30,6 +30,10 @@ static ANALYST_PKCS_12_PATH: &str = "/crypto/analyst/analyst.pfx";
 static ANALYST_CA_CERTIFICATE_PATH: &str = "/crypto/analyst_ca/analyst_ca.pem";
 static ANALYST_CERTIFICATE_PATH: &str = "/crypto/analyst/analyst.pem";
 
+/// Spectator key file subpaths (the analyst registers it as auditor)
+static SPECTATOR_PKCS_12_PATH: &str = "/crypto/spectator/spectator.pfx";
+static SPECTATOR_CERTIFICATE_PATH: &str = "/crypto/spectator/spectator.pem";
+
 /// Analyst config files subpath
 static ANALYST_ALGORITHMS_PATH: &str = "/analyst/algorithms.yaml";
 static ANALYST_BENCHMARKING_CONFIG_PATH: &str = "/analyst/benchmarking_config.yaml";
@@ -37,6 +41,9 @@ static ANALYST_BENCHMARKING_CONFIG_PATH: &str = "/analyst/benchmarking_config.ya
 /// Server Certificate paths
 static SERVER_CA_CERTIFICATE_PATH: &str = "/crypto/server_ca/server_ca.pem";
 
+/// Attestation policy of the companies (written for a local server if missing)
+static ATTESTATION_POLICY_PATH: &str = "/attestation_policy.yaml";
+
 /// Path where the analyst puts the uuids of the companies
 static COMPANY_UUID_PATH: &str = "../data/client_data/uuids.yaml";
 static ANALYST_FINISHED_PATH: &str = "../data/client_data/finished";
@@ -84,7 +91,11 @@ fn main() {
         // Wait for spectator
         std::thread::sleep(Duration::from_millis(SPECTATOR_WAIT_MS));
 
-        start_spectator(&oc, &orchestra_prefix, &eval_name, eval_mode.clone());
+        if spectator_certificate(&orchestra_prefix).is_some() {
+            start_spectator(&oc, &orchestra_prefix, &eval_name, eval_mode.clone());
+        } else {
+            log::warn!(" ## No spectator certificate in the archive -- Nothing is evaluated! ## ");
+        }
 
         // Check for written UUID file of client
         log::info!(" ## Waiting for analysts written UUID file! ## ");
@@ -237,6 +248,7 @@ fn start_analyst(oc: &OrchestraConfig, orchestra_prefix: &String) {
     let pkcs12_path: PathBuf = format!("{}{}", orchestra_prefix, ANALYST_PKCS_12_PATH).into();
     let bc_path: PathBuf =
         format!("{}{}", orchestra_prefix, ANALYST_BENCHMARKING_CONFIG_PATH).into();
+    let auditor_cert_path = spectator_certificate(orchestra_prefix);
 
     let oc_host = oc.server_host.clone();
     let oc_http = oc.server_http.clone();
@@ -256,6 +268,7 @@ fn start_analyst(oc: &OrchestraConfig, orchestra_prefix: &String) {
                 cert_path,
                 algo_path,
                 bc_path,
+                auditor_cert_path,
                 None,
             )
             .await;
@@ -263,6 +276,12 @@ fn start_analyst(oc: &OrchestraConfig, orchestra_prefix: &String) {
     });
 }
 
+/// Certificate of the spectator (if the archive holds one)
+fn spectator_certificate(orchestra_prefix: &String) -> Option<PathBuf> {
+    let path: PathBuf = format!("{}{}", orchestra_prefix, SPECTATOR_CERTIFICATE_PATH).into();
+    path.exists().then_some(path)
+}
+
 /// Start analyst client instance
 fn start_spectator(
     oc: &OrchestraConfig,
@@ -274,7 +293,7 @@ fn start_spectator(
     log::info!(" ## Starting Spectator! ## ");
     let server_cert_path: PathBuf =
         format!("{}{}", orchestra_prefix, SERVER_CA_CERTIFICATE_PATH).into();
-    let pkcs12_path: PathBuf = format!("{}{}", orchestra_prefix, ANALYST_PKCS_12_PATH).into();
+    let pkcs12_path: PathBuf = format!("{}{}", orchestra_prefix, SPECTATOR_PKCS_12_PATH).into();
 
     let eval: PathBuf = format!("{}/{}.csv", EVAL_IO_BASE_PATH, eval_name.to_string_lossy()).into();
 
@@ -301,8 +320,30 @@ fn start_spectator(
     });
 }
 
+/// Attestation policy that the companies require from the server
+///
+/// A local server runs the simulated attester of this executable, thus its
+/// values are known. For a remote server the archive has to hold the policy.
+fn attestation_policy(oc: &OrchestraConfig, orchestra_prefix: &String) -> PathBuf {
+    let policy_path: PathBuf = format!("{}{}", orchestra_prefix, ATTESTATION_POLICY_PATH).into();
+    if !policy_path.exists() {
+        if !oc.exec_server {
+            panic!("The archive has to hold the attestation policy for a remote server!");
+        }
+        let algo_path: PathBuf = format!("{}{}", orchestra_prefix, ANALYST_ALGORITHMS_PATH).into();
+        let reference = AttestationReference::simulated(&algo_path).expect("Could not determine the attestation of the local server!");
+        let policy = format!(
+            "attestation_key: {}\nmeasurement: {}\nalgorithm_hashes: [{}]\n",
+            reference.attestation_key, reference.measurement, reference.algorithm_hash
+        );
+        fs::write(&policy_path, policy).expect("Could not write the attestation policy!");
+    }
+    policy_path
+}
+
 /// Start up as many clients as the analyst requires
 fn start_companies(oc: &OrchestraConfig, orchestra_prefix: &String, companies: Vec<u128>) {
+    let policy_path = attestation_policy(oc, orchestra_prefix);
     for (uuid, i) in companies.iter().zip(0..) {
         let company_crypto_path: PathBuf = format!(
             "{}/crypto/comp{}/comp{}.pfx",
@@ -325,6 +366,7 @@ fn start_companies(oc: &OrchestraConfig, orchestra_prefix: &String, companies: V
         let oc_http = oc.server_http.clone();
         let oc_https = oc.server_https.clone();
         let oc_uuid = uuid.clone();
+        let oc_policy_path = policy_path.clone();
 
         std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().unwrap();
@@ -337,6 +379,7 @@ fn start_companies(oc: &OrchestraConfig, orchestra_prefix: &String, companies: V
                     oc_server_cert_path,
                     company_crypto_path,
                     company_input_path,
+                    oc_policy_path,
                     oc_uuid,
                     None,
                 )
@@ -5,11 +5,27 @@
 //! indicate updates at our clients, i.e. the number of participants 
 //! that are required until the benchmark can begin. (Uses JS EventSource)
 //! The mimetype for SSE is `text/event-stream`.  
+//! 
+//! Clients subscribe to the events of one benchmark, such that the
+//! benchmarks hosted by one server do not see each other's progress.
+//! 
+//! Events are typed [`ServerEvent`]s with an `id:` that increases with
+//! every event. The latest events of each benchmark are kept, thus a
+//! reconnecting client gets the ones it missed (`Last-Event-ID`), except
+//! for measurements (see [`ServerEvent::is_replayed`]).
+//! 
+//! Besides the public channel of a benchmark, events can be addressed to
+//! one company or the analyst team ([`Audience`]). Their clients receive
+//! the public events together with the ones addressed to them.
+//! 
+//! The number of clients is limited, further subscriptions are rejected
+//! with a 429 (`TOO_MANY_REQUESTS`) until clients disconnect.
 
 use std::{
+    collections::{HashMap, VecDeque},
     pin::Pin,
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use actix_web::{
@@ -20,24 +36,65 @@ use actix_web::{
 use futures_util::Stream;
 use parking_lot::Mutex;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use types::message::{error::ErrorCode, event::ServerEvent, response::{BenchmarkID, CompanyID}};
+
+use crate::error::ApiError;
+
+/// Number of events per benchmark that are kept for replay
+const HISTORY_LEN: usize = 128;
+
+/// Capacity of the channel of a client (holds a full replay)
+const CLIENT_CAPACITY: usize = 2 * HISTORY_LEN;
+
+/// Seconds after which a rejected client may subscribe again
+const CLIENT_RETRY_AFTER: u64 = 30;
+
+/// Receivers of an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    /// Everyone that subscribed to the benchmark
+    Public,
+    /// One company of the benchmark
+    Company(CompanyID),
+    /// The analyst team
+    Analyst,
+}
+
+impl Audience {
+    /// Whether a client of this audience receives events for `audience`
+    fn receives(&self, audience: Audience) -> bool {
+        audience == Audience::Public || audience == *self
+    }
+}
 
 /// Broadcaster type which only can send one message at once
 pub struct Broadcaster {
     inner: Mutex<BroadcasterInner>,
+    /// Maximum number of clients (0 for no limit)
+    max_clients: usize,
 }
 
 /// Clients that registered for broadcasting service will be contacted (i.e. companies)
 struct BroadcasterInner {
-    clients: Vec<Sender<Bytes>>,
+    clients: Vec<(BenchmarkID, Audience, Sender<Bytes>)>,
+    /// ID of the next event
+    next_id: u64,
+    /// Latest encoded events of each benchmark with their ID and audience
+    history: HashMap<BenchmarkID, VecDeque<(u64, Audience, Bytes)>>,
 }
 
 impl Broadcaster {
-    pub fn new() -> Data<Self> {
+    /// Broadcaster for at most `max_clients` clients (0 for no limit)
+    pub fn new(max_clients: usize) -> Data<Self> {
         
         // Create new instance which will be attached to the HTTP server
         let broadcaster_data = Data::new(Broadcaster {
+            max_clients,
             inner: Mutex::new(BroadcasterInner {
                 clients: Vec::new(),
+                // IDs start at the current time, thus they keep increasing after a restart
+                next_id: SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_micros() as u64).unwrap_or_default(),
+                history: HashMap::new(),
             }),
         });
 
@@ -62,33 +119,74 @@ impl Broadcaster {
         let mut inner = self.inner.lock();
 
         let mut ok_clients = Vec::new();
-        for client in inner.clients.iter() {
-            let result = client.clone().try_send(Bytes::from("data: keep-alive\n\n"));
+        for (benchmark_id, audience, client) in inner.clients.iter() {
+            let result = client.clone().try_send(Bytes::from(": keep-alive\n\n"));
 
             if let Ok(()) = result {
-                ok_clients.push(client.clone());
+                ok_clients.push((*benchmark_id, *audience, client.clone()));
             }
         }
         inner.clients = ok_clients;
     }
 
-    pub fn new_client(&self) -> Client {
-        let (tx, rx) = channel(100);
+    /// Register a client of `audience` for the events of one benchmark
+    ///
+    /// The events after `last_event_id` are replayed (as far as they are kept).
+    /// Fails with `TOO_MANY_REQUESTS` once the maximum of clients is reached.
+    pub fn new_client(&self, benchmark_id: BenchmarkID, audience: Audience, last_event_id: Option<u64>) -> Result<Client, ApiError> {
+        let mut inner = self.inner.lock();
+
+        if self.max_clients > 0 && inner.clients.len() >= self.max_clients {
+            // Clients that disconnected since the last ping free their place
+            inner.clients.retain(|(_, _, client)| !client.is_closed());
+            if inner.clients.len() >= self.max_clients {
+                return Err(ApiError::from((ErrorCode::TooManyRequests { retry_after: CLIENT_RETRY_AFTER }, format!("The server accepts at most {} event stream clients!", self.max_clients))));
+            }
+        }
+
+        let (tx, rx) = channel(CLIENT_CAPACITY);
+
+        tx.try_send(Bytes::from(": connected\n\n")).unwrap();
+
+        if let (Some(last_event_id), Some(history)) = (last_event_id, inner.history.get(&benchmark_id)) {
+            for (_, _, event) in history.iter().filter(|(id, to, _)| *id > last_event_id && audience.receives(*to)) {
+                tx.try_send(event.clone()).unwrap_or(());
+            }
+        }
+        inner.clients.push((benchmark_id, audience, tx));
+
+        Ok(Client(rx))
+    }
+
+    /// Number of registered clients (stale ones are removed with the next ping)
+    pub fn client_count(&self) -> usize {
+        self.inner.lock().clients.len()
+    }
 
-        tx.try_send(Bytes::from("data: connected\n\n")).unwrap();
+    /// Send an event to all clients of one benchmark
+    pub fn send(&self, benchmark_id: BenchmarkID, event: &ServerEvent) {
+        self.send_to(benchmark_id, Audience::Public, event);
+    }
 
+    /// Send an event to the clients of `audience` in one benchmark
+    pub fn send_to(&self, benchmark_id: BenchmarkID, audience: Audience, event: &ServerEvent) {
         let mut inner = self.inner.lock();
-        inner.clients.push(tx);
+        let id = inner.next_id;
+        inner.next_id += 1;
 
-        Client(rx)
-    }
+        // Transform the event to an SSE message and send it to every receiver (i.e., Client) of the benchmark
+        let data = serde_json::to_string(event).expect("Events are serializable");
+        let msg = Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", id, event.event_type(), data));
 
-    pub fn send(&self, msg: &str) {
-        // Transform string to bytestring and send it to every receiver (i.e., Client)
-        let msg = Bytes::from(["data: ", msg, "\n\n"].concat());
+        if event.is_replayed() {
+            let history = inner.history.entry(benchmark_id).or_default();
+            if history.len() == HISTORY_LEN {
+                history.pop_front();
+            }
+            history.push_back((id, audience, msg.clone()));
+        }
 
-        let inner = self.inner.lock();
-        for client in inner.clients.iter() {
+        for (_, _, client) in inner.clients.iter().filter(|(id, to, _)| *id == benchmark_id && to.receives(audience)) {
             client.clone().try_send(msg.clone()).unwrap_or(());
         }
     }
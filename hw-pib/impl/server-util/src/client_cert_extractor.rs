@@ -6,6 +6,10 @@
 //! participants UUID and his certificate. The certificate
 //! is stored for a company and for the analyst extra and
 //! enforces authenticity of requests.
+//! 
+//! The UUID of a company is part of its certificate, which is
+//! signed by the analyst's CA: the subject alternative name
+//! holds the URI `urn:uuid:<uuid>` (see [`certificate_company_id`]).
 
 use std::{any::Any, net::SocketAddr};
 use actix_tls::accept::rustls::TlsStream;
@@ -13,6 +17,15 @@ use actix_web::{
     dev::Extensions,
     rt::net::TcpStream
 };
+use bcder::{decode::Constructed, Ia5String, Mode, Tag};
+use rustls::Certificate;
+use uuid::Uuid;
+use x509_certificate::{rfc5280, X509Certificate};
+
+/// OID of the subject alternative name extension (2.5.29.17)
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1D, 0x11];
+/// Prefix of UUID URIs (RFC 4122)
+const UUID_URN_PREFIX: &str = "urn:uuid:";
 
 
 #[derive(Debug, Clone)]
@@ -54,4 +67,84 @@ pub fn get_client_cert(connection: &dyn Any, data: &mut Extensions) {
         });
 
     }
-}
\ No newline at end of file
+}
+
+/// UUID of the company that the certificate was issued for
+/// 
+/// The UUID is taken from the first `urn:uuid:` URI of the subject
+/// alternative name, `None` if the certificate holds no such URI.
+pub fn certificate_company_id(certificate: &Certificate) -> Option<u128> {
+    let certificate = X509Certificate::from_der(&certificate.0).ok()?;
+    let certificate: &rfc5280::Certificate = certificate.as_ref();
+    let subject_alt_name = certificate.tbs_certificate.extensions.as_ref()?.iter().find(|extension| extension.id.as_ref() == OID_SUBJECT_ALT_NAME)?;
+
+    // GeneralNames ::= SEQUENCE OF GeneralName, where URIs are [6] IA5String
+    let uris = Mode::Der.decode(subject_alt_name.value.to_bytes().as_ref(), |cons| {
+        cons.take_sequence(|cons: &mut Constructed<_>| {
+            let mut uris = Vec::new();
+            loop {
+                if let Some(uri) = cons.take_opt_value_if(Tag::CTX_6, Ia5String::from_content)? {
+                    uris.push(uri.to_string());
+                } else if cons.skip_one()?.is_none() {
+                    return Ok(uris);
+                }
+            }
+        })
+    }).ok()?;
+
+    let uuid = uris.iter().find(|uri| uri.len() > UUID_URN_PREFIX.len() && uri[..UUID_URN_PREFIX.len()].eq_ignore_ascii_case(UUID_URN_PREFIX))?;
+    Uuid::parse_str(&uuid[UUID_URN_PREFIX.len()..]).ok().map(|uuid| uuid.as_u128())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto_decode::parse_tls_certificate_from_string;
+
+    /// Subject alternative name `DNS:company.example, URI:urn:uuid:6c1b3a4e-8f2d-4c1a-9b7e-2d3f4a5b6c7d`
+    const COMPANY_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBxzCCAW6gAwIBAgIUZ65mzFyWwqp4FJwTU0lO+kGctSgwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHY29tcGFueTAgFw0yNjEwMTgyMzQ5MjhaGA8yMTI2MDkyNDIz
+NDkyOFowEjEQMA4GA1UEAwwHY29tcGFueTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABEbwnXm6ExEtUByZiCz0jPb+3hiKbujNPt53+2IgGwMjiYjefKcm6OMuxeCn
+RUeenh/Qm1RQAiRP0F8Kq/MtKE2jgZ8wgZwwHQYDVR0OBBYEFMvNJm1en7ygJo/T
+KrgAMkY4exF8MB8GA1UdIwQYMBaAFMvNJm1en7ygJo/TKrgAMkY4exF8MA8GA1Ud
+EwEB/wQFMAMBAf8wSQYDVR0RBEIwQIIPY29tcGFueS5leGFtcGxlhi11cm46dXVp
+ZDo2YzFiM2E0ZS04ZjJkLTRjMWEtOWI3ZS0yZDNmNGE1YjZjN2QwCgYIKoZIzj0E
+AwIDRwAwRAIgWWCi1xDdXT8XQPV6Yc3owDO+SEbvc6T+H612Z1ArI6oCIEhVQW9H
+3iWH1qugJNJQA2gbw2+tXj5jPIgbfRJCHll1
+-----END CERTIFICATE-----";
+
+    /// No subject alternative name
+    const PLAIN_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBejCCASGgAwIBAgIUSxQZgHURZkMjUPCL970+Oyyqf6UwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHY29tcGFueTAgFw0yNjEwMTgyMzQ5MjhaGA8yMTI2MDkyNDIz
+NDkyOFowEjEQMA4GA1UEAwwHY29tcGFueTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABAFnfSztMGK7IzAaLCYke/7n42vUg4attMrrzzw3ncNqsw5y1t6MAwIHXxXl
+V9G84kIpH8WoGY9HvQSWsXEBpl2jUzBRMB0GA1UdDgQWBBTEFw96PVT76ysrG+TG
+Xs9MLfZdvTAfBgNVHSMEGDAWgBTEFw96PVT76ysrG+TGXs9MLfZdvTAPBgNVHRMB
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIA7TjvpTXe7hMULWuYudF2get+K/
+kgWzN2+kFUqWm2zGAiBBNUZM12nEFf7hTGXQd1TkPnPcuqWgRrleVqg/T9uFtQ==
+-----END CERTIFICATE-----";
+
+    #[test]
+    pub fn company_id_from_subject_alt_name() {
+        let certificate = parse_tls_certificate_from_string(COMPANY_CERTIFICATE).unwrap();
+        let uuid = Uuid::parse_str("6c1b3a4e-8f2d-4c1a-9b7e-2d3f4a5b6c7d").unwrap().as_u128();
+        assert_eq!(certificate_company_id(&certificate), Some(uuid));
+    }
+
+    #[test]
+    pub fn no_company_id_without_subject_alt_name() {
+        let certificate = parse_tls_certificate_from_string(PLAIN_CERTIFICATE).unwrap();
+        assert_eq!(certificate_company_id(&certificate), None);
+    }
+
+    #[test]
+    pub fn no_company_id_of_malformed_certificate() {
+        let certificate = parse_tls_certificate_from_string(COMPANY_CERTIFICATE).unwrap();
+        let truncated = Certificate(certificate.0[..certificate.0.len() / 2].to_vec());
+        assert_eq!(certificate_company_id(&truncated), None);
+        assert_eq!(certificate_company_id(&Certificate(b"urn:uuid:6c1b3a4e-8f2d-4c1a-9b7e-2d3f4a5b6c7d".to_vec())), None);
+    }
+}
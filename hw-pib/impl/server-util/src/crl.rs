@@ -0,0 +1,302 @@
+//! Certificate revocation lists
+//!
+//! rustls does not check revocation, hence the analyst's CRL is parsed
+//! here: the serial numbers of the revoked certificates are extracted
+//! after the signature of the CRL was verified against the root CA.
+//! Only the DER structure that is needed for this is decoded (RFC 5280).
+//!
+//! A CRL is only valid until its `nextUpdate`, expired CRLs are rejected.
+
+use std::{fs, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+use rustls::Certificate;
+use rustls_pemfile::{read_all, Item};
+
+use crate::der::{read, skip_optional, tbs_certificate, TAG_BIT_STRING, TAG_GENERALIZED_TIME, TAG_INTEGER, TAG_OID, TAG_SEQUENCE, TAG_UTC_TIME};
+
+/// Signature algorithm OIDs (content bytes)
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0B];
+const OID_SHA384_WITH_RSA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0C];
+const OID_SHA512_WITH_RSA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0D];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+const OID_ECDSA_WITH_SHA384: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x03];
+
+/// Revoked serial numbers of a verified CRL
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crl {
+    pub revoked_serials: Vec<Vec<u8>>,
+    /// End of the validity (`nextUpdate`), `None` if the CRL does not state it
+    pub next_update: Option<SystemTime>,
+}
+
+/// Read a PEM or DER encoded CRL that is valid at `now`
+///
+/// The CRL has to be signed by the `issuer` (the analyst's root CA).
+pub fn parse_crl_from_path(path: &Path, issuer: &Certificate, now: SystemTime) -> Result<Crl, Box<dyn std::error::Error>> {
+    if !path.is_file() {
+        return Err("Error: path given is not a regular file, please update to point to a CRL.".into());
+    }
+
+    let content = fs::read(path)?;
+    let crl = if content.starts_with(b"-----BEGIN") {
+        read_all(&mut content.as_slice())?
+            .into_iter()
+            .find_map(|item| match item {
+                Item::Crl(crl) => Some(crl),
+                _ => None,
+            })
+            .ok_or("Malformed CRL file")?
+    } else {
+        content
+    };
+
+    parse_crl(&crl, issuer, now)
+}
+
+/// Verify the DER encoded CRL against the `issuer` and its validity at `now`
+pub fn parse_crl(crl: &[u8], issuer: &Certificate, now: SystemTime) -> Result<Crl, Box<dyn std::error::Error>> {
+    let malformed = || "Malformed CRL";
+
+    // CertificateList ::= SEQUENCE { tbsCertList, signatureAlgorithm, signatureValue }
+    let (certificate_list, _) = read(crl, TAG_SEQUENCE).ok_or_else(malformed)?;
+    let (tbs_cert_list, rest) = read(certificate_list, TAG_SEQUENCE).ok_or_else(malformed)?;
+    let tbs_raw = &certificate_list[..certificate_list.len() - rest.len()];
+    let (algorithm, rest) = read(rest, TAG_SEQUENCE).ok_or_else(malformed)?;
+    let (signature, _) = read(rest, TAG_BIT_STRING).ok_or_else(malformed)?;
+    let (algorithm, _) = read(algorithm, TAG_OID).ok_or_else(malformed)?;
+
+    // No unused bits are allowed in a signature
+    let signature = match signature.split_first() {
+        Some((0, signature)) => signature,
+        _ => return Err(malformed().into()),
+    };
+
+    let issuer = webpki::EndEntityCert::try_from(issuer.0.as_slice()).map_err(|e| format!("Invalid CRL issuer: {:?}", e))?;
+    let verified = signature_algorithms(algorithm)
+        .iter()
+        .any(|signature_algorithm| issuer.verify_signature(signature_algorithm, tbs_raw, signature).is_ok());
+    if !verified {
+        return Err("The CRL is not signed by the root CA".into());
+    }
+
+    // TBSCertList ::= SEQUENCE { version OPTIONAL, signature, issuer, thisUpdate, nextUpdate OPTIONAL, revokedCertificates OPTIONAL, ... }
+    let rest = skip_optional(tbs_cert_list, TAG_INTEGER);
+    let (_, rest) = read(rest, TAG_SEQUENCE).ok_or_else(malformed)?;
+    let (_, rest) = read(rest, TAG_SEQUENCE).ok_or_else(malformed)?;
+    let (this_update, rest) = read_time(rest).ok_or_else(malformed)?;
+    let (next_update, rest) = match read_time(rest) {
+        Some((next_update, rest)) => (Some(next_update), rest),
+        None => (None, rest),
+    };
+    if this_update > now {
+        return Err("The CRL is not valid yet".into());
+    }
+    if matches!(next_update, Some(next_update) if next_update <= now) {
+        return Err("The CRL expired - a new one has to be issued".into());
+    }
+
+    // Without revoked certificates the list is omitted
+    let mut revoked = match read(rest, TAG_SEQUENCE) {
+        Some((revoked, _)) => revoked,
+        None => return Ok(Crl { revoked_serials: Vec::new(), next_update }),
+    };
+
+    let mut serials = Vec::new();
+    while !revoked.is_empty() {
+        let (entry, rest) = read(revoked, TAG_SEQUENCE).ok_or_else(malformed)?;
+        let (serial, _) = read(entry, TAG_INTEGER).ok_or_else(malformed)?;
+        serials.push(serial.to_vec());
+        revoked = rest;
+    }
+    Ok(Crl { revoked_serials: serials, next_update })
+}
+
+/// Read the next element if it is a time (`UTCTime` or `GeneralizedTime` in UTC)
+fn read_time(input: &[u8]) -> Option<(SystemTime, &[u8])> {
+    let (time, rest, year) = match read(input, TAG_UTC_TIME) {
+        // Two digit years 50 to 99 are 1950 to 1999
+        Some((time, rest)) => {
+            let year = digits(time.get(..2)?)?;
+            (time.get(2..)?, rest, if year >= 50 { 1900 + year } else { 2000 + year })
+        }
+        None => {
+            let (time, rest) = read(input, TAG_GENERALIZED_TIME)?;
+            (time.get(4..)?, rest, digits(time.get(..4)?)?)
+        }
+    };
+
+    // MMDDHHMMSSZ (DER encodes the seconds and UTC)
+    if time.len() != 11 || time[10] != b'Z' {
+        return None;
+    }
+    let [month, day, hour, minute, second] = [0, 2, 4, 6, 8].map(|i| digits(&time[i..i + 2]));
+    let (month, day) = (month.filter(|month| (1..=12).contains(month))?, day.filter(|day| (1..=31).contains(day))?);
+    let seconds = days_since_epoch(year, month, day)? * 86400 + hour? * 3600 + minute? * 60 + second?;
+    Some((UNIX_EPOCH + Duration::from_secs(seconds), rest))
+}
+
+/// Decimal number of ASCII digits
+fn digits(digits: &[u8]) -> Option<u64> {
+    digits.iter().try_fold(0, |number, digit| digit.is_ascii_digit().then(|| number * 10 + (digit - b'0') as u64))
+}
+
+/// Days from 1970-01-01 to the date (proleptic Gregorian calendar)
+fn days_since_epoch(year: u64, month: u64, day: u64) -> Option<u64> {
+    let (year, month) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let (era, year_of_era) = (year / 400, year % 400);
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146097 + day_of_era).checked_sub(719468)
+}
+
+/// Serial number of a DER encoded certificate
+pub fn certificate_serial(certificate: &Certificate) -> Option<Vec<u8>> {
+    read(tbs_certificate(&certificate.0)?, TAG_INTEGER).map(|(serial, _)| serial.to_vec())
+}
+
+/// Candidates for a signature algorithm (the curve of ECDSA depends on the issuer's key)
+fn signature_algorithms(oid: &[u8]) -> Vec<&'static webpki::SignatureAlgorithm> {
+    match oid {
+        OID_SHA256_WITH_RSA => vec![&webpki::RSA_PKCS1_2048_8192_SHA256],
+        OID_SHA384_WITH_RSA => vec![&webpki::RSA_PKCS1_2048_8192_SHA384],
+        OID_SHA512_WITH_RSA => vec![&webpki::RSA_PKCS1_2048_8192_SHA512],
+        OID_ECDSA_WITH_SHA256 => vec![&webpki::ECDSA_P256_SHA256, &webpki::ECDSA_P384_SHA256],
+        OID_ECDSA_WITH_SHA384 => vec![&webpki::ECDSA_P256_SHA384, &webpki::ECDSA_P384_SHA384],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto_decode::parse_tls_certificate_from_string;
+
+    /// Root CA of the CRLs
+    const ROOT_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBgjCCASegAwIBAgIUTSMQknQNEmq8ZWKe6IcE2Uh+E5AwCgYIKoZIzj0EAwIw
+FTETMBEGA1UEAwwKYW5hbHlzdC1jYTAgFw0yNjEwMTgyMzUyMzBaGA8yMTI2MDky
+NDIzNTIzMFowFTETMBEGA1UEAwwKYW5hbHlzdC1jYTBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABI3uSCXzaDCPJtBOJKvjvI2R8z4ppOTBCXJHdwcYBlHOi1U6177z
+qMISOlZ3F3FcJKj3+lBasbfqiqqZooH3dSejUzBRMB0GA1UdDgQWBBR6lMCe8TNU
+TsxWkpZrHghFDwnLpTAfBgNVHSMEGDAWgBR6lMCe8TNUTsxWkpZrHghFDwnLpTAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQC1CFZUHvZ6v3qAp81C
+qUs5K4gaHgf8w1q/UqukWBVgIQIhAIesCoFHJVEwAK5uavFKO630U9uQHLpi3Pao
+oPiA6wAO
+-----END CERTIFICATE-----";
+
+    /// Certificate with the serial 0x1A2B that was issued by the root CA
+    const COMPANY_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBXDCCAQGgAwIBAgICGiswCgYIKoZIzj0EAwIwFTETMBEGA1UEAwwKYW5hbHlz
+dC1jYTAgFw0yNjEwMTgyMzUyMzBaGA8yMTI2MDkyNDIzNTIzMFowEjEQMA4GA1UE
+AwwHY29tcGFueTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABLARPXTpXKO4ACOl
+H+gqMouLIf9XiijI5rrCLCyxQlBgWreWCVxeLG6uB/v1RN49mdr/VEVmDCaL5sr5
+aVxMrE+jQjBAMB0GA1UdDgQWBBTScfhdo8eOY5j3IVZHz+fhzMrLhTAfBgNVHSME
+GDAWgBR6lMCe8TNUTsxWkpZrHghFDwnLpTAKBggqhkjOPQQDAgNJADBGAiEA3PgZ
+Gcv0cTNyPnlzqcbbyblK/eFVSFT3VKD5qUdU9+QCIQCwES8ygRD8ODVTD01+dVce
+N3d9c4mY7U7Cl75i5uiy/w==
+-----END CERTIFICATE-----";
+
+    /// Self-signed certificate of another CA
+    const OTHER_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBejCCASGgAwIBAgIUSxQZgHURZkMjUPCL970+Oyyqf6UwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHY29tcGFueTAgFw0yNjEwMTgyMzQ5MjhaGA8yMTI2MDkyNDIz
+NDkyOFowEjEQMA4GA1UEAwwHY29tcGFueTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABAFnfSztMGK7IzAaLCYke/7n42vUg4attMrrzzw3ncNqsw5y1t6MAwIHXxXl
+V9G84kIpH8WoGY9HvQSWsXEBpl2jUzBRMB0GA1UdDgQWBBTEFw96PVT76ysrG+TG
+Xs9MLfZdvTAfBgNVHSMEGDAWgBTEFw96PVT76ysrG+TGXs9MLfZdvTAPBgNVHRMB
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIA7TjvpTXe7hMULWuYudF2get+K/
+kgWzN2+kFUqWm2zGAiBBNUZM12nEFf7hTGXQd1TkPnPcuqWgRrleVqg/T9uFtQ==
+-----END CERTIFICATE-----";
+
+    /// Revokes 0x1A2B, updated at 2026-10-18 23:52:30 UTC (UTCTime) until 2126-09-24 23:52:30 UTC (GeneralizedTime)
+    const REVOKING_CRL: &str = "-----BEGIN X509 CRL-----
+MIHGMG4CAQEwCgYIKoZIzj0EAwIwFTETMBEGA1UEAwwKYW5hbHlzdC1jYRcNMjYx
+MDE4MjM1MjMwWhgPMjEyNjA5MjQyMzUyMzBaMBUwEwICGisXDTI2MTAxODIzNTIz
+MFqgDzANMAsGA1UdFAQEAgIQATAKBggqhkjOPQQDAgNIADBFAiBZTrWuwbwYLg2G
+SqWsT1ZGYdN7MbKdxdKzxcMPKi45EAIhAJxoyPdTRaUEEmwmDgWKmSOhMaFSFoAG
+Rjr4jnEDU5Om
+-----END X509 CRL-----";
+
+    /// No revoked certificates, same validity
+    const EMPTY_CRL: &str = "-----BEGIN X509 CRL-----
+MIGuMFcCAQEwCgYIKoZIzj0EAwIwFTETMBEGA1UEAwwKYW5hbHlzdC1jYRcNMjYx
+MDE4MjM1MjMwWhgPMjEyNjA5MjQyMzUyMzBaoA8wDTALBgNVHRQEBAICEAAwCgYI
+KoZIzj0EAwIDRwAwRAIgBcv/bY+3ee+FKAxSadoAQ/yebgGDU6IXzYSZuMI3fYUC
+ICCe5mFMFr3SQ8B/9ge9B2Vj7xDZGdy94LmAoWfRE7ve
+-----END X509 CRL-----";
+
+    const THIS_UPDATE: u64 = 1792367550;
+    const NEXT_UPDATE: u64 = 4945967550;
+
+    fn der(crl: &str) -> Vec<u8> {
+        match read_all(&mut crl.as_bytes()).unwrap().pop() {
+            Some(Item::Crl(crl)) => crl,
+            _ => panic!("No CRL"),
+        }
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    pub fn crl_with_revoked_serials() {
+        let root_ca = parse_tls_certificate_from_string(ROOT_CA).unwrap();
+        let crl = parse_crl(&der(REVOKING_CRL), &root_ca, at(THIS_UPDATE + 60)).unwrap();
+        assert_eq!(crl.revoked_serials, vec![vec![0x1A, 0x2B]]);
+        assert_eq!(crl.next_update, Some(at(NEXT_UPDATE)));
+
+        let company = parse_tls_certificate_from_string(COMPANY_CERTIFICATE).unwrap();
+        assert_eq!(certificate_serial(&company), Some(vec![0x1A, 0x2B]));
+    }
+
+    #[test]
+    pub fn crl_without_revoked_serials() {
+        let root_ca = parse_tls_certificate_from_string(ROOT_CA).unwrap();
+        let crl = parse_crl(&der(EMPTY_CRL), &root_ca, at(THIS_UPDATE + 60)).unwrap();
+        assert!(crl.revoked_serials.is_empty());
+        assert_eq!(crl.next_update, Some(at(NEXT_UPDATE)));
+    }
+
+    #[test]
+    pub fn expired_crl_is_rejected() {
+        let root_ca = parse_tls_certificate_from_string(ROOT_CA).unwrap();
+        assert!(parse_crl(&der(REVOKING_CRL), &root_ca, at(NEXT_UPDATE)).is_err());
+        assert!(parse_crl(&der(REVOKING_CRL), &root_ca, at(NEXT_UPDATE + 1)).is_err());
+        assert!(parse_crl(&der(REVOKING_CRL), &root_ca, at(NEXT_UPDATE - 1)).is_ok());
+    }
+
+    #[test]
+    pub fn future_crl_is_rejected() {
+        let root_ca = parse_tls_certificate_from_string(ROOT_CA).unwrap();
+        assert!(parse_crl(&der(REVOKING_CRL), &root_ca, at(THIS_UPDATE - 1)).is_err());
+    }
+
+    #[test]
+    pub fn crl_of_other_issuer_is_rejected() {
+        let other_ca = parse_tls_certificate_from_string(OTHER_CA).unwrap();
+        assert!(parse_crl(&der(REVOKING_CRL), &other_ca, at(THIS_UPDATE + 60)).is_err());
+    }
+
+    #[test]
+    pub fn malformed_crl_is_rejected() {
+        let root_ca = parse_tls_certificate_from_string(ROOT_CA).unwrap();
+        let crl = der(REVOKING_CRL);
+        assert!(parse_crl(&crl[..crl.len() - 1], &root_ca, at(THIS_UPDATE + 60)).is_err());
+        assert!(parse_crl(&crl[..crl.len() / 2], &root_ca, at(THIS_UPDATE + 60)).is_err());
+
+        // A modified serial breaks the signature
+        let mut modified = crl.clone();
+        let serial = modified.windows(4).position(|window| window == [0x02, 0x02, 0x1A, 0x2B]).unwrap();
+        modified[serial + 3] = 0x2C;
+        assert!(parse_crl(&modified, &root_ca, at(THIS_UPDATE + 60)).is_err());
+    }
+
+    #[test]
+    pub fn crl_times() {
+        assert_eq!(read_time(b"\x17\x0d261018235230Z").map(|(time, _)| time), Some(at(THIS_UPDATE)));
+        assert_eq!(read_time(b"\x18\x0f21260924235230Z").map(|(time, _)| time), Some(at(NEXT_UPDATE)));
+        assert_eq!(read_time(b"\x17\x0d700101000000Z").map(|(time, _)| time), Some(at(0)));
+        assert_eq!(read_time(b"\x17\x0d261318235230Z"), None);
+        assert_eq!(read_time(b"\x17\x0b2610182352Z"), None);
+    }
+}
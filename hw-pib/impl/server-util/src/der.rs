@@ -0,0 +1,57 @@
+//! Minimal DER reader
+//!
+//! Only the parts of CRLs that the server needs are decoded, elements
+//! are read one after another from a byte slice.
+
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+pub(crate) const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OID: u8 = 0x06;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+pub(crate) const TAG_UTC_TIME: u8 = 0x17;
+pub(crate) const TAG_GENERALIZED_TIME: u8 = 0x18;
+
+/// Context specific tag `[number]` of a constructed (explicit) element
+pub(crate) const fn tag_explicit(number: u8) -> u8 {
+    0xA0 | number
+}
+
+/// Read the next element and return its tag, content and the remaining input
+pub(crate) fn read_any(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        // Long form with up to 4 length bytes
+        let bytes = (first & 0x7F) as usize;
+        if bytes == 0 || bytes > 4 || rest.len() < bytes {
+            return None;
+        }
+        let length = rest[..bytes].iter().fold(0_usize, |length, byte| (length << 8) | *byte as usize);
+        (length, &rest[bytes..])
+    };
+
+    if rest.len() < length {
+        return None;
+    }
+    Some((tag, &rest[..length], &rest[length..]))
+}
+
+/// Read the content of the next element if it has the `tag` and return the remaining input
+pub(crate) fn read(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    read_any(input).filter(|(read_tag, ..)| *read_tag == tag).map(|(_, content, rest)| (content, rest))
+}
+
+/// Skip the next element if it has the `tag` (i.e. an optional element)
+pub(crate) fn skip_optional(input: &[u8], tag: u8) -> &[u8] {
+    read(input, tag).map_or(input, |(_, rest)| rest)
+}
+
+/// Content of the tbsCertificate after the version, i.e. starting with the serial number
+pub(crate) fn tbs_certificate(certificate: &[u8]) -> Option<&[u8]> {
+    // Certificate ::= SEQUENCE { tbsCertificate SEQUENCE { [0] version OPTIONAL, serialNumber, ... }, ... }
+    let (certificate, _) = read(certificate, TAG_SEQUENCE)?;
+    let (tbs_certificate, _) = read(certificate, TAG_SEQUENCE)?;
+    Some(skip_optional(tbs_certificate, tag_explicit(0)))
+}
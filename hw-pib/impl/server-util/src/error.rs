@@ -1,86 +1,58 @@
 use actix_web::{
     error::ResponseError,
-    http::{StatusCode},
+    http::{header::RETRY_AFTER, StatusCode},
     HttpResponse,
 };
 use validator::ValidationErrors;
 
 
 use std::fmt;
-use serde::{Deserialize, Serialize};
 use serde_json::{Map as JsonMap, Value as JsonValue, json};
 use benchmark::error::BenchmarkingError;
+use types::message::error::{ErrorCode, ErrorMsg};
 
 ///
-/// Error types the API can return on user connection
+/// Error type the API returns on user connection: a machine-readable
+/// code (which decides about the HTTP status) and a message.
 ///
 
-#[derive(Debug, Serialize)]
-pub enum ApiError {
-    ParseError(JsonValue),
-    NotImplemented(JsonValue),
-    Unauthorized(JsonValue),
-    BadRequest(JsonValue),
-    InternalServerError(JsonValue),
-    NotFound(JsonValue),
+#[derive(Debug)]
+pub struct ApiError {
+    msg: ErrorMsg,
 }
 
-impl fmt::Display for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ApiError::Unauthorized(error) => {write!(f, "Unauthorized: {}", error)},
-            ApiError::BadRequest(error) => {write!(f, "BadRequest: {}", error)},
-            ApiError::NotImplemented(error) => {write!(f, "(⌒_⌒;) NotImplemented: {}", error)},
-            ApiError::InternalServerError(error) => {write!(f, "InternalServerError: {}", error)},
-            ApiError::NotFound(error) => {write!(f, "NotFound: {}", error)},
-            _ => {write!(f, "Unknown Error!")}
-        } 
+impl ApiError {
+    pub fn code(&self) -> &ErrorCode {
+        &self.msg.code
     }
 }
 
-///
-/// Return a list of error in case multiple errors occur
-///
-
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ApiErrorResponse {
-    errors: Vec<String>,
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
 }
 
 ///
 /// ERROR CONVERSION FOR ERROR TYPES
 ///
 
-/// Utility to make transforming a string reference into an ErrorResponse
-impl From<&String> for ApiError {
-    fn from(error: &String) -> Self {
-        ApiError::BadRequest(
-            json!(ApiErrorResponse {errors: vec![format!("{}", error)]})
-        )
+/// Utility to make transforming a code and a message into an ErrorResponse
+impl From<(ErrorCode, String)> for ApiError {
+    fn from(error: (ErrorCode, String)) -> Self {
+        ApiError { msg: ErrorMsg { code: error.0, message: error.1 } }
     }
 }
 
-impl From<BenchmarkingError> for ApiError {
-    fn from(error: BenchmarkingError) -> Self {
-        ApiError::BadRequest(
-            json!(ApiErrorResponse {errors: vec![format!("Benchmarking Error: {}", error.to_string())]})
-        )
+impl From<(ErrorCode, &str)> for ApiError {
+    fn from(error: (ErrorCode, &str)) -> Self {
+        ApiError::from((error.0, error.1.to_string()))
     }
 }
 
-/// Utility to make transforming a string reference into an ErrorResponse
-impl From<&str> for ApiError {
-    fn from(error: &str) -> Self {
-        ApiError::BadRequest(
-            json!(ApiErrorResponse {errors: vec![error.to_string()]})
-        )
-    }
-}
-
-/// Utility to make transforming a vector of strings into an ErrorResponse
-impl From<Vec<String>> for ApiError {
-    fn from(errors: Vec<String>) -> Self {
-        ApiError::BadRequest(json!(ApiErrorResponse { errors }))
+impl From<BenchmarkingError> for ApiError {
+    fn from(error: BenchmarkingError) -> Self {
+        ApiError::from((error.code(), format!("Benchmarking Error: {}", error)))
     }
 }
 
@@ -102,42 +74,66 @@ impl From<ValidationErrors> for ApiError {
             err_map.insert(field.to_string(), json!(errors));
         }
 
-        ApiError::BadRequest(json!({
-            "errors": err_map,
-        }))
+        ApiError::from((ErrorCode::BadRequest, JsonValue::Object(err_map).to_string()))
     }
 }
 
 /// Implement Error Handling for Standard Error
 impl From<std::fmt::Error> for ApiError {
     fn from(error: std::fmt::Error) -> Self {
-        ApiError::from(&error.to_string())
+        ApiError::from((ErrorCode::Internal, error.to_string()))
     }
 }
 
 /// Implement the `ResponseError`-trait for our error types
 /// - Basically a wrapper for converting own errors into actix' errors
 impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.msg.code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
     fn error_response(&self) -> HttpResponse {
         // Log error to console with custom formatter
         log::error!("{}", self);
 
-        match self {
-            ApiError::BadRequest(error) => {
-                HttpResponse::BadRequest().json(error)
-            }
-            ApiError::NotFound(error) => {
-                HttpResponse::NotFound().json(error)
-            }
-            ApiError::NotImplemented(error) => {
-                HttpResponse::NotFound().json(error)
-            }
-            ApiError::Unauthorized(error) => {
-                HttpResponse::Unauthorized().json(error)
-            }
-            _ => { 
-                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR) 
-            },
+        let mut response = HttpResponse::build(self.status_code());
+        if let ErrorCode::TooManyRequests { retry_after } = self.msg.code {
+            response.insert_header((RETRY_AFTER, retry_after.to_string()));
         }
+        response.json(&self.msg)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::body::to_bytes;
+
+    use super::*;
+
+    #[test]
+    pub fn codes_decide_the_response() {
+        let error = ApiError::from((ErrorCode::InvalidFormat, "The result key is not hex encoded!"));
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.code(), &ErrorCode::InvalidFormat);
+
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+        let body = futures::executor::block_on(to_bytes(response.into_body())).unwrap();
+        let msg: ErrorMsg = serde_json::from_slice(&body).unwrap();
+        assert_eq!(msg, ErrorMsg { code: ErrorCode::InvalidFormat, message: "The result key is not hex encoded!".to_string() });
+    }
+
+    #[test]
+    pub fn too_many_requests_tell_when_to_retry() {
+        let response = ApiError::from((ErrorCode::TooManyRequests { retry_after: 7 }, "Slow down!")).error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "7");
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn errors_of_other_types_keep_their_code() {
+        assert_eq!(ApiError::from(std::fmt::Error).code(), &ErrorCode::Internal);
+        assert_eq!(ApiError::from(ValidationErrors::new()).code(), &ErrorCode::BadRequest);
+    }
+}
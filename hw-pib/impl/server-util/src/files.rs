@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::ops::Deref;
 use std::path::{PathBuf, Path};
 use std::{fmt::Debug, io::Write};
 use std::io::BufReader;
@@ -8,63 +9,200 @@ use futures::TryStreamExt;
 use std::fs::remove_file;
 use uuid::Uuid;
 use crate::error::ApiError;
-use types::consts::SERVER_DATA_PATH;
+use types::{consts::SERVER_DATA_PATH, message::error::ErrorCode};
+use zeroize::Zeroizing;
 
 type FieldName<'a> = &'a str;
 type ContentType<'a> = &'a str;
 
+/// Files of a multipart upload, which are deleted once dropped
+///
+/// The handlers only parse the uploaded files, thus no temporary file
+/// outlives its request (also not if the upload or parsing fails).
+#[derive(Debug, Default)]
+pub struct UploadedFiles<'a> {
+    files: HashMap<&'a str, PathBuf>,
+}
+
+impl<'a> Deref for UploadedFiles<'a> {
+    type Target = HashMap<&'a str, PathBuf>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.files
+    }
+}
+
+impl Drop for UploadedFiles<'_> {
+    fn drop(&mut self) {
+        for file in self.files.values() {
+            if let Err(e) = remove_file(file) {
+                log::warn!("Could not remove the uploaded file {:?}: {}", file, e);
+            }
+        }
+    }
+}
+
+/// Size limits of multipart uploads (bytes)
+///
+/// Exceeding a limit aborts the upload with a 413 (`PAYLOAD_TOO_LARGE`).
+/// Fields that are not accepted are read as well and count towards the
+/// limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadLimits {
+    /// Size of one field
+    pub field: usize,
+    /// Size of all fields of one request
+    pub request: usize,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        UploadLimits { field: 16 * 1024 * 1024, request: 32 * 1024 * 1024 }
+    }
+}
+
+/// Bytes received by one multipart upload
+struct UploadBudget {
+    limits: UploadLimits,
+    received: usize,
+}
+
+impl UploadBudget {
+    fn new(limits: UploadLimits) -> Self {
+        UploadBudget { limits, received: 0 }
+    }
+
+    /// Account a chunk of a field that received `field_size` bytes before
+    fn receive(&mut self, field_size: &mut usize, chunk: usize) -> Result<(), ApiError> {
+        *field_size += chunk;
+        self.received += chunk;
+        if *field_size > self.limits.field {
+            Err(ApiError::from((ErrorCode::PayloadTooLarge, format!("A field of the upload exceeds the limit of {} bytes!", self.limits.field))))
+        } else if self.received > self.limits.request {
+            Err(ApiError::from((ErrorCode::PayloadTooLarge, format!("The upload exceeds the limit of {} bytes!", self.limits.request))))
+        } else {
+            Ok(())
+        }
+    }
+}
 
 /// Save multiparted stream over HTTP
 ///
 /// - You specify the number of files you want to have uploaded
-/// - In case the file number does not match - revert all changes to filesystem
+/// - Accepted fields beyond this number are optional
+/// - In case fewer files were given - revert all changes to filesystem
 /// - The accepted mime_types are used in the vector to specify the file list. The ordering is arbitrary.
 /// - To allow wildcard content types or even text (application/octet-stream) use * as mime_type
-pub async fn save_multipart_files<'a>(mut payload: Multipart, mut accepted_files: Vec<(FieldName<'a>,ContentType<'a>)>, required_files: u16) -> Result<HashMap<&'a str, PathBuf>, ApiError> {
+/// - The files are deleted once the returned [`UploadedFiles`] are dropped
+/// - The upload is aborted once it exceeds the [`UploadLimits`]
+pub async fn save_multipart_files<'a>(mut payload: Multipart, mut accepted_files: Vec<(FieldName<'a>,ContentType<'a>)>, required_files: u16, limits: UploadLimits) -> Result<UploadedFiles<'a>, ApiError> {
 
-    // Save file paths
-    let mut file_list: HashMap<&str, PathBuf> = HashMap::new();
+    // Save file paths (removed again on any error)
+    let mut file_list = UploadedFiles::default();
+    let mut budget = UploadBudget::new(limits);
     
     log::debug!("Multipart upload in progress!");
 
     // iterate over multipart stream
     while let Ok(Some(field)) = payload.try_next().await {
 
-        // Get the content disposition from where on we process the multipart
-        // A `multipart/form-data` is required to have a `content_disposition` by definition
-        // The content-type is also required for every uploaded key by definition
-        let content_disposition = field.content_disposition();
-        let content_type = field.content_type();
-
-        log::debug!("Field Name: {:?} | Field Content-Type: {:?}", content_disposition.get_name(), content_type);
-
         // Require to find the requested field name with according content type
-        match accepted_files.iter().position(
-            |&s| s.0 == content_disposition.get_name().map_or("", |s| s) && s.1 == &content_type.to_string() ) {
+        match accepted_field(&accepted_files, &field) {
             
             Some(index) => {
                 log::debug!("Found a requested type in multipart!");
 
                 // Save everything to the filesystem (regardless whether it was a file before)
-                log::debug!("Saving {:?} to fs", content_disposition.get_name());
-                file_list.insert(accepted_files[index].0, field_to_file(field).await?);
+                log::debug!("Saving {:?} to fs", accepted_files[index].0);
+                let filepath = Path::new(SERVER_DATA_PATH).join(Uuid::new_v4().to_string());
+                file_list.files.insert(accepted_files[index].0, filepath.clone());
+                field_to_file(field, filepath, &mut budget).await?;
                 accepted_files.remove(index);
             }
             None => {
                 log::debug!("Did not find field to be requested! Skipping!");
+                skip_field(field, &mut budget).await?;
                 continue;
             }
         }
     }
     
-    // Verify that the requested number of files was given
-    if file_list.len() == required_files as usize {
+    // Verify that (at least) the requested number of files was given
+    if file_list.len() >= required_files as usize {
         Ok(file_list)
     } else {
-        Err(ApiError::from("You did not upload the requested file count!"))
+        Err(ApiError::from((ErrorCode::BadRequest, "You did not upload the requested file count!")))
+    }
+}
+
+/// Field of a multipart upload in memory with the content type it was sent as
+#[derive(Debug)]
+pub struct UploadedField<'a> {
+    pub content_type: ContentType<'a>,
+    /// Zeroized once dropped
+    pub content: Zeroizing<Vec<u8>>,
+}
+
+/// Read multiparted stream over HTTP into memory
+///
+/// Works like [`save_multipart_files`], but the content never touches the
+/// disk, which is meant for confidential uploads (i.e. company inputs).
+/// The buffers are zeroized once dropped.
+///
+/// A field may be accepted with several content types (e.g. different
+/// formats), the first one of its name that is sent is used. A field with
+/// an accepted name but another content type is rejected (415).
+pub async fn read_multipart_fields<'a>(mut payload: Multipart, mut accepted_fields: Vec<(FieldName<'a>,ContentType<'a>)>, required_fields: u16, limits: UploadLimits) -> Result<HashMap<&'a str, UploadedField<'a>>, ApiError> {
+
+    let mut field_list: HashMap<&str, UploadedField> = HashMap::new();
+    let mut budget = UploadBudget::new(limits);
+
+    log::debug!("Multipart upload into memory in progress!");
+
+    while let Ok(Some(field)) = payload.try_next().await {
+        match accepted_field(&accepted_fields, &field) {
+            Some(index) => {
+                let (name, content_type) = accepted_fields[index];
+                log::debug!("Reading {:?} ({}) into memory", name, content_type);
+                field_list.insert(name, UploadedField { content_type, content: field_to_memory(field, &mut budget).await? });
+                accepted_fields.retain(|accepted| accepted.0 != name);
+            }
+            None => {
+                let content_disposition = field.content_disposition();
+                let name = content_disposition.get_name().unwrap_or_default();
+                let content_types: Vec<&str> = accepted_fields.iter().filter(|accepted| accepted.0 == name).map(|accepted| accepted.1).collect();
+                if !content_types.is_empty() {
+                    return Err(ApiError::from((ErrorCode::UnsupportedMediaType, format!("The field {:?} is accepted as {} only!", name, content_types.join(", ")))));
+                }
+                log::debug!("Did not find field to be requested! Skipping!");
+                skip_field(field, &mut budget).await?;
+                continue;
+            }
+        }
+    }
+
+    if field_list.len() >= required_fields as usize {
+        Ok(field_list)
+    } else {
+        Err(ApiError::from((ErrorCode::BadRequest, "You did not upload the requested file count!")))
     }
 }
 
+/// Position of the field in the accepted fields (by name and content type)
+fn accepted_field(accepted_fields: &[(FieldName, ContentType)], field: &Field) -> Option<usize> {
+
+    // Get the content disposition from where on we process the multipart
+    // A `multipart/form-data` is required to have a `content_disposition` by definition
+    // The content-type is also required for every uploaded key by definition
+    let content_disposition = field.content_disposition();
+    let content_type = field.content_type();
+
+    log::debug!("Field Name: {:?} | Field Content-Type: {:?}", content_disposition.get_name(), content_type);
+
+    accepted_fields.iter().position(
+        |&s| s.0 == content_disposition.get_name().map_or("", |s| s) && s.1 == &content_type.to_string() )
+}
+
 #[allow(unused)]
 /// Decode a Multipart field which has no filename
 /// - considered to be a text input, as UTF8 is assumed
@@ -87,40 +225,57 @@ where T: Debug + for<'de> serde::Deserialize<'de> {
 }
 
 /// Save multiparted file to fileystem in non-blocking fashion
-async fn field_to_file(mut field: Field) -> Result<PathBuf, ApiError> {
-    
-    // Generate a unique file name
-    let filename = Uuid::new_v4().to_string();
-    let filepath = Path::new(SERVER_DATA_PATH).join(filename);
-    let thread_fp = filepath.clone();
+async fn field_to_file(mut field: Field, filepath: PathBuf, budget: &mut UploadBudget) -> Result<(), ApiError> {
 
     // Create file on filesystem with the threadpool
-    let mut f = web::block(move || std::fs::File::create(thread_fp))
+    let mut f = web::block(move || std::fs::File::create(filepath))
         .await
-        .map_err(|_| ApiError::from(&"Could not write given file (fs block)!".to_string()))?
-        .map_err(|_| ApiError::from(&"Could not write given file!".to_string()))?;
+        .map_err(|_| ApiError::from((ErrorCode::Internal, "Could not write given file (fs block)!")))?
+        .map_err(|_| ApiError::from((ErrorCode::Internal, "Could not write given file!")))?;
 
     // Field in turn is stream of *Bytes* object
-    while let Some(chunk) = field.try_next().await.map_err(|_| ApiError::from(&"The given file is corrupt!".to_string()))? {
+    let mut size = 0;
+    while let Some(chunk) = field.try_next().await.map_err(|_| ApiError::from((ErrorCode::BadRequest, "The given file is corrupt!")))? {
+        budget.receive(&mut size, chunk.len())?;
         // filesystem operations are blocking, we have to use threadpool
         f = web::block(move || f.write_all(&chunk).map(|_| f))
             .await
-            .map_err(|_| ApiError::from("Could not write given file to filesystem!"))?
-            .map_err(|_| ApiError::from("Could not write file!"))?;
+            .map_err(|_| ApiError::from((ErrorCode::Internal, "Could not write given file to filesystem!")))?
+            .map_err(|_| ApiError::from((ErrorCode::Internal, "Could not write file!")))?;
     }
 
-    Ok(filepath)
+    Ok(())
 }
 
-/// Cleanup operation to clear uploaded files that dont need to be saved!
-pub async fn remove_files(file_list: Vec<PathBuf>) {
+/// Read a multiparted field into a buffer that is zeroized once dropped
+async fn field_to_memory(mut field: Field, budget: &mut UploadBudget) -> Result<Zeroizing<Vec<u8>>, ApiError> {
+
+    let mut content = Zeroizing::new(Vec::new());
 
-    // Remove all uploaded files → blocking operation!
-    // Remove AFTER all operations are done in parallel
-    for file in file_list.iter() {
-        let file_clone = file.clone();
-        let _ = web::block(move || remove_file(file_clone));
+    while let Some(chunk) = field.try_next().await.map_err(|_| ApiError::from((ErrorCode::BadRequest, "The given file is corrupt!")))? {
+        let mut size = content.len();
+        budget.receive(&mut size, chunk.len())?;
+        // Grow by hand, a reallocation would leave a copy of the content behind
+        if content.capacity() - content.len() < chunk.len() {
+            let mut grown = Zeroizing::new(Vec::with_capacity((content.len() + chunk.len()).max(2 * content.capacity())));
+            grown.extend_from_slice(&content);
+            content = grown;
+        }
+        content.extend_from_slice(&chunk);
     }
+
+    Ok(content)
+}
+
+/// Drain a field that is not accepted, such that it counts towards the limits
+async fn skip_field(mut field: Field, budget: &mut UploadBudget) -> Result<(), ApiError> {
+
+    let mut size = 0;
+    while let Some(chunk) = field.try_next().await.map_err(|_| ApiError::from((ErrorCode::BadRequest, "The given file is corrupt!")))? {
+        budget.receive(&mut size, chunk.len())?;
+    }
+
+    Ok(())
 }
 
 /// Parse a YAML file to a generic specified type that is Deserializable
@@ -132,7 +287,7 @@ where T: Debug + for<'de> serde::Deserialize<'de>
     let open_file_reader = BufReader::new(f);
     
     // Parse company data
-    let data: T = serde_yaml::from_reader(open_file_reader).map_err(|err| Error::from(ApiError::from(&err.to_string())))?;
+    let data: T = serde_yaml::from_reader(open_file_reader).map_err(|err| Error::from(ApiError::from((ErrorCode::InvalidFormat, err.to_string()))))?;
     
     Ok(data)
 }
\ No newline at end of file
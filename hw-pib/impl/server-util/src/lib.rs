@@ -3,4 +3,6 @@ pub mod files;
 pub mod broadcast_event;
 pub mod state;
 pub mod error;
-pub mod client_cert_extractor;
\ No newline at end of file
+pub mod client_cert_extractor;
+pub mod crl;
+mod der;
\ No newline at end of file
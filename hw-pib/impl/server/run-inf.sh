@@ -10,6 +10,6 @@ while [[ ${ITERATIONS} -gt ${COUNT} || ${ITERATIONS} -eq -1 ]]; do
 	#echo ${COUNT}
     fi
 
-    cargo run --release --features=evaluation && wait
+    cargo run --release && wait
 
 done
\ No newline at end of file
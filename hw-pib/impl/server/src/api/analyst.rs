@@ -1,6 +1,6 @@
-//! Analyst routes [`/api/analyst`] for managing Algorithms
+//! Analyst routes [`/api/v1/benchmarks/{bid}/analyst`] for managing Algorithms
 //! 
-//! The analyst can upload his algorithms here.  He can upload or even change them.
+//! The analyst can upload his algorithms for each benchmark here.  He can upload or even change them.
 //! For now also getting uploaded algortihms is possible. When uploaded, algorithms
 //! get verified for correctness (i.e. no dependency issues and syntactical errors).
 //! 
@@ -36,75 +36,117 @@
 //! 
 //! Here the algorithm is requiring `three`, `one` and `two` as input variables as
 //! they are not explicitly mentioned to be computable. 
-
-use std::sync::Arc;
+//! 
+//! Algorithms can only be uploaded before the collection of input data starts.
+//! A modification is possible during the collection as well. Once companies
+//! committed their data, it is only proposed (`algorithms-proposed` event) and
+//! becomes active after every participant confirmed it. Then all uploaded inputs
+//! are verified against the new algorithms again. Inputs that lack variables are
+//! discarded and the companies are notified with an `input-invalid` event to
+//! upload again (without participants, the response lists them directly).
+
+use std::{collections::HashMap, sync::Arc};
 use async_lock::RwLock;
 
 use actix_multipart::Multipart;
-use actix_web::{Responder, web::{Json, Data}};
+use actix_web::{Responder, web::{Json, Data, Path, ReqData}};
+use tracing::Instrument;
 
 use benchmark::Algorithm;
-use server_util::{error::ApiError, files::save_multipart_files, broadcast_event::Broadcaster};
-use types::{message::response::RspMsg, consts::{FORM_DATA_FIELD_02_ALGORITHMS_MIME, FORM_DATA_FIELD_02_ALGORIHTMS_NAME}};
-
-use crate::server::BenchmarkingServer;
-
-pub async fn upload_algorithms(payload: Multipart, srv: Data<Arc<RwLock<BenchmarkingServer>>>, _broadcaster: Data<Broadcaster>) -> Result<impl Responder, ApiError> {
+use server_util::{error::ApiError, files::{save_multipart_files, UploadLimits}, broadcast_event::Broadcaster};
+use types::{entity::{BenchmarkPhase, Company}, message::{error::ErrorCode, event::ServerEvent, response::{RspMsg, BenchmarkID}}, consts::{FORM_DATA_FIELD_02_ALGORITHMS_MIME, FORM_DATA_FIELD_02_ALGORIHTMS_NAME}};
 
-    #[cfg(feature="evaluation")]
-    let now = std::time::SystemTime::now();
-
-    // Extract algorithm from request
-    let required_multiparts = vec![(FORM_DATA_FIELD_02_ALGORIHTMS_NAME, FORM_DATA_FIELD_02_ALGORITHMS_MIME)];
-    let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
-    let required_files = 1;
-    let files = save_multipart_files(payload,required_multiparts, required_files).await.map_err(|e| ApiError::from(&e.to_string()))?;
-    
-    #[cfg(feature="evaluation")]
-    _broadcaster.send(format!("EVAL-ALGO-UPLOAD: {:?}", now.elapsed().unwrap().as_nanos()).as_str());
+use crate::{attestation::algorithm_hash, audit::{Actor, AuditAction}, server::BenchmarkingServer};
 
-    // Parse Algorithm
-    #[cfg(not(feature="evaluation"))]
-    let (algorithms,_,_) = Algorithm::load(files.get(req_multipart_names[0]).ok_or(ApiError::from("Could not process upload!"))?)?;
+pub async fn upload_algorithms(payload: Multipart, limits: Data<UploadLimits>, srv: Data<Arc<RwLock<BenchmarkingServer>>>, actor: ReqData<Actor>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError> {
 
-    #[cfg(feature="evaluation")]
-    let (algorithms,parse, topo) = Algorithm::load(files.get(req_multipart_names[0]).ok_or(ApiError::from("Could not process upload!"))?)?;
+    let benchmark_id = benchmark_id.into_inner();
 
-    #[cfg(feature="evaluation")]
-    {
-        _broadcaster.send(format!("EVAL-ALGO-PARSE: {}", parse).as_str());
-        _broadcaster.send(format!("EVAL-ALGO-TOPO: {}", topo).as_str());
-    }
-    
+    // Fail early for unknown benchmarks or when inputs may already exist
+    srv.read().await.benchmark(benchmark_id)?.require_phase(&[BenchmarkPhase::Setup, BenchmarkPhase::Enrollment])?;
 
-    log::debug!("Uploaded algorithms: {:#?}", algorithms);
+    let algorithms = receive_algorithms(payload, **limits, benchmark_id).await?;
 
     // Write to config
     let mut mut_srv = srv.write().await;
-    mut_srv.set_algorithms(Some(algorithms));
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Setup, BenchmarkPhase::Enrollment])?;
+    let algorithm_hash = algorithm_hash(&algorithms);
+    benchmark.set_algorithms(Some(algorithms));
+    mut_srv.audit(&actor, AuditAction::UploadAlgorithms, Some(benchmark_id), None, Some(algorithm_hash));
 
     Ok(Json(RspMsg::new(true, "Upload successful!".to_string(), ())))
 }
 
-pub async fn modify_algorithms(payload: Multipart, srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>) -> Result<impl Responder, ApiError> {
+pub async fn modify_algorithms(payload: Multipart, limits: Data<UploadLimits>, srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, actor: ReqData<Actor>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError> {
     
+    let benchmark_id = benchmark_id.into_inner();
+
     {
         let srv_rdr = srv.read().await;
+        let benchmark = srv_rdr.benchmark(benchmark_id)?;
         // Require an exisiting configuration
-        if srv_rdr.algorithms().is_none() {
-            return Err(ApiError::from("No algorithms present!"));
+        if benchmark.algorithms().is_none() {
+            return Err(ApiError::from((ErrorCode::NotFound, "No algorithms present!")));
         }
+        benchmark.require_phase(&[BenchmarkPhase::Setup, BenchmarkPhase::Enrollment, BenchmarkPhase::Collection])?;
+    }
+
+    let algorithms = receive_algorithms(payload, **limits, benchmark_id).await?;
+
+    let mut mut_srv = srv.write().await;
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Setup, BenchmarkPhase::Enrollment, BenchmarkPhase::Collection])?;
+    let algorithm_hash = algorithm_hash(&algorithms);
+
+    // Participants only consented to the active algorithms
+    if benchmark.companies().values().any(Company::does_participate) {
+        benchmark.set_pending_algorithms(algorithms);
+        let awaiting = benchmark.awaiting_confirmation().len();
+        broadcaster.send(benchmark_id, &ServerEvent::AlgorithmsProposed { hash: algorithm_hash.clone() });
+        mut_srv.audit(&actor, AuditAction::ModifyAlgorithms, Some(benchmark_id), None, Some(format!("proposed {}", algorithm_hash)));
+
+        return Ok(Json(RspMsg::new(true, format!("Modification awaits the confirmation of {} participants!", awaiting), HashMap::new())));
     }
 
-    upload_algorithms(payload, srv, broadcaster).await
+    // Replace the algorithms and verify the present inputs against them
+    let invalid_inputs = mut_srv.activate_algorithms(benchmark_id, algorithms, &broadcaster)?;
+    mut_srv.audit(&actor, AuditAction::ModifyAlgorithms, Some(benchmark_id), None, Some(algorithm_hash));
+
+    Ok(Json(RspMsg::new(true, format!("Modification successful! {} inputs got invalid.", invalid_inputs.len()), invalid_inputs)))
+}
+
+/// Receive and parse an uploaded algorithm file
+///
+/// The upload is traced as `upload_algorithms` span, the parsing as
+/// `load_algorithms` span (with `parse` and `topo_sort` within).
+async fn receive_algorithms(payload: Multipart, limits: UploadLimits, benchmark_id: BenchmarkID) -> Result<Algorithm, ApiError> {
+
+    // Extract algorithm from request
+    let required_multiparts = vec![(FORM_DATA_FIELD_02_ALGORIHTMS_NAME, FORM_DATA_FIELD_02_ALGORITHMS_MIME)];
+    let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
+    let required_files = 1;
+    let files = save_multipart_files(payload,required_multiparts, required_files, limits)
+        .instrument(tracing::info_span!("upload_algorithms", benchmark = benchmark_id))
+        .await?;
+
+    // Parse Algorithm
+    let file = files.get(req_multipart_names[0]).ok_or(ApiError::from((ErrorCode::BadRequest, "Could not process upload!")))?;
+    let algorithms = tracing::info_span!("load_algorithms", benchmark = benchmark_id).in_scope(|| Algorithm::load_bundle(file))?;
+
+
+    log::debug!("Uploaded algorithms for benchmark {}: {:#?}", benchmark_id, algorithms);
+
+    Ok(algorithms)
 }
 
-pub async fn get_algorithms(srv: Data<Arc<RwLock<BenchmarkingServer>>>,) -> Result<impl Responder, ApiError> {
+pub async fn get_algorithms(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError> {
     let srv_rdr = srv.read().await;
+    let benchmark = srv_rdr.benchmark(*benchmark_id)?;
     // Require an exisiting configuration
-    if srv_rdr.algorithms().is_none() {
-        Err(ApiError::from("No algorithms present!"))
+    if benchmark.algorithms().is_none() {
+        Err(ApiError::from((ErrorCode::NotFound, "No algorithms present!")))
     } else {
-        Ok(Json(RspMsg::new(true, "Algorithms".to_string(), srv_rdr.algorithms().unwrap().clone())))
+        Ok(Json(RspMsg::new(true, "Algorithms".to_string(), benchmark.algorithms().unwrap().clone())))
     }
 }
\ No newline at end of file
@@ -1,66 +1,113 @@
 //! Benchmarking administration
 //! 
 //! These routes are managed by the analyst and are for managing the
-//! benchmarking process, i.e. by adding configuration or asking for
-//! it. Especially companies should be able to have insights on the 
-//! name, description and k-anonymity of the server that were 
-//! configured by the analyst.
+//! benchmarking process, i.e. by creating further benchmarks, adding
+//! configuration or asking for it. Especially companies should be able
+//! to have insights on the name, description and k-anonymity of the
+//! server that were configured by the analyst.
 //! 
 //! Here the companies are also enrolled and the analyst can even check
 //! which company did not upload their data, i.e. administrational info.
+//! Companies can also be removed again, which revokes their certificate.
+//! 
+//! The analyst moves the benchmark through its phases (see
+//! [`BenchmarkPhase`]), all participants can query the current phase.
 //! 
 //! Finally the analyst can broadcast events to all clients that are 
 //! enrolled in the broadcasting channel and start the benchmark, when
 //! enough participants uploaded their data (k-anonymity).
 
-use std::{sync::Arc, fs, str::FromStr};
+use std::{sync::Arc, fs, str::FromStr, collections::HashMap};
 use async_lock::RwLock;
 
 use actix_multipart::Multipart;
-use actix_web::{Responder, web::{Json, self, Path, Data}};
+use actix_web::{Responder, web::{Json, self, Path, Data, ReqData}, HttpRequest};
 use uuid::Uuid;
 
 use types::{message::{
-    request::{AnalystBenchmarkingMsg, AnalystEventMsg},
-    response::RspMsg,
-}, entity::{BenchmarkingConfig, Company}, consts::{FORM_DATA_FIELD_03_CONFIGURATION_MIME, FORM_DATA_FIELD_03_CONFIGURATION_NAME}};
-use server_util::{error::ApiError, files::save_multipart_files, broadcast_event::Broadcaster};
+    error::ErrorCode,
+    event::ServerEvent,
+    request::{AnalystBenchmarkingMsg, AnalystEventMsg, AnalystPhaseMsg},
+    response::{RspMsg, BenchmarkID},
+}, entity::{BenchmarkingConfig, BenchmarkPhase, Company}, consts::{C_ROUTE_BENCHMARKS, C_ROUTE_ENROLL_EVENTS, FORM_DATA_FIELD_03_CONFIGURATION_MIME, FORM_DATA_FIELD_03_CONFIGURATION_NAME}};
+use server_util::{error::ApiError, files::{save_multipart_files, UploadLimits}, broadcast_event::{Audience, Broadcaster}};
 
-use crate::{server::BenchmarkingServer, benchmark::run_benchmark};
+use crate::{server::BenchmarkingServer, benchmark::run_benchmark, audit::{Actor, AuditAction}, api::company::subscribe_events};
 
 ///
 /// SERVER CONFIGURATION_ROUTES
 /// 
 
+/// List all benchmarks of the server with their configuration
+pub async fn get_benchmarks(srv: Data<Arc<RwLock<BenchmarkingServer>>>,) -> Result<impl Responder, ApiError>{
+    
+    let srv_rdr = srv.read().await;
+    let benchmarks: HashMap<BenchmarkID, BenchmarkingConfig> = srv_rdr.benchmarks().iter().map(|(id, benchmark)| (*id, benchmark.benchmarking_config().clone())).collect();
+    Ok(Json(RspMsg::new(true, "Benchmarks".to_string(), benchmarks)))
+}
+
+/// Create a further benchmark with its own configuration
+/// 
+/// The benchmark is independent of all others, i.e. it has its own
+/// algorithms, companies and results. The response holds its ID.
+pub async fn create_benchmark(srv: Data<Arc<RwLock<BenchmarkingServer>>>, actor: ReqData<Actor>, payload: Multipart, limits: Data<UploadLimits>) -> Result<impl Responder, ApiError>{
+
+    let mut mut_srv = srv.write().await;
+
+    // Force initial configuration
+    if mut_srv.crypto_config().root_ca_certificate().is_none() {
+        return Err(ApiError::from((ErrorCode::Conflict, "Settings have to be initialized first")));
+    }
+
+    // Extract config
+    let required_multiparts = vec![(FORM_DATA_FIELD_03_CONFIGURATION_NAME, FORM_DATA_FIELD_03_CONFIGURATION_MIME)];
+    let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
+    let required_files = 1;
+    let files = save_multipart_files(payload,required_multiparts, required_files, **limits).await?;
+
+    // Parse benchmarking configuration information
+    let data = fs::read_to_string(files.get(req_multipart_names[0]).ok_or(ApiError::from((ErrorCode::BadRequest, "Did not find yaml to be correctly uploaded (incorrect name)!")))?);
+    let benchmarking_config: BenchmarkingConfig = serde_yaml::from_str::<BenchmarkingConfig>(&data.unwrap()).map_err(|e| ApiError::from((ErrorCode::InvalidFormat, e.to_string())))?;
+
+    let benchmark_id = mut_srv.add_benchmark(benchmarking_config);
+    mut_srv.audit(&actor, AuditAction::CreateBenchmark, Some(benchmark_id), None, None);
+    log::info!("Created benchmark {} - hosting {} benchmarks", benchmark_id, mut_srv.benchmarks().len());
+
+    Ok(Json(RspMsg::new(true, format!("Benchmark with ID {} successfully created!", benchmark_id), benchmark_id)))
+}
+
 /// Get benchmarking config or throw error if there is none
-pub async fn get_benchmark_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>,) -> Result<impl Responder, ApiError>{
+pub async fn get_benchmark_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError>{
     
     let srv_rdr = srv.read().await;
-    Ok(Json(RspMsg::new(true,"Benchmark Config".to_string() , srv_rdr.benchmarking_config().clone())))
+    Ok(Json(RspMsg::new(true,"Benchmark Config".to_string() , srv_rdr.benchmark(*benchmark_id)?.benchmarking_config().clone())))
 }
 
 /// Change initially set options afterwards
-pub async fn modify_benchmark_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, payload: Multipart) -> Result<impl Responder, ApiError>{
+pub async fn modify_benchmark_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, actor: ReqData<Actor>, benchmark_id: Path<BenchmarkID>, payload: Multipart, limits: Data<UploadLimits>) -> Result<impl Responder, ApiError>{
 
     let mut mut_srv = srv.write().await;
 
     // Force initial configuration
     if mut_srv.crypto_config().root_ca_certificate().is_none() {
-        return Err(ApiError::from(&("Settings have to be initialized first".to_string())));
+        return Err(ApiError::from((ErrorCode::Conflict, "Settings have to be initialized first")));
     }
 
     // Extract config
     let required_multiparts = vec![(FORM_DATA_FIELD_03_CONFIGURATION_NAME, FORM_DATA_FIELD_03_CONFIGURATION_MIME)];
     let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
     let required_files = 1;
-    let files = save_multipart_files(payload,required_multiparts, required_files).await?;
+    let files = save_multipart_files(payload,required_multiparts, required_files, **limits).await?;
 
     // Parse benchmarking server configuration information
-    let data = fs::read_to_string(files.get(req_multipart_names[0]).ok_or(ApiError::from("Did not find yaml to be correctly uploaded (incorrect name)!"))?);
-    let server_config: BenchmarkingConfig = serde_yaml::from_str::<BenchmarkingConfig>(&data.unwrap()).map_err(|e| ApiError::from(&e.to_string()))?;
+    let data = fs::read_to_string(files.get(req_multipart_names[0]).ok_or(ApiError::from((ErrorCode::BadRequest, "Did not find yaml to be correctly uploaded (incorrect name)!")))?);
+    let server_config: BenchmarkingConfig = serde_yaml::from_str::<BenchmarkingConfig>(&data.unwrap()).map_err(|e| ApiError::from((ErrorCode::InvalidFormat, e.to_string())))?;
 
     // Skip check for valid configuration
-    mut_srv.set_benchmarking_config_all(server_config.clone());
+    let benchmark = mut_srv.benchmark_mut(*benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Setup, BenchmarkPhase::Enrollment])?;
+    benchmark.set_benchmarking_config_all(server_config.clone());
+    mut_srv.audit(&actor, AuditAction::ModifyConfig, Some(*benchmark_id), None, None);
 
     // Respond with success
     Ok(
@@ -83,35 +130,38 @@ pub async fn modify_benchmark_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>,
 /// This lets the server generate a UUID for a company.
 /// The UUID is shared by the analyst to the respective
 /// company which performs further registration on its own.
-pub async fn company_enroll(srv: Data<Arc<RwLock<BenchmarkingServer>>>) -> Result<impl Responder, ApiError>{
+pub async fn company_enroll(srv: Data<Arc<RwLock<BenchmarkingServer>>>, actor: ReqData<Actor>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError>{
 
     let mut mut_srv = srv.write().await;
+    let benchmark = mut_srv.benchmark_mut(*benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Enrollment])?;
 
     // Verify that an algorithm has been provided - this assures that enrollment of companies is always possible with their algorithms
     // Otherwise their input data cannot be semantically verified (i.e. missing fields)
-    if mut_srv.algorithms().is_none() {
-        return Err(ApiError::from("No algorithms have been provided up until now. They are required in advance!"));
+    if benchmark.algorithms().is_none() {
+        return Err(ApiError::from((ErrorCode::Conflict, "No algorithms have been provided up until now. They are required in advance!")));
     }
 
     // Generate new UUID for company 
     let mut company_id = Uuid::new_v4().as_u128();
 
     // In case there are miraculously collisions
-    while mut_srv.companies().get(&company_id).is_some() {
+    while benchmark.companies().get(&company_id).is_some() {
         company_id = Uuid::new_v4().as_u128();
     }
 
-    log::debug!("Generating company: {:?}, exists: {:?}", company_id, mut_srv.companies().get(&company_id).is_some());
+    log::debug!("Generating company: {:?}, exists: {:?}", company_id, benchmark.companies().get(&company_id).is_some());
 
     // Populate settings info data structure with new company information
     let company = Company::default();
-    mut_srv.set_companies().insert(company_id, company);
+    benchmark.set_companies().insert(company_id, company);
 
-    log::info!("Currently enrolled companies: {}", mut_srv.companies().len());
+    log::info!("Currently enrolled companies in benchmark {}: {}", benchmark_id, benchmark.companies().len());
+    mut_srv.audit(&actor, AuditAction::EnrollCompany, Some(*benchmark_id), Some(company_id), None);
 
-    // Send positive response
+    // Send positive response, the company's certificate has to hold its UUID
     Ok(Json(
-        RspMsg::new(true, format!("Company with ID {} successfully added!", company_id), company_id)
+        RspMsg::new(true, format!("Company with ID {} successfully added! Its certificate has to hold the URI urn:uuid:{}", company_id, Uuid::from_u128(company_id)), company_id)
     ))
 }
 
@@ -119,19 +169,20 @@ pub async fn company_enroll(srv: Data<Arc<RwLock<BenchmarkingServer>>>) -> Resul
 /// Retrieve a company on request
 /// 
 /// This request returns an ApiError in case the company was not found.
-pub async fn get_company_status(srv: Data<Arc<RwLock<BenchmarkingServer>>>, company_id: Path<String>,) -> Result<impl Responder, ApiError>{
+pub async fn get_company_status(srv: Data<Arc<RwLock<BenchmarkingServer>>>, path: Path<(BenchmarkID, String)>,) -> Result<impl Responder, ApiError>{
     
+    let (benchmark_id, company_id) = path.into_inner();
     let srv_rdr = srv.read().await;
 
     // Extract uuid from request
     let company_id_uuid = match u128::from_str(&company_id) {
         Ok(uuid) => uuid,
-        Err(_) => { return Err(ApiError::from("Could not parse the given Company UUID!")); }
+        Err(_) => { return Err(ApiError::from((ErrorCode::BadRequest, "Could not parse the given Company UUID!"))); }
     };
 
     // Get information on company and respond with current info
-    let company = match srv_rdr.companies().get(&company_id_uuid) {
-        None => { return Err(ApiError::from("Could not find requested company!")); },
+    let company = match srv_rdr.benchmark(benchmark_id)?.companies().get(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::NotFound, "Could not find requested company!"))); },
         Some(result) => result
     };
 
@@ -142,6 +193,50 @@ pub async fn get_company_status(srv: Data<Arc<RwLock<BenchmarkingServer>>>, comp
         () )))
 }
 
+/// Remove a company from the benchmark and revoke its certificate
+/// 
+/// Its input and results are dropped. The removal is not possible
+/// during a computation, since the company's input is in use.
+pub async fn revoke_company(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, actor: ReqData<Actor>, path: Path<(BenchmarkID, String)>,) -> Result<impl Responder, ApiError>{
+
+    let (benchmark_id, company_id) = path.into_inner();
+    let mut mut_srv = srv.write().await;
+
+    // Extract uuid from request
+    let company_id_uuid = match u128::from_str(&company_id) {
+        Ok(uuid) => uuid,
+        Err(_) => { return Err(ApiError::from((ErrorCode::BadRequest, "Could not parse the given Company UUID!"))); }
+    };
+
+    let revocation_list = mut_srv.crypto_config().revocation_list().clone();
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Enrollment, BenchmarkPhase::Collection, BenchmarkPhase::ResultsPublished])?;
+
+    let company = match benchmark.remove_company(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::NotFound, "Could not find requested company!"))); },
+        Some(company) => company
+    };
+
+    // Unregistered companies have no certificate to revoke
+    if let Some(certificate) = company.certificate() {
+        if !revocation_list.revoke(certificate) {
+            log::warn!("[WARN ADMIN] Certificate of company {} has no readable serial number!", company_id_uuid);
+        }
+    }
+
+    log::info!("Removed company {} from benchmark {}, currently enrolled: {}", company_id_uuid, benchmark_id, benchmark.companies().len());
+    broadcaster.send_to(benchmark_id, Audience::Company(company_id_uuid), &ServerEvent::CompanyRevoked { company: company_id_uuid });
+    broadcaster.send_to(benchmark_id, Audience::Analyst, &ServerEvent::CompanyRevoked { company: company_id_uuid });
+
+    // The removed company might have been the last one to confirm pending algorithms
+    if let Some(algorithms) = benchmark.take_confirmed_algorithms() {
+        mut_srv.activate_algorithms(benchmark_id, algorithms, &broadcaster)?;
+    }
+    mut_srv.audit(&actor, AuditAction::RevokeCompany, Some(benchmark_id), Some(company_id_uuid), None);
+
+    Ok(Json(RspMsg::new(true, format!("Company {} was removed and its certificate revoked!", company_id_uuid), ())))
+}
+
 ///
 /// BENCHMARKING ROUTES
 /// 
@@ -150,45 +245,98 @@ pub async fn get_company_status(srv: Data<Arc<RwLock<BenchmarkingServer>>>, comp
 pub async fn start_benchmark(
     analyst_bm_msg: web::Json<AnalystBenchmarkingMsg>, 
     srv: Data<Arc<RwLock<BenchmarkingServer>>>,
-    broadcaster: Data<Broadcaster>) -> Result<impl Responder, ApiError>{
+    broadcaster: Data<Broadcaster>,
+    actor: ReqData<Actor>,
+    benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError>{
 
-    let srv_handle = srv.clone();
-    let srv_rdr = srv_handle.read().await;
+    let benchmark_id = benchmark_id.into_inner();
+    let mut mut_srv = srv.write().await;
+    let benchmark = mut_srv.benchmark(benchmark_id)?;
 
+    // Only one computation per benchmark and no input changes while computing
+    benchmark.require_phase(&[BenchmarkPhase::Collection])?;
 
     // Todo: Extract information from the request to find out which KPIs should be evaluated (let analyst select)
     // Now: all are selected regardless of analyst's choice
     log::debug!("Selected analyst Benchmarking KPIs: {:?}", analyst_bm_msg);
     
     // Check whether all participants have ready data - error out in case of missing
-    if srv_rdr.active_participants() < srv_rdr.benchmarking_config().k_anonymity() {
-        return Err(ApiError::from(&format!("K-anonymity threshold not satisfied: {} / {}", srv_rdr.active_participants(), srv_rdr.benchmarking_config().k_anonymity())));
+    if benchmark.active_participants() < benchmark.benchmarking_config().k_anonymity() {
+        return Err(ApiError::from((ErrorCode::Conflict, format!("K-anonymity threshold not satisfied: {} / {}", benchmark.active_participants(), benchmark.benchmarking_config().k_anonymity()))));
     }
 
-    // WARNING: THIS UNLOCK MIGHT BE TOO LATE!
+    mut_srv.set_phase(benchmark_id, BenchmarkPhase::Computing, &broadcaster)?;
+    mut_srv.audit(&actor, AuditAction::StartBenchmark, Some(benchmark_id), None, None);
+
+    // Unlock before the computation requests the lock
+    drop(mut_srv);
 
     // Spawn threads for computation of benchmarks and return imediately
     std::thread::spawn(move || { 
-        match run_benchmark(srv, broadcaster.into_inner()) {
+        // A panic of the computation fails the benchmark like an error, the phase is reset either way
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run_benchmark(srv.clone(), benchmark_id, broadcaster.clone().into_inner()).map_err(|err| err.to_string())))
+            .unwrap_or_else(|_| Err("The computation panicked!".to_string()));
+        match result {
             Ok(()) => log::info!("Benchmarking successfully finished!"),
             Err(err) => {
+                // Other benchmarks are unaffected, this one can be started again
                 log::error!("Benchmarking failed due to invalid computation: {}", err);
-                std::process::exit(-1);
+                if let Ok(benchmark) = futures::executor::block_on(srv.write()).benchmark_mut(benchmark_id) {
+                    benchmark.abort_computation();
+                }
+                broadcaster.send_to(benchmark_id, Audience::Analyst, &ServerEvent::ComputationFailed { reason: err.to_string() });
+                broadcaster.send(benchmark_id, &ServerEvent::PhaseChanged { phase: BenchmarkPhase::Collection });
             },
         } });
 
     // Put another message in body
-    Ok(Json(RspMsg::new(true, "Benchmarking successfully stated. Listen for events on /api/events.".to_string(), ())))
+    Ok(Json(RspMsg::new(true, format!("Benchmarking successfully stated. Listen for events on {}{}{}.", C_ROUTE_BENCHMARKS, benchmark_id, C_ROUTE_ENROLL_EVENTS), ())))
 }
 
 ///
 /// ANALYST SERVER MANAGEMENT
 /// 
 
-/// Send custom broadcast message to all registered participants
-pub async fn broadcast_event(event_msg: web::Json<AnalystEventMsg>, broadcaster: Data<Broadcaster>) -> Result<impl Responder, ApiError>{
+/// Send custom broadcast message to all registered participants of a benchmark
+pub async fn broadcast_event(event_msg: web::Json<AnalystEventMsg>, srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError>{
+    // Nobody listens to closed benchmarks
+    if srv.read().await.benchmark(*benchmark_id)?.phase() == BenchmarkPhase::Closed {
+        return Err(ApiError::from((ErrorCode::WrongPhase { phase: BenchmarkPhase::Closed }, "The benchmark is closed!")));
+    }
+
     // Broadcast message to all registered clients
-    broadcaster.send(event_msg.event.as_str());
+    broadcaster.send(*benchmark_id, &ServerEvent::AnalystMessage { message: event_msg.into_inner().event });
     // Put another message in body
     Ok(Json(RspMsg::new(true, "Event successfully dispatched".to_string(), ())))
-}
\ No newline at end of file
+}
+
+/// Events of the benchmark together with the ones for the analyst team
+/// 
+/// The team learns about failed computations and companies with invalid
+/// input or expired results, which the public event stream does not share.
+pub async fn analyst_event_stream(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, benchmark_id: Path<BenchmarkID>, req: HttpRequest) -> Result<impl Responder, ApiError> {
+    subscribe_events(&srv, &broadcaster, *benchmark_id, Audience::Analyst, &req).await
+}
+
+/// Current phase of a benchmark (for all participants)
+pub async fn get_phase(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError>{
+    
+    let srv_rdr = srv.read().await;
+    Ok(Json(RspMsg::new(true, "Benchmark phase".to_string(), srv_rdr.benchmark(*benchmark_id)?.phase())))
+}
+
+/// Move a benchmark into its next phase
+/// 
+/// The computation and the publication of results are started by the
+/// server itself, thus the analyst only opens the enrollment, the
+/// collection and closes the benchmark.
+pub async fn change_phase(phase_msg: web::Json<AnalystPhaseMsg>, srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, actor: ReqData<Actor>, benchmark_id: Path<BenchmarkID>) -> Result<impl Responder, ApiError>{
+
+    let mut mut_srv = srv.write().await;
+    mut_srv.benchmark(*benchmark_id)?.check_analyst_phase(phase_msg.phase)?;
+
+    mut_srv.set_phase(*benchmark_id, phase_msg.phase, &broadcaster)?;
+    mut_srv.audit(&actor, AuditAction::ChangePhase, Some(*benchmark_id), None, Some(phase_msg.phase.to_string()));
+
+    Ok(Json(RspMsg::new(true, format!("Benchmark {} is now in phase {}", benchmark_id, phase_msg.phase), phase_msg.phase)))
+}
@@ -6,193 +6,298 @@
 //! from the analyst or on the progress of one benchmark.
 //! 
 //! Similar to the analyst, the companies also do the HMAC procedure
-//! to authenticate their messages. 
+//! to authenticate their messages. A company is identified by the
+//! UUID in its certificate, which the analyst's CA signed.
+//! 
+//! Every route is bound to the phases of the benchmark, e.g. input data
+//! can only be changed during the collection.
+//! 
+//! Results are sealed to the key that a company registered and deleted
+//! after it confirmed their download. Inputs are only held in memory
+//! (never written to disk) and wiped after the results were assembled.
+//! 
+//! Inputs are uploaded as YAML, JSON or CSV, the content type of the
+//! multipart field selects the parser. All formats pass the same checks.
+//! 
+//! Companies can inspect the algorithms that are run on their data.
+//! Uploading the input consents to the active algorithms, further
+//! modifications by the analyst have to be confirmed explicitly.
 
-use std::{sync::Arc, str::FromStr};
+use std::sync::Arc;
 use async_lock::RwLock;
 
 use actix_http::header;
 use actix_multipart::Multipart;
-use actix_web::{Responder, web::{Json, Data, Path}, HttpResponse, HttpRequest};
+use actix_web::{Responder, web::{Json, Data, Path, ReqData}, HttpResponse, HttpRequest};
+use tracing::Instrument;
 use rustls::Certificate;
 use server_util::{
     error::ApiError,
-    files::{save_multipart_files}, broadcast_event::Broadcaster,
+    files::{read_multipart_fields, UploadedField, UploadLimits}, broadcast_event::{Audience, Broadcaster},
+    client_cert_extractor::certificate_company_id,
 };
-use types::{message::response::RspMsg, input::Input, consts::{FORM_DATA_FIELD_04_COMPANY_INPUT_NAME, FORM_DATA_FIELD_04_COMPANY_INPUT_MIME}};
+use benchmark::Algorithm;
+use types::{message::{error::ErrorCode, event::ServerEvent, request::{CompanyConfirmMsg, CompanySetupMsg}, response::{AlgorithmInfo, AlgorithmsMsg, RspMsg, BenchmarkID}}, entity::{BenchmarkPhase, Role}, input::{Input, InputFormat}, consts::{FORM_DATA_FIELD_04_COMPANY_INPUT_NAME, LAST_EVENT_ID_FIELD}};
 
-use crate::server::BenchmarkingServer;
+use crate::{attestation::algorithm_hash, audit::{Actor, AuditAction}, integrity::KeyExchange, sealing::parse_result_key, middleware::request_verifier::CompanyIdentity, server::BenchmarkingServer};
 
 ///
 /// Company specific routes
 ///
 
 /// Perform company input data upload and store it in data structure
-pub async fn upload_input_data(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, company_id: Path<String>, payload: Multipart) -> Result<impl Responder, ApiError>{
+pub async fn upload_input_data(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>, actor: ReqData<Actor>, payload: Multipart, limits: Data<UploadLimits>) -> Result<impl Responder, ApiError>{
     
-    let (input, company_id_uuid) = {
-        let mut mut_srv = srv.write().await;
+    let (benchmark_id, company_id_uuid) = (benchmark_id.into_inner(), company.0);
 
-        // Extract uuid from request
-        let company_id_uuid = match u128::from_str(&company_id) {
-            Ok(uuid) => uuid,
-            Err(_) => { return Err(ApiError::from("Could not parse the given Company UUID!")); }
-        };
+    let input = {
+        let mut mut_srv = srv.write().await;
     
+        let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+        benchmark.require_phase(&[BenchmarkPhase::Collection])?;
+
         // Get information on company and respond with current info
-        let company = match mut_srv.set_companies().get_mut(&company_id_uuid) {
-            None => { return Err(ApiError::from("The given ID is not enrolled - the provided UUID might be incorrect!")); },
+        let company = match benchmark.set_companies().get_mut(&company_id_uuid) {
+            None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
             Some(result) => result
         };
     
         // No changes allowed before integriy cannot be assured
         if company.certificate().is_none() {
-            return Err(ApiError::from("The requesting company has not setup signing - This is required in advance!"));
+            return Err(ApiError::from((ErrorCode::CompanyNotRegistered, "The requesting company has not setup signing - This is required in advance!")));
         }
     
         if company.input_data().size() != 0 {
-            return Err(ApiError::from("Existing data has to be modified!"));
+            return Err(ApiError::from((ErrorCode::Conflict, "Existing data has to be modified!")));
         }
     
 
-        #[cfg(feature="evaluation")]
-        let now = std::time::SystemTime::now();
-
         // Extract config
-        let required_multiparts = vec![(FORM_DATA_FIELD_04_COMPANY_INPUT_NAME, FORM_DATA_FIELD_04_COMPANY_INPUT_MIME)];
-        let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
-        let required_files = 1;
-        let files = save_multipart_files(payload,required_multiparts, required_files).await?;
-        
-        #[cfg(feature="evaluation")]
-        {
-            broadcaster.send(format!("EVAL-COMP-UPLOAD {:?}", now.elapsed().unwrap().as_nanos()).as_str());
-        }
+        let files = read_multipart_fields(payload, input_multiparts(), 1, **limits)
+            .instrument(tracing::info_span!("upload_input", benchmark = benchmark_id))
+            .await?;
 
-        let input_data = Input::load(files.get(&req_multipart_names[0]).ok_or(ApiError::from("File upload not successful!"))?).map_err(|e| ApiError::from(&e.to_string()))?;
-
-        (input_data, company_id_uuid)
+        tracing::info_span!("parse_input", benchmark = benchmark_id).in_scope(|| parse_input(files.get(FORM_DATA_FIELD_04_COMPANY_INPUT_NAME)))?
     };
     
-    #[cfg(not(feature="evaluation"))]
-    {
+    // Verified under a read lock only, the version tells which algorithms were checked
+    let (verified_version, verified_hash) = {
         let srv_rdr = srv.read().await;
-        srv_rdr.algorithms().clone().unwrap().verify_input(input.clone())?;
-    }
+        let benchmark = srv_rdr.benchmark(benchmark_id)?;
+        let algorithms = benchmark.algorithms().unwrap();
+        algorithms.verify_input(&input)?;
+        (benchmark.algorithm_version(), algorithm_hash(algorithms))
+    };
 
-    // Since we know that the company exists from above we skip checks
     let mut mut_srv = srv.write().await;
-    let company = mut_srv.set_companies().get_mut(&company_id_uuid).unwrap();
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
 
-    // Finally set input data
+    // The lock was released for verification, the collection might have ended,
+    // the company might have been revoked or the algorithms modified meanwhile
+    benchmark.require_phase(&[BenchmarkPhase::Collection])?;
+    if benchmark.algorithm_version() != verified_version {
+        return Err(ApiError::from((ErrorCode::Conflict, "The algorithms were modified during the upload - check them and upload again!")));
+    }
+    let company = benchmark.set_companies().get_mut(&company_id_uuid).ok_or_else(|| ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!")))?;
+    if company.input_data().size() != 0 {
+        return Err(ApiError::from((ErrorCode::Conflict, "Existing data has to be modified!")));
+    }
+
+    // Finally set input data, committing it consents to the verified algorithms
     company.set_input_data(input.clone());
+    company.set_confirmed_algorithm(verified_hash);
     
     // Bump the active participants count
-    mut_srv.increment_active_participants();
+    benchmark.increment_active_participants();
 
     // Check if enough participants enrolled and send a broadcast 
-    if mut_srv.active_participants() == mut_srv.benchmarking_config().k_anonymity() {
-        broadcaster.send("all-participants-enrolled");
+    if benchmark.active_participants() == benchmark.benchmarking_config().k_anonymity() {
+        broadcaster.send(benchmark_id, &ServerEvent::AllParticipantsEnrolled);
     }else {
-        broadcaster.send(format!("{} of {} participants enrolled!", mut_srv.active_participants(), mut_srv.benchmarking_config().k_anonymity()).as_str())
+        broadcaster.send(benchmark_id, &ServerEvent::ParticipantEnrolled { active: benchmark.active_participants(), required: benchmark.benchmarking_config().k_anonymity() })
     }
+    mut_srv.audit(&actor, AuditAction::UploadInput, Some(benchmark_id), Some(company_id_uuid), None);
 
     // Insert the data to the input
     Ok(Json(RspMsg::new(true, "Successfully uploaded input data!".to_string(), input)))
 }
 
 /// Perform company input data upload and modify existing data in state
-pub async fn modify_input_data(srv: Data<Arc<RwLock<BenchmarkingServer>>>, company_id: Path<String>, payload: Multipart) -> Result<impl Responder, ApiError>{
+pub async fn modify_input_data(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>, actor: ReqData<Actor>, payload: Multipart, limits: Data<UploadLimits>) -> Result<impl Responder, ApiError>{
     
+    let (benchmark_id, company_id_uuid) = (benchmark_id.into_inner(), company.0);
     let mut mut_srv = srv.write().await;
-
-    // Extract uuid from request
-    let company_id_uuid = match u128::from_str(&company_id) {
-        Ok(uuid) => uuid,
-        Err(_) => { return Err(ApiError::from("Could not parse the given Company UUID!")); }
-    };
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Collection])?;
 
     // Get information on company and respond with current info
-    let company = match mut_srv.set_companies().get_mut(&company_id_uuid) {
-        None => { return Err(ApiError::from("You are not enrolled or your UUID is incorrect!")); },
+    let company = match benchmark.companies().get(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
         Some(result) => result
     };
 
     // No changes allowed before integriy cannot be assured
     if company.certificate().is_none() {
-        return Err(ApiError::from("The requesting company has not setup signing - This is required in advance!"));
+        return Err(ApiError::from((ErrorCode::CompanyNotRegistered, "The requesting company has not setup signing - This is required in advance!")));
     }
 
     // Check that no config is present
     if !company.does_participate() {
-        return Err(ApiError::from("No input data in memory - nothing to modify - perform an upload first."))
+        return Err(ApiError::from((ErrorCode::NotFound, "No input data in memory - nothing to modify - perform an upload first.")))
     }
 
     // Extract config
-    let required_multiparts = vec![("input_data", "text/yaml")];
-    let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
-    let required_files = 1;
-    let files = save_multipart_files(payload,required_multiparts, required_files).await.map_err(|e| ApiError::from(&e.to_string()))?;
-    
-    let input_data = Input::load(files.get(&req_multipart_names[0]).ok_or(ApiError::from("File upload not successful!"))?).map_err(|e| ApiError::from(&e.to_string()))?;
-    company.set_input_data(input_data.clone());
+    let files = read_multipart_fields(payload, input_multiparts(), 1, **limits).await?;
+    let input_data = parse_input(files.get(FORM_DATA_FIELD_04_COMPANY_INPUT_NAME))?;
 
-    #[cfg(not(feature="evaluation"))]
-    mut_srv.algorithms().clone().unwrap().verify_input(input_data.clone())?;
+    // A rejected modification keeps the previous input
+    benchmark.algorithms().unwrap().verify_input(&input_data)?;
+    benchmark.set_companies().get_mut(&company_id_uuid).unwrap().set_input_data(input_data.clone());
+    mut_srv.audit(&actor, AuditAction::ModifyInput, Some(benchmark_id), Some(company_id_uuid), None);
 
     // Insert the data to the input
     Ok(Json(RspMsg::new(true, "Successfully uploaded input data!".to_string(), input_data)))
 }
 
+/// The input field in all formats that are accepted
+fn input_multiparts() -> Vec<(&'static str, &'static str)> {
+    InputFormat::ALL.iter().map(|format| (FORM_DATA_FIELD_04_COMPANY_INPUT_NAME, format.mime())).collect()
+}
+
+/// Parse the uploaded input in the format of its content type
+fn parse_input(field: Option<&UploadedField>) -> Result<Input, ApiError> {
+    let field = field.ok_or(ApiError::from((ErrorCode::BadRequest, "File upload not successful!")))?;
+    let format = InputFormat::from_mime(field.content_type).ok_or(ApiError::from((ErrorCode::UnsupportedMediaType, format!("Inputs of type {} are not supported!", field.content_type))))?;
+
+    Input::from_slice(&field.content, format).map_err(|e| ApiError::from((ErrorCode::InvalidFormat, e.to_string())))
+}
+
 /// Return a copy of already uploaded company data to the participant or respond with error
-pub async fn get_input_data(srv: Data<Arc<RwLock<BenchmarkingServer>>>, company_id: Path<String>,) -> Result<impl Responder, ApiError>{
+pub async fn get_input_data(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>,) -> Result<impl Responder, ApiError>{
     
+    let (benchmark_id, company_id_uuid) = (benchmark_id.into_inner(), company.0);
     let srv_rdr = srv.read().await;
 
-    // Extract uuid from request
-    let company_id_uuid = match u128::from_str(&company_id) {
-        Ok(uuid) => uuid,
-        Err(_) => { return Err(ApiError::from("Could not parse the given Company UUID!")); }
-    };
+    let benchmark = srv_rdr.benchmark(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Collection, BenchmarkPhase::Computing, BenchmarkPhase::ResultsPublished])?;
 
     // Get information on company and respond with current info
-    let company = match srv_rdr.companies().get(&company_id_uuid) {
-        None => { return Err(ApiError::from("You are not enrolled or your UUID is incorrect!")); },
+    let company = match benchmark.companies().get(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
         Some(result) => result
     };
 
     // No changes allowed before integriy cannot be assured
     if company.certificate().is_none() {
-        return Err(ApiError::from("The requesting company has not setup signing - This is required in advance!"));
+        return Err(ApiError::from((ErrorCode::CompanyNotRegistered, "The requesting company has not setup signing - This is required in advance!")));
+    }
+
+    if company.input_wiped() {
+        return Err(ApiError::from((ErrorCode::NotFound, "Your input data was wiped after the results were assembled!")));
     }
 
     Ok(Json(RspMsg::new(true, format!("{} input data", company_id_uuid), company.input_data().clone())))
 }
 
-/// Retrieve company result - is `None` (i.e., `null`) if no result exists
-pub async fn get_results(srv: Data<Arc<RwLock<BenchmarkingServer>>>, company_id: Path<String>,) -> Result<impl Responder, ApiError>{
+/// Retrieve the company results, which are sealed to its result key
+pub async fn get_results(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>, actor: ReqData<Actor>,) -> Result<impl Responder, ApiError>{
     
-    let srv_rdr = srv.read().await;
+    let (benchmark_id, company_id_uuid) = (benchmark_id.into_inner(), company.0);
+    let mut mut_srv = srv.write().await;
 
-    // Extract uuid from request
-    let company_id_uuid = match u128::from_str(company_id.as_str()) {
-        Ok(uuid) => uuid,
-        Err(_) => { return Err(ApiError::from("Could not parse the given Company UUID!")); }
-    };
+    let benchmark = mut_srv.benchmark(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::ResultsPublished])?;
 
     // Get information on company and respond with current info
-    let company = match srv_rdr.companies().get(&company_id_uuid) {
-        None => { return Err(ApiError::from("You are not enrolled or your UUID is incorrect!")); },
+    let company = match benchmark.companies().get(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
         Some(result) => result
     };
 
-    // Check if results exist
-    if company.results_data().size() == 0 {
-        return Err(ApiError::from("No results available!"));
-    }
+    // Results are deleted after their download was confirmed or they expired
+    let results = company.sealed_results().cloned().ok_or_else(|| ApiError::from((ErrorCode::NotFound, "No results available (anymore)!")))?;
+    mut_srv.audit(&actor, AuditAction::RetrieveResults, Some(benchmark_id), Some(company_id_uuid), None);
+    // Retrievals are no modification for the state persister
+    mut_srv.persist();
     
     // Return results
-    Ok(Json(RspMsg::new(true, format!("Results for company {}", company_id_uuid), company.results_data().clone())))
+    Ok(Json(RspMsg::new(true, format!("Results for company {}", company_id_uuid), results)))
+}
+
+/// Confirm the download of the results, which deletes them on the server
+pub async fn confirm_results(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>, actor: ReqData<Actor>,) -> Result<impl Responder, ApiError>{
+    
+    let (benchmark_id, company_id_uuid) = (benchmark_id.into_inner(), company.0);
+    let mut mut_srv = srv.write().await;
+
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::ResultsPublished, BenchmarkPhase::Closed])?;
+
+    let company = match benchmark.set_companies().get_mut(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
+        Some(result) => result
+    };
+
+    if company.sealed_results().is_none() {
+        return Err(ApiError::from((ErrorCode::NotFound, "No results available (anymore)!")));
+    }
+    company.set_sealed_results(None);
+    mut_srv.audit(&actor, AuditAction::ConfirmResults, Some(benchmark_id), Some(company_id_uuid), None);
+
+    Ok(Json(RspMsg::new(true, format!("Results for company {} were deleted", company_id_uuid), ())))
+}
+
+/// Show the algorithms that are run on the inputs and a pending modification
+/// 
+/// The hashes equal the ones of the attestation evidence.
+pub async fn get_company_algorithms(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: Path<BenchmarkID>,) -> Result<impl Responder, ApiError>{
+    
+    let benchmark_id = benchmark_id.into_inner();
+    let srv_rdr = srv.read().await;
+    let benchmark = srv_rdr.benchmark(benchmark_id)?;
+
+    let info = |algorithms: &Algorithm, version: u64| AlgorithmInfo { version, hash: algorithm_hash(algorithms), algorithm: algorithms.clone() };
+    let algorithms = AlgorithmsMsg {
+        active: benchmark.algorithms().map(|algorithms| info(algorithms, benchmark.algorithm_version())),
+        pending: benchmark.pending_algorithms().map(|algorithms| info(algorithms, benchmark.algorithm_version() + 1)),
+    };
+
+    Ok(Json(RspMsg::new(true, format!("Algorithms of benchmark {}", benchmark_id), algorithms)))
+}
+
+/// Confirm the pending modification of the algorithms
+/// 
+/// The modification becomes active as soon as all participants confirmed it.
+pub async fn confirm_algorithms(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>, actor: ReqData<Actor>, confirm_msg: Json<CompanyConfirmMsg>) -> Result<impl Responder, ApiError>{
+    
+    let (benchmark_id, company_id_uuid) = (benchmark_id.into_inner(), company.0);
+    let mut mut_srv = srv.write().await;
+
+
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Collection])?;
+
+    // Only the exact pending algorithms can be confirmed
+    let pending_hash = benchmark.pending_algorithms().map(algorithm_hash).ok_or_else(|| ApiError::from((ErrorCode::NotFound, "No modification of the algorithms is pending!")))?;
+    if !pending_hash.eq_ignore_ascii_case(&confirm_msg.algorithm_hash) {
+        return Err(ApiError::from((ErrorCode::Conflict, format!("The pending algorithms have the hash {}!", pending_hash))));
+    }
+
+    match benchmark.set_companies().get_mut(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
+        Some(company) => company.set_confirmed_algorithm(pending_hash.clone()),
+    };
+
+    // Activate the modification with the last confirmation
+    let awaiting = benchmark.awaiting_confirmation().len();
+    let confirmed_algorithms = benchmark.take_confirmed_algorithms();
+    mut_srv.audit(&actor, AuditAction::ConfirmAlgorithms, Some(benchmark_id), Some(company_id_uuid), Some(pending_hash));
+    if let Some(algorithms) = confirmed_algorithms {
+        mut_srv.activate_algorithms(benchmark_id, algorithms, &broadcaster)?;
+        return Ok(Json(RspMsg::new(true, "Confirmed, the modified algorithms are active now!".to_string(), ())));
+    }
+
+    Ok(Json(RspMsg::new(true, format!("Confirmed, awaiting {} more participants!", awaiting), ())))
 }
 
 /// Companies register for events that happen on the server.
@@ -200,8 +305,31 @@ pub async fn get_results(srv: Data<Arc<RwLock<BenchmarkingServer>>>, company_id:
 /// The server transmits its status (e.g. whether enough participants joined)
 /// and informs all clients instead of relying on polling mechanisms.
 /// We explicitly need to use `text/event-stream` as mimetype.
-pub async fn enroll_event_stream(broadcaster: Data<Broadcaster>) -> Result<impl Responder, ApiError> {
-    let rx = broadcaster.new_client();
+/// Reconnecting clients get the events after their `Last-Event-ID` again.
+pub async fn enroll_event_stream(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, benchmark_id: Path<BenchmarkID>, req: HttpRequest) -> Result<impl Responder, ApiError> {
+    subscribe_events(&srv, &broadcaster, *benchmark_id, Audience::Public, &req).await
+}
+
+/// Events of the benchmark together with the ones for this company only
+/// 
+/// E.g. invalid input, failed computations or ready results are not
+/// shared on the public event stream.
+pub async fn company_event_stream(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Data<Broadcaster>, benchmark_id: Path<BenchmarkID>, company: ReqData<CompanyIdentity>, req: HttpRequest) -> Result<impl Responder, ApiError> {
+    subscribe_events(&srv, &broadcaster, *benchmark_id, Audience::Company(company.0), &req).await
+}
+
+/// Register a client of `audience` for the events of an open benchmark
+pub(crate) async fn subscribe_events(srv: &RwLock<BenchmarkingServer>, broadcaster: &Broadcaster, benchmark_id: BenchmarkID, audience: Audience, req: &HttpRequest) -> Result<HttpResponse, ApiError> {
+    
+    // Only existing and open benchmarks can be subscribed to
+    if srv.read().await.benchmark(benchmark_id)?.phase() == BenchmarkPhase::Closed {
+        return Err(ApiError::from((ErrorCode::WrongPhase { phase: BenchmarkPhase::Closed }, "The benchmark is closed!")));
+    }
+    let last_event_id = match req.headers().get(LAST_EVENT_ID_FIELD) {
+        Some(value) => Some(value.to_str().ok().and_then(|id| id.trim().parse::<u64>().ok()).ok_or_else(|| ApiError::from((ErrorCode::BadRequest, "The Last-Event-ID is no event ID!")))?),
+        None => None,
+    };
+    let rx = broadcaster.new_client(benchmark_id, audience, last_event_id)?;
 
     Ok(HttpResponse::Ok()
     .append_header((header::CONTENT_TYPE, "text/event-stream"))
@@ -211,30 +339,45 @@ pub async fn enroll_event_stream(broadcaster: Data<Broadcaster>) -> Result<impl
 
 /// Submission of new company HMAC for succeeding messages
 /// 
-/// The procedure is similar to the registration function.
-pub async fn register(srv: Data<Arc<RwLock<BenchmarkingServer>>>, company_id: Path<String>, req: HttpRequest) -> Result<impl Responder, ApiError>{
+/// The certificate of the connection and the HMAC key are registered
+/// together and can only be set once.
+pub async fn register(srv: Data<Arc<RwLock<BenchmarkingServer>>>, key_exchange: Data<KeyExchange>, benchmark_id: Path<BenchmarkID>, setup_msg: Json<CompanySetupMsg>, req: HttpRequest) -> Result<impl Responder, ApiError>{
     
+    let benchmark_id = benchmark_id.into_inner();
     let mut mut_srv = srv.write().await;
 
-    // Extract uuid from request
-    let company_id_uuid = match u128::from_str(&company_id) {
-        Ok(uuid) => uuid,
-        Err(_) => { return Err(ApiError::from("Could not parse the given Company UUID!")); }
-    };
+    // The UUID is signed by the analyst's CA as part of the certificate
+    let certificate = req.conn_data::<Certificate>().cloned().ok_or_else(|| ApiError::from((ErrorCode::NotAuthenticated, "No client certificate present!")))?;
+    let company_id_uuid = certificate_company_id(&certificate).ok_or_else(|| ApiError::from((ErrorCode::PermissionDenied, "Your certificate does not hold a company UUID!")))?;
+
+    // Get information on company and respond with current info
+    let revocation_list = mut_srv.crypto_config().revocation_list().clone();
+    let benchmark = mut_srv.benchmark_mut(benchmark_id)?;
+    benchmark.require_phase(&[BenchmarkPhase::Enrollment, BenchmarkPhase::Collection])?;
 
     // Get information on company and respond with current info
-    let company = match mut_srv.set_companies().get_mut(&company_id_uuid) {
-        None => { return Err(ApiError::from("You are not enrolled or your UUID is incorrect!")); },
+    let company = match benchmark.set_companies().get_mut(&company_id_uuid) {
+        None => { return Err(ApiError::from((ErrorCode::CompanyNotEnrolled, "The UUID of your certificate is not enrolled!"))); },
         Some(result) => result
     };
 
     // Check if Certificate was already configured
     if company.certificate().is_some() {
-        return Err(ApiError::from("You have already registered your certificate!"));
+        return Err(ApiError::from((ErrorCode::Conflict, "You have already registered your certificate!")));
     }
 
-    // Insert Certificate into structure 
-    company.set_certificate(req.conn_data::<Certificate>().unwrap().clone());
+    if revocation_list.is_revoked(&certificate) {
+        return Err(ApiError::from((ErrorCode::CertificateRevoked, "Your certificate was revoked!")));
+    }
+    let hmac_key = key_exchange.open(&setup_msg.exchange_key, &setup_msg.public_key, &setup_msg.encrypted_company_hmac)?;
+    let result_public_key = parse_result_key(&setup_msg.result_public_key)?;
+
+    // Insert Certificate and HMAC key into structure 
+    let actor = Actor::new(Role::Company, &certificate);
+    company.set_certificate(certificate);
+    *company.set_hmac_key() = Some(hmac_key);
+    company.set_result_public_key(result_public_key);
+    mut_srv.audit(&actor, AuditAction::RegisterCompany, Some(benchmark_id), Some(company_id_uuid), None);
 
     // Respond and show changes
     Ok(Json(RspMsg::new(true, format!("{} successfully registered HMAC!", company_id_uuid), ())))
@@ -1,17 +1,20 @@
 //! Basic information for HTTP server i.e. static pages and favicons (actually not very important)
 
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 use actix_http::Method;
-use actix_web::{Responder, web::Json, HttpRequest};
+use actix_web::{Responder, web::{Json, Data}, HttpRequest, HttpResponse};
+use async_lock::RwLock;
 use actix_files::NamedFile;
 
 use server_util::error::ApiError;
-use types::message::response::RspMsg;
+use types::message::{error::ErrorCode, response::RspMsg};
 use types::consts::{SERVER_STATIC_PATH, SERVER_FAVICON, SERVER_EXT_FAVICON};
 use rustls::Certificate;
 use server_util::client_cert_extractor::ConnectionInfo;
 
+use crate::{openapi, server::BenchmarkingServer};
+
 
 /// Index handler
 pub async fn index(req: HttpRequest) -> Result<impl Responder, ApiError> {
@@ -21,7 +24,7 @@ pub async fn index(req: HttpRequest) -> Result<impl Responder, ApiError> {
             Ok(Json(RspMsg::new(true, "TEEBench Benchmarking Application".to_string(), ())))
         },
         _ => {
-            Err(ApiError::from(&("You might be using an incorrect method!".to_string())))
+            Err(ApiError::from((ErrorCode::BadRequest, "You might be using an incorrect method!")))
         }
     }
 }
@@ -33,15 +36,36 @@ pub async fn favicon(req: HttpRequest) -> Result<impl Responder, ApiError> {
             let favicon_path = Path::new(SERVER_STATIC_PATH).join(SERVER_FAVICON).with_extension(SERVER_EXT_FAVICON);
             match NamedFile::open(favicon_path) {
                 Ok(favicon) => Ok(favicon),
-                _ => Err(ApiError::from(&("No favicon provided!".to_string())))
+                _ => Err(ApiError::from((ErrorCode::NotFound, "No favicon provided!")))
             }
         },
         _ => {
-            Err(ApiError::from(&("You might be using an incorrect method!".to_string())))
+            Err(ApiError::from((ErrorCode::BadRequest, "You might be using an incorrect method!")))
         }
     }
 }
 
+/// OpenAPI description of the routes and messages
+pub async fn openapi() -> impl Responder {
+    Json(openapi::document())
+}
+
+/// Liveness: the server answers requests
+pub async fn live() -> impl Responder {
+    Json(RspMsg::new(true, "Alive".to_string(), ()))
+}
+
+/// Readiness: the server is configured and serves the full API
+///
+/// The setup server (before the configuration) is alive but not ready.
+pub async fn ready(srv: Data<Arc<RwLock<BenchmarkingServer>>>) -> HttpResponse {
+    if srv.read().await.crypto_config().root_ca_certificate().is_some() {
+        HttpResponse::Ok().json(RspMsg::new(true, "Ready".to_string(), ()))
+    } else {
+        HttpResponse::ServiceUnavailable().json(RspMsg::new(false, "Not configured yet -- awaiting the setup".to_string(), ()))
+    }
+}
+
 /// Default handler
 pub async fn default_handler() -> actix_web::HttpResponse{
     actix_web::HttpResponse::NotFound().json(
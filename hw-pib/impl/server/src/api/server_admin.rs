@@ -1,21 +1,26 @@
 //! **ROUTE HANDLERS**
 //! 
-//! - Setup benchmarking config with Certificates, YAML and Analyst HMAC
+//! - Setup benchmarking config with Certificates and YAML.
+//!   The config creates the first benchmark (`DEFAULT_BENCHMARK_ID`).
 //! - The server `check_config` is for attestation purposes: Here the server
-//!   shows his public key and attestation data.
+//!   returns attestation evidence bound to its TLS key and the algorithms.
+//! - The key exchange offers keys of the server to which the analyst and
+//!   the companies encrypt their HMAC keys (see [`crate::integrity`]).
+//! - The metrics of the server are exported for the analyst team (see
+//!   [`crate::metrics`]).
 //! - Shutdown kills the server and therefore cleans up all the memory.
 
-use std::{sync::{mpsc, Arc}, fs};
+use std::{sync::{mpsc, Arc}, fs, collections::HashMap};
 
 use actix_multipart::Multipart;
-use actix_web::{Result, Responder, web::{Json, Data}, web};
+use actix_web::{Result, Responder, web::{Json, Data, Query, ReqData}, web, HttpRequest, HttpResponse};
 use rustls::Certificate;
-use server_util::{error::ApiError, files::save_multipart_files, crypto_decode::parse_tls_certificate_from_path};
+use server_util::{broadcast_event::Broadcaster, error::ApiError, files::{save_multipart_files, UploadLimits}, crypto_decode::{parse_tls_certificate_from_path, parse_tls_certificates_from_path}};
 use async_lock::RwLock;
 
-use types::{message::response::{RspMsg, ServerStatus}, entity::{BenchmarkingConfig, Analyst}, consts::{FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME, FORM_DATA_FIELD_01_CONFIGURATION_NAME, FORM_DATA_FIELD_01_CONFIGURATION_MIME, FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_NAME, FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME, FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_MIME}};
+use types::{message::{error::ErrorCode, request::{AnalystSetupMsg, AttestMsg}, response::{KeyExchangeOffer, RspMsg, ServerStatus, BenchmarkID}}, entity::{BenchmarkingConfig, Analyst, Role}, consts::{FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME, FORM_DATA_FIELD_01_CONFIGURATION_NAME, FORM_DATA_FIELD_01_CONFIGURATION_MIME, FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_NAME, FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME, FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_MIME, FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_NAME, FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_MIME, FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_NAME, FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_MIME}};
 
-use crate::server::BenchmarkingServer;
+use crate::{audit::{Actor, AuditAction}, integrity::KeyExchange, metrics::Metrics, server::BenchmarkingServer};
 
 
  /// We receive in intial configuration:
@@ -23,7 +28,8 @@ use crate::server::BenchmarkingServer;
  /// - The server Root CA certificate for doing TLS authentication
  /// - The server configuration
  /// - The analysts certificate is given for authentication
-pub async fn setup_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, payload: Multipart, stopper: web::Data<mpsc::Sender<()>>) -> Result<impl Responder, ApiError> {
+ /// - Optionally, the certificates of co-analysts and auditors (see [`Role`])
+pub async fn setup_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, payload: Multipart, limits: Data<UploadLimits>, stopper: web::Data<mpsc::Sender<()>>) -> Result<impl Responder, ApiError> {
     
     // Get instance for config
     let mut mut_srv = srv.write().await;
@@ -31,7 +37,7 @@ pub async fn setup_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, payload: M
     // Check if server was previously configured and reject
     if mut_srv.crypto_config().root_ca_certificate().is_some() {
         log::info!("Server is already configured - Skipping reconfiguration..");
-        return Err(ApiError::from(&("Configuration is locked after initial setting. You need to reset the server for reconfiguration!".to_string())));
+        return Err(ApiError::from((ErrorCode::Conflict, "Configuration is locked after initial setting. You need to reset the server for reconfiguration!")));
     } 
 
     // Parse the received message (multipart)
@@ -39,31 +45,51 @@ pub async fn setup_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, payload: M
     let required_multiparts = vec![
         (FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_NAME,FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_MIME),
         (FORM_DATA_FIELD_01_CONFIGURATION_NAME, FORM_DATA_FIELD_01_CONFIGURATION_MIME),
-        (FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME,FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME)];
+        (FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME,FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME),
+        (FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_NAME,FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_MIME),
+        (FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_NAME,FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_MIME)];
     let req_multipart_names: Vec<&str> = required_multiparts.iter().map(|x| x.0).collect();
     let required_files = 3;
-    let files = save_multipart_files(payload,required_multiparts, required_files).await?;
+    let files = save_multipart_files(payload,required_multiparts, required_files, **limits).await?;
     
     // Check the received root ca certificate (for rustls' client certification)
-    let ca_certificate: Certificate = match parse_tls_certificate_from_path(files.get(req_multipart_names[0]).ok_or( ApiError::from("Did not find the root certificate to be correctly uploaded (incorrect name)!"))?) {
+    let ca_certificate: Certificate = match parse_tls_certificate_from_path(files.get(req_multipart_names[0]).ok_or( ApiError::from((ErrorCode::BadRequest, "Did not find the root certificate to be correctly uploaded (incorrect name)!")))?) {
         Ok(ca_certificate) => { ca_certificate},
-        _ => { return Err(ApiError::from(&("CA-Certificate parsing failed!".to_string()))); }
+        _ => { return Err(ApiError::from((ErrorCode::InvalidFormat, "CA-Certificate parsing failed!"))); }
     };
 
     // Parse benchmarking server configuration information
-    let data = fs::read_to_string(files.get(req_multipart_names[1]).ok_or(ApiError::from("Did not find yaml to be correctly uploaded (incorrect name)!"))?);
-    let server_config: BenchmarkingConfig = serde_yaml::from_str::<BenchmarkingConfig>(&data.unwrap()).map_err(|e| ApiError::from(&e.to_string()))?;
+    let data = fs::read_to_string(files.get(req_multipart_names[1]).ok_or(ApiError::from((ErrorCode::BadRequest, "Did not find yaml to be correctly uploaded (incorrect name)!")))?);
+    let server_config: BenchmarkingConfig = serde_yaml::from_str::<BenchmarkingConfig>(&data.unwrap()).map_err(|e| ApiError::from((ErrorCode::InvalidFormat, e.to_string())))?;
     
     // Check the received root ca certificate (for rustls' client certification)
-    let analyst_certificate: Certificate = match parse_tls_certificate_from_path(files.get(req_multipart_names[2]).ok_or( ApiError::from("Did not find the admin certificate to be correctly uploaded (incorrect name)!"))?) {
+    let analyst_certificate: Certificate = match parse_tls_certificate_from_path(files.get(req_multipart_names[2]).ok_or( ApiError::from((ErrorCode::BadRequest, "Did not find the admin certificate to be correctly uploaded (incorrect name)!")))?) {
         Ok(ca_certificate) => { ca_certificate},
-        _ => { return Err(ApiError::from(&("CA-Certificate parsing failed!".to_string()))); }
+        _ => { return Err(ApiError::from((ErrorCode::InvalidFormat, "CA-Certificate parsing failed!"))); }
     };
 
+    // The analyst team: the uploader owns the server, further members are optional
+    let owner = Actor::new(Role::Owner, &analyst_certificate);
+    let mut analysts = vec![Analyst::new(analyst_certificate, Role::Owner)];
+    for (name, role) in [(req_multipart_names[3], Role::CoAnalyst), (req_multipart_names[4], Role::Auditor)] {
+        let certificates = match files.get(name) {
+            Some(path) => parse_tls_certificates_from_path(path).map_err(|_| ApiError::from((ErrorCode::InvalidFormat, format!("Parsing of the {} certificates failed!", role))))?,
+            None => continue,
+        };
+        for certificate in certificates {
+            if analysts.iter().any(|analyst| analyst.certificate().eq(&certificate)) {
+                return Err(ApiError::from((ErrorCode::Conflict, "A certificate can only hold one role!")));
+            }
+            analysts.push(Analyst::new(certificate, role));
+        }
+    }
+
     // Create config and set it in the Application
-    mut_srv.set_analyst(Analyst::new(analyst_certificate));
-    mut_srv.set_benchmarking_config_all(server_config.clone());
+    mut_srv.set_analysts(analysts);
+    let benchmark_id = mut_srv.add_benchmark(server_config.clone());
     mut_srv.set_crypto_config().set_root_ca_certificate(ca_certificate);
+    mut_srv.audit(&owner, AuditAction::Setup, Some(benchmark_id), None, None);
+    mut_srv.persist();
 
     // Free lock before killing server
     drop(mut_srv);
@@ -84,21 +110,85 @@ pub async fn setup_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, payload: M
 }
 
 /// Return configuration in case there is already one
-pub async fn check_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>,) -> Result<impl Responder, ApiError> {
+/// 
+/// The attestation evidence includes the nonce of the query (if any).
+pub async fn check_config(srv: Data<Arc<RwLock<BenchmarkingServer>>>, attest_msg: Query<AttestMsg>) -> Result<impl Responder, ApiError> {
     
     let srv_rdr = srv.read().await;
+    let benchmarks: HashMap<BenchmarkID, BenchmarkingConfig> = srv_rdr.benchmarks().iter().map(|(id, benchmark)| (*id, benchmark.benchmarking_config().clone())).collect();
+    let evidence = srv_rdr.attestation_evidence(&attest_msg.nonce)?;
     Ok(Json(
         RspMsg::new(
             true, 
             "Current Server Configuration".into(), 
-            ServerStatus::new(srv_rdr.crypto_config().root_ca_certificate().is_some(), srv_rdr.crypto_config().enclave_certificate().encode_pem(), srv_rdr.benchmarking_config().clone()))
+            ServerStatus::new(srv_rdr.crypto_config().root_ca_certificate().is_some(), evidence, benchmarks))
         )
     )
 }
 
+/// Offer an ephemeral key of the server for the exchange of an HMAC key
+pub async fn offer_key_exchange(key_exchange: Data<KeyExchange>) -> Result<impl Responder, ApiError> {
+    
+    let exchange_key = key_exchange.offer()?;
+    Ok(Json(RspMsg::new(true, "Encrypt the HMAC key to this key!".into(), KeyExchangeOffer { exchange_key })))
+}
+
+/// Submission of the analyst HMAC for succeeding messages
+/// 
+/// Every member of the analyst team sets its key once, afterwards
+/// all its requests have to carry an HMAC.
+pub async fn exchange_analyst_key(srv: Data<Arc<RwLock<BenchmarkingServer>>>, key_exchange: Data<KeyExchange>, setup_msg: Json<AnalystSetupMsg>, actor: ReqData<Actor>, req: HttpRequest) -> Result<impl Responder, ApiError> {
+    
+    let certificate = req.conn_data::<Certificate>().cloned().ok_or_else(|| ApiError::from((ErrorCode::NotAuthenticated, "No client certificate present!")))?;
+    let mut mut_srv = srv.write().await;
+    let analyst = mut_srv.analyst_mut(&certificate).ok_or_else(|| ApiError::from((ErrorCode::PermissionDenied, "Your certificate is not part of the analyst team!")))?;
+    if analyst.hmac_key().is_some() {
+        return Err(ApiError::from((ErrorCode::Conflict, "The HMAC key was already exchanged!")));
+    }
+
+    let hmac_key = key_exchange.open(&setup_msg.exchange_key, &setup_msg.public_key, &setup_msg.encrypted_analyst_hmac)?;
+    *analyst.set_hmac_key() = Some(hmac_key);
+    mut_srv.audit(&actor, AuditAction::ExchangeAnalystKey, None, None, None);
+    mut_srv.persist();
+
+    Ok(Json(RspMsg::new(true, "Successfully registered HMAC!".into(), ())))
+}
+
+/// Export of the audit log for the analyst team
+/// 
+/// The response states whether the hash chain is intact (a broken
+/// chain is no success, the entries are still returned), the
+/// recipients can verify it on their own (see [`crate::audit`]).
+pub async fn get_audit_log(srv: Data<Arc<RwLock<BenchmarkingServer>>>) -> Result<impl Responder, ApiError> {
+    
+    let srv_rdr = srv.read().await;
+    let audit_log = srv_rdr.audit_log();
+    let intact = audit_log.verify();
+    if !intact {
+        log::error!("The audit log is not intact - it was modified!");
+    }
+    let message = format!("Audit log with {} entries (hash chain {})", audit_log.entries().len(), if intact { "intact" } else { "broken" });
+    Ok(Json(RspMsg::new(intact, message, audit_log.entries().to_vec())))
+}
+
+/// Metrics of the server in the Prometheus text format
+pub async fn get_metrics(srv: Data<Arc<RwLock<BenchmarkingServer>>>, metrics: Data<Metrics>, broadcaster: Data<Broadcaster>) -> impl Responder {
+
+    let srv_rdr = srv.read().await;
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render(Some(&srv_rdr), Some(&broadcaster)))
+}
+
 /// Shutdown of server and clearing of state
-pub async fn shutdown(stopper: web::Data<mpsc::Sender<()>>) -> Result<impl Responder, ApiError> {
+pub async fn shutdown(srv: Data<Arc<RwLock<BenchmarkingServer>>>, actor: ReqData<Actor>, stopper: web::Data<mpsc::Sender<()>>) -> Result<impl Responder, ApiError> {
     
+    // A regular shutdown ends the benchmark, thus it is not recovered on restart
+    let mut mut_srv = srv.write().await;
+    mut_srv.audit(&actor, AuditAction::Shutdown, None, None, None);
+    mut_srv.clear_persisted();
+    drop(mut_srv);
+
     // graceful shutdown on the HTTP Server
     stopper.send(()).unwrap();
     
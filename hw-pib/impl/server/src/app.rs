@@ -6,42 +6,56 @@
 //! Then the HTTP server shuts down and the HTTPs server with the provided configuration starts up.
 //! This ulimately enables a secure connection among all participants.
 
-use rustls::{ServerConfig, server::AllowAnyAuthenticatedClient};
+use rustls::{Certificate, ServerConfig, server::AllowAnyAuthenticatedClient};
 use server_util::{
     broadcast_event::Broadcaster,
-    client_cert_extractor::get_client_cert
+    client_cert_extractor::get_client_cert,
+    crl::parse_crl_from_path,
 };
 
 
-use actix_web::{App, HttpServer, web::{self}, dev::ServerHandle};
+use actix_web::{App, HttpServer, web::{self, Data}, dev::ServerHandle, rt::time::{interval_at, Instant}};
 
-use std::sync::{mpsc, Arc};
+use std::{path::Path, sync::{mpsc, Arc}, time::{Duration, SystemTime}};
 use async_lock::RwLock;
 use std::thread;
 
 use crate::{
     server::BenchmarkingServer, crypto::Crypto, routes::{http_routes, https_routes}, api::index::default_handler,
+    middleware::{state_persister::PersistState, request_metrics::RecordMetrics, rate_limiter::RateLimiter}, integrity::KeyExchange, revocation::{RevocationCheckingVerifier, RevocationList},
+    metrics::Metrics, instrumentation::SpanExport,
 };
 
+/// Interval in seconds in which expired results are purged
+const RESULT_PURGE_INTERVAL: u64 = 60;
+/// Interval in seconds in which the CRL is reloaded
+const CRL_RELOAD_INTERVAL: u64 = 300;
+
 /// HTTP configuration server startup
-pub async fn http_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>) -> std::io::Result<()> {
+/// 
+/// The metrics are shared with the HTTPs server, which exports them.
+pub async fn http_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>, metrics: Data<Metrics>) -> std::io::Result<()> {
 
         // create a channel for server shutdown
         let (tx, rx) = mpsc::channel::<()>();
 
-        let (socket, shutdown_timeout, workers);
+        let (socket, shutdown_timeout, workers, upload_limits);
         {
             // HTTP Server configs
             let srv_rdr = benchmarking_server.read().await;
             socket = format!("{}:{}", srv_rdr.server_config().host(), srv_rdr.server_config().port());
             shutdown_timeout = srv_rdr.server_config().shutdown_timeout();
             workers = srv_rdr.server_config().http_workers();
+            upload_limits = web::Data::new(srv_rdr.server_config().upload_limits());
         }
 
         let http_server= HttpServer::new(move|| {
             App::new()
                 .app_data(web::Data::new(tx.clone()))       // For halting the server through IPC
                 .app_data(web::Data::new(benchmarking_server.clone()))     // For modifying the config at runtime
+                .app_data(metrics.clone())                                        // For counting requests
+                .app_data(upload_limits.clone())                                  // For capping the setup upload
+                .wrap(RecordMetrics)
                 //.wrap(ReqLogging)                                                // Logging information on incomming msgs 
                 //.wrap(RspLogging)                                                // Logging information on outgoing msgs 
                 .configure(http_routes)                               // Configure routes for server
@@ -72,31 +86,56 @@ pub async fn http_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>) -
 }
 
 /// HTTPs application server startup
-pub async fn https_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>) -> std::io::Result<()> {
+/// 
+/// Measured spans are sent over its event streams (if configured).
+pub async fn https_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>, metrics: Data<Metrics>, span_export: Arc<SpanExport>) -> std::io::Result<()> {
 
     // create a channel for server shutdown
     let (tx, rx) = mpsc::channel::<()>();
 
-    // Create a broadcaster for server sent events
-    let broadcaster = Broadcaster::new();
-
     // HTTP Server configs
-    let (tls_socket, shutdown_timeout, crypto_config, workers);
+    let (tls_socket, shutdown_timeout, crypto_config, crl_path, workers, upload_limits, rate_limiter, max_sse_clients);
     {
         // HTTP Server configs
         let srv_rdr = benchmarking_server.read().await;
         tls_socket = format!("{}:{}", srv_rdr.server_config().host(), srv_rdr.server_config().tls_port());
         shutdown_timeout = srv_rdr.server_config().shutdown_timeout();
         crypto_config = srv_rdr.crypto_config().clone();
+        crl_path = srv_rdr.server_config().crl_path().to_string();
         workers = srv_rdr.server_config().https_workers();
+        upload_limits = web::Data::new(srv_rdr.server_config().upload_limits());
+        rate_limiter = web::Data::new(RateLimiter::new(srv_rdr.server_config().rate_limit_per_minute(), srv_rdr.server_config().rate_limit_burst()));
+        max_sse_clients = srv_rdr.server_config().max_sse_clients();
+    }
+
+    // Create a broadcaster for server sent events
+    let broadcaster = Broadcaster::new(max_sse_clients);
+    span_export.attach(broadcaster.clone());
+
+    // Offered keys for the exchange of HMAC keys
+    let key_exchange = web::Data::new(KeyExchange::default());
+
+    // Results that are not downloaded are only kept for the retention time
+    spawn_result_purge(benchmarking_server.clone(), broadcaster.clone());
+
+    // A CRL expires, newer ones are picked up from the same path
+    if !crl_path.is_empty() {
+        let root_ca = crypto_config.root_ca_certificate().clone().expect("[FATAL] Root CA Certificate is not provided!");
+        spawn_crl_reload(crypto_config.revocation_list().clone(), root_ca, crl_path.clone());
     }
 
     // Configure HTTPs server and bind it with rustls on the preconfigured port
     let https_server= HttpServer::new(move|| {
         App::new()
             .app_data(broadcaster.clone())
+            .app_data(key_exchange.clone())
             .app_data(web::Data::new(tx.clone()))
             .app_data(web::Data::new(benchmarking_server.clone()))
+            .app_data(metrics.clone())
+            .app_data(upload_limits.clone())
+            .app_data(rate_limiter.clone())
+            .wrap(PersistState)                                               // Snapshot state after modifications
+            .wrap(RecordMetrics)                                              // Count and time requests (incl. snapshots)
             //.wrap(ReqLogging)
             //.wrap(RspLogging)
             .configure(https_routes)
@@ -106,7 +145,7 @@ pub async fn https_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>)
     .shutdown_timeout(shutdown_timeout)
     .bind_rustls(
         tls_socket, 
-        tls_setup(crypto_config)
+        tls_setup(crypto_config, &crl_path)
     )?
     .workers(workers);
 
@@ -127,11 +166,47 @@ pub async fn https_server(benchmarking_server: Arc<RwLock<BenchmarkingServer>>)
     server.await
 }
 
+/// Periodically delete the results whose retention time is over
+fn spawn_result_purge(benchmarking_server: Arc<RwLock<BenchmarkingServer>>, broadcaster: Data<Broadcaster>) {
+    actix_web::rt::spawn(async move {
+        let mut interval = interval_at(Instant::now(), Duration::from_secs(RESULT_PURGE_INTERVAL));
+
+        loop {
+            interval.tick().await;
+            let mut mut_srv = benchmarking_server.write().await;
+            if mut_srv.purge_expired_results(&broadcaster) {
+                mut_srv.persist();
+            }
+        }
+    });
+}
+
+/// Periodically reload the CRL of the root CA
+///
+/// An invalid or expired CRL is not loaded, thus certificates count as
+/// revoked once the loaded CRL expired.
+fn spawn_crl_reload(revocation_list: RevocationList, root_ca: Certificate, crl_path: String) {
+    actix_web::rt::spawn(async move {
+        let period = Duration::from_secs(CRL_RELOAD_INTERVAL);
+        let mut interval = interval_at(Instant::now() + period, period);
+
+        loop {
+            interval.tick().await;
+            match parse_crl_from_path(Path::new(&crl_path), &root_ca, SystemTime::now()) {
+                Ok(crl) => revocation_list.load_crl(crl),
+                Err(err) => log::error!("Could not reload the CRL of the root CA: {}", err),
+            }
+        }
+    });
+}
+
 /// TLS configuration
 /// 
 /// Use RSA keys and Certificate for authentication
 /// Use a provided CA_root Certificate for client Authentication
-pub fn tls_setup(crypto_cfg: Crypto) -> rustls::ServerConfig {
+/// Reject client certificates that are revoked by the CRL of the CA_root
+/// (if configured) or by the analyst
+pub fn tls_setup(crypto_cfg: Crypto, crl_path: &str) -> rustls::ServerConfig {
     
     // This cert store has to be extended with a add method for DER-encoded certs
     let mut client_auth_roots = rustls::RootCertStore::empty();
@@ -143,8 +218,15 @@ pub fn tls_setup(crypto_cfg: Crypto) -> rustls::ServerConfig {
 
     log::info!("Root CA Certificate configured!");
 
+    // Revoked serials of the CRL that is signed by the root CA
+    if !crl_path.is_empty() {
+        let crl = parse_crl_from_path(Path::new(crl_path), &root_ca, SystemTime::now()).expect("[FATAL] Could not load the CRL of the root CA!");
+        log::info!("CRL configured with {} revoked certificates!", crl.revoked_serials.len());
+        crypto_cfg.revocation_list().load_crl(crl);
+    }
+
     // Configure server with client certificate verifier
-    let verifier = AllowAnyAuthenticatedClient::new(client_auth_roots);
+    let verifier = Arc::new(RevocationCheckingVerifier::new(AllowAnyAuthenticatedClient::new(client_auth_roots), crypto_cfg.revocation_list().clone()));
 
     let config = ServerConfig::builder()
         .with_safe_defaults()
@@ -0,0 +1,158 @@
+//! **Remote Attestation**
+//!
+//! Clients have to be assured that they talk to the genuine server code
+//! before they hand over any data. The server therefore produces
+//! [`AttestationEvidence`]: a quote-like report over the measurement of
+//! the running code, the hash of its TLS certificate and the hashes of
+//! the benchmark algorithms. Binding the TLS certificate prevents a relay
+//! of the evidence by another server, binding the algorithms shows which
+//! computation is performed on the inputs.
+//!
+//! The evidence is produced by an [`Attester`]:
+//!
+//! - [`SimulatedAttester`]: Stand-in for builds without SGX. The measurement
+//!   is the hash of the server executable and the report is signed with the
+//!   enclave key of the crypto templates. This shows the binding of TLS key
+//!   and algorithms but does **not** prove that the code runs in an enclave!
+//!
+//! Hardware attesters (e.g. quotes of the SGX quoting enclave) implement the
+//! same trait.
+
+use std::{collections::BTreeMap, fmt, fs, path::{Path, PathBuf}};
+
+use benchmark::{atomic::Atomic, Algorithm};
+use ring::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{KeyPair, RsaKeyPair, RSA_PKCS1_SHA256},
+};
+use rustls::Certificate;
+use server_util::{crypto_decode::parse_tls_private_key_from_path, error::ApiError};
+use types::{
+    consts::{EXT_PRIVATE_KEY, SERVER_CRYPTO_PATH, SERVER_ENCLAVE_CRYPTO},
+    message::{error::ErrorCode, response::{AttestationEvidence, BenchmarkID}},
+};
+
+/// Maximum length of the hex encoded client nonce
+const MAX_NONCE_LEN: usize = 128;
+
+/// Producer of attestation evidence
+pub trait Attester: fmt::Debug + Send + Sync {
+    /// Name of the attester that is reported in the evidence
+    fn kind(&self) -> &'static str;
+
+    /// Measurement of the running server code
+    fn measurement(&self) -> &[u8];
+
+    /// DER encoded public key that verifies the signatures
+    fn public_key(&self) -> &[u8];
+
+    /// Sign the report data of the evidence
+    fn sign(&self, report_data: &[u8]) -> Result<Vec<u8>, ApiError>;
+}
+
+/// Software attester for builds without SGX
+pub struct SimulatedAttester {
+    key_pair: RsaKeyPair,
+    measurement: Vec<u8>,
+}
+
+impl fmt::Debug for SimulatedAttester {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimulatedAttester").field("measurement", &hex::encode(&self.measurement)).finish()
+    }
+}
+
+impl SimulatedAttester {
+    /// Load the enclave key and measure the running executable
+    pub fn load(key_path: &Path) -> std::io::Result<Self> {
+        let invalid = |err: String| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+
+        let key = parse_tls_private_key_from_path(&key_path.to_path_buf()).map_err(|err| invalid(err.to_string()))?;
+        let key_pair = RsaKeyPair::from_der(&key.0).map_err(|err| invalid(err.to_string()))?;
+
+        let executable = fs::read(std::env::current_exe()?)?;
+        let measurement = digest(&SHA256, &executable).as_ref().to_vec();
+
+        Ok(SimulatedAttester { key_pair, measurement })
+    }
+}
+
+impl Attester for SimulatedAttester {
+    fn kind(&self) -> &'static str {
+        "simulated"
+    }
+
+    fn measurement(&self) -> &[u8] {
+        &self.measurement
+    }
+
+    fn public_key(&self) -> &[u8] {
+        self.key_pair.public_key().as_ref()
+    }
+
+    fn sign(&self, report_data: &[u8]) -> Result<Vec<u8>, ApiError> {
+        let mut signature = vec![0_u8; self.key_pair.public_modulus_len()];
+        self.key_pair
+            .sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), report_data, &mut signature)
+            .map_err(|_| ApiError::from((ErrorCode::Internal, "Could not sign attestation evidence!")))?;
+        Ok(signature)
+    }
+}
+
+/// Values that companies pin in their attestation policy (hex encoded)
+///
+/// The orchestra runs the server in-process with the simulated attester,
+/// thus it knows the values before the server starts.
+#[derive(Debug, Clone)]
+pub struct AttestationReference {
+    pub attestation_key: String,
+    pub measurement: String,
+    pub algorithm_hash: String,
+}
+
+impl AttestationReference {
+    /// Values of the simulated attester of this executable for the algorithms at `algorithm_path`
+    pub fn simulated(algorithm_path: &PathBuf) -> std::io::Result<Self> {
+        let key_path = Path::new(SERVER_CRYPTO_PATH).join(SERVER_ENCLAVE_CRYPTO).with_extension(EXT_PRIVATE_KEY);
+        let attester = SimulatedAttester::load(&key_path)?;
+        let algorithm = Algorithm::load(algorithm_path).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
+
+        Ok(AttestationReference {
+            attestation_key: hex::encode(attester.public_key()),
+            measurement: hex::encode(attester.measurement()),
+            algorithm_hash: algorithm_hash(&algorithm),
+        })
+    }
+}
+
+/// Hex encoded SHA-256 of the algorithm's operations
+///
+/// The operations are sorted by name, thus the hash does not depend
+/// on the order of the operations in the uploaded file.
+pub fn algorithm_hash(algorithm: &Algorithm) -> String {
+    let mut operations: Vec<&Atomic> = algorithm.operations().iter().collect();
+    operations.sort_by(|a, b| a.name().cmp(b.name()));
+    let operations = serde_json::to_vec(&operations).expect("Operations are serializable");
+    hex::encode(digest(&SHA256, &operations))
+}
+
+/// Produce signed evidence for the TLS certificate, the algorithms and the client nonce
+pub fn attest(attester: &dyn Attester, tls_certificate: &Certificate, algorithm_hashes: BTreeMap<BenchmarkID, String>, nonce: &str) -> Result<AttestationEvidence, ApiError> {
+    if nonce.len() > MAX_NONCE_LEN || hex::decode(nonce).is_err() {
+        return Err(ApiError::from((ErrorCode::InvalidFormat, "The nonce has to be hex encoded with at most 64 bytes!")));
+    }
+
+    let mut evidence = AttestationEvidence {
+        attester: attester.kind().to_string(),
+        measurement: hex::encode(attester.measurement()),
+        tls_certificate_hash: hex::encode(digest(&SHA256, &tls_certificate.0)),
+        algorithm_hashes,
+        nonce: nonce.to_string(),
+        attestation_key: hex::encode(attester.public_key()),
+        signature: String::new(),
+    };
+    evidence.signature = hex::encode(attester.sign(&evidence.report_data())?);
+
+    Ok(evidence)
+}
@@ -0,0 +1,205 @@
+//! **Audit Log**
+//!
+//! Every state-changing action on the server (and the retrieval of results)
+//! is appended to the audit log together with the role and the certificate
+//! fingerprint (SHA-256) of its actor. Only the kind of the action and the
+//! addressed benchmark or company are recorded, never uploaded values.
+//!
+//! The entries form a hash chain: the hash of an entry is the SHA-256 over the
+//! JSON array `[sequence, timestamp, role, fingerprint, action, benchmark,
+//! company, detail, previous_hash]` (see [`AuditEntry::compute_hash`]). The
+//! first entry follows [`GENESIS_HASH`]. Modifying or removing an entry thus
+//! breaks the chain of all succeeding entries, which is checked on export and
+//! when the log is restored from a snapshot (a broken log is not continued).
+
+use chrono::{SecondsFormat, Utc};
+use ring::digest::{digest, SHA256};
+use rustls::Certificate;
+use serde::{Deserialize, Serialize};
+use types::{
+    entity::Role,
+    message::response::{BenchmarkID, CompanyID},
+};
+use uuid::Uuid;
+
+/// Predecessor hash of the first entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Participant that performed an action
+///
+/// The request verification hands it to the handlers (`ReqData<Actor>`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    role: Role,
+    /// SHA-256 of the DER encoded certificate (hex)
+    fingerprint: String,
+}
+
+impl Actor {
+    pub fn new(role: Role, certificate: &Certificate) -> Self {
+        Actor { role, fingerprint: hex::encode(digest(&SHA256, &certificate.0)) }
+    }
+}
+
+/// Recorded actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Setup,
+    CreateBenchmark,
+    ModifyConfig,
+    ChangePhase,
+    EnrollCompany,
+    RevokeCompany,
+    RegisterCompany,
+    UploadInput,
+    ModifyInput,
+    UploadAlgorithms,
+    ModifyAlgorithms,
+    ConfirmAlgorithms,
+    StartBenchmark,
+    RetrieveResults,
+    ConfirmResults,
+    ExchangeAnalystKey,
+    Shutdown,
+}
+
+/// One action in the hash chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    sequence: u64,
+    /// RFC 3339 (UTC)
+    timestamp: String,
+    actor: Actor,
+    action: AuditAction,
+    benchmark: Option<BenchmarkID>,
+    /// UUID of the addressed company
+    company: Option<String>,
+    /// Non-sensitive details, e.g. the hash of algorithms or a phase
+    detail: Option<String>,
+    previous_hash: String,
+    hash: String,
+}
+
+impl AuditEntry {
+    /// Hash over the content of the entry and its predecessor's hash
+    pub fn compute_hash(&self) -> String {
+        let content = serde_json::json!([
+            self.sequence,
+            self.timestamp,
+            self.actor.role,
+            self.actor.fingerprint,
+            self.action,
+            self.benchmark,
+            self.company,
+            self.detail,
+            self.previous_hash,
+        ]);
+        hex::encode(digest(&SHA256, content.to_string().as_bytes()))
+    }
+}
+
+/// Append-only log of all actions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Append an action at the end of the chain
+    pub fn append(&mut self, actor: &Actor, action: AuditAction, benchmark: Option<BenchmarkID>, company: Option<CompanyID>, detail: Option<String>) {
+        let previous_hash = self.entries.last().map_or_else(|| GENESIS_HASH.to_string(), |entry| entry.hash.clone());
+        let mut entry = AuditEntry {
+            sequence: self.entries.len() as u64,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            actor: actor.clone(),
+            action,
+            benchmark,
+            company: company.map(|id| Uuid::from_u128(id).to_string()),
+            detail,
+            previous_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        self.entries.push(entry);
+    }
+
+    /// Whether every entry is intact and follows its predecessor
+    pub fn verify(&self) -> bool {
+        let mut previous_hash = GENESIS_HASH;
+        for (sequence, entry) in self.entries.iter().enumerate() {
+            if entry.sequence != sequence as u64 || entry.previous_hash != previous_hash || entry.hash != entry.compute_hash() {
+                return false;
+            }
+            previous_hash = &entry.hash;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn audit_log() -> AuditLog {
+        let owner = Actor::new(Role::Owner, &Certificate(b"owner".to_vec()));
+        let company = Actor::new(Role::Company, &Certificate(b"company".to_vec()));
+
+        let mut audit_log = AuditLog::default();
+        audit_log.append(&owner, AuditAction::Setup, None, None, None);
+        audit_log.append(&owner, AuditAction::EnrollCompany, Some(0), Some(7), None);
+        audit_log.append(&company, AuditAction::UploadInput, Some(0), Some(7), None);
+        audit_log.append(&owner, AuditAction::StartBenchmark, Some(0), None, Some("collection".to_string()));
+        audit_log
+    }
+
+    #[test]
+    pub fn appended_entries_form_a_chain() {
+        let audit_log = audit_log();
+        assert_eq!(audit_log.entries().len(), 4);
+        assert_eq!(audit_log.entries()[0].previous_hash, GENESIS_HASH);
+        assert_eq!(audit_log.entries()[2].company, Some(Uuid::from_u128(7).to_string()));
+        assert!(audit_log.verify());
+        assert!(AuditLog::default().verify());
+    }
+
+    #[test]
+    pub fn tampered_entry_breaks_the_chain() {
+        let mut audit_log = audit_log();
+        audit_log.entries[2].action = AuditAction::ModifyInput;
+        assert!(!audit_log.verify());
+
+        // Even with a recomputed hash the successor does not follow anymore
+        audit_log.entries[2].hash = audit_log.entries[2].compute_hash();
+        assert!(!audit_log.verify());
+    }
+
+    #[test]
+    pub fn deleted_entry_breaks_the_chain() {
+        let mut audit_log = audit_log();
+        audit_log.entries.remove(1);
+        assert!(!audit_log.verify());
+
+        let mut audit_log = self::audit_log();
+        audit_log.entries.remove(0);
+        assert!(!audit_log.verify());
+    }
+
+    #[test]
+    pub fn reordered_entries_break_the_chain() {
+        let mut audit_log = audit_log();
+        audit_log.entries.swap(1, 2);
+        assert!(!audit_log.verify());
+
+        // Swapped sequence numbers do not help either
+        audit_log.entries.swap(1, 2);
+        audit_log.entries[1].sequence = 2;
+        audit_log.entries[2].sequence = 1;
+        audit_log.entries.swap(1, 2);
+        assert!(!audit_log.verify());
+    }
+}
@@ -5,18 +5,34 @@
 //! the full processor's capacity without running into bottlenecks.
 
 use std::{sync::Arc, collections::HashMap};
-use async_lock::{RwLock, RwLockUpgradableReadGuard};
+use async_lock::RwLock;
 use actix_web::web::Data;
 use benchmark::{error::BenchmarkingError};
-use server_util::broadcast_event::Broadcaster;
+use server_util::broadcast_event::{Audience, Broadcaster};
 use rayon::prelude::*;
-use types::output::{Output, OutputVariable};
+use types::{output::{Output, OutputVariable}, message::{event::ServerEvent, response::{BenchmarkID, CompanyID}}, entity::{BenchmarkPhase, Company, InputRetention}};
 use std::sync::mpsc::channel;
 
-use crate::server::BenchmarkingServer;
+use crate::{sealing::seal_output, server::BenchmarkingServer};
+
+/// Number of steps that the computation reports
+const PROGRESS_STEPS: u32 = 4;
+
+/// Copies of the participating companies for the computation
+///
+/// Their inputs are wiped once the computation is over, whether it succeeded or not.
+struct Participants(HashMap<CompanyID, Company>);
+
+impl Drop for Participants {
+    fn drop(&mut self) {
+        self.0.values_mut().for_each(Company::wipe_input_data);
+    }
+}
 
 /// Benchmarking start
 ///
+/// The benchmark with the given ID is computed; all other benchmarks
+/// of the server stay untouched.
 /// We start the threads for all companies & KPIs to perform
 /// computation of results. The results get aggregated in the
 /// end s.t. the results get stored at each company individually.
@@ -24,83 +40,87 @@ use crate::server::BenchmarkingServer;
 /// The benchmarking uses the broadcaster where it posts events
 /// depending on what part of the benchmarking is finished (i.e
 /// shows the percentage of tasks that have performed through).
-pub fn run_benchmark(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Arc<Broadcaster>) -> Result<(), BenchmarkingError> {
+/// The stages are traced as spans `compute` (with `compute_company`
+/// for every company), `cluster`, `aggregate` and `assemble` within
+/// the `benchmark` span (see [`crate::instrumentation`]).
+/// 
+/// The server is only locked to copy the inputs and algorithms and to
+/// store the sealed results, thus the requests of other benchmarks are
+/// served meanwhile. The phase `Computing` keeps the inputs and the
+/// algorithms of this benchmark unchanged.
+pub fn run_benchmark(srv: Data<Arc<RwLock<BenchmarkingServer>>>, benchmark_id: BenchmarkID, broadcaster: Arc<Broadcaster>) -> Result<(), BenchmarkingError> {
+
+    let _benchmark = tracing::info_span!("benchmark", benchmark = benchmark_id).entered();
 
     ////////////////////////////////////////////////////////
     //  STAGE 0 -- Prepare company input data             //  
     ////////////////////////////////////////////////////////
 
-    // Get lock on data and partition it on multiple threads (IN PLACE!)
-    let server = srv.try_upgradable_read().ok_or(BenchmarkingError::from("Could not read data now. Try again later!".to_string())).map_err(|e| e)?;
+    // Copy the algorithms and the participants under a short read lock
+    let (algorithms, participants) = {
+        let server = futures::executor::block_on(srv.read());
+        let benchmark = server.benchmark(benchmark_id).map_err(|e| BenchmarkingError::from(e.to_string()))?;
+        let algorithms = benchmark.algorithms().cloned().ok_or_else(|| BenchmarkingError::from("No algorithms were provided!".to_string()))?;
+        let participants = benchmark.companies().iter()
+            .filter(|(_, company)| company.does_participate())
+            .map(|(company_id, company)| (*company_id, company.clone()))
+            .collect();
+        (algorithms, Participants(participants))
+    };
 
     ////////////////////////////////////////////////////////
     //  STAGE 1 -- Perform KPI computation for companies  //  
     ////////////////////////////////////////////////////////
 
     // Broadcast message to all registered clients that computation starts
-    broadcaster.send("Company benchmark started!");
+    broadcaster.send(benchmark_id, &progress(0, "Company benchmark started!"));
     // MSPC (multiple producer single consumer) channel
     let (sender, receiver) = channel();
 
     ////////////////////////////////////////////////////////
     
-    #[cfg(feature="evaluation")]
-    let now = std::time::SystemTime::now();
+    let compute = tracing::info_span!("compute");
 
-    server.companies().par_iter()
+    // Companies without (valid) input do not take part
+    compute.in_scope(|| participants.0.par_iter()
         .try_for_each_with(sender, |s, (company_id, company)| {
             
-            #[cfg(feature="evaluation")]
-            let company_now = std::time::SystemTime::now();
-            
             // Compute algorithms for each company and return their results
-            let benchmarking_results = server.algorithms().unwrap().run(company);
-
-            #[cfg(feature="evaluation")]
-            {
-                broadcaster.send(format!("EVAL-BENCH-COMP-SINGLE: {:?}", company_now.elapsed().unwrap().as_nanos()).as_str());
-            }
-            
+            // (the threads of the pool do not know the span of the stage)
+            let benchmarking_results = tracing::info_span!(parent: &compute, "compute_company").in_scope(|| algorithms.run(company));
 
             match benchmarking_results {
                 Ok(output) => { 
-                    #[cfg(feature="evaluation")]
-                    {
-                        broadcaster.send(format!("EVAL-COMP-PARSE {:?}", output.1).as_str());
-                    }
-                    s.send((*company_id, output.0)).ok(); 
+                    s.send((*company_id, output)).ok(); 
                     Ok(()) 
                 },
                 Err(e) => {
                     log::error!("Encountered benchmarking error in thread for company {}: {}", company_id, e);
-                    broadcaster.send(format!("Encountered Error in computation: {}", e).as_str());
+                    broadcaster.send_to(benchmark_id, Audience::Company(*company_id), &ServerEvent::ComputationFailed { reason: e.to_string() });
                     Err(e)
                 }
             }
 
-        })?;
-
-    #[cfg(feature="evaluation")]
-    broadcaster.send(format!("EVAL-BENCH-COMP-TOTAL: {:?}", now.elapsed().unwrap().as_nanos()).as_str());
+        }))?;
+    drop(compute);
 
     ////////////////////////////////////////////////////////
 
     let company_kpis: HashMap<u128, Output> = receiver.iter().collect();
-    broadcaster.send(&format!("[1/4] Processing Aggregation: Complete! Clustering data..."));
+    broadcaster.send(benchmark_id, &progress(1, "Processing Aggregation: Complete! Clustering data..."));
     log::info!("Collected KPI results! -- Starting KPI Clustering!");
 
     ////////////////////////////////////////////////////////
     // STAGE 2 -- Cluster vars for aggregation            //
     ////////////////////////////////////////////////////////
 
-    let kpis = server.algorithms().unwrap().get_kpis().clone();
+    let kpis = algorithms.get_kpis().clone();
 
     ////////////////////////////////////////////////////////
 
     let (sender, receiver) = channel();
 
-    #[cfg(feature="evaluation")]
-    let now = std::time::SystemTime::now();
+    let cluster = tracing::info_span!("cluster").entered();
 
     kpis.par_iter().for_each_with(sender, |s: &mut std::sync::mpsc::Sender<(&str, Vec<&Vec<f64>>)>, atomic| {
         // This vector holds all companies results for one specific KPI
@@ -117,13 +137,11 @@ pub fn run_benchmark(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Ar
     });
 
     let mut clustered_kpis: HashMap<&str, Vec<&Vec<f64>>>  = receiver.iter().collect();
-
-    #[cfg(feature="evaluation")]
-    broadcaster.send(format!("EVAL-BENCH-CLUST: {:?}", now.elapsed().unwrap().as_nanos()).as_str());
+    drop(cluster);
     
     ////////////////////////////////////////////////////////
 
-    broadcaster.send(&format!("[2/4] Clustering KPI results: Complete! Aggregating..."));
+    broadcaster.send(benchmark_id, &progress(2, "Clustering KPI results: Complete! Aggregating..."));
     log::info!("Clustering Complete! -- Aggregation starting!");
 
     ////////////////////////////////////////////////////////
@@ -132,38 +150,30 @@ pub fn run_benchmark(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Ar
 
     ////////////////////////////////////////////////////////
 
-    #[cfg(feature="evaluation")]
-    let now = std::time::SystemTime::now();
+    let aggregate = tracing::info_span!("aggregate").entered();
 
     let statistical_data: HashMap<&str, OutputVariable> = clustered_kpis.par_iter_mut().map(|(&kpi_name, results)| {
         // Compute overall metrics
-        let aggregate = server.algorithms().unwrap().aggregate_atomic_var(results).unwrap();
+        let aggregate = algorithms.aggregate_atomic_var(results).unwrap();
         (kpi_name, aggregate)
     }).collect();
-
-    #[cfg(feature="evaluation")]
-    broadcaster.send(format!("EVAL-BENCH-AGG: {:?}", now.elapsed().unwrap().as_nanos()).as_str());
+    drop(aggregate);
     
     ////////////////////////////////////////////////////////
 
     log::info!("Aggregation Complete -- Assembling information!");
 
-    #[cfg(not(feature="evaluation"))]
-    broadcaster.send(&format!("[3/4] Processing Aggregation: Complete! Assembling information.."));
+    broadcaster.send(benchmark_id, &progress(3, "Processing Aggregation: Complete! Assembling information.."));
 
     ////////////////////////////////////////////////////////
     // STAGE 4 -- Assemble informations for all clients   //
     ////////////////////////////////////////////////////////
 
-    // Perform upgrade on rwlock and update data in place
-    let mut server_write = RwLockUpgradableReadGuard::try_upgrade(server).expect("Could not get writer upgrade");
-
-    ////////////////////////////////////////////////////////
-
-    #[cfg(feature="evaluation")]
-    let now = std::time::SystemTime::now();
+    let assemble = tracing::info_span!("assemble").entered();
 
-    for (company_id, company) in server_write.set_companies().iter_mut() {
+    // All companies are sealed before any results are stored, a failure leaves none behind
+    let mut sealed = Vec::new();
+    for (company_id, company) in participants.0.iter() {
         
         // Copy statistical results into company results
         let vars = company_kpis
@@ -177,21 +187,53 @@ pub fn run_benchmark(srv: Data<Arc<RwLock<BenchmarkingServer>>>, broadcaster: Ar
             })
             .collect();
         
-        company.set_results_data(Output::from_output_vars(vars));
+        // Only the company can open its results
+        let public_key = company.result_public_key().ok_or_else(|| BenchmarkingError::from(format!("Company {} has no result key!", company_id)))?;
+        let sealed_results = seal_output(public_key, benchmark_id, *company_id, &Output::from_output_vars(vars)).map_err(|e| BenchmarkingError::from(e.to_string()))?;
+        sealed.push((*company_id, sealed_results));
+    }
+    drop(assemble);
+
+    // Lock the server only to store the results
+    let mut server_write = futures::executor::block_on(srv.write());
+    let benchmark = server_write.benchmark_mut(benchmark_id).map_err(|e| BenchmarkingError::from(e.to_string()))?;
+    let mut ready = Vec::new();
+    for (company_id, sealed_results) in sealed {
+        if let Some(company) = benchmark.set_companies().get_mut(&company_id) {
+            company.set_sealed_results(Some(sealed_results));
+            ready.push(company_id);
+        }
+    }
+
+    // The inputs are not needed anymore once the results are sealed
+    if benchmark.benchmarking_config().input_retention() == InputRetention::WipeAfterResults {
+        let wiped = benchmark.wipe_inputs();
+        log::info!("Wiped the inputs of {} companies!", wiped);
+        broadcaster.send(benchmark_id, &ServerEvent::InputsWiped { companies: wiped });
     }
 
-    #[cfg(feature="evaluation")]
-    broadcaster.send(format!("EVAL-BENCH-ASSEMBLE: {:?}", now.elapsed().unwrap().as_nanos()).as_str());
+    server_write.set_phase(benchmark_id, BenchmarkPhase::ResultsPublished, &broadcaster).map_err(|e| BenchmarkingError::from(e.to_string()))?;
+    server_write.persist();
+    drop(server_write);
+
+    for company_id in ready {
+        broadcaster.send_to(benchmark_id, Audience::Company(company_id), &ServerEvent::ResultsReady);
+    }
 
     log::info!("Assembling information Complete!");
-    broadcaster.send(&format!("[4/4] Assembling Information: Complete! You can return your results now!"));
+    broadcaster.send(benchmark_id, &progress(4, "Assembling Information: Complete! You can return your results now!"));
     
     // Let the server sleep s.t. all clients can setup their sockets
     std::thread::sleep(std::time::Duration::from_millis(1000));
     
-    broadcaster.send("benchmarking-success");
+    broadcaster.send(benchmark_id, &ServerEvent::BenchmarkingSuccess);
 
     log::info!("Benchmarking finished!");
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Progress event after `step` of the computation
+fn progress(step: u32, message: &str) -> ServerEvent {
+    ServerEvent::Progress { step, steps: PROGRESS_STEPS, message: message.to_string() }
+}
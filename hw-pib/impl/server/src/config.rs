@@ -3,6 +3,10 @@ use serde::Deserialize;
 
 use types::consts::*;
 
+use server_util::files::UploadLimits;
+
+use crate::persistence::SealingMode;
+
 
 #[derive(Clone, Deserialize, Debug, Default)]
 /// Server Configuration
@@ -23,6 +27,58 @@ pub struct Config {
     https_workers: usize,
     #[serde(default)]
     shutdown_timeout: u64,
+    /// Encrypted state snapshot (persistence is disabled if empty)
+    #[serde(default)]
+    snapshot_path: String,
+    /// Origin of the snapshot key
+    #[serde(default)]
+    sealing: SealingMode,
+    #[serde(default)]
+    sealing_key_path: String,
+    /// CRL of the analyst's root CA (revocation checking is disabled if empty)
+    #[serde(default)]
+    crl_path: String,
+    /// File to which measured spans are appended as JSON lines (disabled if empty)
+    #[serde(default)]
+    span_export_path: String,
+    /// Send measured spans as `evaluation` events to the analyst team
+    #[serde(default)]
+    span_events: bool,
+    /// Requests per minute of every client (rate limiting is disabled if 0)
+    #[serde(default = "default_rate_limit_per_minute")]
+    rate_limit_per_minute: u32,
+    /// Requests of a client at once before it is throttled
+    #[serde(default = "default_rate_limit_burst")]
+    rate_limit_burst: u32,
+    /// Size of one field of a multipart upload (bytes)
+    #[serde(default = "default_max_field_size")]
+    max_field_size: usize,
+    /// Size of a whole multipart upload (bytes)
+    #[serde(default = "default_max_request_size")]
+    max_request_size: usize,
+    /// Clients of the event streams (unlimited if 0)
+    #[serde(default = "default_max_sse_clients")]
+    max_sse_clients: usize,
+}
+
+fn default_rate_limit_per_minute() -> u32 {
+    120
+}
+
+fn default_rate_limit_burst() -> u32 {
+    30
+}
+
+fn default_max_field_size() -> usize {
+    UploadLimits::default().field
+}
+
+fn default_max_request_size() -> usize {
+    UploadLimits::default().request
+}
+
+fn default_max_sse_clients() -> usize {
+    1024
 }
 
 
@@ -63,4 +119,44 @@ impl Config {
     pub fn shutdown_timeout(&self) -> u64 {
         self.shutdown_timeout
     }
+
+    pub fn snapshot_path(&self) -> &str {
+        &self.snapshot_path
+    }
+
+    pub fn sealing(&self) -> SealingMode {
+        self.sealing
+    }
+
+    pub fn sealing_key_path(&self) -> &str {
+        &self.sealing_key_path
+    }
+
+    pub fn crl_path(&self) -> &str {
+        &self.crl_path
+    }
+
+    pub fn span_export_path(&self) -> &str {
+        &self.span_export_path
+    }
+
+    pub fn span_events(&self) -> bool {
+        self.span_events
+    }
+
+    pub fn rate_limit_per_minute(&self) -> u32 {
+        self.rate_limit_per_minute
+    }
+
+    pub fn rate_limit_burst(&self) -> u32 {
+        self.rate_limit_burst
+    }
+
+    pub fn upload_limits(&self) -> UploadLimits {
+        UploadLimits { field: self.max_field_size, request: self.max_request_size }
+    }
+
+    pub fn max_sse_clients(&self) -> usize {
+        self.max_sse_clients
+    }
 }
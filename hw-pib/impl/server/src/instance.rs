@@ -0,0 +1,359 @@
+//! Benchmark instances
+//!
+//! One server hosts several independent benchmarks. Each of them has
+//! its own configuration (e.g. k-anonymity), algorithm, participants
+//! and results. They are addressed by their [`BenchmarkID`].
+//! 
+//! Each benchmark passes through the phases of [`BenchmarkPhase`],
+//! handlers check the phase before modifying the benchmark.
+//! 
+//! Once companies committed their data, the algorithms are only
+//! replaced after every participant confirmed the modification.
+//! 
+//! Results are kept sealed to the companies until they confirm their
+//! download or the retention time of the configuration is over. The
+//! inputs are wiped after the results were assembled (unless configured
+//! to be kept).
+//! 
+//! [`BenchmarkID`]: types::message::response::BenchmarkID
+
+use std::{collections::HashMap, time::{SystemTime, UNIX_EPOCH}};
+
+use benchmark::Algorithm;
+use serde::{Deserialize, Serialize};
+use server_util::error::ApiError;
+use types::{entity::{BenchmarkPhase, BenchmarkingConfig, Company}, input::Input, message::{error::ErrorCode, response::CompanyID}};
+
+use crate::attestation::algorithm_hash;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchmarkInstance {
+    /// Benchmarking Configuration
+    benchmarking_config: BenchmarkingConfig,
+    /// Companies, where  the companyID Value is and the value is the DER key, the certificate and a monotonic counter
+    companies: HashMap<CompanyID, Company>,
+    /// Analyst Algorithms
+    algorithms: Option<Algorithm>,
+    // Number of uploaded files
+    active_participants: u64,
+    /// Current phase of the lifecycle
+    #[serde(default)]
+    phase: BenchmarkPhase,
+    /// Version of the algorithms (incremented on every change)
+    #[serde(default)]
+    algorithm_version: u64,
+    /// Modification of the algorithms that awaits the confirmation of the participants
+    #[serde(default)]
+    pending_algorithms: Option<Algorithm>,
+    /// Time of the publication of the results (UNIX seconds)
+    #[serde(default)]
+    results_published_at: Option<u64>,
+}
+
+impl BenchmarkInstance {
+    pub fn new(benchmarking_config: BenchmarkingConfig) -> Self {
+        BenchmarkInstance { benchmarking_config, ..Default::default() }
+    }
+
+    pub fn benchmarking_config(&self) -> &BenchmarkingConfig {
+        &self.benchmarking_config
+    }
+
+    pub fn companies(&self) -> &HashMap<CompanyID, Company> {
+        &self.companies
+    }
+
+    pub fn algorithms(&self) -> Option<&Algorithm> {
+        self.algorithms.as_ref()
+    }
+
+    pub fn algorithm_version(&self) -> u64 {
+        self.algorithm_version
+    }
+
+    pub fn pending_algorithms(&self) -> Option<&Algorithm> {
+        self.pending_algorithms.as_ref()
+    }
+
+    /// Participants that did not yet confirm the pending algorithms
+    pub fn awaiting_confirmation(&self) -> Vec<CompanyID> {
+        let pending_hash = match &self.pending_algorithms {
+            Some(pending_algorithms) => algorithm_hash(pending_algorithms),
+            None => return Vec::new(),
+        };
+        self.companies
+            .iter()
+            .filter(|(_, company)| company.does_participate() && company.confirmed_algorithm() != Some(&pending_hash))
+            .map(|(company_id, _)| *company_id)
+            .collect()
+    }
+
+    pub fn active_participants(&self) -> u64 {
+        self.active_participants
+    }
+
+    pub fn phase(&self) -> BenchmarkPhase {
+        self.phase
+    }
+
+    /// Error out if the benchmark is in none of the `allowed` phases
+    pub fn require_phase(&self, allowed: &[BenchmarkPhase]) -> Result<(), ApiError> {
+        if allowed.contains(&self.phase) {
+            Ok(())
+        } else {
+            Err(ApiError::from((ErrorCode::WrongPhase { phase: self.phase }, format!("Not allowed while the benchmark is in phase {}!", self.phase))))
+        }
+    }
+
+    // Modification of benchmark instance
+
+    pub fn set_benchmarking_config_all(&mut self, cfg: BenchmarkingConfig) {
+        self.benchmarking_config = cfg;
+    }
+
+    pub fn set_companies(&mut self) -> &mut HashMap<CompanyID, Company> {
+        &mut self.companies
+    }
+
+    pub fn set_algorithms(&mut self, algorithms: Option<Algorithm>) {
+        self.algorithms = algorithms;
+        self.algorithm_version += 1;
+        self.pending_algorithms = None;
+    }
+
+    /// Propose a modification of the algorithms to the participants
+    pub fn set_pending_algorithms(&mut self, algorithms: Algorithm) {
+        self.pending_algorithms = Some(algorithms);
+    }
+
+    /// Take the pending algorithms once all participants confirmed them
+    pub fn take_confirmed_algorithms(&mut self) -> Option<Algorithm> {
+        if self.awaiting_confirmation().is_empty() {
+            self.pending_algorithms.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn increment_active_participants(&mut self) {
+        self.active_participants += 1;
+    }
+
+    /// Remove a company together with its certificate, input and results
+    pub fn remove_company(&mut self, company_id: &CompanyID) -> Option<Company> {
+        let company = self.companies.remove(company_id)?;
+        if company.does_participate() {
+            self.active_participants = self.active_participants.saturating_sub(1);
+        }
+        Some(company)
+    }
+
+    /// Verify all uploaded inputs against the current algorithms
+    ///
+    /// Inputs that lack required variables are discarded such that the
+    /// company has to upload them again. Returns the missing variables
+    /// per affected company.
+    pub fn revalidate_inputs(&mut self) -> HashMap<CompanyID, Vec<String>> {
+        let mut invalid = HashMap::new();
+        let algorithms = match &self.algorithms {
+            Some(algorithms) => algorithms,
+            None => return invalid,
+        };
+
+        for (company_id, company) in self.companies.iter_mut().filter(|(_, company)| company.does_participate()) {
+            let missing_vars = algorithms.missing_input_vars(company.input_data());
+            if !missing_vars.is_empty() {
+                company.set_input_data(Input::default());
+                self.active_participants = self.active_participants.saturating_sub(1);
+                invalid.insert(*company_id, missing_vars);
+            }
+        }
+        invalid
+    }
+
+    /// Error out if the analyst may not move the benchmark into `phase`
+    ///
+    /// The analyst opens the enrollment (once algorithms exist) and the
+    /// collection and closes the benchmark, the computation sets the others.
+    pub fn check_analyst_phase(&self, phase: BenchmarkPhase) -> Result<(), ApiError> {
+        match phase {
+            BenchmarkPhase::Enrollment if self.algorithms.is_none() => {
+                Err(ApiError::from((ErrorCode::Conflict, "No algorithms have been provided up until now. They are required in advance!")))
+            },
+            BenchmarkPhase::Enrollment | BenchmarkPhase::Collection | BenchmarkPhase::Closed => Ok(()),
+            phase => Err(ApiError::from((ErrorCode::BadRequest, format!("Phase {} is not set by the analyst!", phase)))),
+        }
+    }
+
+    /// Move to the succeeding phase (phases cannot be skipped or reverted)
+    pub fn set_phase(&mut self, phase: BenchmarkPhase) -> Result<(), ApiError> {
+        if self.phase.next() != Some(phase) {
+            return Err(ApiError::from((ErrorCode::WrongPhase { phase: self.phase }, format!("Cannot change from phase {} to {}!", self.phase, phase))));
+        }
+        self.phase = phase;
+        if phase == BenchmarkPhase::ResultsPublished {
+            self.results_published_at = Some(unix_time());
+        }
+        Ok(())
+    }
+
+    /// Zeroize the inputs of all participants and return their number
+    pub fn wipe_inputs(&mut self) -> usize {
+        self.companies
+            .values_mut()
+            .filter(|company| company.input_data().size() != 0)
+            .map(Company::wipe_input_data)
+            .count()
+    }
+
+    /// Delete the sealed results once the retention time is over
+    ///
+    /// Returns the companies whose results were not downloaded in time.
+    pub fn purge_expired_results(&mut self, now: u64) -> Vec<CompanyID> {
+        let expired = match self.results_published_at {
+            Some(published_at) => now >= published_at.saturating_add(self.benchmarking_config.result_retention()),
+            None => false,
+        };
+        if !expired {
+            return Vec::new();
+        }
+
+        self.companies
+            .iter_mut()
+            .filter(|(_, company)| company.sealed_results().is_some())
+            .map(|(company_id, company)| {
+                company.set_sealed_results(None);
+                *company_id
+            })
+            .collect()
+    }
+
+    /// Return to the collection after an interrupted computation
+    pub fn abort_computation(&mut self) {
+        if self.phase == BenchmarkPhase::Computing {
+            self.phase = BenchmarkPhase::Collection;
+        }
+    }
+}
+
+/// Current time in UNIX seconds
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use types::input::InputFormat;
+
+    use super::*;
+
+    /// Algorithm that sums up the given input variables
+    fn sum_of(vars: &[&str]) -> Algorithm {
+        let dir = std::env::temp_dir().join(format!("teebench-instance-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join(format!("{}.yaml", vars.join("_")));
+        fs::write(&path, format!("operations:\n  - name: sum\n    op: Addition\n    is_kpi: true\n    var: [{}]\n", vars.join(", "))).unwrap();
+        let algorithms = Algorithm::load(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        algorithms
+    }
+
+    fn input(vars: &[&str]) -> Input {
+        let input: String = vars.iter().map(|var| format!("  - name: {}\n    min_val: 0\n    max_val: 10\n    values: [ 1.0 ]\n", var)).collect();
+        Input::from_slice(format!("vars:\n{}", input).as_bytes(), InputFormat::Yaml).unwrap()
+    }
+
+    #[test]
+    pub fn modified_algorithms_invalidate_inputs() {
+        let mut benchmark = BenchmarkInstance::default();
+        benchmark.set_algorithms(Some(sum_of(&["a", "b"])));
+
+        for (company_id, vars) in [(1_u128, vec!["a", "b"]), (2, vec!["a", "b", "c"])] {
+            let mut company = Company::new();
+            company.set_input_data(input(&vars));
+            benchmark.set_companies().insert(company_id, company);
+            benchmark.increment_active_participants();
+        }
+        assert!(benchmark.revalidate_inputs().is_empty());
+        assert_eq!(benchmark.active_participants(), 2);
+
+        let version = benchmark.algorithm_version();
+        benchmark.set_algorithms(Some(sum_of(&["a", "c"])));
+        assert_ne!(benchmark.algorithm_version(), version);
+
+        let invalid = benchmark.revalidate_inputs();
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[&1], vec!["c".to_string()]);
+        assert_eq!(benchmark.active_participants(), 1);
+        assert!(!benchmark.companies()[&1].does_participate());
+        assert!(benchmark.companies()[&2].does_participate());
+    }
+
+    #[test]
+    pub fn phases_follow_the_lifecycle() {
+        let mut benchmark = BenchmarkInstance::default();
+        assert_eq!(benchmark.phase(), BenchmarkPhase::Setup);
+
+        let mut phases = Vec::new();
+        while let Some(next) = benchmark.phase().next() {
+            benchmark.set_phase(next).unwrap();
+            phases.push(next);
+        }
+        assert_eq!(phases, vec![BenchmarkPhase::Enrollment, BenchmarkPhase::Collection, BenchmarkPhase::Computing, BenchmarkPhase::ResultsPublished, BenchmarkPhase::Closed]);
+        assert!(benchmark.results_published_at.is_some());
+    }
+
+    #[test]
+    pub fn phases_cannot_be_skipped_or_reverted() {
+        let mut benchmark = BenchmarkInstance::default();
+        assert!(matches!(benchmark.set_phase(BenchmarkPhase::Collection).unwrap_err().code(), ErrorCode::WrongPhase { phase: BenchmarkPhase::Setup }));
+        assert!(benchmark.set_phase(BenchmarkPhase::Setup).is_err());
+
+        benchmark.set_phase(BenchmarkPhase::Enrollment).unwrap();
+        benchmark.set_phase(BenchmarkPhase::Collection).unwrap();
+        assert!(benchmark.set_phase(BenchmarkPhase::Enrollment).is_err());
+        assert!(benchmark.set_phase(BenchmarkPhase::Closed).is_err());
+        assert_eq!(benchmark.phase(), BenchmarkPhase::Collection);
+    }
+
+    #[test]
+    pub fn require_phase_rejects_other_phases() {
+        let mut benchmark = BenchmarkInstance::default();
+        benchmark.set_phase(BenchmarkPhase::Enrollment).unwrap();
+
+        assert!(benchmark.require_phase(&[BenchmarkPhase::Setup, BenchmarkPhase::Enrollment]).is_ok());
+        let err = benchmark.require_phase(&[BenchmarkPhase::Collection]).unwrap_err();
+        assert!(matches!(err.code(), ErrorCode::WrongPhase { phase: BenchmarkPhase::Enrollment }));
+        assert!(benchmark.require_phase(&[]).is_err());
+    }
+
+    #[test]
+    pub fn analyst_sets_only_its_phases() {
+        let mut benchmark = BenchmarkInstance::default();
+        assert!(matches!(benchmark.check_analyst_phase(BenchmarkPhase::Enrollment).unwrap_err().code(), ErrorCode::Conflict));
+
+        benchmark.set_algorithms(Some(sum_of(&["a"])));
+        for phase in [BenchmarkPhase::Enrollment, BenchmarkPhase::Collection, BenchmarkPhase::Closed] {
+            assert!(benchmark.check_analyst_phase(phase).is_ok());
+        }
+        for phase in [BenchmarkPhase::Setup, BenchmarkPhase::Computing, BenchmarkPhase::ResultsPublished] {
+            assert!(benchmark.check_analyst_phase(phase).is_err());
+        }
+    }
+
+    #[test]
+    pub fn aborted_computation_returns_to_the_collection() {
+        let mut benchmark = BenchmarkInstance::default();
+        benchmark.abort_computation();
+        assert_eq!(benchmark.phase(), BenchmarkPhase::Setup);
+
+        for phase in [BenchmarkPhase::Enrollment, BenchmarkPhase::Collection, BenchmarkPhase::Computing] {
+            benchmark.set_phase(phase).unwrap();
+        }
+        benchmark.abort_computation();
+        assert_eq!(benchmark.phase(), BenchmarkPhase::Collection);
+        assert!(benchmark.set_phase(BenchmarkPhase::Computing).is_ok());
+    }
+}
@@ -0,0 +1,178 @@
+//! **Instrumentation**
+//!
+//! The uploads, the parsing of algorithms and inputs and the stages of the
+//! computation are traced as spans (see [`SPAN_METRICS`]). A layer of the
+//! tracing subscriber measures every span of the server and the benchmark
+//! crate once it closes and exports its duration:
+//!
+//! - to the [`Metrics`] (durations of the computation stages),
+//! - as JSON line to the file of `span_export_path` (if configured),
+//! - as `evaluation` event to the analyst team of the benchmark (if
+//!   `span_events` is configured), which the spectator records.
+//!
+//! Spans carry the ID of their benchmark in the `benchmark` field, nested
+//! spans (e.g. `parse` within `load_algorithms`) inherit it.
+
+use std::{fs::{File, OpenOptions}, io::{LineWriter, Write}, sync::Arc, time::{Duration, Instant}};
+
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use server_util::broadcast_event::{Audience, Broadcaster};
+use tracing::{field::{Field, Visit}, span::{Attributes, Id}, Subscriber};
+use tracing_subscriber::{layer::{Context, SubscriberExt}, registry::LookupSpan, Layer, Registry};
+use types::message::{event::{EvalMetric, ServerEvent}, response::BenchmarkID};
+
+use crate::{config::Config, metrics::{Metrics, Stage}};
+
+/// Field of the spans that holds the benchmark ID
+const BENCHMARK_FIELD: &str = "benchmark";
+
+/// Crates whose spans are measured
+const TARGETS: [&str; 2] = ["server", "benchmark"];
+
+/// Spans that are reported to the analyst team with their metric
+///
+/// The computation of single companies (`compute_company`) is only
+/// reported within the whole computation.
+const SPAN_METRICS: [(&str, EvalMetric); 9] = [
+    ("upload_algorithms", EvalMetric::AlgoUpload),
+    ("parse", EvalMetric::AlgoParse),
+    ("topo_sort", EvalMetric::AlgoTopo),
+    ("upload_input", EvalMetric::CompUpload),
+    ("parse_input", EvalMetric::CompParse),
+    ("compute", EvalMetric::BenchCompTotal),
+    ("cluster", EvalMetric::BenchClust),
+    ("aggregate", EvalMetric::BenchAgg),
+    ("assemble", EvalMetric::BenchAssemble),
+];
+
+/// One measured span as exported to the file
+#[derive(Debug, Serialize)]
+struct SpanRecord<'a> {
+    name: &'a str,
+    target: &'a str,
+    benchmark: Option<BenchmarkID>,
+    /// RFC 3339
+    start: String,
+    duration_ns: u128,
+}
+
+/// Destinations of the measured spans
+pub struct SpanExport {
+    metrics: Data<Metrics>,
+    file: Option<Mutex<LineWriter<File>>>,
+    /// Event streams once the HTTPS server runs (only with `span_events`)
+    broadcaster: Mutex<Option<Data<Broadcaster>>>,
+    events: bool,
+}
+
+impl SpanExport {
+    /// Send the measurements as events over this broadcaster from now on
+    pub fn attach(&self, broadcaster: Data<Broadcaster>) {
+        if self.events {
+            *self.broadcaster.lock() = Some(broadcaster);
+        }
+    }
+
+    fn export(&self, name: &str, target: &str, benchmark: Option<BenchmarkID>, start: DateTime<Utc>, duration: Duration) {
+        if let Some(stage) = Stage::from_span(name) {
+            self.metrics.record_stage(stage, duration);
+        }
+
+        if let Some(file) = &self.file {
+            let record = SpanRecord { name, target, benchmark, start: start.to_rfc3339(), duration_ns: duration.as_nanos() };
+            if let Ok(line) = serde_json::to_string(&record) {
+                writeln!(file.lock(), "{}", line).unwrap_or_else(|e| log::warn!("Could not export span {}: {}", name, e));
+            }
+        }
+
+        if let (Some(broadcaster), Some(benchmark)) = (self.broadcaster.lock().as_ref(), benchmark) {
+            if let Some((_, metric)) = SPAN_METRICS.iter().find(|(span, _)| *span == name) {
+                broadcaster.send_to(benchmark, Audience::Analyst, &ServerEvent::Evaluation { metric: *metric, value: duration.as_nanos() });
+            }
+        }
+    }
+}
+
+/// Start of a span and the benchmark it belongs to
+#[derive(Debug, Clone, Copy)]
+struct Timing {
+    start: Instant,
+    started_at: DateTime<Utc>,
+    benchmark: Option<BenchmarkID>,
+}
+
+/// Reads the benchmark ID of a span
+struct BenchmarkVisitor(Option<BenchmarkID>);
+
+impl Visit for BenchmarkVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == BENCHMARK_FIELD {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Layer that measures the spans and hands them to the [`SpanExport`]
+struct SpanTimings {
+    export: Arc<SpanExport>,
+}
+
+impl<S> Layer<S> for SpanTimings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !TARGETS.iter().any(|target| attrs.metadata().target().starts_with(target)) {
+            return;
+        }
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let mut visitor = BenchmarkVisitor(None);
+        attrs.record(&mut visitor);
+        let benchmark = visitor.0.or_else(|| span.parent().and_then(|parent| parent.extensions().get::<Timing>().and_then(|timing| timing.benchmark)));
+
+        span.extensions_mut().insert(Timing { start: Instant::now(), started_at: Utc::now(), benchmark });
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            let timing = span.extensions().get::<Timing>().copied();
+            if let Some(timing) = timing {
+                self.export.export(span.name(), span.metadata().target(), timing.benchmark, timing.started_at, timing.start.elapsed());
+            }
+        }
+    }
+}
+
+/// Install the span measurements as global tracing subscriber
+///
+/// The spans of all builds are measured, the export file is appended to.
+pub fn init(config: &Config, metrics: Data<Metrics>) -> Arc<SpanExport> {
+    let file = match config.span_export_path() {
+        "" => None,
+        path => match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(Mutex::new(LineWriter::new(file))),
+            Err(e) => {
+                log::warn!("Could not open the span export {}: {} -- Spans are not exported!", path, e);
+                None
+            }
+        },
+    };
+
+    let export = Arc::new(SpanExport { metrics, file, broadcaster: Mutex::new(None), events: config.span_events() });
+
+    let subscriber = Registry::default().with(SpanTimings { export: export.clone() });
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        log::warn!("A tracing subscriber is already installed -- Spans are not measured!");
+    }
+
+    export
+}
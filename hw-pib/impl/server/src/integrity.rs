@@ -0,0 +1,109 @@
+//! **Request Integrity**
+//!
+//! Next to the TLS client certificate every request of the analyst and
+//! the companies carries an HMAC over its method, path, nonce and body
+//! (see [`request_mac_data`]). The HMAC keys are chosen by the participants
+//! and handed over encrypted to a key of the server:
+//!
+//! 1. The server offers an ephemeral X25519 key ([`KeyExchange::offer`]).
+//! 2. The participant agrees on a secret with an ephemeral key of its own
+//!    and derives an AES-256-GCM key (HKDF-SHA256) that encrypts its HMAC key.
+//! 3. The server agrees on the same secret ([`KeyExchange::open`]) and stores
+//!    the HMAC key with the participant. Every offered key is used once.
+//!
+//! The nonce of a request is a counter that has to increase with every
+//! request, which prevents replaying recorded requests.
+//!
+//! [`request_mac_data`]: types::message::request::request_mac_data
+
+use std::{collections::HashMap, fmt};
+
+use parking_lot::Mutex;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf::{Salt, HKDF_SHA256},
+    constant_time::verify_slices_are_equal,
+    hmac,
+    rand::SystemRandom,
+};
+use server_util::error::ApiError;
+use types::{
+    consts::{HMAC_KEY_EXCHANGE_INFO, HMAC_KEY_LEN},
+    entity::HmacKey,
+    message::error::ErrorCode,
+};
+
+/// Maximum number of offered keys that await their exchange
+const MAX_OFFERS: usize = 1024;
+
+/// Ephemeral keys of the server that were offered for key exchanges
+#[derive(Default)]
+pub struct KeyExchange {
+    offers: Mutex<HashMap<String, EphemeralPrivateKey>>,
+}
+
+impl fmt::Debug for KeyExchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyExchange").field("offers", &self.offers.lock().len()).finish()
+    }
+}
+
+impl KeyExchange {
+    /// Generate an ephemeral key and return its hex encoded public key
+    pub fn offer(&self) -> Result<String, ApiError> {
+        let private_key = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new()).map_err(|_| ApiError::from((ErrorCode::Internal, "Could not generate an exchange key!")))?;
+        let public_key = hex::encode(private_key.compute_public_key().map_err(|_| ApiError::from((ErrorCode::Internal, "Could not generate an exchange key!")))?.as_ref());
+
+        let mut offers = self.offers.lock();
+        // Offers that are never used must not pile up
+        if offers.len() >= MAX_OFFERS {
+            if let Some(unused) = offers.keys().next().cloned() {
+                offers.remove(&unused);
+            }
+        }
+        offers.insert(public_key.clone(), private_key);
+
+        Ok(public_key)
+    }
+
+    /// Decrypt the HMAC key of a participant with the offered `exchange_key`
+    pub fn open(&self, exchange_key: &str, public_key: &str, encrypted_hmac: &str) -> Result<HmacKey, ApiError> {
+        let private_key = self.offers.lock().remove(exchange_key).ok_or_else(|| ApiError::from((ErrorCode::Conflict, "The exchange key was not offered or is already used!")))?;
+        let public_key = hex::decode(public_key).map_err(|_| ApiError::from((ErrorCode::InvalidFormat, "The public key is not hex encoded!")))?;
+        let mut data = hex::decode(encrypted_hmac).map_err(|_| ApiError::from((ErrorCode::InvalidFormat, "The encrypted HMAC key is not hex encoded!")))?;
+        if data.len() < NONCE_LEN {
+            return Err(ApiError::from((ErrorCode::InvalidFormat, "The encrypted HMAC key is truncated!")));
+        }
+        let mut ciphertext = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data).map_err(|_| ApiError::from((ErrorCode::InvalidFormat, "Invalid nonce of the encrypted HMAC key!")))?;
+
+        let key = agreement::agree_ephemeral(private_key, &UnparsedPublicKey::new(&X25519, public_key), ApiError::from((ErrorCode::NotAuthenticated, "Key agreement failed!")), |secret| {
+            let prk = Salt::new(HKDF_SHA256, &[]).extract(secret);
+            let okm = prk.expand(&[HMAC_KEY_EXCHANGE_INFO], &AES_256_GCM).map_err(|_| ApiError::from((ErrorCode::Internal, "Key derivation failed!")))?;
+            Ok(LessSafeKey::new(UnboundKey::from(okm)))
+        })?;
+        let hmac_key = key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| ApiError::from((ErrorCode::NotAuthenticated, "The HMAC key could not be decrypted!")))?;
+
+        if hmac_key.len() != HMAC_KEY_LEN {
+            return Err(ApiError::from((ErrorCode::InvalidFormat, format!("The HMAC key has to be {} bytes long!", HMAC_KEY_LEN))));
+        }
+        Ok(HmacKey::new(hmac_key.to_vec()))
+    }
+}
+
+/// Check the hex encoded HMAC (SHA-256) of the request data
+///
+/// The data is given in parts (e.g. the chunks of a body), which are
+/// authenticated as one without copying them together.
+pub fn verify_mac<'a>(key: &HmacKey, data: impl IntoIterator<Item = &'a [u8]>, mac: &str) -> bool {
+    let mac = match hex::decode(mac) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    let mut context = hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA256, key.key()));
+    data.into_iter().for_each(|part| context.update(part));
+    verify_slices_are_equal(context.sign().as_ref(), &mac).is_ok()
+}
@@ -3,16 +3,30 @@
 use std::sync::Arc;
 use async_lock::RwLock;
 
-use crate::{app::{http_server, https_server}, server::BenchmarkingServer};
+use actix_web::web::Data;
+
+use crate::{app::{http_server, https_server}, metrics::Metrics, server::BenchmarkingServer};
 
 mod config;
 mod server;
+mod instance;
 mod crypto;
 mod app;
 mod routes;
 mod api;
 mod benchmark;
 mod middleware;
+mod persistence;
+mod attestation;
+mod integrity;
+mod revocation;
+mod audit;
+pub mod sealing;
+mod openapi;
+mod metrics;
+mod instrumentation;
+
+pub use attestation::AttestationReference;
 
 /// The entry point of the server
 /// 
@@ -24,12 +38,17 @@ pub fn execute_server() -> std::io::Result<()> {
     // Initialize server
     let benchmarking_server = BenchmarkingServer::load();
     let shutdown_timeout = benchmarking_server.server_config().shutdown_timeout();
+    let metrics = Data::new(Metrics::default());
+    let span_export = instrumentation::init(benchmarking_server.server_config(), metrics.clone());
     let arc_benchmarking_server = Arc::new(RwLock::new(benchmarking_server));
     
-    // Start HTTP setup server
-    actix::System::new().block_on(async {
-        http_server(arc_benchmarking_server.clone()).await
-    }).expect("HTTP Server did not terminate successfully!");
+    // Start HTTP setup server (unless the setup was restored from a snapshot)
+    let is_configured = arc_benchmarking_server.try_read().expect("Could not acquire lock for setup check!").crypto_config().root_ca_certificate().is_some();
+    if !is_configured {
+        actix::System::new().block_on(async {
+            http_server(arc_benchmarking_server.clone(), metrics.clone()).await
+        }).expect("HTTP Server did not terminate successfully!");
+    }
 
     // In case the server got interrupted (i.e. no config provided) avoid crashing it
     if arc_benchmarking_server.try_read().expect("Could not acquire lock for shutdown check!").crypto_config().root_ca_certificate().is_none() {
@@ -42,7 +61,7 @@ pub fn execute_server() -> std::io::Result<()> {
 
     // Start application server with rich functionality
     actix::System::new().block_on(async {
-        https_server(arc_benchmarking_server.clone()).await
+        https_server(arc_benchmarking_server.clone(), metrics, span_export).await
     }).expect("HTTPs Server did not terminate successfully!");
 
 
@@ -0,0 +1,256 @@
+//! **Server metrics**
+//!
+//! Counters and histograms of the server in the Prometheus text format
+//! (served at `/metrics` to the analyst team):
+//!
+//! - requests by route, method and status with their latencies,
+//! - sizes of multipart uploads by route,
+//! - durations of the stages of the benchmark computation (measured by
+//!   their spans, see [`crate::instrumentation`]).
+//!
+//! Gauges (participants, phases and event stream clients) are read from the
+//! state of the server when the metrics are rendered. Routes are labeled by
+//! their pattern (e.g. `/api/v1/benchmarks/{bid}/company/input_data`), thus
+//! no IDs end up in the metrics.
+
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use parking_lot::Mutex;
+use server_util::broadcast_event::Broadcaster;
+
+use crate::server::BenchmarkingServer;
+
+/// Upper bounds of the buckets of latencies and durations (seconds)
+const DURATION_BUCKETS: [f64; 14] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// Upper bounds of the buckets of upload sizes (bytes)
+const SIZE_BUCKETS: [f64; 7] = [1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9];
+
+/// Label of requests that matched no route
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Stages of the benchmark computation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// KPI computation of all companies
+    Compute,
+    /// Clustering the results by KPI
+    Cluster,
+    /// Aggregation of the statistics
+    Aggregate,
+    /// Sealing the results of every company
+    Assemble,
+}
+
+impl Stage {
+    /// Stage that a span of the computation measures
+    pub fn from_span(name: &str) -> Option<Self> {
+        match name {
+            "compute" => Some(Stage::Compute),
+            "cluster" => Some(Stage::Cluster),
+            "aggregate" => Some(Stage::Aggregate),
+            "assemble" => Some(Stage::Assemble),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Stage::Compute => "compute",
+            Stage::Cluster => "cluster",
+            Stage::Aggregate => "aggregate",
+            Stage::Assemble => "assemble",
+        }
+    }
+}
+
+/// Cumulative histogram with fixed buckets
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram { bounds, counts: vec![0; bounds.len()], sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Bucket, sum and count lines of the histogram with its labels
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, count).ok();
+        }
+        writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count).ok();
+        writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum).ok();
+        writeln!(out, "{}_count{{{}}} {}", name, labels, self.count).ok();
+    }
+}
+
+/// Metrics that are recorded while the server runs
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<MetricsInner>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    /// Requests by (route, method, status)
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// Latencies by (route, method)
+    latencies: BTreeMap<(String, String), Histogram>,
+    /// Upload sizes by route
+    uploads: BTreeMap<String, Histogram>,
+    stages: BTreeMap<Stage, Histogram>,
+}
+
+impl Metrics {
+    /// Count a handled request and its latency
+    pub fn record_request(&self, route: &str, method: &str, status: u16, latency: Duration) {
+        let mut inner = self.inner.lock();
+        *inner.requests.entry((route.to_string(), method.to_string(), status)).or_default() += 1;
+        inner.latencies
+            .entry((route.to_string(), method.to_string()))
+            .or_insert_with(|| Histogram::new(&DURATION_BUCKETS))
+            .observe(latency.as_secs_f64());
+    }
+
+    /// Record the size of a multipart upload
+    pub fn record_upload(&self, route: &str, bytes: u64) {
+        self.inner.lock().uploads.entry(route.to_string()).or_insert_with(|| Histogram::new(&SIZE_BUCKETS)).observe(bytes as f64);
+    }
+
+    /// Record the duration of one stage of a benchmark computation
+    pub fn record_stage(&self, stage: Stage, duration: Duration) {
+        self.inner.lock().stages.entry(stage).or_insert_with(|| Histogram::new(&DURATION_BUCKETS)).observe(duration.as_secs_f64());
+    }
+
+    /// All metrics in the Prometheus text format
+    ///
+    /// The server is optional as the setup server has no benchmarks and no
+    /// event streams yet.
+    pub fn render(&self, server: Option<&BenchmarkingServer>, broadcaster: Option<&Broadcaster>) -> String {
+        let mut out = String::new();
+
+        {
+            let inner = self.inner.lock();
+
+            out.push_str("# HELP teebench_http_requests_total Handled requests by route, method and status.\n# TYPE teebench_http_requests_total counter\n");
+            for ((route, method, status), count) in inner.requests.iter() {
+                writeln!(out, "teebench_http_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}", escape(route), method, status, count).ok();
+            }
+
+            out.push_str("# HELP teebench_http_request_duration_seconds Latency of the requests by route and method.\n# TYPE teebench_http_request_duration_seconds histogram\n");
+            for ((route, method), histogram) in inner.latencies.iter() {
+                histogram.render(&mut out, "teebench_http_request_duration_seconds", &format!("route=\"{}\",method=\"{}\"", escape(route), method));
+            }
+
+            out.push_str("# HELP teebench_upload_size_bytes Size of the multipart uploads by route.\n# TYPE teebench_upload_size_bytes histogram\n");
+            for (route, histogram) in inner.uploads.iter() {
+                histogram.render(&mut out, "teebench_upload_size_bytes", &format!("route=\"{}\"", escape(route)));
+            }
+
+            out.push_str("# HELP teebench_benchmark_stage_duration_seconds Duration of the stages of the benchmark computation.\n# TYPE teebench_benchmark_stage_duration_seconds histogram\n");
+            for (stage, histogram) in inner.stages.iter() {
+                histogram.render(&mut out, "teebench_benchmark_stage_duration_seconds", &format!("stage=\"{}\"", stage.label()));
+            }
+        }
+
+        if let Some(server) = server {
+            // Sorted by ID, thus the output is stable
+            let benchmarks: BTreeMap<_, _> = server.benchmarks().iter().collect();
+
+            out.push_str("# HELP teebench_active_participants Companies with valid input by benchmark.\n# TYPE teebench_active_participants gauge\n");
+            for (id, benchmark) in benchmarks.iter() {
+                writeln!(out, "teebench_active_participants{{benchmark=\"{}\"}} {}", id, benchmark.active_participants()).ok();
+            }
+
+            out.push_str("# HELP teebench_benchmark_phase Current phase of every benchmark (1 for the current one).\n# TYPE teebench_benchmark_phase gauge\n");
+            for (id, benchmark) in benchmarks.iter() {
+                writeln!(out, "teebench_benchmark_phase{{benchmark=\"{}\",phase=\"{}\"}} 1", id, benchmark.phase()).ok();
+            }
+        }
+
+        if let Some(broadcaster) = broadcaster {
+            out.push_str("# HELP teebench_sse_clients Connected clients of the event streams.\n# TYPE teebench_sse_clients gauge\n");
+            writeln!(out, "teebench_sse_clients {}", broadcaster.client_count()).ok();
+        }
+
+        out
+    }
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn histograms_are_cumulative() {
+        let mut histogram = Histogram::new(&DURATION_BUCKETS);
+        histogram.observe(0.002);
+        histogram.observe(2.0);
+        histogram.observe(100.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "latency", "route=\"/\"");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), DURATION_BUCKETS.len() + 3);
+        assert_eq!(lines[0], "latency_bucket{route=\"/\",le=\"0.001\"} 0");
+        assert_eq!(lines[1], "latency_bucket{route=\"/\",le=\"0.005\"} 1");
+        assert_eq!(lines[9], "latency_bucket{route=\"/\",le=\"2.5\"} 2");
+        assert_eq!(lines[13], "latency_bucket{route=\"/\",le=\"60\"} 2");
+        assert_eq!(lines[14], "latency_bucket{route=\"/\",le=\"+Inf\"} 3");
+        assert_eq!(lines[15], "latency_sum{route=\"/\"} 102.002");
+        assert_eq!(lines[16], "latency_count{route=\"/\"} 3");
+
+        // Without labels only the bucket bound is labeled
+        let mut out = String::new();
+        Histogram::new(&SIZE_BUCKETS).render(&mut out, "size", "");
+        assert!(out.starts_with("size_bucket{le=\"1000\"} 0\n"));
+        assert!(out.ends_with("size_count{} 0\n"));
+    }
+
+    #[test]
+    pub fn label_values_are_escaped() {
+        assert_eq!(escape("/api/v1/benchmarks/{bid}"), "/api/v1/benchmarks/{bid}");
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    pub fn recorded_metrics_are_rendered() {
+        let metrics = Metrics::default();
+        metrics.record_request("/metrics", "GET", 200, Duration::from_millis(3));
+        metrics.record_request("/metrics", "GET", 200, Duration::from_millis(3));
+        metrics.record_request("/x\"y", "POST", 404, Duration::from_millis(3));
+        metrics.record_upload("/upload", 2048);
+        metrics.record_stage(Stage::Aggregate, Duration::from_secs(1));
+
+        let out = metrics.render(None, None);
+        assert!(out.contains("teebench_http_requests_total{route=\"/metrics\",method=\"GET\",status=\"200\"} 2\n"));
+        assert!(out.contains("teebench_http_requests_total{route=\"/x\\\"y\",method=\"POST\",status=\"404\"} 1\n"));
+        assert!(out.contains("teebench_http_request_duration_seconds_count{route=\"/metrics\",method=\"GET\"} 2\n"));
+        assert!(out.contains("teebench_upload_size_bytes_bucket{route=\"/upload\",le=\"10000\"} 1\n"));
+        assert!(out.contains("teebench_benchmark_stage_duration_seconds_sum{stage=\"aggregate\"} 1\n"));
+
+        // Gauges need the server and the broadcaster
+        assert!(!out.contains("teebench_active_participants"));
+        assert!(!out.contains("teebench_sse_clients"));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod request_verifier;
-#[cfg(not(feature="evaluation"))]
+pub mod state_persister;
+pub mod request_metrics;
+pub mod rate_limiter;
 pub mod request_logger;
-#[cfg(not(feature="evaluation"))]
 pub mod response_logger;
\ No newline at end of file
@@ -0,0 +1,258 @@
+use std::{collections::HashMap, rc::Rc, time::Instant};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{
+        forward_ready,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform},
+    Error, web::Data
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use parking_lot::Mutex;
+use ring::digest::{digest, SHA256};
+use rustls::Certificate;
+use server_util::error::ApiError;
+use types::message::error::ErrorCode;
+
+/// Number of tracked identities from which on idle ones are forgotten
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Maximal number of tracked identities, the longest unseen one is evicted beyond
+const MAX_IDENTITIES: usize = 16 * 1024;
+
+/// Token buckets of the identities that sent requests
+///
+/// Every identity (the fingerprint of its client certificate or its IP
+/// address without one) may send `burst` requests at once, which are
+/// refilled with `per_minute` requests per minute. At most `capacity`
+/// identities are tracked, such that clients with changing addresses
+/// cannot exhaust the memory of the server.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: u32,
+    burst: u32,
+    capacity: usize,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Rate limits of all identities (disabled for `per_minute` 0)
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        RateLimiter { per_minute, burst: burst.max(1), capacity: MAX_IDENTITIES, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// Take one request of `identity`, or the seconds until it may send the next one
+    fn take(&self, identity: String) -> Result<(), u64> {
+        self.take_at(identity, Instant::now())
+    }
+
+    fn take_at(&self, identity: String, now: Instant) -> Result<(), u64> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let (rate, burst) = (self.per_minute as f64 / 60.0, self.burst as f64);
+        let refill = |bucket: &Bucket| (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+
+        let mut buckets = self.buckets.lock();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // Refilled buckets are the same as new ones
+            buckets.retain(|_, bucket| refill(bucket) < burst);
+        }
+        if buckets.len() >= self.capacity && !buckets.contains_key(&identity) {
+            // Busy identities are rather kept than the ones that were not seen for the longest time
+            let unseen = buckets.iter().min_by_key(|(_, bucket)| bucket.updated).map(|(identity, _)| identity.clone());
+            if let Some(unseen) = unseen {
+                buckets.remove(&unseen);
+            }
+        }
+
+        let bucket = buckets.entry(identity).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+/// Rate Limiter for throttling repeated requests of one identity
+///
+/// Requests beyond the limits of the [`RateLimiter`] (part of the
+/// application data) are rejected with a 429 (`TOO_MANY_REQUESTS`) and
+/// the seconds after which to retry.
+pub struct LimitRate;
+
+/// Middleware factory is of `Transform` trait
+/// `S` - type of the next service
+/// `B` - type of response's body
+impl<S: 'static ,B> Transform<S, ServiceRequest> for LimitRate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    // Responses produced by the service.
+    type Response = ServiceResponse<EitherBody<B>>;
+    // Errors produced by the service.
+    type Error = Error;
+    // Errors produced while building a transform service.
+    type InitError = ();
+    // The `TransformService` value created by this factory, which is the LimitRate"Service"
+    type Transform = LimitRateMiddleware<S>;
+    // The future response value.
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    /// Creates and returns a new instance of our middleware "service"
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(
+            Ok(
+                LimitRateMiddleware {
+                    service: Rc::new(service)
+                }
+            )
+        )
+    }
+
+}
+
+pub struct LimitRateMiddleware<S> {
+    // Avoid lifetime issues with reference counting
+    service: Rc<S>,
+}
+
+
+impl<S: 'static,B> Service<ServiceRequest> for LimitRateMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    // Responses given by the service.
+    type Response = ServiceResponse<EitherBody<B>>;
+    // Errors produced by the service when polling readiness
+    type Error = Error;
+    // Future Response value (promise)
+    type Future =  LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    // Reject the request if its identity exceeded its limits
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+
+        let limited = req.app_data::<Data<RateLimiter>>().and_then(|limiter| limiter.take(identity(&req)).err());
+        if let Some(retry_after) = limited {
+            let error = ApiError::from((ErrorCode::TooManyRequests { retry_after }, format!("Too many requests - retry after {} seconds!", retry_after)));
+            return Box::pin(ready(Ok(req.error_response(error).map_into_right_body())));
+        }
+
+        // Clone the Rc pointers so we can move them into the async block.
+        let srv = self.service.clone();
+
+        Box::pin(async move {
+            srv.call(req).await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+
+}
+
+/// Fingerprint of the client certificate (SHA-256), otherwise the IP address of the peer
+fn identity(req: &ServiceRequest) -> String {
+    match req.conn_data::<Certificate>() {
+        Some(certificate) => hex::encode(digest(&SHA256, &certificate.0)),
+        None => req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn limiter(per_minute: u32, burst: u32, capacity: usize) -> RateLimiter {
+        RateLimiter { capacity, ..RateLimiter::new(per_minute, burst) }
+    }
+
+    #[test]
+    pub fn burst_is_refilled_per_minute() {
+        let limiter = limiter(60, 3, MAX_IDENTITIES);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.take_at("a".to_string(), now), Ok(()));
+        }
+        assert_eq!(limiter.take_at("a".to_string(), now), Err(1));
+        // Other identities have their own bucket
+        assert_eq!(limiter.take_at("b".to_string(), now), Ok(()));
+
+        // One request per second, the burst is not exceeded after a long pause
+        assert_eq!(limiter.take_at("a".to_string(), now + Duration::from_secs(1)), Ok(()));
+        assert_eq!(limiter.take_at("a".to_string(), now + Duration::from_secs(1)), Err(1));
+        for _ in 0..3 {
+            assert_eq!(limiter.take_at("a".to_string(), now + Duration::from_secs(600)), Ok(()));
+        }
+        assert!(limiter.take_at("a".to_string(), now + Duration::from_secs(600)).is_err());
+    }
+
+    #[test]
+    pub fn retry_after_is_rounded_up() {
+        let limiter = limiter(6, 1, MAX_IDENTITIES);
+        let now = Instant::now();
+
+        assert_eq!(limiter.take_at("a".to_string(), now), Ok(()));
+        assert_eq!(limiter.take_at("a".to_string(), now), Err(10));
+        assert_eq!(limiter.take_at("a".to_string(), now + Duration::from_millis(4500)), Err(6));
+        assert_eq!(limiter.take_at("a".to_string(), now + Duration::from_secs(11)), Ok(()));
+
+        // Disabled without requests per minute
+        let unlimited = RateLimiter::new(0, 1);
+        assert!((0..100).all(|_| unlimited.take_at("a".to_string(), now).is_ok()));
+    }
+
+    #[test]
+    pub fn refilled_buckets_are_pruned() {
+        let limiter = limiter(60, 2, MAX_IDENTITIES);
+        let now = Instant::now();
+
+        for i in 0..PRUNE_THRESHOLD {
+            limiter.take_at(i.to_string(), now).unwrap();
+        }
+        // Buckets that are not refilled yet are kept
+        limiter.take_at("busy".to_string(), now + Duration::from_millis(500)).unwrap();
+        assert_eq!(limiter.buckets.lock().len(), PRUNE_THRESHOLD + 1);
+
+        limiter.take_at("late".to_string(), now + Duration::from_secs(1)).unwrap();
+        assert_eq!(limiter.buckets.lock().keys().collect::<std::collections::HashSet<_>>(), ["busy".to_string(), "late".to_string()].iter().collect());
+    }
+
+    #[test]
+    pub fn identities_are_capped() {
+        let limiter = limiter(1, 1, 4);
+        let now = Instant::now();
+
+        for i in 0..4 {
+            limiter.take_at(i.to_string(), now + Duration::from_secs(i)).unwrap();
+        }
+        // Known identities do not evict others
+        assert!(limiter.take_at("3".to_string(), now + Duration::from_secs(4)).is_err());
+        assert_eq!(limiter.buckets.lock().len(), 4);
+
+        // The identity that was not seen for the longest time is forgotten
+        limiter.take_at("new".to_string(), now + Duration::from_secs(5)).unwrap();
+        let buckets = limiter.buckets.lock();
+        assert_eq!(buckets.len(), 4);
+        assert!(!buckets.contains_key("0") && buckets.contains_key("3") && buckets.contains_key("new"));
+    }
+}
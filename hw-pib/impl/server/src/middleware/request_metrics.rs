@@ -0,0 +1,162 @@
+use std::{cell::Cell, rc::Rc, time::Instant};
+
+use actix_web::{
+    dev::{
+        forward_ready,
+        Payload,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform},
+    http::header::CONTENT_TYPE,
+    Error, HttpMessage, web::Data
+};
+use futures::{future::{ready, LocalBoxFuture, Ready}, StreamExt};
+
+use crate::metrics::{Metrics, UNMATCHED_ROUTE};
+
+/// Request Metrics for counting and timing every request
+///
+/// Requests are recorded by their route pattern, method and status when
+/// a [`Metrics`] registry is part of the application data. The size of
+/// multipart uploads counts the bytes of the body that were read, thus
+/// chunked uploads (without content length) are recorded as well.
+pub struct RecordMetrics;
+
+/// Middleware factory is of `Transform` trait
+/// `S` - type of the next service
+/// `B` - type of response's body
+impl<S: 'static ,B> Transform<S, ServiceRequest> for RecordMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    // Responses produced by the service.
+    type Response = ServiceResponse<B>;
+    // Errors produced by the service.
+    type Error = Error;
+    // Errors produced while building a transform service.
+    type InitError = ();
+    // The `TransformService` value created by this factory, which is the RecordMetrics"Service"
+    type Transform = RecordMetricsMiddleware<S>;
+    // The future response value.
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    /// Creates and returns a new instance of our middleware "service"
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(
+            Ok(
+                RecordMetricsMiddleware {
+                    service: Rc::new(service)
+                }
+            )
+        )
+    }
+
+}
+
+pub struct RecordMetricsMiddleware<S> {
+    // Avoid lifetime issues with reference counting
+    service: Rc<S>,
+}
+
+
+impl<S: 'static,B> Service<ServiceRequest> for RecordMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    // Responses given by the service.
+    type Response = ServiceResponse<B>;
+    // Errors produced by the service when polling readiness
+    type Error = Error;
+    // Future Response value (promise)
+    type Future =  LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    // Process the request and record it with its latency afterwards
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+
+        let start = Instant::now();
+        let metrics = req.app_data::<Data<Metrics>>().cloned();
+        let route = req.match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let method = req.method().to_string();
+
+        // Only uploads of files are of interest (not the JSON messages)
+        let is_multipart = req.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).is_some_and(|value| value.starts_with("multipart/"));
+        let upload_size = is_multipart.then(|| {
+            let size = Rc::new(Cell::new(0_u64));
+            let counted = size.clone();
+            let payload = req.take_payload().inspect(move |chunk| {
+                if let Ok(chunk) = chunk {
+                    counted.set(counted.get() + chunk.len() as u64);
+                }
+            });
+            req.set_payload(Payload::Stream { payload: Box::pin(payload) });
+            size
+        });
+
+        // Clone the Rc pointers so we can move them into the async block.
+        let srv = self.service.clone();
+
+        Box::pin(async move {
+
+            let res = srv.call(req).await;
+
+            if let Some(metrics) = metrics {
+                // Errors of the inner services become responses later on
+                let status = match &res {
+                    Ok(res) => res.status().as_u16(),
+                    Err(e) => e.as_response_error().status_code().as_u16(),
+                };
+                metrics.record_request(&route, &method, status, start.elapsed());
+                if let Some(upload_size) = upload_size {
+                    metrics.record_upload(&route, upload_size.get());
+                }
+            }
+
+            res
+        })
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::{test, web::{self, Bytes}, App, HttpResponse};
+
+    use super::*;
+
+    #[actix_web::test]
+    pub async fn uploads_without_content_length_are_recorded() {
+        let metrics = Data::new(Metrics::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(metrics.clone())
+                .wrap(RecordMetrics)
+                .route("/upload", web::post().to(|body: Bytes| async move { HttpResponse::Ok().body(body.len().to_string()) })),
+        )
+        .await;
+
+        // The body is streamed in chunks without a content length
+        let (_, mut payload) = actix_http::h1::Payload::create(true);
+        payload.unread_data(Bytes::from(vec![1_u8; 1500]));
+        payload.unread_data(Bytes::from(vec![1_u8; 1000]));
+        let (req, _) = test::TestRequest::post()
+            .uri("/upload")
+            .insert_header((CONTENT_TYPE, "multipart/form-data; boundary=teebench"))
+            .to_request()
+            .replace_payload(payload.into());
+        assert!(req.headers().get(actix_web::http::header::CONTENT_LENGTH).is_none());
+
+        let res = test::call_service(&app, req).await;
+        assert_eq!(test::read_body(res).await, "2500");
+
+        let out = metrics.render(None, None);
+        assert!(out.contains("teebench_upload_size_bytes_sum{route=\"/upload\"} 2500\n"), "{}", out);
+        assert!(out.contains("teebench_http_requests_total{route=\"/upload\",method=\"POST\",status=\"200\"} 1\n"));
+    }
+}
@@ -1,6 +1,7 @@
 use std::{rc::Rc, sync::Arc};
 use async_lock::RwLock;
 
+use actix_http::h1;
 use actix_web::{
     body::EitherBody,
     dev::{
@@ -9,35 +10,90 @@ use actix_web::{
         ServiceRequest,
         ServiceResponse,
         Transform},
-    Error, web::Data
+    Error, HttpMessage, http::Method, web::{Bytes, Data}
 };
-use futures::{future::LocalBoxFuture, FutureExt};
+use futures::{future::LocalBoxFuture, FutureExt, StreamExt};
 use futures_util::future::{ready, Ready};
 use rustls::Certificate;
-use types::consts::X_APPLICATION_FIELD;
+use server_util::{client_cert_extractor::certificate_company_id, error::ApiError, files::UploadLimits};
+use types::{
+    consts::{C_ROUTE_ANALYST_EXT_KEY_EXCHANGE, X_COUNTER_FIELD, X_HMAC_FIELD},
+    entity::Role,
+    message::{error::ErrorCode, request::request_mac_header, response::{BenchmarkID, CompanyID}},
+};
 use std::str::FromStr;
 
-use crate::server::BenchmarkingServer;
+use crate::{audit::Actor, integrity::verify_mac, server::BenchmarkingServer};
 
 // Two steps in middleware processing:
 // 1. Middleware initialization, middleware factory gets called with
 //    next service in chain as parameter.
 // 2. Middleware's call method gets called with normal request.
 
+/// Company that was authenticated by its certificate
+/// 
+/// The middleware hands it to the company handlers (`ReqData<CompanyIdentity>`).
+#[derive(Debug, Clone, Copy)]
+pub struct CompanyIdentity(pub CompanyID);
+
+/// Roles that may access a route
+/// 
+/// Reading requests (`GET`, `HEAD`) and modifying requests are granted
+/// separately, e.g. auditors only read the configuration and status.
+#[derive(Debug, Clone, Copy)]
+pub struct Permissions {
+    read: &'static [Role],
+    modify: &'static [Role],
+}
+
+impl Permissions {
+    /// Companies access their own data
+    pub const COMPANY: Permissions = Permissions::new(&[Role::Company], &[Role::Company]);
+    /// Only the owner of the server (setup and shutdown)
+    pub const OWNER: Permissions = Permissions::new(&[Role::Owner], &[Role::Owner]);
+    /// Administration of benchmarks, auditors have read-only access
+    pub const ADMINISTRATION: Permissions = Permissions::new(&[Role::Owner, Role::CoAnalyst, Role::Auditor], &[Role::Owner, Role::CoAnalyst]);
+    /// Every member of the analyst team (e.g. for their own key exchange)
+    pub const ANALYST_TEAM: Permissions = Permissions::new(&[Role::Owner, Role::CoAnalyst, Role::Auditor], &[Role::Owner, Role::CoAnalyst, Role::Auditor]);
+
+    pub const fn new(read: &'static [Role], modify: &'static [Role]) -> Self {
+        Permissions { read, modify }
+    }
+
+    /// Roles that are allowed to send a request with the method
+    pub fn roles(&self, method: &Method) -> &'static [Role] {
+        if method == Method::GET || method == Method::HEAD {
+            self.read
+        } else {
+            self.modify
+        }
+    }
+}
+
 /// Signature Verification Middleware to check the authenticity of the requester
+/// 
+/// Next to the TLS certificate, the HMAC of the request and its counter
+/// are verified (see [`crate::integrity`]). Members of the analyst team are
+/// identified by their certificate, companies by the UUID in their certificate,
+/// requests without certificate are rejected. The role of the requester has
+/// to be part of the route's [`Permissions`], it is handed on to the handlers
+/// as [`Actor`] (`ReqData<Actor>`).
 pub struct VerifyRequest {
-    verify_company: bool,
+    permissions: Permissions,
+    /// Whether the HMAC of the request is verified
+    hmac: bool,
 }
 
-/// Verify either routes for companies or for the analyst
-#[allow(unused)]
 impl VerifyRequest {
-    pub fn verify_company() -> Self {
-        VerifyRequest { verify_company: true }
+    pub fn permit(permissions: Permissions) -> Self {
+        VerifyRequest { permissions, hmac: true }
     }
 
-    pub fn verify_analyst() -> Self {
-        VerifyRequest { verify_company: false }
+    /// Authenticate by the certificate only, for clients that cannot sign
+    /// requests (e.g. a Prometheus scraper of the metrics)
+    pub fn certificate_only(mut self) -> Self {
+        self.hmac = false;
+        self
     }
 }
 
@@ -65,7 +121,8 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(VerifySignatureMiddleware { 
             service: Rc::new(service),
-            verify_company: self.verify_company,
+            permissions: self.permissions,
+            hmac: self.hmac,
         }))
     }
 
@@ -74,7 +131,8 @@ where
 #[derive(Clone)]
 pub struct VerifySignatureMiddleware<S> {
     service: Rc<S>,
-    verify_company: bool,
+    permissions: Permissions,
+    hmac: bool,
 }
 
 
@@ -97,10 +155,11 @@ where
     forward_ready!(service);
 
     // Process the request and return the response asynchronously.
-    fn call(&self, req: ServiceRequest) -> Self::Future {
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
 
-        // Whether to verify the companies or the analyst
-        let verify_company = self.verify_company;
+        // Roles that may access the route with the method of the request
+        let roles = self.permissions.roles(req.method());
+        let hmac = self.hmac;
 
         // Clone the Rc pointers so we can move them into the async block.
         let srv = self.service.clone();
@@ -108,60 +167,153 @@ where
 
         Box::pin(async move {
 
-            let (analyst, companies) = {
-                let srv_rdr = req.app_data::<Data<Arc<RwLock<BenchmarkingServer>>>>().unwrap().read().await;
-                let analyst_info = srv_rdr.analyst().unwrap().clone();
-                let companies = srv_rdr.companies().clone();
-                (analyst_info, companies)
-            };
+            // Companies are enrolled per benchmark (which is part of the path)
+            let benchmark_id = req.match_info().get("bid").and_then(|bid| BenchmarkID::from_str(bid).ok());
 
+            let server = req.app_data::<Data<Arc<RwLock<BenchmarkingServer>>>>().unwrap().clone();
             // Certificate of request
             let certificate = match req.conn_data::<Certificate>() {
                 Some(cert) => cert.clone(),
-                None => { return Ok(srv.call(req).map(map_body_left).await?); }
+                None => { return Ok(reject(req, ErrorCode::NotAuthenticated, "No client certificate - no access granted!")); }
+            };
+
+            // The company ID is signed by the analyst's CA as part of the certificate
+            let certificate_company = certificate_company_id(&certificate);
+
+            // Only the certificate and HMAC key of the requester are copied
+            let (analyst, company, revocation_list) = {
+                let srv_rdr = server.read().await;
+                let analyst_info = srv_rdr.analyst(&certificate).map(|analyst| (analyst.role(), analyst.hmac_key().cloned()));
+                let company = benchmark_id
+                    .and_then(|bid| srv_rdr.benchmarks().get(&bid))
+                    .zip(certificate_company)
+                    .and_then(|(benchmark, company_id)| benchmark.companies().get(&company_id))
+                    .map(|company| (company.certificate().clone(), company.hmac_key().cloned()));
+                (analyst_info, company, srv_rdr.crypto_config().revocation_list().clone())
             };
             // log::info!("Certificate of request: {:?}", certificate);
 
-            if verify_company {
+            // Connections established before a revocation are still open
+            if revocation_list.is_revoked(&certificate) {
+                return Ok(reject(req, ErrorCode::CertificateRevoked, "Certificate was revoked - no access granted!"));
+            }
+
+            let (role, company_id, hmac_key) = if let Some((role, hmac_key)) = analyst {
+
+                //
+                // ANALYST TEAM VERIFICATION
+                //
+
+                (role, None, hmac_key)
+
+            } else if roles.contains(&Role::Company) {
 
                 //
                 // COMPANY VERIFICATION
                 //
 
-                // Extract the company ID from the header
-                let company_id = req.headers().get(X_APPLICATION_FIELD).map_or("", |result| result.to_str().unwrap());
-                let company_id_num = match u128::from_str(company_id) {
-                    Ok(uuid) => uuid,
-                    _ => { return Ok(req.into_response("Malformed UUID!").map_into_boxed_body().map_into_right_body()); }
+                let company_id_num = match certificate_company {
+                    Some(uuid) => uuid,
+                    None => { return Ok(reject(req, ErrorCode::PermissionDenied, "Certificate does not hold a company UUID!")); }
                 };
                 log::debug!("UUID of Request for company {}", company_id_num);
                 
-                if let Some(company) = companies.get(&company_id_num) {
-                    if let Some(company_certificate) = company.certificate() {
+                if let Some((company_certificate, company_hmac_key)) = company {
+                    if let Some(company_certificate) = company_certificate {
                         if company_certificate.eq(&certificate) {
-                            Ok(srv.call(req).map(map_body_left).await?)
+                            req.extensions_mut().insert(CompanyIdentity(company_id_num));
+                            (Role::Company, Some(company_id_num), company_hmac_key)
                         }else{
-                            Ok(req.into_response("Certificate missmatch - no access granted!").map_into_boxed_body().map_into_right_body())
+                            return Ok(reject(req, ErrorCode::PermissionDenied, "Certificate missmatch - no access granted!"));
                         }
                     } else {
-                        Ok(req.into_response("Please register your certificate first!").map_into_boxed_body().map_into_right_body())
+                        return Ok(reject(req, ErrorCode::CompanyNotRegistered, "Please register your certificate first!"));
                     }
                 } else {
-                    Ok(req.into_response("Company was not found!").map_into_boxed_body().map_into_right_body())
+                    return Ok(reject(req, ErrorCode::CompanyNotEnrolled, "Company was not found!"));
                 }
 
             } else {
+                return Ok(reject(req, ErrorCode::PermissionDenied, "Certificate missmatch - no access granted!"));
+            };
 
-                //
-                // ANALYST VERIFICATION
-                //
+            //
+            // PERMISSION VERIFICATION
+            //
+
+            if !roles.contains(&role) {
+                let denied = format!("The role {} has no permission for this request!", role);
+                return Ok(reject(req, ErrorCode::PermissionDenied, denied));
+            }
+            req.extensions_mut().insert(Actor::new(role, &certificate));
+
+            //
+            // INTEGRITY VERIFICATION
+            //
+
+            if !hmac {
+                return srv.call(req).map(map_body_left).await;
+            }
+
+            // Only the analyst key exchange itself is not authenticated by an HMAC
+            let hmac_key = match hmac_key {
+                Some(hmac_key) => hmac_key,
+                None if role != Role::Company && req.path() == C_ROUTE_ANALYST_EXT_KEY_EXCHANGE => { return srv.call(req).map(map_body_left).await; }
+                None => { return Ok(reject(req, ErrorCode::NotAuthenticated, "Please exchange your HMAC key first!")); }
+            };
+
+            let mac = req.headers().get(X_HMAC_FIELD).and_then(|mac| mac.to_str().ok()).map(String::from);
+            let counter = req.headers().get(X_COUNTER_FIELD).and_then(|counter| counter.to_str().ok()).and_then(|counter| u64::from_str(counter).ok());
+            let (mac, counter) = match (mac, counter) {
+                (Some(mac), Some(counter)) => (mac, counter),
+                _ => { return Ok(reject(req, ErrorCode::NotAuthenticated, "Request is not authenticated - HMAC or counter missing!")); }
+            };
+
+            // The chunks of the body are read for the HMAC and handed on afterwards (up to the size limit of
+            // requests), they are not copied together since the body may hold the plaintext input of a company
+            let max_size = req.app_data::<Data<UploadLimits>>().map_or_else(|| UploadLimits::default().request, |limits| limits.request);
+            let mut payload = req.take_payload();
+            let (mut chunks, mut size): (Vec<Bytes>, usize) = (Vec::new(), 0);
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk?;
+                size += chunk.len();
+                if size > max_size {
+                    return Ok(reject(req, ErrorCode::PayloadTooLarge, format!("The request exceeds the limit of {} bytes!", max_size)));
+                }
+                chunks.push(chunk);
+            }
+
+            let path = req.uri().path_and_query().map_or(req.path(), |path| path.as_str());
+            let header = request_mac_header(req.method().as_str(), path, counter);
+            if !verify_mac(&hmac_key, std::iter::once(header.as_slice()).chain(chunks.iter().map(|chunk| chunk.as_ref())), &mac) {
+                return Ok(reject(req, ErrorCode::NotAuthenticated, "Invalid HMAC - request was modified!"));
+            }
 
-                if analyst.certificate().eq(&certificate) {
-                    Ok(srv.call(req).map(map_body_left).await?)
-                }else{
-                    Ok(req.into_response("Certificate missmatch - no access granted!").map_into_boxed_body().map_into_right_body())   
+            // Counters have to increase, i.e. recorded requests cannot be replayed
+            let is_fresh = {
+                let mut srv_wrt = server.write().await;
+                let stored_key = match company_id {
+                    Some(company_id) => benchmark_id
+                        .and_then(|bid| srv_wrt.benchmark_mut(bid).ok())
+                        .and_then(|benchmark| benchmark.set_companies().get_mut(&company_id))
+                        .and_then(|company| company.set_hmac_key().as_mut()),
+                    None => srv_wrt.analyst_mut(&certificate).and_then(|analyst| analyst.set_hmac_key().as_mut()),
+                };
+                match stored_key {
+                    Some(stored_key) => stored_key.advance(counter),
+                    None => false,
                 }
+            };
+            if !is_fresh {
+                return Ok(reject(req, ErrorCode::NotAuthenticated, "Replayed request - the counter has to increase!"));
             }
+
+            // Unread chunks are put in front, thus the last one goes first
+            let (_, mut restored_payload) = h1::Payload::create(true);
+            chunks.into_iter().rev().for_each(|chunk| restored_payload.unread_data(chunk));
+            req.set_payload(restored_payload.into());
+
+            srv.call(req).map(map_body_left).await
         })
     }
 }
@@ -169,4 +321,9 @@ where
 // Helper function for body mapping (left = OK)
 fn map_body_left<B, E>(res: Result<ServiceResponse<B>, E>,) -> Result<ServiceResponse<EitherBody<B>>, E> {
     res.map(|res| res.map_into_left_body())
-}
\ No newline at end of file
+}
+
+// Helper function for rejected requests (right = error with its code and status)
+fn reject<B>(req: ServiceRequest, code: ErrorCode, message: impl Into<String>) -> ServiceResponse<EitherBody<B>> {
+    req.error_response(ApiError::from((code, message.into()))).map_into_right_body()
+}
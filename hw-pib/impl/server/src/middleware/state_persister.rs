@@ -0,0 +1,101 @@
+use std::{rc::Rc, sync::Arc};
+use async_lock::RwLock;
+
+use actix_web::{
+    dev::{
+        forward_ready,
+        Service,
+        ServiceRequest,
+        ServiceResponse,
+        Transform},
+    http::Method,
+    Error, web::Data
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::server::BenchmarkingServer;
+
+/// State Persister for snapshotting the server after state changes
+///
+/// Every successful request that is not a `GET` may have modified
+/// the [`BenchmarkingServer`], thus a snapshot is written afterwards.
+pub struct PersistState;
+
+/// Middleware factory is of `Transform` trait
+/// `S` - type of the next service
+/// `B` - type of response's body
+impl<S: 'static ,B> Transform<S, ServiceRequest> for PersistState
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    // Responses produced by the service.
+    type Response = ServiceResponse<B>;
+    // Errors produced by the service.
+    type Error = Error;
+    // Errors produced while building a transform service.
+    type InitError = ();
+    // The `TransformService` value created by this factory, which is the PersistState"Service"
+    type Transform = PersistStateMiddleware<S>;
+    // The future response value.
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    /// Creates and returns a new instance of our middleware "service"
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(
+            Ok(
+                PersistStateMiddleware {
+                    service: Rc::new(service)
+                }
+            )
+        )
+    }
+
+}
+
+pub struct PersistStateMiddleware<S> {
+    // Avoid lifetime issues with reference counting
+    service: Rc<S>,
+}
+
+
+impl<S: 'static,B> Service<ServiceRequest> for PersistStateMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    // Responses given by the service.
+    type Response = ServiceResponse<B>;
+    // Errors produced by the service when polling readiness
+    type Error = Error;
+    // Future Response value (promise)
+    type Future =  LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    // Process the request and snapshot the state after modifications
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+
+        let modifies_state = req.method() != Method::GET;
+        let benchmarking_server = req.app_data::<Data<Arc<RwLock<BenchmarkingServer>>>>().cloned();
+
+        // Clone the Rc pointers so we can move them into the async block.
+        let srv = self.service.clone();
+
+        Box::pin(async move {
+
+            let res = srv.call(req).await?;
+
+            if modifies_state && res.status().is_success() {
+                if let Some(benchmarking_server) = benchmarking_server {
+                    benchmarking_server.read().await.persist();
+                }
+            }
+
+            Ok(res)
+        })
+    }
+
+}
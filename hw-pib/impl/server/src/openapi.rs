@@ -0,0 +1,382 @@
+//! **OpenAPI description**
+//!
+//! The routes of [`crate::routes`] with their roles, parameters and message
+//! types as OpenAPI 3.1 document (served at `/api/v1/openapi.json`). Paths
+//! are built from the route constants of `types::consts`, the schemas follow
+//! the message types of `types::message`.
+//!
+//! Routes with roles (`x-roles`) are authenticated by the client certificate
+//! (mutual TLS) and the HMAC headers of the request, except for the metrics
+//! and the key exchange of the analyst team (certificate only).
+
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use types::consts::*;
+
+/// Message body of a request or response
+enum Body {
+    None,
+    /// JSON message (schema name)
+    Json(&'static str),
+    /// Multipart form with its fields (name, mime, required)
+    Multipart(&'static [(&'static str, &'static str, bool)]),
+    /// Server sent events (`ServerEvent`)
+    EventStream,
+    /// Plain text (description)
+    Text(&'static str),
+}
+
+/// One route with one method
+struct Route {
+    path: String,
+    method: &'static str,
+    summary: &'static str,
+    /// Roles that may use the route, empty for everyone
+    roles: &'static [&'static str],
+    request: Body,
+    /// Schema of the `content` of the `RspMsg`
+    response: Body,
+}
+
+const OWNER: &[&str] = &["Owner"];
+const ANALYST: &[&str] = &["Owner", "CoAnalyst"];
+const ANALYST_TEAM: &[&str] = &["Owner", "CoAnalyst", "Auditor"];
+const COMPANY: &[&str] = &["Company"];
+const ANY: &[&str] = &[];
+
+const SETUP_FORM: &[(&str, &str, bool)] = &[
+    (FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_NAME, FORM_DATA_FIELD_01_ROOT_CA_CERTIFICATE_MIME, true),
+    (FORM_DATA_FIELD_01_CONFIGURATION_NAME, FORM_DATA_FIELD_01_CONFIGURATION_MIME, true),
+    (FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME, FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME, true),
+    (FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_NAME, FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_MIME, false),
+    (FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_NAME, FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_MIME, false),
+];
+const ALGORITHMS_FORM: &[(&str, &str, bool)] = &[(FORM_DATA_FIELD_02_ALGORIHTMS_NAME, FORM_DATA_FIELD_02_ALGORITHMS_MIME, true)];
+const CONFIGURATION_FORM: &[(&str, &str, bool)] = &[(FORM_DATA_FIELD_03_CONFIGURATION_NAME, FORM_DATA_FIELD_03_CONFIGURATION_MIME, true)];
+const INPUT_FORM: &[(&str, &str, bool)] = &[(FORM_DATA_FIELD_04_COMPANY_INPUT_NAME, FORM_DATA_FIELD_04_COMPANY_INPUT_MIMES, true)];
+
+/// All routes of the HTTPS server
+fn routes() -> Vec<Route> {
+    let benchmark = |route: &str| format!("{}{{bid}}{}", C_ROUTE_BENCHMARKS, route);
+    let route = |path: String, method: &'static str, summary: &'static str, roles: &'static [&'static str], request: Body, response: Body| Route { path, method, summary, roles, request, response };
+
+    vec![
+        route(ROUTE_WHOAMI.to_string(), "get", "Connection information on the peer and its certificate", ANY, Body::None, Body::Json("String")),
+        route(C_ROUTE_OPENAPI.to_string(), "get", "This OpenAPI description", ANY, Body::None, Body::None),
+        route(ROUTE_HEALTH_LIVE.to_string(), "get", "Liveness of the server", ANY, Body::None, Body::None),
+        route(ROUTE_HEALTH_READY.to_string(), "get", "Readiness of the server (503 until the setup is done)", ANY, Body::None, Body::None),
+        route(ROUTE_METRICS.to_string(), "get", "Metrics of the server", ANALYST_TEAM, Body::None, Body::Text("Prometheus text format (version 0.0.4)")),
+        route(C_ROUTE_SETUP.to_string(), "post", "Setup of the server (over HTTP only once, afterwards it reports that the server is configured)", OWNER, Body::Multipart(SETUP_FORM), Body::None),
+        route(C_ROUTE_ATTEST.to_string(), "get", "Attestation evidence bound to the nonce of the client", ANY, Body::None, Body::Json("ServerStatus")),
+        route(C_ROUTE_KEY_EXCHANGE.to_string(), "get", "Ephemeral key to which the HMAC key is encrypted", ANY, Body::None, Body::Json("KeyExchangeOffer")),
+        route(C_ROUTE_SHUTDOWN.to_string(), "post", "Shut the server down", OWNER, Body::None, Body::None),
+        route(C_ROUTE_ANALYST_EXT_BENCHMARKS.to_string(), "post", "Create a further benchmark, returns its ID", ANALYST, Body::Multipart(CONFIGURATION_FORM), Body::Json("BenchmarkID")),
+        route(C_ROUTE_ANALYST_EXT_BENCHMARKS.to_string(), "get", "All benchmarks with their configuration", ANALYST_TEAM, Body::None, Body::Json("Benchmarks")),
+        route(C_ROUTE_ANALYST_EXT_KEY_EXCHANGE.to_string(), "post", "Set the HMAC key of a team member (the only analyst route without HMAC)", ANALYST_TEAM, Body::Json("AnalystSetupMsg"), Body::None),
+        route(C_ROUTE_ANALYST_EXT_AUDIT_LOG.to_string(), "get", "Export of the hash-chained audit log", ANALYST_TEAM, Body::None, Body::Json("AuditLog")),
+        route(benchmark(C_ROUTE_COMPANY_EXT_REGISTER), "post", "Register the certificate, the HMAC key and the result key of a company", ANY, Body::Json("CompanySetupMsg"), Body::None),
+        route(benchmark(C_ROUTE_COMPANY_EXT_INPUT_DATA), "post", "Upload the input of the company (YAML, JSON or CSV by the content type)", COMPANY, Body::Multipart(INPUT_FORM), Body::Json("Input")),
+        route(benchmark(C_ROUTE_COMPANY_EXT_INPUT_DATA), "put", "Modify the input of the company", COMPANY, Body::Multipart(INPUT_FORM), Body::Json("Input")),
+        route(benchmark(C_ROUTE_COMPANY_EXT_INPUT_DATA), "get", "The uploaded input of the company", COMPANY, Body::None, Body::Json("Input")),
+        route(benchmark(C_ROUTE_COMPANY_EXT_RESULTS), "get", "The results sealed to the result key of the company", COMPANY, Body::None, Body::Json("SealedResults")),
+        route(benchmark(C_ROUTE_COMPANY_EXT_RESULTS), "delete", "Confirm the download of the results, which deletes them", COMPANY, Body::None, Body::None),
+        route(benchmark(C_ROUTE_COMPANY_EXT_ALGORITHMS), "get", "The active and the pending algorithms with version and hash", COMPANY, Body::None, Body::Json("AlgorithmsMsg")),
+        route(benchmark(C_ROUTE_COMPANY_EXT_ALGORITHMS), "post", "Confirm the pending modification of the algorithms", COMPANY, Body::Json("CompanyConfirmMsg"), Body::None),
+        route(benchmark(C_ROUTE_COMPANY_EXT_EVENTS), "get", "Public events together with the ones for the company", COMPANY, Body::None, Body::EventStream),
+        route(benchmark(C_ROUTE_ENROLL_EVENTS), "get", "Public events of the benchmark", ANY, Body::None, Body::EventStream),
+        route(benchmark(C_ROUTE_PHASE), "get", "Current phase of the benchmark", ANY, Body::None, Body::Json("BenchmarkPhase")),
+        route(benchmark(C_ROUTE_ANALYST_EXT_BENCHMARK_CONFIG), "put", "Modify the configuration of the benchmark", ANALYST, Body::Multipart(CONFIGURATION_FORM), Body::Json("BenchmarkingConfig")),
+        route(benchmark(C_ROUTE_ANALYST_EXT_BENCHMARK_CONFIG), "get", "Configuration of the benchmark", ANALYST_TEAM, Body::None, Body::Json("BenchmarkingConfig")),
+        route(benchmark(&format!("{}{{id}}", C_ROUTE_ANALYST_EXT_COMPANY_STATUS_ID)), "get", "Status of a company", ANALYST_TEAM, Body::None, Body::Json("CompanyStatus")),
+        route(benchmark(&format!("{}{{id}}", C_ROUTE_ANALYST_EXT_COMPANY_STATUS_ID)), "delete", "Remove a company and revoke its certificate", ANALYST, Body::None, Body::None),
+        route(benchmark(C_ROUTE_ANALYST_EXT_ENROLL_COMPANY), "post", "Enroll a company, returns its UUID", ANALYST, Body::None, Body::Json("CompanyID")),
+        route(benchmark(C_ROUTE_ANALYST_EXT_ALGORITHMS), "post", "Upload the algorithms (bundled, without includes)", ANALYST, Body::Multipart(ALGORITHMS_FORM), Body::None),
+        route(benchmark(C_ROUTE_ANALYST_EXT_ALGORITHMS), "put", "Modify the algorithms, returns the inputs that got invalid", ANALYST, Body::Multipart(ALGORITHMS_FORM), Body::Json("InvalidInputs")),
+        route(benchmark(C_ROUTE_ANALYST_EXT_ALGORITHMS), "get", "The active algorithms", ANALYST_TEAM, Body::None, Body::Json("Algorithm")),
+        route(benchmark(C_ROUTE_ANALYST_EXT_BENCHMARK), "post", "Start the computation of the benchmark", ANALYST, Body::Json("AnalystBenchmarkingMsg"), Body::None),
+        route(benchmark(C_ROUTE_ANALYST_EXT_EVENT), "post", "Broadcast a message over the public events", ANALYST, Body::Json("AnalystEventMsg"), Body::None),
+        route(benchmark(C_ROUTE_ANALYST_EXT_PHASE), "put", "Move the benchmark into its next phase", ANALYST, Body::Json("AnalystPhaseMsg"), Body::Json("BenchmarkPhase")),
+        route(benchmark(C_ROUTE_ANALYST_EXT_EVENTS), "get", "Public events together with the ones for the analyst team", ANALYST_TEAM, Body::None, Body::EventStream),
+    ]
+}
+
+fn schema_ref(name: &str) -> JsonValue {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn operation(route: &Route) -> JsonValue {
+    let mut operation = JsonMap::new();
+    operation.insert("summary".into(), json!(route.summary));
+
+    // Path parameters of the benchmark and the company
+    let mut parameters = Vec::new();
+    if route.path.contains("{bid}") {
+        parameters.push(json!({ "name": "bid", "in": "path", "required": true, "schema": { "type": "integer", "format": "uint64" } }));
+    }
+    if route.path.contains("{id}") {
+        parameters.push(json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "string", "format": "uuid" } }));
+    }
+    if route.path == C_ROUTE_ATTEST {
+        parameters.push(json!({ "name": "nonce", "in": "query", "required": false, "schema": { "type": "string", "description": "Hex, at most 64 bytes" } }));
+    }
+    if matches!(route.response, Body::EventStream) {
+        parameters.push(json!({ "name": LAST_EVENT_ID_FIELD, "in": "header", "required": false, "schema": { "type": "integer" }, "description": "Replay the events after this ID" }));
+    }
+    if !parameters.is_empty() {
+        operation.insert("parameters".into(), json!(parameters));
+    }
+
+    match route.request {
+        Body::Json(name) => {
+            operation.insert("requestBody".into(), json!({ "required": true, "content": { "application/json": { "schema": schema_ref(name) } } }));
+        }
+        Body::Multipart(fields) => {
+            let properties: JsonMap<String, JsonValue> = fields.iter().map(|(name, _, _)| (name.to_string(), json!({ "type": "string", "format": "binary" }))).collect();
+            let encoding: JsonMap<String, JsonValue> = fields.iter().map(|(name, mime, _)| (name.to_string(), json!({ "contentType": mime }))).collect();
+            let required: Vec<&str> = fields.iter().filter(|(_, _, required)| *required).map(|(name, _, _)| *name).collect();
+            operation.insert(
+                "requestBody".into(),
+                json!({ "required": true, "content": { "multipart/form-data": {
+                    "schema": { "type": "object", "properties": properties, "required": required },
+                    "encoding": encoding,
+                } } }),
+            );
+        }
+        Body::None | Body::EventStream | Body::Text(_) => {}
+    }
+
+    let ok = match route.response {
+        Body::EventStream => json!({ "description": "Server sent events", "content": { "text/event-stream": { "schema": schema_ref("ServerEvent") } } }),
+        Body::Text(description) => json!({ "description": description, "content": { "text/plain": { "schema": { "type": "string" } } } }),
+        Body::Json(name) => json!({ "description": "Success", "content": { "application/json": { "schema": {
+            "allOf": [schema_ref("RspMsg"), { "type": "object", "properties": { "content": schema_ref(name) } }]
+        } } } }),
+        Body::None | Body::Multipart(_) => json!({ "description": "Success", "content": { "application/json": { "schema": schema_ref("RspMsg") } } }),
+    };
+    operation.insert("responses".into(), json!({ "200": ok, "default": { "$ref": "#/components/responses/Error" } }));
+
+    if !route.roles.is_empty() {
+        operation.insert("x-roles".into(), json!(route.roles));
+        let security = if route.path == ROUTE_METRICS || route.path == C_ROUTE_ANALYST_EXT_KEY_EXCHANGE {
+            json!([{ "mutualTLS": [] }])
+        } else {
+            json!([{ "mutualTLS": [], "hmac": [], "counter": [] }])
+        };
+        operation.insert("security".into(), security);
+    }
+    JsonValue::Object(operation)
+}
+
+/// Schemas of the message types
+fn schemas() -> JsonValue {
+    let hex = json!({ "type": "string", "description": "Hex encoded" });
+    let phases = json!(["setup", "enrollment", "collection", "computing", "results_published", "closed"]);
+    json!({
+        "RspMsg": { "type": "object", "required": ["success", "message", "content"], "properties": {
+            "success": { "type": "boolean" }, "message": { "type": "string" }, "content": {} } },
+        "ErrorMsg": { "type": "object", "required": ["code", "message"], "properties": {
+            "code": { "type": "string", "enum": ["BAD_REQUEST", "NOT_AUTHENTICATED", "CERTIFICATE_REVOKED", "PERMISSION_DENIED", "COMPANY_NOT_ENROLLED",
+                "COMPANY_NOT_REGISTERED", "BENCHMARK_NOT_FOUND", "NOT_FOUND", "WRONG_PHASE", "CONFLICT", "INPUT_MISSING_VARIABLES", "ALGORITHM_CYCLE",
+                "INVALID_ALGORITHM", "INVALID_FORMAT", "PAYLOAD_TOO_LARGE", "UNSUPPORTED_MEDIA_TYPE", "TOO_MANY_REQUESTS", "INTERNAL"] },
+            "message": { "type": "string" },
+            "phase": { "type": "string", "enum": phases, "description": "WRONG_PHASE: current phase" },
+            "variables": { "type": "array", "items": { "type": "string" }, "description": "INPUT_MISSING_VARIABLES: missing variables" },
+            "cycle": { "type": "array", "items": { "type": "string" }, "description": "ALGORITHM_CYCLE: operations of the cycle" },
+            "retry_after": { "type": "integer", "description": "TOO_MANY_REQUESTS: seconds until a retry (also the Retry-After header)" } } },
+        "String": { "type": "string" },
+        "BenchmarkID": { "type": "integer", "format": "uint64" },
+        "CompanyID": { "type": "integer", "format": "uint128", "description": "UUID of the company as number" },
+        "BenchmarkPhase": { "type": "string", "enum": phases },
+        "BenchmarkingConfig": { "type": "object", "description": "Configuration of a benchmark (see the server configuration format)" },
+        "Benchmarks": { "type": "object", "additionalProperties": schema_ref("BenchmarkingConfig"), "description": "Configuration by benchmark ID" },
+        "Input": { "type": "object", "description": "Input variables of a company" },
+        "Algorithm": { "type": "object", "description": "Operations of the algorithms" },
+        "CompanyStatus": { "type": "object", "description": "Registration and participation of a company" },
+        "InvalidInputs": { "type": "object", "additionalProperties": { "type": "array", "items": { "type": "string" } }, "description": "Missing variables by company ID" },
+        "AuditLog": { "type": "array", "items": { "type": "object", "properties": {
+            "sequence": { "type": "integer" }, "timestamp": { "type": "string", "format": "date-time" },
+            "actor": { "type": "object", "properties": { "role": { "type": "string" }, "fingerprint": hex } },
+            "action": { "type": "string" }, "benchmark": { "type": ["integer", "null"] }, "company": { "type": ["string", "null"], "format": "uuid" },
+            "detail": { "type": ["string", "null"] }, "previous_hash": hex, "hash": hex } } },
+        "AttestationEvidence": { "type": "object", "properties": {
+            "attester": { "type": "string" }, "measurement": hex, "tls_certificate_hash": hex,
+            "algorithm_hashes": { "type": "object", "additionalProperties": hex }, "nonce": hex, "attestation_key": hex, "signature": hex } },
+        "ServerStatus": { "type": "object", "properties": {
+            "is_setup": { "type": "boolean" }, "evidence": schema_ref("AttestationEvidence"), "benchmarks": schema_ref("Benchmarks") } },
+        "KeyExchangeOffer": { "type": "object", "required": ["exchange_key"], "properties": { "exchange_key": hex } },
+        "AnalystSetupMsg": { "type": "object", "required": ["exchange_key", "public_key", "encrypted_analyst_hmac"], "properties": {
+            "exchange_key": hex, "public_key": hex, "encrypted_analyst_hmac": hex } },
+        "CompanySetupMsg": { "type": "object", "required": ["exchange_key", "public_key", "encrypted_company_hmac", "result_public_key"], "properties": {
+            "exchange_key": hex, "public_key": hex, "encrypted_company_hmac": hex, "result_public_key": hex } },
+        "CompanyConfirmMsg": { "type": "object", "required": ["algorithm_hash"], "properties": { "algorithm_hash": hex } },
+        "AnalystBenchmarkingMsg": { "type": "object", "properties": { "selected_kpis": { "type": ["array", "null"], "items": { "type": "string" } } } },
+        "AnalystEventMsg": { "type": "object", "required": ["event"], "properties": { "event": { "type": "string" } } },
+        "AnalystPhaseMsg": { "type": "object", "required": ["phase"], "properties": { "phase": schema_ref("BenchmarkPhase") } },
+        "SealedResults": { "type": "object", "required": ["public_key", "sealed_output"], "properties": { "public_key": hex, "sealed_output": hex } },
+        "AlgorithmInfo": { "type": "object", "properties": { "version": { "type": "integer" }, "hash": hex, "algorithm": schema_ref("Algorithm") } },
+        "AlgorithmsMsg": { "type": "object", "properties": {
+            "active": { "oneOf": [schema_ref("AlgorithmInfo"), { "type": "null" }] }, "pending": { "oneOf": [schema_ref("AlgorithmInfo"), { "type": "null" }] } } },
+        "ServerEvent": { "type": "object", "required": ["kind"], "description": "JSON in the data field, the event field holds the type and the id field the event ID", "properties": {
+            "kind": { "type": "string", "enum": ["progress", "benchmarking_success", "evaluation", "participant_enrolled", "all_participants_enrolled",
+                "company_revoked", "analyst_message", "phase_changed", "algorithms_proposed", "algorithms_activated", "inputs_wiped",
+                "results_ready", "results_expired", "input_invalid", "computation_failed"] } } },
+    })
+}
+
+/// The OpenAPI document of the server
+pub fn document() -> JsonValue {
+    let mut paths = JsonMap::new();
+    for route in routes() {
+        let path = paths.entry(route.path.clone()).or_insert_with(|| json!({}));
+        path[route.method] = operation(&route);
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "TEEBench Benchmarking Application",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": format!("API version {}. Failed requests answer with an ErrorMsg, its code decides about the HTTP status.", API_VERSION),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "responses": {
+                "Error": { "description": "Failed request", "content": { "application/json": { "schema": schema_ref("ErrorMsg") } } },
+            },
+            "securitySchemes": {
+                "mutualTLS": { "type": "mutualTLS", "description": "Client certificate of the analyst team or a company" },
+                "hmac": { "type": "apiKey", "in": "header", "name": X_HMAC_FIELD, "description": "HMAC-SHA256 over method, path, counter and body (hex)" },
+                "counter": { "type": "apiKey", "in": "header", "name": X_COUNTER_FIELD, "description": "Counter that increases with every request" },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Values of the route constants of `types::consts` (literals and `concatcp!`)
+    fn route_constants() -> HashMap<String, String> {
+        let expressions: HashMap<&str, &str> = include_str!("../../types/src/consts.rs")
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("pub const ")?.split_once(": &str = "))
+            .map(|(name, expression)| (name, expression.trim_end_matches(';')))
+            .collect();
+
+        fn resolve(expression: &str, expressions: &HashMap<&str, &str>) -> String {
+            let expression = expression.trim();
+            if let Some(arguments) = expression.strip_prefix("concatcp!(").and_then(|rest| rest.strip_suffix(')')) {
+                arguments.split(',').map(|argument| resolve(argument, expressions)).collect()
+            } else if let Some(literal) = expression.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+                literal.to_string()
+            } else {
+                resolve(expressions.get(expression).unwrap_or_else(|| panic!("Unknown constant {}", expression)), expressions)
+            }
+        }
+
+        expressions.keys().filter(|name| name.contains("ROUTE")).map(|name| (name.to_string(), resolve(name, &expressions))).collect()
+    }
+
+    /// Path of a resource within a scope (like actix joins them)
+    fn join(prefix: &str, path: &str) -> String {
+        if prefix.is_empty() || prefix.ends_with('/') || path.starts_with('/') {
+            format!("{}{}", prefix, path)
+        } else {
+            format!("{}/{}", prefix, path)
+        }
+    }
+
+    /// Resources of `https_routes` with their methods (`None` for resources that take every method)
+    fn registered_routes() -> Vec<(String, Option<String>)> {
+        let constants = route_constants();
+        let source = include_str!("routes.rs");
+        let start = source.find("pub fn https_routes").unwrap();
+        let end = source[start..].find("\n}").map_or(source.len(), |end| start + end);
+        // Comments may hold parentheses as well
+        let code: String = source[start..end].lines().map(|line| line.split("//").next().unwrap()).collect();
+        let argument = |rest: &str| -> String {
+            let name = &rest[..rest.find(')').unwrap()];
+            constants.get(name.trim()).unwrap_or_else(|| panic!("Unknown route constant {}", name)).clone()
+        };
+
+        let mut routes = Vec::new();
+        let mut depth = 0;
+        let mut scopes: Vec<(usize, String)> = Vec::new();
+        let mut resource: Option<(usize, String)> = None;
+        for (i, c) in code.char_indices() {
+            let rest = &code[i..];
+            let prefix = scopes.last().map_or("", |(_, prefix)| prefix.as_str()).to_string();
+            if let Some(rest) = rest.strip_prefix("web::scope(") {
+                scopes.push((depth, join(&prefix, &argument(rest))));
+            } else if let Some(rest) = rest.strip_prefix("web::resource(") {
+                resource = Some((depth, join(&prefix, &argument(rest))));
+            } else if let Some((resource_depth, path)) = &resource {
+                if let Some(method) = ["get", "post", "put", "delete"].into_iter().find(|method| rest.starts_with(&format!("web::{}()", method))) {
+                    routes.push((path.clone(), Some(method.to_string())));
+                } else if rest.starts_with(".to(") && depth == *resource_depth {
+                    routes.push((path.clone(), None));
+                }
+            }
+
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    scopes.retain(|(scope_depth, _)| *scope_depth <= depth);
+                    if matches!(resource, Some((resource_depth, _)) if resource_depth > depth) {
+                        resource = None;
+                    }
+                }
+                _ => (),
+            }
+        }
+        routes
+    }
+
+    #[test]
+    pub fn registered_routes_are_documented() {
+        let document = document();
+        let paths = document["paths"].as_object().unwrap();
+        let registered = registered_routes();
+        assert!(registered.len() > 30, "Routes were not found: {:?}", registered);
+
+        for (path, method) in &registered {
+            // The website itself is not part of the API
+            if path == ROUTE_INDEX || path == ROUTE_FAVICON {
+                continue;
+            }
+            let operations = paths.get(path).unwrap_or_else(|| panic!("{} is not documented", path));
+            if let Some(method) = method {
+                assert!(operations.get(method).is_some(), "{} {} is not documented", method, path);
+            }
+        }
+
+        // Documented routes are registered as well
+        for (path, operations) in paths {
+            for method in operations.as_object().unwrap().keys() {
+                assert!(
+                    registered.iter().any(|(registered_path, registered_method)| registered_path == path && registered_method.iter().all(|registered_method| registered_method == method)),
+                    "{} {} is not registered",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn schemas_of_the_routes_exist() {
+        let document = document();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+
+        // Every reference points to a schema
+        let text = document.to_string();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(schemas.contains_key(name), "Schema {} is missing", name);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! **State Persistence**
+//!
+//! The state of the [`BenchmarkingServer`] is snapshotted to disk such
+//! that a restart during a (long) data collection does not lose the
+//! companies, their certificates, inputs and results or the algorithms.
+//!
+//! Snapshots are encrypted with AES-256-GCM. The key originates from
+//! one of two sources ([`SealingMode`]):
+//!
+//! - `enclave`: The key is provisioned to the enclave only after a
+//!   successful attestation (i.e. injected by the SCONE CAS into the
+//!   configured path). It is never generated by the server itself.
+//! - `file`: Stand-in for builds without SGX. The key is generated on
+//!   first use and stored as plain file. This does **not** protect the
+//!   snapshot against the host!
+//!
+//! [`BenchmarkingServer`]: crate::server::BenchmarkingServer
+
+use std::{
+    fmt,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+};
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use types::{entity::Analyst, message::response::BenchmarkID};
+
+use crate::{audit::AuditLog, instance::BenchmarkInstance};
+
+/// Associated data of all snapshots (format version)
+const SNAPSHOT_AAD: &[u8] = b"teebench-snapshot-v1";
+/// Length of the snapshot key in bytes
+const KEY_LEN: usize = 32;
+
+/// Origin of the snapshot encryption key
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SealingMode {
+    /// Key is provisioned to the attested enclave
+    Enclave,
+    /// Key is stored in a local file (non-SGX stand-in)
+    #[default]
+    File,
+}
+
+/// Serializable state of the server
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(default)]
+    pub root_ca_certificate: Option<Vec<u8>>,
+    #[serde(default)]
+    pub analysts: Vec<Analyst>,
+    #[serde(default)]
+    pub benchmarks: Vec<(BenchmarkID, BenchmarkInstance)>,
+    #[serde(default)]
+    pub next_benchmark_id: BenchmarkID,
+    /// Serial numbers of revoked client certificates
+    #[serde(default)]
+    pub revoked_serials: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub audit_log: AuditLog,
+}
+
+/// Encrypted snapshot storage
+///
+/// Once closed (on a regular shutdown) nothing is stored anymore, since
+/// requests that are still processed would write a fresh snapshot.
+#[derive(Clone)]
+pub struct Persistence {
+    path: PathBuf,
+    key: Arc<LessSafeKey>,
+    closed: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for Persistence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Persistence").field("path", &self.path).finish()
+    }
+}
+
+impl Persistence {
+    /// Create the snapshot storage at `path` with a key of the given origin
+    pub fn new(path: &Path, mode: SealingMode, key_path: &Path) -> Result<Self> {
+        let key = load_key(mode, key_path)?;
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid snapshot key"))?;
+
+        Ok(Persistence { path: path.to_path_buf(), key: Arc::new(LessSafeKey::new(key)), closed: Arc::new(AtomicBool::new(false)) })
+    }
+
+    /// Encrypt and write the snapshot (atomically replaces the previous one)
+    pub fn store(&self, snapshot: &Snapshot) -> Result<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut data = serde_json::to_vec(snapshot)?;
+
+        let mut nonce = [0_u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).map_err(|_| Error::new(ErrorKind::Other, "No randomness available"))?;
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(SNAPSHOT_AAD), &mut data)
+            .map_err(|_| Error::new(ErrorKind::Other, "Could not encrypt snapshot"))?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, [&nonce[..], &data].concat())?;
+        fs::rename(tmp_path, &self.path)
+    }
+
+    /// Read and decrypt the last snapshot if there is one
+    pub fn restore(&self) -> Result<Option<Snapshot>> {
+        let mut data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        if data.len() < NONCE_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, "Snapshot is truncated"));
+        }
+        let mut ciphertext = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data).map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid snapshot nonce"))?;
+        let plaintext = self.key
+            .open_in_place(nonce, Aad::from(SNAPSHOT_AAD), &mut ciphertext)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Snapshot could not be decrypted (wrong key or tampered)"))?;
+
+        Ok(Some(serde_json::from_slice(plaintext)?))
+    }
+
+    /// Remove the snapshot and store none anymore (on a regular shutdown)
+    pub fn close(&self) -> Result<()> {
+        self.closed.store(true, Ordering::SeqCst);
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Load the hex encoded snapshot key
+///
+/// Only the file stand-in generates a missing key, a provisioned
+/// enclave key has to be present.
+fn load_key(mode: SealingMode, key_path: &Path) -> Result<Vec<u8>> {
+    let encoded = match (fs::read_to_string(key_path), mode) {
+        (Ok(encoded), _) => encoded,
+        (Err(err), SealingMode::File) if err.kind() == ErrorKind::NotFound => {
+            log::warn!("[WARN ADMIN] Generating unsealed snapshot key at {} - only use this without SGX!", key_path.display());
+            let mut key = [0_u8; KEY_LEN];
+            SystemRandom::new().fill(&mut key).map_err(|_| Error::new(ErrorKind::Other, "No randomness available"))?;
+            let encoded = hex::encode(key);
+            fs::write(key_path, &encoded)?;
+            encoded
+        }
+        (Err(err), _) => return Err(err),
+    };
+
+    let key = hex::decode(encoded.trim()).map_err(|_| Error::new(ErrorKind::InvalidData, "Snapshot key is not hex encoded"))?;
+    if key.len() != KEY_LEN {
+        return Err(Error::new(ErrorKind::InvalidData, "Snapshot key has to be 32 hex encoded bytes"));
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(next_benchmark_id: BenchmarkID) -> Snapshot {
+        Snapshot { root_ca_certificate: None, analysts: Vec::new(), benchmarks: Vec::new(), next_benchmark_id, revoked_serials: Vec::new(), audit_log: AuditLog::default() }
+    }
+
+    #[test]
+    pub fn shutdown_is_not_restored_on_restart() {
+        let dir = std::env::temp_dir().join(format!("teebench-persistence-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (path, key_path) = (dir.join("snapshot"), dir.join("snapshot.key"));
+
+        let persistence = Persistence::new(&path, SealingMode::File, &key_path).unwrap();
+        persistence.store(&snapshot(3)).unwrap();
+        assert_eq!(Persistence::new(&path, SealingMode::File, &key_path).unwrap().restore().unwrap().unwrap().next_benchmark_id, 3);
+
+        // Requests that finish after the shutdown persist the state again
+        persistence.close().unwrap();
+        persistence.clone().store(&snapshot(4)).unwrap();
+
+        let restarted = Persistence::new(&path, SealingMode::File, &key_path).unwrap();
+        assert!(restarted.restore().unwrap().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! **Certificate Revocation**
+//!
+//! Client certificates are revoked by their serial number. The serial
+//! numbers originate from the CRL of the analyst's root CA (loaded on
+//! the TLS setup and reloaded periodically) and from companies that the
+//! analyst removed from a benchmark. Once the CRL expired without being
+//! replaced, the status of certificates is unknown and all count as revoked.
+//!
+//! The [`RevocationList`] is shared between the TLS verifier, which
+//! rejects revoked certificates during the handshake, and the request
+//! verification, which covers connections that were established before
+//! the revocation.
+
+use std::{collections::HashSet, sync::Arc, time::SystemTime};
+
+use parking_lot::RwLock;
+use rustls::{
+    server::{ClientCertVerified, ClientCertVerifier},
+    Certificate, DistinguishedNames, Error,
+};
+use server_util::crl::{certificate_serial, Crl};
+
+/// Serial numbers of revoked client certificates
+#[derive(Debug, Clone, Default)]
+pub struct RevocationList {
+    serials: Arc<RwLock<HashSet<Vec<u8>>>>,
+    /// End of the validity of the loaded CRL (if it states one)
+    crl_next_update: Arc<RwLock<Option<SystemTime>>>,
+}
+
+impl RevocationList {
+    /// Revoke the certificate, returns false if it has no readable serial number
+    pub fn revoke(&self, certificate: &Certificate) -> bool {
+        match certificate_serial(certificate) {
+            Some(serial) => {
+                self.serials.write().insert(serial);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn revoke_serials(&self, serials: impl IntoIterator<Item = Vec<u8>>) {
+        self.serials.write().extend(serials);
+    }
+
+    /// Revoke the serials of a (newer) CRL, which is valid until its next update
+    pub fn load_crl(&self, crl: Crl) {
+        self.revoke_serials(crl.revoked_serials);
+        *self.crl_next_update.write() = crl.next_update;
+    }
+
+    /// Certificates without a readable serial number or with an expired CRL count as revoked
+    pub fn is_revoked(&self, certificate: &Certificate) -> bool {
+        if matches!(*self.crl_next_update.read(), Some(next_update) if next_update <= SystemTime::now()) {
+            return true;
+        }
+        match certificate_serial(certificate) {
+            Some(serial) => self.serials.read().contains(&serial),
+            None => true,
+        }
+    }
+
+    pub fn serials(&self) -> Vec<Vec<u8>> {
+        self.serials.read().iter().cloned().collect()
+    }
+}
+
+/// Client certificate verifier that additionally rejects revoked certificates
+pub struct RevocationCheckingVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    revocation_list: RevocationList,
+}
+
+impl RevocationCheckingVerifier {
+    pub fn new(inner: Arc<dyn ClientCertVerifier>, revocation_list: RevocationList) -> Self {
+        RevocationCheckingVerifier { inner, revocation_list }
+    }
+}
+
+impl ClientCertVerifier for RevocationCheckingVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        self.inner.client_auth_root_subjects()
+    }
+
+    fn verify_client_cert(&self, end_entity: &Certificate, intermediates: &[Certificate], now: SystemTime) -> Result<ClientCertVerified, Error> {
+        let verified = self.inner.verify_client_cert(end_entity, intermediates, now)?;
+        if self.revocation_list.is_revoked(end_entity) {
+            log::warn!("Rejected a revoked client certificate!");
+            return Err(Error::InvalidCertificateData("Certificate is revoked".to_string()));
+        }
+        Ok(verified)
+    }
+}
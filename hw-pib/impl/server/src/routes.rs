@@ -5,8 +5,14 @@ use crate::{api::{
         modify_benchmark_config,
         company_enroll,
         get_company_status,
+        revoke_company,
         broadcast_event,
-        start_benchmark
+        start_benchmark,
+        get_benchmarks,
+        create_benchmark,
+        get_phase,
+        change_phase,
+        analyst_event_stream,
     },
     company::{
         register,
@@ -14,20 +20,24 @@ use crate::{api::{
         get_input_data,
         modify_input_data,
         get_results,
+        confirm_results,
         enroll_event_stream,
+        company_event_stream,
+        get_company_algorithms,
+        confirm_algorithms,
     },
     analyst::{
         upload_algorithms,
         get_algorithms,
         modify_algorithms,
     },
-    server_admin::{ setup_config, check_config, shutdown},
-    index::{ index, favicon, whoami },
-}, middleware::request_verifier::VerifyRequest
+    server_admin::{ setup_config, check_config, offer_key_exchange, exchange_analyst_key, get_audit_log, get_metrics, shutdown},
+    index::{ index, favicon, whoami, openapi, live, ready },
+}, middleware::{request_verifier::{VerifyRequest, Permissions}, rate_limiter::LimitRate}
 };
 
 use actix_web::web;
-use types::consts::{ROUTE_FAVICON, ROUTE_SETUP, ROUTE_WHOAMI, ROUTE_API, ROUTE_INDEX, ROUTE_ATTEST, ROUTE_SHUTDOWN, ROUTE_COMPANY, ROUTE_ENROLL_EVENTS, S_ROUTE_COMPANY_EXT_INPUT_DATA_ID, S_ROUTE_COMPANY_EXT_REGISTER_ID, S_ROUTE_COMPANY_EXT_RESULTS_ID, ROUTE_ANALYST, ROUTE_ANALYST_EXT_BENCHMARK_CONFIG, S_ROUTE_ANALYST_EXT_COMPANY_STATUS, ROUTE_ANALYST_EXT_ENROLL_COMPANY, ROUTE_ANALYST_EXT_ALGORITHMS, ROUTE_ANALYST_EXT_BENCHMARK, ROUTE_ANALYST_EXT_EVENT};
+use types::consts::{ROUTE_FAVICON, ROUTE_SETUP, ROUTE_WHOAMI, ROUTE_API, ROUTE_INDEX, ROUTE_ATTEST, ROUTE_KEY_EXCHANGE, ROUTE_ANALYST_EXT_KEY_EXCHANGE, ROUTE_ANALYST_EXT_AUDIT_LOG, ROUTE_SHUTDOWN, S_ROUTE_BENCHMARK_ID, ROUTE_ANALYST_EXT_BENCHMARKS, ROUTE_PHASE, ROUTE_ANALYST_EXT_PHASE, ROUTE_COMPANY, ROUTE_ENROLL_EVENTS, ROUTE_COMPANY_EXT_INPUT_DATA, ROUTE_COMPANY_EXT_REGISTER, ROUTE_COMPANY_EXT_RESULTS, ROUTE_COMPANY_EXT_ALGORITHMS, ROUTE_COMPANY_EXT_EVENTS, ROUTE_ANALYST, ROUTE_ANALYST_EXT_BENCHMARK_CONFIG, S_ROUTE_ANALYST_EXT_COMPANY_STATUS, ROUTE_ANALYST_EXT_ENROLL_COMPANY, ROUTE_ANALYST_EXT_ALGORITHMS, ROUTE_ANALYST_EXT_BENCHMARK, ROUTE_ANALYST_EXT_EVENT, ROUTE_ANALYST_EXT_EVENTS, ROUTE_OPENAPI, ROUTE_METRICS, ROUTE_HEALTH_LIVE, ROUTE_HEALTH_READY};
 
 ///
 /// NOTE: GET Routes are used for debugging purposes and will be disabled
@@ -43,6 +53,9 @@ pub fn http_routes(app: &mut web::ServiceConfig) {
         .service(web::resource(ROUTE_INDEX).to(index))
         // For participants to get their certificate which they use and their connection
         .service(web::resource(ROUTE_WHOAMI).to(whoami))
+        // Probes of orchestrators (ready only after the setup)
+        .service(web::resource(ROUTE_HEALTH_LIVE).route(web::get().to(live)))
+        .service(web::resource(ROUTE_HEALTH_READY).route(web::get().to(ready)))
         // API access
         .service(web::scope(ROUTE_API)
             // server setup route
@@ -53,6 +66,10 @@ pub fn http_routes(app: &mut web::ServiceConfig) {
             .service(web::resource(ROUTE_ATTEST)
                 .route(web::get().to(check_config))
             )
+            // description of the API
+            .service(web::resource(ROUTE_OPENAPI)
+                .route(web::get().to(openapi))
+            )
         );
 }
 
@@ -65,8 +82,23 @@ pub fn https_routes(app: &mut web::ServiceConfig) {
         .service(web::resource(ROUTE_INDEX).to(index))
         // For participants to get their certificate which they use and their connection
         .service(web::resource(ROUTE_WHOAMI).to(whoami))
+        // Probes of orchestrators
+        .service(web::resource(ROUTE_HEALTH_LIVE).route(web::get().to(live)))
+        .service(web::resource(ROUTE_HEALTH_READY).route(web::get().to(ready)))
+        // Metrics of the server
+        .service(web::resource(ROUTE_METRICS)
+            //
+            // Request counts, latencies, participants and computation
+            // durations reveal no inputs, but are only for the analyst team.
+            // Scrapers cannot sign their requests, the certificate suffices.
+            //
+            .wrap(VerifyRequest::permit(Permissions::ADMINISTRATION).certificate_only())
+            .route(web::get().to(get_metrics))
+        )
         // API access
         .service(web::scope(ROUTE_API)
+            // Throttle repeated requests of every identity
+            .wrap(LimitRate)
             // server setup routes
             .service(web::resource(ROUTE_SETUP)
                 //
@@ -78,7 +110,7 @@ pub fn https_routes(app: &mut web::ServiceConfig) {
                 // **gets only the ability to change but not to set**
                 // the config later on.
                 // 
-                .wrap(VerifyRequest::verify_analyst())
+                .wrap(VerifyRequest::permit(Permissions::OWNER))
                 .route(web::post().to(setup_config))
             )
             // Server attestation route
@@ -89,100 +121,188 @@ pub fn https_routes(app: &mut web::ServiceConfig) {
                 // 
                 .route(web::get().to(check_config))
             )
+            // API description route
+            .service(web::resource(ROUTE_OPENAPI)
+                //
+                // OpenAPI description of all routes and messages, so that
+                // integrations can generate their clients.
+                //
+                .route(web::get().to(openapi))
+            )
+            // Key exchange route
+            .service(web::resource(ROUTE_KEY_EXCHANGE)
+                //
+                // Participants get a key of the server to which they
+                // encrypt their HMAC key (only used once).
+                //
+                .route(web::get().to(offer_key_exchange))
+            )
             .service(web::resource(ROUTE_SHUTDOWN)
                 //
-                // Only the analyst has access to shutdown the server
-                // Thus the server verifies requests to be from him.
+                // Only the owner of the server has access to shutdown the
+                // server. Thus the server verifies requests to be from him.
                 //
-                .wrap(VerifyRequest::verify_analyst())
+                .wrap(VerifyRequest::permit(Permissions::OWNER))
                 .route(web::post().to(shutdown))
             )
-            .service(web::scope(ROUTE_COMPANY)
+            .service(web::scope(ROUTE_ANALYST)
                 //
-                // Company paths: These paths are for companies
-                // to manage themselves w.r.t. their data, enrollment
-                // and results. The access to these routes is company
-                // exclusive (by ID).
-                // 
-                .service(web::resource(S_ROUTE_COMPANY_EXT_REGISTER_ID)
-                    //
-                    // Company registration with Certificate for further communication
-                    // This route is not secured as the setup first extracts the peers
-                    // TLS certificate from the request.
-                    //
-                    .route(web::post().to(register))
+                // Benchmark management: the analysts create further
+                // benchmarks next to the one of the setup config.
+                //
+                .service(web::resource(ROUTE_ANALYST_EXT_BENCHMARKS)
+                    .wrap(VerifyRequest::permit(Permissions::ADMINISTRATION))
+                    .route(web::post().to(create_benchmark))
+                    .route(web::get().to(get_benchmarks))
                 )
-                // !TODO! [WARN] PLEASE change to header ID or decide on this
-                .service(web::resource(S_ROUTE_COMPANY_EXT_INPUT_DATA_ID)
+                .service(web::resource(ROUTE_ANALYST_EXT_KEY_EXCHANGE)
                     //
-                    // Company data upload and change data are inherently
-                    // required to be secured by request verification.
+                    // Every team member sets its HMAC key once after the setup
                     //
-                    .wrap(VerifyRequest::verify_company())
-                    .route(web::post().to(upload_input_data))
-                    .route(web::put().to(modify_input_data))
-                    .route(web::get().to(get_input_data))
+                    .wrap(VerifyRequest::permit(Permissions::ANALYST_TEAM))
+                    .route(web::post().to(exchange_analyst_key))
                 )
-                .service(web::resource(S_ROUTE_COMPANY_EXT_RESULTS_ID)
+                .service(web::resource(ROUTE_ANALYST_EXT_AUDIT_LOG)
                     //
-                    // Similarly: getting company results for given inputs.
+                    // Export of the hash-chained audit log (also for auditors)
                     //
-                    .wrap(VerifyRequest::verify_company())
-                    .route(web::get().to(get_results))
+                    .wrap(VerifyRequest::permit(Permissions::ADMINISTRATION))
+                    .route(web::get().to(get_audit_log))
                 )
             )
-            .service(web::resource(ROUTE_ENROLL_EVENTS)
+            .service(web::scope(S_ROUTE_BENCHMARK_ID)
                 //
-                // Registration for company updates on benchmarking
-                // Event path: This path is "publicly" available (i.e.
-                // to all participants) and dispatches the status of 
-                // the server (i.e. the benchmarking progress).
+                // Benchmark paths: All following routes address one
+                // benchmark by its ID. Benchmarks are independent, i.e.
+                // they have their own algorithms, companies and results.
                 //
-                .route(web::get().to(enroll_event_stream))
-            )
-            .service(web::scope(ROUTE_ANALYST)
-                //
-                // analyst configuration, registration and benchmark
-                //    
-                .wrap(VerifyRequest::verify_analyst())
-                .service(web::resource(ROUTE_ANALYST_EXT_BENCHMARK_CONFIG)
+                .service(web::scope(ROUTE_COMPANY)
                     //
-                    // config for k-anonymity or data to provide to companies
-                    //
-                    .route(web::put().to(modify_benchmark_config))
-                    .route(web::get().to(get_benchmark_config))
+                    // Company paths: These paths are for companies
+                    // to manage themselves w.r.t. their data, enrollment
+                    // and results. The access to these routes is company
+                    // exclusive (by the UUID in the certificate).
+                    // 
+                    .service(web::resource(ROUTE_COMPANY_EXT_REGISTER)
+                        //
+                        // Company registration with Certificate and HMAC key for further
+                        // communication. This route is not secured as the setup first extracts
+                        // the peers TLS certificate from the request.
+                        //
+                        .route(web::post().to(register))
+                    )
+                    .service(web::resource(ROUTE_COMPANY_EXT_INPUT_DATA)
+                        //
+                        // Company data upload and change data are inherently
+                        // required to be secured by request verification.
+                        //
+                        .wrap(VerifyRequest::permit(Permissions::COMPANY))
+                        .route(web::post().to(upload_input_data))
+                        .route(web::put().to(modify_input_data))
+                        .route(web::get().to(get_input_data))
+                    )
+                    .service(web::resource(ROUTE_COMPANY_EXT_RESULTS)
+                        //
+                        // Similarly: getting company results for given inputs
+                        // (sealed to the company) and confirming their download.
+                        //
+                        .wrap(VerifyRequest::permit(Permissions::COMPANY))
+                        .route(web::get().to(get_results))
+                        .route(web::delete().to(confirm_results))
+                    )
+                    .service(web::resource(ROUTE_COMPANY_EXT_ALGORITHMS)
+                        //
+                        // Transparency on the algorithms that are run on the
+                        // inputs and consent to their modification.
+                        //
+                        .wrap(VerifyRequest::permit(Permissions::COMPANY))
+                        .route(web::get().to(get_company_algorithms))
+                        .route(web::post().to(confirm_algorithms))
+                    )
+                    .service(web::resource(ROUTE_COMPANY_EXT_EVENTS)
+                        //
+                        // Private event stream: the public events together
+                        // with the ones that concern this company only.
+                        //
+                        .wrap(VerifyRequest::permit(Permissions::COMPANY))
+                        .route(web::get().to(company_event_stream))
+                    )
                 )
-                .service(web::resource(S_ROUTE_ANALYST_EXT_COMPANY_STATUS)
+                .service(web::resource(ROUTE_ENROLL_EVENTS)
                     //
-                    // Get company status (in case of k < required k)
+                    // Registration for company updates on benchmarking
+                    // Event path: This path is "publicly" available (i.e.
+                    // to all participants) and dispatches the status of 
+                    // the server (i.e. the benchmarking progress). Events
+                    // about single companies are only sent privately.
                     //
-                    .route(web::get().to(get_company_status))
+                    .route(web::get().to(enroll_event_stream))
                 )
-                .service(web::resource(ROUTE_ANALYST_EXT_ENROLL_COMPANY)
+                .service(web::resource(ROUTE_PHASE)
                     //
-                    // registration for companies (not already having an id)
+                    // Lifecycle phase of the benchmark, which decides
+                    // about the routes that are currently usable.
                     //
-                    .route(web::post().to(company_enroll))
+                    .route(web::get().to(get_phase))
                 )
-                .service(web::resource(ROUTE_ANALYST_EXT_ALGORITHMS)
-                    // 
-                    // Algorithm upload and modification
-                    // 
-                    .route(web::post().to(upload_algorithms))
-                    .route(web::put().to(modify_algorithms))
-                    .route(web::get().to(get_algorithms))
-                )
-                .service(web::resource(ROUTE_ANALYST_EXT_BENCHMARK)
-                    //
-                    // start benchmarking → server sends SSE as status messages
+                .service(web::scope(ROUTE_ANALYST)
                     //
-                    .route(web::post().to(start_benchmark))
-                )
-                .service(web::resource(ROUTE_ANALYST_EXT_EVENT)
+                    // analyst configuration, registration and benchmark
+                    // (auditors only read the configuration and status)
                     //    
-                    // Analyst custom events
-                    //
-                    .route(web::post().to(broadcast_event))
+                    .wrap(VerifyRequest::permit(Permissions::ADMINISTRATION))
+                    .service(web::resource(ROUTE_ANALYST_EXT_BENCHMARK_CONFIG)
+                        //
+                        // config for k-anonymity or data to provide to companies
+                        //
+                        .route(web::put().to(modify_benchmark_config))
+                        .route(web::get().to(get_benchmark_config))
+                    )
+                    .service(web::resource(S_ROUTE_ANALYST_EXT_COMPANY_STATUS)
+                        //
+                        // Get company status (in case of k < required k) or remove the company
+                        //
+                        .route(web::get().to(get_company_status))
+                        .route(web::delete().to(revoke_company))
+                    )
+                    .service(web::resource(ROUTE_ANALYST_EXT_ENROLL_COMPANY)
+                        //
+                        // registration for companies (not already having an id)
+                        //
+                        .route(web::post().to(company_enroll))
+                    )
+                    .service(web::resource(ROUTE_ANALYST_EXT_ALGORITHMS)
+                        // 
+                        // Algorithm upload and modification
+                        // 
+                        .route(web::post().to(upload_algorithms))
+                        .route(web::put().to(modify_algorithms))
+                        .route(web::get().to(get_algorithms))
+                    )
+                    .service(web::resource(ROUTE_ANALYST_EXT_BENCHMARK)
+                        //
+                        // start benchmarking → server sends SSE as status messages
+                        //
+                        .route(web::post().to(start_benchmark))
+                    )
+                    .service(web::resource(ROUTE_ANALYST_EXT_EVENT)
+                        //    
+                        // Analyst custom events
+                        //
+                        .route(web::post().to(broadcast_event))
+                    )
+                    .service(web::resource(ROUTE_ANALYST_EXT_PHASE)
+                        //
+                        // Move the benchmark into its next phase
+                        //
+                        .route(web::put().to(change_phase))
+                    )
+                    .service(web::resource(ROUTE_ANALYST_EXT_EVENTS)
+                        //
+                        // Private event stream of the analyst team
+                        //
+                        .route(web::get().to(analyst_event_stream))
+                    )
                 )
             )
         );
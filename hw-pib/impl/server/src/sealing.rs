@@ -0,0 +1,59 @@
+//! **Sealed Results**
+//!
+//! The results of a company are never kept in plaintext: as soon as the
+//! benchmark assembled them, they are encrypted to the X25519 key that the
+//! company registered together with its HMAC key. An ephemeral key of the
+//! server agrees on a secret with the company's key, HKDF-SHA256 derives
+//! the AES-256-GCM key that encrypts the JSON encoded [`Output`]. The
+//! benchmark and company ID are authenticated as associated data.
+//!
+//! The ephemeral key is dropped right after the encryption, thus only the
+//! company can open its results (neither the server nor its snapshots).
+//! Sealed results are deleted after the company confirmed their download
+//! or once the retention time of the benchmark is over.
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf::{Salt, HKDF_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use server_util::error::ApiError;
+use types::{consts::RESULT_SEALING_INFO, message::{error::ErrorCode, response::{result_sealing_aad, BenchmarkID, CompanyID, SealedResults}}, output::Output};
+
+/// Length of an X25519 public key in bytes
+const X25519_PUBLIC_KEY_LEN: usize = 32;
+
+/// Check that the hex encoded result key of a company is an X25519 public key
+pub fn parse_result_key(public_key: &str) -> Result<String, ApiError> {
+    let key = hex::decode(public_key.trim()).map_err(|_| ApiError::from((ErrorCode::InvalidFormat, "The result key is not hex encoded!")))?;
+    if key.len() != X25519_PUBLIC_KEY_LEN {
+        return Err(ApiError::from((ErrorCode::InvalidFormat, format!("The result key has to be {} bytes long!", X25519_PUBLIC_KEY_LEN))));
+    }
+    Ok(hex::encode(key))
+}
+
+/// Encrypt the output to the hex encoded result key of a company
+pub fn seal_output(public_key: &str, benchmark_id: BenchmarkID, company_id: CompanyID, output: &Output) -> Result<SealedResults, ApiError> {
+    let company_key = hex::decode(public_key).map_err(|_| ApiError::from((ErrorCode::Internal, "The stored result key is not hex encoded!")))?;
+    let mut sealed_output = serde_json::to_vec(output).map_err(|e| ApiError::from((ErrorCode::Internal, e.to_string())))?;
+
+    let rng = SystemRandom::new();
+    let mut nonce = [0_u8; NONCE_LEN];
+    rng.fill(&mut nonce).map_err(|_| ApiError::from((ErrorCode::Internal, "No randomness available for sealing the results!")))?;
+    let private_key = EphemeralPrivateKey::generate(&X25519, &rng).map_err(|_| ApiError::from((ErrorCode::Internal, "Could not generate a sealing key!")))?;
+    let server_key = private_key.compute_public_key().map_err(|_| ApiError::from((ErrorCode::Internal, "Could not generate a sealing key!")))?;
+
+    let key = agreement::agree_ephemeral(private_key, &UnparsedPublicKey::new(&X25519, company_key), ApiError::from((ErrorCode::Internal, "Key agreement failed!")), |secret| {
+        let prk = Salt::new(HKDF_SHA256, &[]).extract(secret);
+        let okm = prk.expand(&[RESULT_SEALING_INFO], &AES_256_GCM).map_err(|_| ApiError::from((ErrorCode::Internal, "Key derivation failed!")))?;
+        Ok(LessSafeKey::new(UnboundKey::from(okm)))
+    })?;
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(result_sealing_aad(benchmark_id, company_id)), &mut sealed_output)
+        .map_err(|_| ApiError::from((ErrorCode::Internal, "The results could not be encrypted!")))?;
+
+    Ok(SealedResults {
+        public_key: hex::encode(server_key.as_ref()),
+        sealed_output: hex::encode([&nonce[..], &sealed_output].concat()),
+    })
+}
@@ -1,11 +1,18 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use benchmark::Algorithm;
+use rustls::Certificate;
+use server_util::{error::ApiError, broadcast_event::{Audience, Broadcaster}};
 
 use crate::{
+    attestation::{self, Attester, SimulatedAttester},
+    audit::{Actor, AuditAction, AuditLog},
     config::Config,
     crypto::Crypto,
+    instance::{self, BenchmarkInstance},
+    persistence::{Persistence, Snapshot},
 };
-use benchmark::Algorithm;
-use types::entity::{Analyst, Company, BenchmarkingConfig};
+use types::{consts::{EXT_PRIVATE_KEY, SERVER_CRYPTO_PATH, SERVER_ENCLAVE_CRYPTO}, entity::{Analyst, BenchmarkPhase, BenchmarkingConfig}, message::{error::ErrorCode, event::ServerEvent, response::{AttestationEvidence, BenchmarkID, CompanyID}}};
 
 
 
@@ -13,89 +20,212 @@ use types::entity::{Analyst, Company, BenchmarkingConfig};
 pub struct BenchmarkingServer {
     /// Static Server Configuration
     server_config: Config,
-    /// Benchmarking Configuration
-    benchmarking_config: BenchmarkingConfig,
-    /// Crypto configuration 
+    /// Crypto configuration
     crypto_config: Crypto,
-    /// Companies, where  the companyID Value is and the value is the DER key, the certificate and a monotonic counter
-    companies: HashMap<u128, Company>,
-    /// Analyst information
-    analyst: Option<Analyst>, 
-    /// Analyst Algorithms
-    algorithms: Option<Algorithm>,
-    // Number of uploaded files
-    active_participants: u64,
+    /// Analyst team (owner, co-analysts and auditors)
+    analysts: Vec<Analyst>,
+    /// Independent benchmarks hosted by this server
+    benchmarks: HashMap<BenchmarkID, BenchmarkInstance>,
+    /// ID for the next benchmark that is created
+    next_benchmark_id: BenchmarkID,
+    /// Encrypted snapshots of the state (if enabled)
+    persistence: Option<Persistence>,
+    /// Producer of the attestation evidence
+    attester: Arc<dyn Attester>,
+    /// Hash-chained record of all actions
+    audit_log: AuditLog,
 }
 
 impl BenchmarkingServer {
     pub fn load() -> Self {
         // Load configuration from standard path
-        let server_config = Config::load(); 
-        let benchmarking_config = BenchmarkingConfig::default();
+        let server_config = Config::load();
         let crypto_config = Crypto::load();
 
-        let companies: HashMap<u128, Company> = HashMap::new();
-        let analyst: Option<Analyst> = None;
-        let algorithms: Option<Algorithm> = None;
+        let analysts: Vec<Analyst> = Vec::new();
+        let benchmarks: HashMap<BenchmarkID, BenchmarkInstance> = HashMap::new();
 
-        let active_participants = 0_u64;
+        let persistence = if server_config.snapshot_path().is_empty() {
+            None
+        } else {
+            Some(Persistence::new(Path::new(server_config.snapshot_path()), server_config.sealing(), Path::new(server_config.sealing_key_path())).expect("[FATAL] Could not load snapshot key!"))
+        };
 
-        let benchmarking_server = BenchmarkingServer {server_config, benchmarking_config, crypto_config, companies, analyst, algorithms, active_participants  };
+        // Without SGX support only the simulated attester is available
+        let enclave_key_path = Path::new(SERVER_CRYPTO_PATH).join(SERVER_ENCLAVE_CRYPTO).with_extension(EXT_PRIVATE_KEY);
+        let attester: Arc<dyn Attester> = Arc::new(SimulatedAttester::load(&enclave_key_path).expect("[FATAL] Could not load attester!"));
+        log::warn!("[WARN ADMIN] Using the simulated attester - the evidence does not prove execution in an enclave!");
+
+        let mut benchmarking_server = BenchmarkingServer {server_config, crypto_config, analysts, benchmarks, next_benchmark_id: 0, persistence, attester, audit_log: AuditLog::default() };
+
+        // Recover the state of a previous run
+        let snapshot = benchmarking_server.persistence.as_ref().map(|persistence| persistence.restore().expect("[FATAL] Could not restore server snapshot!"));
+        if let Some(Some(snapshot)) = snapshot {
+            log::info!("Restoring server state from snapshot!");
+            benchmarking_server.restore(snapshot);
+        }
 
         benchmarking_server
     }
 
-    pub fn server_config(&self) -> &Config {
-        &self.server_config
+    /// Write an encrypted snapshot of the state (no-op if persistence is disabled)
+    pub fn persist(&self) {
+        if let Some(persistence) = &self.persistence {
+            let snapshot = Snapshot {
+                root_ca_certificate: self.crypto_config.root_ca_certificate().as_ref().map(|cert| cert.0.clone()),
+                analysts: self.analysts.clone(),
+                benchmarks: self.benchmarks.iter().map(|(id, benchmark)| (*id, benchmark.clone())).collect(),
+                next_benchmark_id: self.next_benchmark_id,
+                revoked_serials: self.crypto_config.revocation_list().serials(),
+                audit_log: self.audit_log.clone(),
+            };
+
+            if let Err(err) = persistence.store(&snapshot) {
+                log::error!("Could not persist server state: {}", err);
+            }
+        }
+    }
+
+    /// Remove the persisted state and stop persisting (on regular shutdown)
+    pub fn clear_persisted(&self) {
+        if let Some(persistence) = &self.persistence {
+            if let Err(err) = persistence.close() {
+                log::error!("Could not remove server snapshot: {}", err);
+            }
+        }
     }
 
-    pub fn benchmarking_config(&self) -> &BenchmarkingConfig {
-        &self.benchmarking_config
+    /// Apply a snapshot on the freshly loaded server
+    fn restore(&mut self, snapshot: Snapshot) {
+        // A modified log is not continued, the snapshot has to be investigated
+        assert!(snapshot.audit_log.verify(), "[FATAL] The audit log of the snapshot is not intact - it was modified!");
+
+        if let Some(root_ca) = snapshot.root_ca_certificate {
+            self.crypto_config.set_root_ca_certificate(Certificate(root_ca));
+        }
+        self.analysts = snapshot.analysts;
+        self.benchmarks = snapshot.benchmarks.into_iter().collect();
+        // A computation does not survive a restart, it has to be started again
+        self.benchmarks.values_mut().for_each(BenchmarkInstance::abort_computation);
+        self.next_benchmark_id = snapshot.next_benchmark_id;
+        self.crypto_config.revocation_list().revoke_serials(snapshot.revoked_serials);
+        self.audit_log = snapshot.audit_log;
+    }
+
+    pub fn server_config(&self) -> &Config {
+        &self.server_config
     }
 
     pub fn crypto_config(&self) -> &Crypto {
         &self.crypto_config
     }
 
-    pub fn companies(&self) -> &HashMap<u128, Company> {
-        &self.companies
+    /// Member of the analyst team that uses the certificate
+    pub fn analyst(&self, certificate: &Certificate) -> Option<&Analyst> {
+        self.analysts.iter().find(|analyst| analyst.certificate().eq(certificate))
     }
 
-    pub fn analyst(&self) -> Option<&Analyst> {
-        self.analyst.as_ref()
+    pub fn benchmarks(&self) -> &HashMap<BenchmarkID, BenchmarkInstance> {
+        &self.benchmarks
     }
 
-    pub fn algorithms(&self) -> Option<&Algorithm> {
-        self.algorithms.as_ref()
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
     }
 
-    pub fn active_participants(&self) -> u64 {
-        self.active_participants
+    /// Get a benchmark or an error if it does not exist
+    pub fn benchmark(&self, id: BenchmarkID) -> Result<&BenchmarkInstance, ApiError> {
+        self.benchmarks.get(&id).ok_or_else(|| ApiError::from((ErrorCode::BenchmarkNotFound, format!("Benchmark {} does not exist!", id))))
     }
 
-    /// Modification of benchmarking server 
+    /// Attestation evidence for the current TLS certificate and algorithms
+    pub fn attestation_evidence(&self, nonce: &str) -> Result<AttestationEvidence, ApiError> {
+        // The TLS server presents the last certificate of the chain
+        let tls_certificate = self.crypto_config.server_certificate().last().ok_or_else(|| ApiError::from((ErrorCode::Internal, "No TLS certificate present!")))?;
+        let algorithm_hashes = self.benchmarks
+            .iter()
+            .filter_map(|(id, benchmark)| benchmark.algorithms().map(|algorithms| (*id, attestation::algorithm_hash(algorithms))))
+            .collect();
 
-    pub fn set_benchmarking_config_all(&mut self, cfg: BenchmarkingConfig) {
-        self.benchmarking_config = cfg;
+        attestation::attest(self.attester.as_ref(), tls_certificate, algorithm_hashes, nonce)
     }
 
+    /// Modification of benchmarking server
+
     pub fn set_crypto_config(&mut self) -> &mut Crypto {
         &mut self.crypto_config
     }
 
-    pub fn set_companies(&mut self) -> &mut HashMap<u128, Company> {
-        &mut self.companies
+    /// Record an action of a participant in the audit log
+    pub fn audit(&mut self, actor: &Actor, action: AuditAction, benchmark: Option<BenchmarkID>, company: Option<CompanyID>, detail: Option<String>) {
+        self.audit_log.append(actor, action, benchmark, company, detail);
+    }
+
+    pub fn set_analysts(&mut self, analysts: Vec<Analyst>) {
+        self.analysts = analysts;
+    }
+
+    pub fn analyst_mut(&mut self, certificate: &Certificate) -> Option<&mut Analyst> {
+        self.analysts.iter_mut().find(|analyst| analyst.certificate().eq(certificate))
     }
 
-    pub fn set_analyst(&mut self, analyst: Analyst) {
-        self.analyst = Some(analyst);
+    /// Get a modifiable benchmark or an error if it does not exist
+    pub fn benchmark_mut(&mut self, id: BenchmarkID) -> Result<&mut BenchmarkInstance, ApiError> {
+        self.benchmarks.get_mut(&id).ok_or_else(|| ApiError::from((ErrorCode::BenchmarkNotFound, format!("Benchmark {} does not exist!", id))))
     }
 
-    pub fn set_algorithms(&mut self, algorithms: Option<Algorithm>) {
-        self.algorithms = algorithms;
+    /// Move a benchmark into its next phase and announce it on the event stream
+    pub fn set_phase(&mut self, id: BenchmarkID, phase: BenchmarkPhase, broadcaster: &Broadcaster) -> Result<(), ApiError> {
+        self.benchmark_mut(id)?.set_phase(phase)?;
+        log::info!("Benchmark {} is now in phase {}", id, phase);
+        broadcaster.send(id, &ServerEvent::PhaseChanged { phase });
+        Ok(())
     }
 
-    pub fn increment_active_participants(&mut self) {
-        self.active_participants += 1;
+    /// Replace the algorithms of a benchmark and verify the present inputs against them
+    ///
+    /// Inputs that got invalid are discarded, the affected companies are notified
+    /// to upload again. Returns the missing variables per affected company.
+    pub fn activate_algorithms(&mut self, id: BenchmarkID, algorithms: Algorithm, broadcaster: &Broadcaster) -> Result<HashMap<CompanyID, Vec<String>>, ApiError> {
+        let benchmark = self.benchmark_mut(id)?;
+        let algorithm_hash = attestation::algorithm_hash(&algorithms);
+        benchmark.set_algorithms(Some(algorithms));
+        let invalid_inputs = benchmark.revalidate_inputs();
+
+        log::info!("Benchmark {} runs algorithms {} (version {})", id, algorithm_hash, benchmark.algorithm_version());
+        broadcaster.send(id, &ServerEvent::AlgorithmsActivated { hash: algorithm_hash.clone() });
+        for (company_id, missing_vars) in invalid_inputs.iter() {
+            log::info!("Input of company {} in benchmark {} got invalid, missing: {:?}", company_id, id, missing_vars);
+            let event = ServerEvent::InputInvalid { company: *company_id, missing: missing_vars.clone() };
+            broadcaster.send_to(id, Audience::Company(*company_id), &event);
+            broadcaster.send_to(id, Audience::Analyst, &event);
+        }
+
+        Ok(invalid_inputs)
     }
-}
\ No newline at end of file
+
+    /// Delete the sealed results of all benchmarks whose retention time is over
+    ///
+    /// Returns whether any results were deleted.
+    pub fn purge_expired_results(&mut self, broadcaster: &Broadcaster) -> bool {
+        let now = instance::unix_time();
+        let mut purged = false;
+        for (id, benchmark) in self.benchmarks.iter_mut() {
+            for company_id in benchmark.purge_expired_results(now) {
+                log::info!("Results of company {} in benchmark {} expired and were deleted", company_id, id);
+                broadcaster.send_to(*id, Audience::Company(company_id), &ServerEvent::ResultsExpired { company: company_id });
+                broadcaster.send_to(*id, Audience::Analyst, &ServerEvent::ResultsExpired { company: company_id });
+                purged = true;
+            }
+        }
+        purged
+    }
+
+    /// Create a new benchmark and return its ID
+    pub fn add_benchmark(&mut self, cfg: BenchmarkingConfig) -> BenchmarkID {
+        let id = self.next_benchmark_id;
+        self.next_benchmark_id += 1;
+        self.benchmarks.insert(id, BenchmarkInstance::new(cfg));
+        id
+    }
+}
@@ -53,11 +53,15 @@ main() {
 #     a config path to the create a CSR. 
 #     Standard is a file similar to 
 #     `client_cert_ext.cnf`.
+#   - [--company_id=uuid] → The UUID that
+#     the analyst enrolled for the company.
+#     It becomes part of the subject 
+#     alternative name (urn:uuid:<uuid>).
 #   - [--verbose] → Output all set params.
 #   - [--help] → Display help text.
 
 show_help(){
-    echo -e "\nThis script generates certificates.\n - Either a CA certificate or CA-signed certificates:\n - CA-signed certificates require an existing CA certificate!\n - TLS Certificates as they are nothing more then a self-signed CA-certificate and key.\n\n [Example CA]:              ./generate_certs.sh --ca=true --keylength=2048 --root_ca=test_ca --dns_name=teebench.xyz --validity=365 --verbose # generates a root CA\n [Example Client w/ CFGs]:  ./generate_certs.sh --client=true --keylength=2048 --client_name=comp05 --root_ca=test_ca --client_cert_ext=client_cert_ext.cnf --csr_config=csr.conf --validity=365 --dns_name=teebench.xyz --verbose\n [Example Client w/o CFG]: ./generate_certs.sh --client=true --keylength=8192 --client_name=test_client --root_ca=test_ca --validity=365 --dns_name=teebench.xyz --verbose\n[WARN] To use the certificates you need to add them to your system trust store (as root) or to your local trust store (i.e. in Chromium & Evolution / Firefox)!";
+    echo -e "\nThis script generates certificates.\n - Either a CA certificate or CA-signed certificates:\n - CA-signed certificates require an existing CA certificate!\n - TLS Certificates as they are nothing more then a self-signed CA-certificate and key.\n\n [Example CA]:              ./generate_certs.sh --ca=true --keylength=2048 --root_ca=test_ca --dns_name=teebench.xyz --validity=365 --verbose # generates a root CA\n [Example Client w/ CFGs]:  ./generate_certs.sh --client=true --keylength=2048 --client_name=comp05 --root_ca=test_ca --client_cert_ext=client_cert_ext.cnf --csr_config=csr.conf --validity=365 --dns_name=teebench.xyz --verbose\n [Example Client w/o CFG]: ./generate_certs.sh --client=true --keylength=8192 --client_name=test_client --root_ca=test_ca --validity=365 --dns_name=teebench.xyz --verbose\n [Example Company]:         ./generate_certs.sh --client=true --client_name=comp05 --root_ca=test_ca --company_id=2f1c0e5a-6f0b-4c55-9a43-1d2c3b4a5f60 --dns_name=teebench.xyz\n[WARN] To use the certificates you need to add them to your system trust store (as root) or to your local trust store (i.e. in Chromium & Evolution / Firefox)!";
     exit 0;
 }
 
@@ -105,6 +109,9 @@ evaluate_input() {
             "--client_cert_ext="* )
             CLIENT_CERT_EXT_PATH="${opt#*=}"
             ;;
+            "--company_id="* )
+            COMPANY_ID="${opt#*=}"
+            ;;
             "--verbose" )
             VERBOSE=true;
             shift;;
@@ -161,6 +168,7 @@ print_verbose() {
     echo "DNS Name          : $DNS_NAME";
     echo "CSR Cofig Path    : $CLIENT_CSR_PATH";
     echo "Client Config Path: $CLIENT_CERT_EXT_PATH";
+    echo "Company ID        : $COMPANY_ID";
     echo "Validity          : $VALIDITY";
     echo "Verbose Output    : $VERBOSE";
     echo -e "\n##############";
@@ -271,6 +279,7 @@ subjectAltName = @alt_names
 
 [alt_names]
 DNS.1 = $DNS_NAME
+$(if [[ -n $COMPANY_ID ]]; then echo "URI.1 = urn:uuid:$COMPANY_ID"; fi)
 
 EOF
         CLIENT_CERT_EXT_PATH=client_cert_ext.cnf; 
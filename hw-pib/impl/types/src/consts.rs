@@ -28,7 +28,7 @@ pub const SERVER_CRYPTO_PATH: &str = "../templates/crypto";
 
 /// File for server certificate (pem) AND private key (key)
 pub const SERVER_SERVER_CRYPTO: &str = "server/server";
-/// File for enclave certificate (pem)
+/// Enclave key that signs the attestation evidence (pem)
 pub const SERVER_ENCLAVE_CRYPTO: &str = "enclave/enclave";
 
 
@@ -50,7 +50,20 @@ pub const SERVER_SHUTDOWN_TIMEOUT: u64 = 10;
 ///
 /// HEADER FIELDS
 /// 
-pub const X_APPLICATION_FIELD: &str = "X-Application-Teebench";
+/// HMAC of the request (hex)
+pub const X_HMAC_FIELD: &str = "X-Teebench-Hmac";
+/// Nonce of the request, a counter that increases with every request
+pub const X_COUNTER_FIELD: &str = "X-Teebench-Counter";
+/// ID of the last received server sent event (replay on reconnect)
+pub const LAST_EVENT_ID_FIELD: &str = "Last-Event-ID";
+/// Info for deriving the key that encrypts the exchanged HMAC key
+pub const HMAC_KEY_EXCHANGE_INFO: &[u8] = b"teebench-hmac-key-exchange-v1";
+/// Info for deriving the key that encrypts the results of a company
+pub const RESULT_SEALING_INFO: &[u8] = b"teebench-result-sealing-v1";
+/// Time that results are kept after their publication (7 days)
+pub const DEFAULT_RESULT_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+/// Length of the HMAC keys in bytes
+pub const HMAC_KEY_LEN: usize = 32;
 
 
 ///
@@ -64,6 +77,11 @@ pub const FORM_DATA_FIELD_01_CONFIGURATION_NAME: &str = "configuration";
 pub const FORM_DATA_FIELD_01_CONFIGURATION_MIME: &str = "text/yaml";
 pub const FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_NAME: &str = "analyst-certificate";
 pub const FORM_DATA_FIELD_01_ANALYST_CERTIFICATE_MIME: &str = "application/x-x509-ca-cert";
+/// Optional: PEM files with the certificates of the co-analysts and auditors
+pub const FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_NAME: &str = "co-analyst-certificates";
+pub const FORM_DATA_FIELD_01_CO_ANALYST_CERTIFICATES_MIME: &str = "application/x-x509-ca-cert";
+pub const FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_NAME: &str = "auditor-certificates";
+pub const FORM_DATA_FIELD_01_AUDITOR_CERTIFICATES_MIME: &str = "application/x-x509-ca-cert";
 
 /// 02. ANALYST-ALGORITHM-UPLOAD
 pub const FORM_DATA_FIELD_02_ALGORIHTMS_NAME: &str = "algorithms";
@@ -76,6 +94,10 @@ pub const FORM_DATA_FIELD_03_CONFIGURATION_MIME: &str = "text/yaml";
 /// 04. UPLOAD-INPUT-DATA
 pub const FORM_DATA_FIELD_04_COMPANY_INPUT_NAME: &str = "input_data";
 pub const FORM_DATA_FIELD_04_COMPANY_INPUT_MIME: &str = "text/yaml";
+/// Further formats of the input, the content type of the field selects the parser
+pub const FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_JSON: &str = "application/json";
+pub const FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_CSV: &str = "text/csv";
+pub const FORM_DATA_FIELD_04_COMPANY_INPUT_MIMES: &str = concatcp!(FORM_DATA_FIELD_04_COMPANY_INPUT_MIME, ", ", FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_JSON, ", ", FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_CSV);
 
 ///
 /// API ROUTES GENERAL
@@ -86,31 +108,54 @@ pub const ROUTE_FAVICON: &str = "/favicon.ico";
 pub const ROUTE_INDEX: &str = "/";
 pub const ROUTE_WHOAMI: &str = "/whoami";
 
-/// API NAME
-pub const ROUTE_API: &str = "/api";
+/// OPERATIONS (monitoring and orchestration, outside of the versioned API)
+pub const ROUTE_METRICS: &str = "/metrics";
+pub const ROUTE_HEALTH_LIVE: &str = "/health/live";
+pub const ROUTE_HEALTH_READY: &str = "/health/ready";
+
+/// API NAME (versioned, incompatible changes get a new version)
+pub const API_VERSION: &str = "v1";
+pub const ROUTE_API: &str = concatcp!("/api/", API_VERSION);
+
+/// OpenAPI description of the routes
+pub const ROUTE_OPENAPI: &str = "openapi.json";
 
 /// SETUP SPECIFIC
 pub const ROUTE_SETUP: &str = "setup";
 pub const ROUTE_ATTEST: &str = "attest";
+pub const ROUTE_KEY_EXCHANGE: &str = "key_exchange";
 pub const ROUTE_SHUTDOWN: &str = "shutdown";
 
+/// BENCHMARK SPECIFIC (company, analyst and event routes are scoped by a benchmark)
+pub const ROUTE_BENCHMARKS: &str = "benchmarks";
+
 /// COMPANY SPECIFIC
 pub const ROUTE_COMPANY: &str = "company";
 pub const ROUTE_COMPANY_EXT_REGISTER: &str = "register";
 pub const ROUTE_COMPANY_EXT_INPUT_DATA: &str = "input_data";
 pub const ROUTE_COMPANY_EXT_RESULTS: &str = "results";
+pub const ROUTE_COMPANY_EXT_ALGORITHMS: &str = "algorithms";
+pub const ROUTE_COMPANY_EXT_EVENTS: &str = "events";
 
 /// EVENT SPECIFIC
 pub const ROUTE_ENROLL_EVENTS: &str = "events";
 
+/// PHASE SPECIFIC
+pub const ROUTE_PHASE: &str = "phase";
+
 /// ANALYST SPECIFIC
 pub const ROUTE_ANALYST: &str = "analyst";
+pub const ROUTE_ANALYST_EXT_BENCHMARKS: &str = "benchmarks";
+pub const ROUTE_ANALYST_EXT_KEY_EXCHANGE: &str = "key_exchange";
+pub const ROUTE_ANALYST_EXT_AUDIT_LOG: &str = "audit_log";
 pub const ROUTE_ANALYST_EXT_BENCHMARK_CONFIG: &str = "benchmark_config";
 pub const ROUTE_ANALYST_EXT_COMPANY_STATUS: &str = "company";
 pub const ROUTE_ANALYST_EXT_ENROLL_COMPANY: &str = "enroll_company";
 pub const ROUTE_ANALYST_EXT_ALGORITHMS: &str = "algorithms";
 pub const ROUTE_ANALYST_EXT_BENCHMARK: &str = "benchmark";
 pub const ROUTE_ANALYST_EXT_EVENT: &str = "event";
+pub const ROUTE_ANALYST_EXT_PHASE: &str = "phase";
+pub const ROUTE_ANALYST_EXT_EVENTS: &str = "events";
 
 ///
 /// MAKE ROUTES EASIER FOR CLIENTS TO USE
@@ -119,32 +164,47 @@ pub const ROUTE_ANALYST_EXT_EVENT: &str = "event";
 /// SETUP SPECIFIC
 pub const C_ROUTE_SETUP: &str = concatcp!(ROUTE_API, "/", ROUTE_SETUP);
 pub const C_ROUTE_ATTEST: &str = concatcp!(ROUTE_API, "/", ROUTE_ATTEST);
+pub const C_ROUTE_KEY_EXCHANGE: &str = concatcp!(ROUTE_API, "/", ROUTE_KEY_EXCHANGE);
 pub const C_ROUTE_SHUTDOWN: &str = concatcp!(ROUTE_API, "/", ROUTE_SHUTDOWN);
+pub const C_ROUTE_OPENAPI: &str = concatcp!(ROUTE_API, "/", ROUTE_OPENAPI);
 
-/// COMPANY SPECIFIC [HAS TRAILING "/" for appending UUID]
-pub const C_ROUTE_COMPANY_EXT_REGISTER_ID: &str = concatcp!(ROUTE_API, "/", ROUTE_COMPANY, "/", ROUTE_COMPANY_EXT_REGISTER, "/");
-pub const C_ROUTE_COMPANY_EXT_INPUT_DATA_ID: &str = concatcp!(ROUTE_API, "/",  ROUTE_COMPANY, "/",ROUTE_COMPANY_EXT_INPUT_DATA, "/");
-pub const C_ROUTE_COMPANY_EXT_RESULTS_ID: &str = concatcp!(ROUTE_API, "/",  ROUTE_COMPANY, "/",ROUTE_COMPANY_EXT_RESULTS, "/");
+/// BENCHMARK SPECIFIC [HAS TRAILING "/" for appending the benchmark ID]
+pub const C_ROUTE_BENCHMARKS: &str = concatcp!(ROUTE_API, "/", ROUTE_BENCHMARKS, "/");
 
-/// EVENT SPECIFIC
-pub const C_ROUTE_ENROLL_EVENTS: &str = concatcp!(ROUTE_API, "/", ROUTE_ENROLL_EVENTS);
+/// COMPANY SPECIFIC [RELATIVE TO BENCHMARK, the company is identified by its certificate]
+pub const C_ROUTE_COMPANY_EXT_REGISTER: &str = concatcp!("/", ROUTE_COMPANY, "/", ROUTE_COMPANY_EXT_REGISTER);
+pub const C_ROUTE_COMPANY_EXT_INPUT_DATA: &str = concatcp!("/",  ROUTE_COMPANY, "/",ROUTE_COMPANY_EXT_INPUT_DATA);
+pub const C_ROUTE_COMPANY_EXT_RESULTS: &str = concatcp!("/",  ROUTE_COMPANY, "/",ROUTE_COMPANY_EXT_RESULTS);
+pub const C_ROUTE_COMPANY_EXT_ALGORITHMS: &str = concatcp!("/",  ROUTE_COMPANY, "/",ROUTE_COMPANY_EXT_ALGORITHMS);
+pub const C_ROUTE_COMPANY_EXT_EVENTS: &str = concatcp!("/",  ROUTE_COMPANY, "/",ROUTE_COMPANY_EXT_EVENTS);
+
+/// EVENT SPECIFIC [RELATIVE TO BENCHMARK]
+pub const C_ROUTE_ENROLL_EVENTS: &str = concatcp!("/", ROUTE_ENROLL_EVENTS);
+
+/// PHASE SPECIFIC [RELATIVE TO BENCHMARK]
+pub const C_ROUTE_PHASE: &str = concatcp!("/", ROUTE_PHASE);
 
 /// ANALYST SPECIFIC
-pub const C_ROUTE_ANALYST_EXT_BENCHMARK_CONFIG: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_BENCHMARK_CONFIG);
-pub const C_ROUTE_ANALYST_EXT_COMPANY_STATUS_ID: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_COMPANY_STATUS, "/");
-pub const C_ROUTE_ANALYST_EXT_ENROLL_COMPANY: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_ENROLL_COMPANY);
-pub const C_ROUTE_ANALYST_EXT_ALGORITHMS: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_ALGORITHMS);
-pub const C_ROUTE_ANALYST_EXT_BENCHMARK: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_BENCHMARK);
-pub const C_ROUTE_ANALYST_EXT_EVENT: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_EVENT);
+pub const C_ROUTE_ANALYST_EXT_BENCHMARKS: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_BENCHMARKS);
+pub const C_ROUTE_ANALYST_EXT_KEY_EXCHANGE: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_KEY_EXCHANGE);
+pub const C_ROUTE_ANALYST_EXT_AUDIT_LOG: &str = concatcp!(ROUTE_API, "/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_AUDIT_LOG);
+
+/// ANALYST SPECIFIC [RELATIVE TO BENCHMARK]
+pub const C_ROUTE_ANALYST_EXT_BENCHMARK_CONFIG: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_BENCHMARK_CONFIG);
+pub const C_ROUTE_ANALYST_EXT_COMPANY_STATUS_ID: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_COMPANY_STATUS, "/");
+pub const C_ROUTE_ANALYST_EXT_ENROLL_COMPANY: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_ENROLL_COMPANY);
+pub const C_ROUTE_ANALYST_EXT_ALGORITHMS: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_ALGORITHMS);
+pub const C_ROUTE_ANALYST_EXT_BENCHMARK: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_BENCHMARK);
+pub const C_ROUTE_ANALYST_EXT_EVENT: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_EVENT);
+pub const C_ROUTE_ANALYST_EXT_PHASE: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_PHASE);
+pub const C_ROUTE_ANALYST_EXT_EVENTS: &str = concatcp!("/", ROUTE_ANALYST, "/", ROUTE_ANALYST_EXT_EVENTS);
 
 
 ///
 /// MAKE ROUTES FOR SERVER EASIER TO USE
 /// 
 
-pub const S_ROUTE_COMPANY_EXT_REGISTER_ID: &str = concatcp!(ROUTE_COMPANY_EXT_REGISTER, "/{id}");
-pub const S_ROUTE_COMPANY_EXT_INPUT_DATA_ID: &str = concatcp!(ROUTE_COMPANY_EXT_INPUT_DATA, "/{id}");
-pub const S_ROUTE_COMPANY_EXT_RESULTS_ID: &str = concatcp!(ROUTE_COMPANY_EXT_RESULTS, "/{id}");
+pub const S_ROUTE_BENCHMARK_ID: &str = concatcp!(ROUTE_BENCHMARKS, "/{bid}");
 
 pub const S_ROUTE_ANALYST_EXT_COMPANY_STATUS: &str = concatcp!(ROUTE_ANALYST_EXT_COMPANY_STATUS, "/{id}");
 
@@ -166,12 +226,21 @@ pub const CC_CLIENT_SERVER_CA_CERTIFICATE: &str = "server_certificate_path";
 
 /// The companies provide their input data
 pub const CC_COMPANY_INPUT_DATA_PATH_KEY: &str = "input_data_path";
+/// The companies may provide a policy for the server attestation
+pub const CC_COMPANY_ATTESTATION_POLICY_KEY: &str = "attestation_policy_path";
+/// Optional: export of the results (CSV, JSON or XLSX by the extension)
+pub const CC_COMPANY_RESULTS_PATH_KEY: &str = "results_path";
+/// Optional: HTML report of the results
+pub const CC_COMPANY_REPORT_PATH_KEY: &str = "report_path";
 
 /// The analyst provides the ca certificate, his own certificate, the config and his algorithms 
 pub const CC_ANALYST_CA_CERTIFICATE_KEY: &str = "analyst_ca_cert_path";
 pub const CC_ANALYST_ALGORITHMS_KEY: &str = "algorithm_path";
 pub const CC_ANALYST_BENCHMARK_CONFIG_KEY: &str = "benchmarking_config_path";
 pub const CC_ANALYST_CERTIFICATE_KEY: &str = "analyst_cert_path";
+/// Optionally, the analyst provides the certificates of his team for the setup
+pub const CC_ANALYST_CO_ANALYST_CERTIFICATES_KEY: &str = "co_analyst_certs_path";
+pub const CC_ANALYST_AUDITOR_CERTIFICATES_KEY: &str = "auditor_certs_path";
 
 /// The spectator writes to a specific file which holds the configuration
 pub const CC_SPECTATOR_EVAL_OUTPUT_KEY: &str = "spectator_eval_output_path";
\ No newline at end of file
@@ -1,15 +1,33 @@
+use std::fmt;
+
 use serde::{Serialize, Deserialize};
 use rustls::Certificate;
+use zeroize::Zeroize;
 
-use crate::output::Output;
+use crate::{consts::DEFAULT_RESULT_RETENTION_SECS, message::response::SealedResults};
 
 use super::input::Input;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Company {
+    #[serde(with = "der_certificate::optional")]
     certificate: Option<Certificate>,
     input_data: Input,
-    results_data: Output,
+    /// Results encrypted to the result key (until download or retention end)
+    #[serde(default)]
+    sealed_results: Option<SealedResults>,
+    /// X25519 key of the company for its results (hex, set on registration)
+    #[serde(default)]
+    result_public_key: Option<String>,
+    /// Hash of the algorithms the company consented to
+    #[serde(default)]
+    confirmed_algorithm: Option<String>,
+    /// Key for the request integrity (set once on registration)
+    #[serde(default)]
+    hmac_key: Option<HmacKey>,
+    /// Whether the input was wiped after the results were assembled
+    #[serde(default)]
+    input_wiped: bool,
 }
 
 impl Company {
@@ -21,50 +39,144 @@ impl Company {
         &self.input_data
     }
 
-    pub fn results_data(&self) -> &Output {
-        &self.results_data
+    pub fn sealed_results(&self) -> Option<&SealedResults> {
+        self.sealed_results.as_ref()
+    }
+
+    pub fn result_public_key(&self) -> Option<&String> {
+        self.result_public_key.as_ref()
     }
 
     pub fn certificate(&self) -> &Option<Certificate> {
         &self.certificate
     }
 
+    pub fn hmac_key(&self) -> Option<&HmacKey> {
+        self.hmac_key.as_ref()
+    }
+
+    pub fn confirmed_algorithm(&self) -> Option<&String> {
+        self.confirmed_algorithm.as_ref()
+    }
+
+    pub fn input_wiped(&self) -> bool {
+        self.input_wiped
+    }
+
     pub fn does_participate(&self) -> bool {
-        self.input_data.size() != 0 as usize
+        self.input_data.size() != 0 || self.input_wiped
     }
 
     pub fn set_input_data(&mut self, input_data: Input){
         self.input_data = input_data;
     }
 
-    pub fn set_results_data(&mut self, results_data: Output){
-        self.results_data = results_data;
+    /// Overwrite the input (the company still counts as participant)
+    pub fn wipe_input_data(&mut self) {
+        self.input_data.zeroize();
+        self.input_wiped = true;
+    }
+
+    pub fn set_sealed_results(&mut self, sealed_results: Option<SealedResults>) {
+        self.sealed_results = sealed_results;
+    }
+
+    pub fn set_result_public_key(&mut self, result_public_key: String) {
+        self.result_public_key = Some(result_public_key);
     }
 
     pub fn set_certificate(&mut self, cert: Certificate) {
         self.certificate = Some(cert);
     }
+
+    pub fn set_confirmed_algorithm(&mut self, algorithm_hash: String) {
+        self.confirmed_algorithm = Some(algorithm_hash);
+    }
+
+    pub fn set_hmac_key(&mut self) -> &mut Option<HmacKey> {
+        &mut self.hmac_key
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Member of the analyst team (owner, co-analyst or auditor)
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Analyst {
+    #[serde(with = "der_certificate")]
     certificate: Certificate,
+    /// Role of the member, the setup certificate belongs to the owner
+    #[serde(default)]
+    role: Role,
+    /// Key for the request integrity (set once after the setup)
+    #[serde(default)]
+    hmac_key: Option<HmacKey>,
 }
 
 impl Analyst {
-    pub fn new(certificate: Certificate) -> Self {
-        Analyst { certificate }
+    pub fn new(certificate: Certificate, role: Role) -> Self {
+        Analyst { certificate, role, hmac_key: None }
     }
 
     pub fn certificate(&self) -> &Certificate {
         &self.certificate
     }
 
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn hmac_key(&self) -> Option<&HmacKey> {
+        self.hmac_key.as_ref()
+    }
+
+    pub fn set_hmac_key(&mut self) -> &mut Option<HmacKey> {
+        &mut self.hmac_key
+    }
+
     pub fn set_certificate(&mut self, certificate: Certificate) {
         self.certificate = certificate;
     }
 }
 
+/// Symmetric key that authenticates the requests of one participant
+///
+/// Every request carries a counter that has to increase, such that
+/// recorded requests cannot be replayed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HmacKey {
+    key: Vec<u8>,
+    counter: u64,
+}
+
+impl HmacKey {
+    pub fn new(key: Vec<u8>) -> Self {
+        HmacKey { key, counter: 0 }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+
+    /// Accept the counter of a request if it is higher than all previous ones
+    pub fn advance(&mut self, counter: u64) -> bool {
+        if counter > self.counter {
+            self.counter = counter;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacKey").field("counter", &self.counter).finish()
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, Serialize, Default)]
 pub struct BenchmarkingConfig {
     /// The server name
@@ -77,11 +189,21 @@ pub struct BenchmarkingConfig {
     eval_mode: bool,
     /// Offloaded Operations (only relevant for eval)
     offload: Vec<String>,
+    /// Seconds that results are kept after their publication
+    #[serde(default = "default_result_retention")]
+    result_retention: u64,
+    /// What happens to the inputs once the results are assembled
+    #[serde(default)]
+    input_retention: InputRetention,
+}
+
+fn default_result_retention() -> u64 {
+    DEFAULT_RESULT_RETENTION_SECS
 }
 
 impl BenchmarkingConfig {
     pub fn new(name: String, description: String, k_anonymity: u64, eval_mode: bool, offload: Vec<String>) -> Self {
-        BenchmarkingConfig { name, description, k_anonymity, eval_mode, offload }
+        BenchmarkingConfig { name, description, k_anonymity, eval_mode, offload, result_retention: DEFAULT_RESULT_RETENTION_SECS, input_retention: InputRetention::default() }
     }
 
     pub fn name(&self) -> &str {
@@ -104,6 +226,14 @@ impl BenchmarkingConfig {
         &self.offload
     }
 
+    pub fn result_retention(&self) -> u64 {
+        self.result_retention
+    }
+
+    pub fn input_retention(&self) -> InputRetention {
+        self.input_retention
+    }
+
     /// Modification
 
     pub fn set_name(&mut self, name: String) {
@@ -125,4 +255,131 @@ impl BenchmarkingConfig {
     pub fn set_offload(&mut self, offload: Vec<String>) {
         self.offload = offload;
     }
+
+    pub fn set_result_retention(&mut self, result_retention: u64) {
+        self.result_retention = result_retention;
+    }
+
+    pub fn set_input_retention(&mut self, input_retention: InputRetention) {
+        self.input_retention = input_retention;
+    }
+}
+
+/// Retention policy for the inputs of the companies
+///
+/// - `WipeAfterResults`: The inputs are zeroized as soon as the results
+///   are assembled and sealed, a company cannot retrieve its input anymore.
+/// - `Keep`: The inputs are kept until the company is removed or the
+///   server shuts down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputRetention {
+    #[default]
+    WipeAfterResults,
+    Keep,
+}
+
+/// Lifecycle of one benchmark
+/// 
+/// The phases are passed in order, every route is restricted to
+/// the phases in which it makes sense (e.g. input data can only be
+/// changed during the collection).
+/// 
+/// - `Setup`: The analyst configures the benchmark and its algorithms.
+/// - `Enrollment`: The analyst enrolls companies which then register.
+/// - `Collection`: Companies upload their input data.
+/// - `Computing`: The server computes the benchmark (nothing changes).
+/// - `ResultsPublished`: Companies retrieve their results.
+/// - `Closed`: The benchmark is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchmarkPhase {
+    #[default]
+    Setup,
+    Enrollment,
+    Collection,
+    Computing,
+    ResultsPublished,
+    Closed,
+}
+
+impl BenchmarkPhase {
+    /// The phase that succeeds this one (`None` when closed)
+    pub fn next(&self) -> Option<BenchmarkPhase> {
+        match self {
+            BenchmarkPhase::Setup => Some(BenchmarkPhase::Enrollment),
+            BenchmarkPhase::Enrollment => Some(BenchmarkPhase::Collection),
+            BenchmarkPhase::Collection => Some(BenchmarkPhase::Computing),
+            BenchmarkPhase::Computing => Some(BenchmarkPhase::ResultsPublished),
+            BenchmarkPhase::ResultsPublished => Some(BenchmarkPhase::Closed),
+            BenchmarkPhase::Closed => None,
+        }
+    }
+}
+
+impl fmt::Display for BenchmarkPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BenchmarkPhase::Setup => write!(f, "setup"),
+            BenchmarkPhase::Enrollment => write!(f, "enrollment"),
+            BenchmarkPhase::Collection => write!(f, "collection"),
+            BenchmarkPhase::Computing => write!(f, "computing"),
+            BenchmarkPhase::ResultsPublished => write!(f, "results_published"),
+            BenchmarkPhase::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// Roles of the participants that decide about their access to routes
+///
+/// - `Owner`: The analyst who set up the server, has full access.
+/// - `CoAnalyst`: Shares the administration of the benchmarks with the owner.
+/// - `Auditor`: Reads the configuration and status but modifies nothing.
+/// - `Company`: Accesses only its own data (identified by its certificate).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Owner,
+    CoAnalyst,
+    Auditor,
+    Company,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Owner => write!(f, "owner"),
+            Role::CoAnalyst => write!(f, "co_analyst"),
+            Role::Auditor => write!(f, "auditor"),
+            Role::Company => write!(f, "company"),
+        }
+    }
+}
+
+/// (De-)Serialization of TLS certificates as DER bytes
+mod der_certificate {
+    use rustls::Certificate;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(cert: &Certificate, serializer: S) -> Result<S::Ok, S::Error> {
+        cert.0.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Certificate, D::Error> {
+        Ok(Certificate(Vec::deserialize(deserializer)?))
+    }
+
+    pub mod optional {
+        use rustls::Certificate;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<S: Serializer>(cert: &Option<Certificate>, serializer: S) -> Result<S::Ok, S::Error> {
+            cert.as_ref().map(|cert| &cert.0).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Certificate>, D::Error> {
+            Ok(Option::<Vec<u8>>::deserialize(deserializer)?.map(Certificate))
+        }
+    }
 }
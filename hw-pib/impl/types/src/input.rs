@@ -1,11 +1,120 @@
 //! Load and organize the inputs of `Algorithms`
+//!
+//! Inputs are accepted in several formats (see [`InputFormat`]), which all
+//! result in the same [`Input`] and thus pass the same validation.
 
 use serde::{Deserialize, Serialize};
-use serde_yaml::Error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs::OpenOptions;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+use crate::consts::{FORM_DATA_FIELD_04_COMPANY_INPUT_MIME, FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_CSV, FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_JSON};
+
+/// Formats of input files
+///
+/// - `Yaml`: the `vars` list of [`InputFmt`]
+/// - `Json`: the same `vars` list or an object of variable names to their values
+/// - `Csv`: one row per variable (the header is exactly `name,values`, then
+///   the name and the values of each variable) or wide (any other header
+///   holds the variable names, every row one value per variable)
+///
+/// A variable name may only occur once in every format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    #[default]
+    Yaml,
+    Json,
+    Csv,
+}
+
+impl InputFormat {
+    pub const ALL: [InputFormat; 3] = [InputFormat::Yaml, InputFormat::Json, InputFormat::Csv];
+
+    /// Content type of the format in uploads
+    pub fn mime(&self) -> &'static str {
+        match self {
+            InputFormat::Yaml => FORM_DATA_FIELD_04_COMPANY_INPUT_MIME,
+            InputFormat::Json => FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_JSON,
+            InputFormat::Csv => FORM_DATA_FIELD_04_COMPANY_INPUT_MIME_CSV,
+        }
+    }
+
+    /// Format of a content type (parameters like the charset are ignored)
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        InputFormat::ALL.into_iter().find(|format| format.mime().eq_ignore_ascii_case(essence))
+    }
+
+    /// Format of a file by its extension (YAML unless `.json` or `.csv`)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase()).as_deref() {
+            Some("json") => InputFormat::Json,
+            Some("csv") => InputFormat::Csv,
+            _ => InputFormat::Yaml,
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputFormat::Yaml => write!(f, "YAML"),
+            InputFormat::Json => write!(f, "JSON"),
+            InputFormat::Csv => write!(f, "CSV"),
+        }
+    }
+}
+
+/// Failure to read or parse an input file
+#[derive(Debug)]
+pub enum InputError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    Csv(String),
+    DuplicateVariable(String),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Io(e) => write!(f, "Could not read the input: {}", e),
+            InputError::Yaml(e) => write!(f, "Invalid YAML input: {}", e),
+            InputError::Json(e) => write!(f, "Invalid JSON input: {}", e),
+            InputError::Csv(e) => write!(f, "Invalid CSV input: {}", e),
+            InputError::DuplicateVariable(name) => write!(f, "The variable {:?} occurs more than once", name),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+impl From<std::io::Error> for InputError {
+    fn from(e: std::io::Error) -> Self {
+        InputError::Io(e)
+    }
+}
+
+impl From<serde_yaml::Error> for InputError {
+    fn from(e: serde_yaml::Error) -> Self {
+        InputError::Yaml(e)
+    }
+}
+
+impl From<serde_json::Error> for InputError {
+    fn from(e: serde_json::Error) -> Self {
+        InputError::Json(e)
+    }
+}
+
+impl From<csv::Error> for InputError {
+    fn from(e: csv::Error) -> Self {
+        InputError::Csv(e.to_string())
+    }
+}
 
 /// Defines the field in the Input file
 #[derive(Debug, Clone, Deserialize, PartialEq, Serialize, Default)]
@@ -59,25 +168,99 @@ pub struct InputFmt {
     vars: Vec<InputVariable>,
 }
 
+/// JSON inputs either follow the YAML structure or map names to values
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonInputFmt {
+    Vars(InputFmt),
+    Values(BTreeMap<String, Vec<f64>>),
+}
+
+impl InputFmt {
+    /// Parse an input file of the given format
+    pub fn from_slice(data: &[u8], format: InputFormat) -> Result<Self, InputError> {
+        let input_vec = match format {
+            InputFormat::Yaml => serde_yaml::from_slice(data)?,
+            InputFormat::Json => match serde_json::from_slice(data)? {
+                JsonInputFmt::Vars(input_vec) => input_vec,
+                JsonInputFmt::Values(values) => InputFmt::from(values.into_iter().map(|(name, values)| InputVariable::new(name, values)).collect::<Vec<_>>()),
+            },
+            InputFormat::Csv => Self::from_csv(data)?,
+        };
+
+        // A repeated variable would silently replace the former one
+        let mut names = HashSet::new();
+        if let Some(var) = input_vec.vars.iter().find(|var| !names.insert(var.name.as_str())) {
+            return Err(InputError::DuplicateVariable(var.name.clone()));
+        }
+        Ok(input_vec)
+    }
+
+    /// Parse CSV with one row per variable (header `name,values`) or one column per variable
+    fn from_csv(data: &[u8]) -> Result<Self, InputError> {
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).trim(csv::Trim::All).from_reader(data);
+        let mut rows = reader.records();
+        let header = match rows.next() {
+            Some(header) => header?,
+            None => return Ok(InputFmt { vars: Vec::new() }),
+        };
+        let parse = |cell: &str, name: &str| cell.parse::<f64>().map_err(|_| InputError::Csv(format!("Value {:?} of {:?} is not a number", cell, name)));
+
+        let mut vars = Vec::new();
+        let rows_layout = header.len() == 2 && header[0].eq_ignore_ascii_case("name") && header[1].eq_ignore_ascii_case("values");
+        if rows_layout {
+            // One row per variable: its name followed by its values
+            for row in rows {
+                let row = row?;
+                let mut cells = row.iter();
+                let name = cells.next().unwrap_or_default();
+                if name.is_empty() {
+                    return Err(InputError::Csv(format!("Row {} has no variable name", row.position().map_or(0, |p| p.line()))));
+                }
+                let values = cells.filter(|cell| !cell.is_empty()).map(|cell| parse(cell, name)).collect::<Result<Vec<_>, _>>()?;
+                vars.push(InputVariable::new(name.to_string(), values));
+            }
+        } else {
+            // Wide: the header holds the names, empty cells are skipped
+            vars = header.iter().map(|name| InputVariable::new(name.to_string(), Vec::new())).collect();
+            if vars.iter().any(|var| var.name.is_empty()) {
+                return Err(InputError::Csv("The header has a column without variable name".to_string()));
+            }
+            for row in rows {
+                let row = row?;
+                if row.len() > vars.len() {
+                    return Err(InputError::Csv(format!("Row {} has more values than variables", row.position().map_or(0, |p| p.line()))));
+                }
+                for (var, cell) in vars.iter_mut().zip(row.iter()) {
+                    if !cell.is_empty() {
+                        var.values.push(parse(cell, &var.name)?);
+                    }
+                }
+            }
+        }
+
+        Ok(InputFmt { vars })
+    }
+}
+
 impl Input {
-    /// Load the Config file and parse its contents
-    pub fn load(path: &PathBuf) -> Result<Self, Error> {
+    /// Load the input file and parse its contents (format by its extension)
+    pub fn load(path: &PathBuf) -> Result<Self, InputError> {
         let file = OpenOptions::new()
             .read(true)
-            .open(path)
-            .expect("Could not open INPUTs_FILE");
+            .open(path)?;
 
-        let buf = BufReader::new(file);
-        let input_vec: InputFmt = serde_yaml::from_reader(buf)?;
+        let mut buf = Vec::new();
+        std::io::Read::read_to_end(&mut BufReader::new(file), &mut buf)?;
+        let input = Self::from_slice(&buf, InputFormat::from_path(path));
+        buf.zeroize();
 
-        // Insert in map
-        let mut var_map: HashMap<String, InputVariable> =
-            HashMap::with_capacity(input_vec.vars.len());
-        for var in input_vec.vars {
-            var_map.insert(var.name().to_string(), var);
-        }
+        input
+    }
 
-        Ok(Self { vars: var_map })
+    /// Parse the contents of an input file that is already in memory
+    pub fn from_slice(data: &[u8], format: InputFormat) -> Result<Self, InputError> {
+        Ok(Self::from(InputFmt::from_slice(data, format)?))
     }
 
     /// Number of variables
@@ -94,8 +277,115 @@ impl Input {
     }
 }
 
+impl From<InputFmt> for Input {
+    fn from(input_vec: InputFmt) -> Self {
+        // Insert in map
+        let mut var_map: HashMap<String, InputVariable> =
+            HashMap::with_capacity(input_vec.vars.len());
+        for var in input_vec.vars {
+            var_map.insert(var.name().to_string(), var);
+        }
+
+        Input { vars: var_map }
+    }
+}
+
+impl Zeroize for InputVariable {
+    fn zeroize(&mut self) {
+        self.name.zeroize();
+        self.max_val.zeroize();
+        self.min_val.zeroize();
+        self.values.zeroize();
+    }
+}
+
+/// Overwrite the names and values of all variables and remove them
+impl Zeroize for Input {
+    fn zeroize(&mut self) {
+        for (mut name, mut var) in self.vars.drain() {
+            name.zeroize();
+            var.zeroize();
+        }
+    }
+}
+
+/// Inputs are confidential, no copy is left behind in released memory
+impl Drop for Input {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl From<Vec<InputVariable>> for InputFmt {
     fn from(vars: Vec<InputVariable>) -> Self {
         InputFmt { vars }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows_and_columns() {
+        let rows = Input::from_slice(b"name,values\nrevenue,1.5,2\ncosts,3\n", InputFormat::Csv).unwrap();
+        let columns = Input::from_slice(b"revenue,costs\n1.5,3\n2,\n", InputFormat::Csv).unwrap();
+
+        for input in [rows, columns] {
+            assert_eq!(input.size(), 2);
+            assert_eq!(input.get_input_var("revenue").unwrap().values(), &vec![1.5, 2.0]);
+            assert_eq!(input.get_input_var("costs").unwrap().values(), &vec![3.0]);
+        }
+    }
+
+    #[test]
+    fn csv_layout_by_header() {
+        // Only the exact header selects one row per variable
+        let columns = Input::from_slice(b"name,revenue\n1,2\n", InputFormat::Csv).unwrap();
+        assert_eq!(columns.get_input_var("name").unwrap().values(), &vec![1.0]);
+        assert_eq!(columns.get_input_var("revenue").unwrap().values(), &vec![2.0]);
+
+        let rows = Input::from_slice(b"Name,Values\nrevenue,1,2\n", InputFormat::Csv).unwrap();
+        assert_eq!(rows.size(), 1);
+        assert_eq!(rows.get_input_var("revenue").unwrap().values(), &vec![1.0, 2.0]);
+
+        assert!(Input::from_slice(b"name\nrevenue,1\n", InputFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn duplicate_variables_are_rejected() {
+        let inputs: [(&[u8], InputFormat); 4] = [
+            (b"name,values\nrevenue,1\nrevenue,2\n", InputFormat::Csv),
+            (b"revenue,costs,revenue\n1,2,3\n", InputFormat::Csv),
+            (br#"{"vars": [{"name": "revenue", "min_val": 0, "max_val": 0, "values": [1.0]}, {"name": "revenue", "min_val": 0, "max_val": 0, "values": [2.0]}]}"#, InputFormat::Json),
+            (b"vars:\n  - {name: revenue, min_val: 0, max_val: 0, values: [1.0]}\n  - {name: revenue, min_val: 0, max_val: 0, values: [2.0]}\n", InputFormat::Yaml),
+        ];
+        for (data, format) in inputs {
+            assert!(matches!(Input::from_slice(data, format), Err(InputError::DuplicateVariable(name)) if name == "revenue"), "{}", format);
+        }
+    }
+
+    #[test]
+    fn csv_invalid_value() {
+        assert!(Input::from_slice(b"name,values\nrevenue,abc\n", InputFormat::Csv).is_err());
+        assert!(Input::from_slice(b"revenue\n1,2\n", InputFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn json_vars_and_values() {
+        let vars = Input::from_slice(br#"{"vars": [{"name": "revenue", "min_val": 0, "max_val": 0, "values": [1.0]}]}"#, InputFormat::Json).unwrap();
+        let values = Input::from_slice(br#"{"revenue": [1.0]}"#, InputFormat::Json).unwrap();
+
+        for input in [vars, values] {
+            assert_eq!(input.get_input_var("revenue").unwrap().values(), &vec![1.0]);
+        }
+    }
+
+    #[test]
+    fn format_of_mime_and_path() {
+        assert_eq!(InputFormat::from_mime("text/csv; charset=utf-8"), Some(InputFormat::Csv));
+        assert_eq!(InputFormat::from_mime("text/plain"), None);
+        assert_eq!(InputFormat::from_path(Path::new("inputs.JSON")), InputFormat::Json);
+        assert_eq!(InputFormat::from_path(Path::new("inputs.yaml")), InputFormat::Yaml);
+    }
+}
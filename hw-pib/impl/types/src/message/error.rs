@@ -0,0 +1,194 @@
+//! Error message formats
+//!
+//! Failed requests are answered with an [`ErrorMsg`]: a machine-readable
+//! `code` (with the details of the code, e.g. the missing variables) and a
+//! message for humans. The HTTP status of the response follows from the
+//! code ([`ErrorCode::status`]), clients match on the code instead of
+//! parsing messages.
+//!
+//! `{"code": "INPUT_MISSING_VARIABLES", "variables": ["revenue"], "message": "..."}`
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::BenchmarkPhase;
+
+/// Reason of a failed request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Malformed request (400)
+    BadRequest,
+    /// No client certificate, no HMAC key or an invalid / replayed HMAC (401)
+    NotAuthenticated,
+    /// The certificate of the request was revoked (401)
+    CertificateRevoked,
+    /// The role of the certificate may not use the route (403)
+    PermissionDenied,
+    /// The UUID of the certificate is not enrolled in the benchmark (403)
+    CompanyNotEnrolled,
+    /// The company has not registered its certificate yet (403)
+    CompanyNotRegistered,
+    /// No benchmark with the requested ID (404)
+    BenchmarkNotFound,
+    /// The requested company, input, results or algorithms do not exist (404)
+    NotFound,
+    /// The route is not usable in the current phase of the benchmark (409)
+    WrongPhase { phase: BenchmarkPhase },
+    /// The request conflicts with the state of the server, e.g. a repeated registration (409)
+    Conflict,
+    /// The input lacks variables of the algorithms (422)
+    InputMissingVariables { variables: Vec<String> },
+    /// The operations of the algorithms depend on each other in a cycle (422)
+    AlgorithmCycle { cycle: Vec<String> },
+    /// The algorithms are malformed or cannot be computed (422)
+    InvalidAlgorithm,
+    /// The uploaded file could not be parsed (422)
+    InvalidFormat,
+    /// An uploaded field or the whole upload exceeds its size limit (413)
+    PayloadTooLarge,
+    /// The content type of an uploaded file is not supported (415)
+    UnsupportedMediaType,
+    /// Too many requests of the client or event stream clients, retry after `retry_after` seconds (429)
+    TooManyRequests { retry_after: u64 },
+    /// Failure of the server (500)
+    Internal,
+}
+
+impl ErrorCode {
+    /// HTTP status code of the responses with this code
+    pub fn status(&self) -> u16 {
+        match self {
+            ErrorCode::BadRequest => 400,
+            ErrorCode::NotAuthenticated | ErrorCode::CertificateRevoked => 401,
+            ErrorCode::PermissionDenied | ErrorCode::CompanyNotEnrolled | ErrorCode::CompanyNotRegistered => 403,
+            ErrorCode::BenchmarkNotFound | ErrorCode::NotFound => 404,
+            ErrorCode::WrongPhase { .. } | ErrorCode::Conflict => 409,
+            ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::UnsupportedMediaType => 415,
+            ErrorCode::InputMissingVariables { .. } | ErrorCode::AlgorithmCycle { .. } | ErrorCode::InvalidAlgorithm | ErrorCode::InvalidFormat => 422,
+            ErrorCode::TooManyRequests { .. } => 429,
+            ErrorCode::Internal => 500,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::BadRequest => write!(f, "BAD_REQUEST"),
+            ErrorCode::NotAuthenticated => write!(f, "NOT_AUTHENTICATED"),
+            ErrorCode::CertificateRevoked => write!(f, "CERTIFICATE_REVOKED"),
+            ErrorCode::PermissionDenied => write!(f, "PERMISSION_DENIED"),
+            ErrorCode::CompanyNotEnrolled => write!(f, "COMPANY_NOT_ENROLLED"),
+            ErrorCode::CompanyNotRegistered => write!(f, "COMPANY_NOT_REGISTERED"),
+            ErrorCode::BenchmarkNotFound => write!(f, "BENCHMARK_NOT_FOUND"),
+            ErrorCode::NotFound => write!(f, "NOT_FOUND"),
+            ErrorCode::WrongPhase { phase } => write!(f, "WRONG_PHASE ({})", phase),
+            ErrorCode::Conflict => write!(f, "CONFLICT"),
+            ErrorCode::InputMissingVariables { variables } => write!(f, "INPUT_MISSING_VARIABLES ({})", variables.join(", ")),
+            ErrorCode::AlgorithmCycle { cycle } => write!(f, "ALGORITHM_CYCLE ({})", cycle.join(" -> ")),
+            ErrorCode::InvalidAlgorithm => write!(f, "INVALID_ALGORITHM"),
+            ErrorCode::InvalidFormat => write!(f, "INVALID_FORMAT"),
+            ErrorCode::PayloadTooLarge => write!(f, "PAYLOAD_TOO_LARGE"),
+            ErrorCode::UnsupportedMediaType => write!(f, "UNSUPPORTED_MEDIA_TYPE"),
+            ErrorCode::TooManyRequests { retry_after } => write!(f, "TOO_MANY_REQUESTS (retry after {}s)", retry_after),
+            ErrorCode::Internal => write!(f, "INTERNAL"),
+        }
+    }
+}
+
+/// Body of a failed request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorMsg {
+    #[serde(flatten)]
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ErrorMsg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn msg(code: ErrorCode) -> ErrorMsg {
+        ErrorMsg { code, message: "Failed!".to_string() }
+    }
+
+    #[test]
+    pub fn codes_are_flattened_into_the_message() {
+        assert_eq!(serde_json::to_value(msg(ErrorCode::BadRequest)).unwrap(), json!({ "code": "BAD_REQUEST", "message": "Failed!" }));
+        assert_eq!(
+            serde_json::to_value(msg(ErrorCode::WrongPhase { phase: BenchmarkPhase::ResultsPublished })).unwrap(),
+            json!({ "code": "WRONG_PHASE", "phase": "results_published", "message": "Failed!" })
+        );
+        assert_eq!(
+            serde_json::to_value(msg(ErrorCode::InputMissingVariables { variables: vec!["revenue".to_string()] })).unwrap(),
+            json!({ "code": "INPUT_MISSING_VARIABLES", "variables": ["revenue"], "message": "Failed!" })
+        );
+        assert_eq!(
+            serde_json::to_value(msg(ErrorCode::TooManyRequests { retry_after: 3 })).unwrap(),
+            json!({ "code": "TOO_MANY_REQUESTS", "retry_after": 3, "message": "Failed!" })
+        );
+    }
+
+    #[test]
+    pub fn messages_round_trip() {
+        let codes = [
+            ErrorCode::BadRequest,
+            ErrorCode::CompanyNotRegistered,
+            ErrorCode::WrongPhase { phase: BenchmarkPhase::Collection },
+            ErrorCode::AlgorithmCycle { cycle: vec!["a".to_string(), "b".to_string(), "a".to_string()] },
+            ErrorCode::TooManyRequests { retry_after: 1 },
+            ErrorCode::Internal,
+        ];
+        for code in codes {
+            let json = serde_json::to_string(&msg(code.clone())).unwrap();
+            assert_eq!(serde_json::from_str::<ErrorMsg>(&json).unwrap(), msg(code));
+        }
+
+        // Unknown codes are rejected
+        assert!(serde_json::from_str::<ErrorMsg>(r#"{ "code": "TEAPOT", "message": "Failed!" }"#).is_err());
+    }
+
+    #[test]
+    pub fn codes_decide_the_status() {
+        let statuses = [
+            (ErrorCode::BadRequest, 400),
+            (ErrorCode::NotAuthenticated, 401),
+            (ErrorCode::CertificateRevoked, 401),
+            (ErrorCode::PermissionDenied, 403),
+            (ErrorCode::CompanyNotEnrolled, 403),
+            (ErrorCode::CompanyNotRegistered, 403),
+            (ErrorCode::BenchmarkNotFound, 404),
+            (ErrorCode::NotFound, 404),
+            (ErrorCode::WrongPhase { phase: BenchmarkPhase::Setup }, 409),
+            (ErrorCode::Conflict, 409),
+            (ErrorCode::InputMissingVariables { variables: Vec::new() }, 422),
+            (ErrorCode::AlgorithmCycle { cycle: Vec::new() }, 422),
+            (ErrorCode::InvalidAlgorithm, 422),
+            (ErrorCode::InvalidFormat, 422),
+            (ErrorCode::PayloadTooLarge, 413),
+            (ErrorCode::UnsupportedMediaType, 415),
+            (ErrorCode::TooManyRequests { retry_after: 1 }, 429),
+            (ErrorCode::Internal, 500),
+        ];
+        for (code, status) in statuses {
+            assert_eq!(code.status(), status, "{}", code);
+        }
+    }
+
+    #[test]
+    pub fn display_names_the_code() {
+        assert_eq!(msg(ErrorCode::Conflict).to_string(), "[CONFLICT] Failed!");
+        assert_eq!(ErrorCode::AlgorithmCycle { cycle: vec!["a".to_string(), "b".to_string()] }.to_string(), "ALGORITHM_CYCLE (a -> b)");
+    }
+}
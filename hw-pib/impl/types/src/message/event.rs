@@ -0,0 +1,124 @@
+//! Server sent event formats
+//!
+//! Every event of a benchmark is a JSON encoded [`ServerEvent`] in the
+//! `data:` field of the event stream. The `event:` field holds its
+//! [`EventType`] and the `id:` field a number that increases with every
+//! event of the server. Reconnecting clients send the last received id
+//! in the `Last-Event-ID` header and the server replays what they missed.
+//!
+//! Events about one company (e.g. invalid input or its results) are only
+//! sent over the authenticated channels of the company and the analyst.
+//!
+//! Keep-alive messages are comments (`: keep-alive`), thus they never
+//! reach the event handlers of the clients.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::BenchmarkPhase;
+
+use super::response::CompanyID;
+
+/// Type of an event (the SSE `event:` field)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventType {
+    /// Progress of the computation
+    Progress,
+    /// Participation of the companies
+    Enrollment,
+    /// Custom message of the analyst
+    AnalystMessage,
+    /// Phases, algorithms and data of the benchmark
+    Lifecycle,
+    /// Failures that participants have to react to
+    Error,
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventType::Progress => write!(f, "progress"),
+            EventType::Enrollment => write!(f, "enrollment"),
+            EventType::AnalystMessage => write!(f, "analyst-message"),
+            EventType::Lifecycle => write!(f, "lifecycle"),
+            EventType::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Measured spans of the server (sent if `span_events` is configured)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvalMetric {
+    AlgoUpload,
+    AlgoParse,
+    AlgoTopo,
+    CompUpload,
+    CompParse,
+    BenchCompTotal,
+    BenchClust,
+    BenchAgg,
+    BenchAssemble,
+}
+
+/// Events of a benchmark, the `kind` tells them apart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// Step of the computation that is done (`step` of `steps`)
+    Progress { step: u32, steps: u32, message: String },
+    /// All results are assembled and can be retrieved
+    BenchmarkingSuccess,
+    /// Duration (ns) or count of an evaluated operation
+    Evaluation { metric: EvalMetric, value: u128 },
+    /// A company uploaded its input
+    ParticipantEnrolled { active: u64, required: u64 },
+    /// Enough companies uploaded their input (k-anonymity)
+    AllParticipantsEnrolled,
+    /// The analyst removed a company
+    CompanyRevoked { company: CompanyID },
+    /// Custom message of the analyst
+    AnalystMessage { message: String },
+    /// The benchmark moved into another phase
+    PhaseChanged { phase: BenchmarkPhase },
+    /// Modified algorithms await the confirmation of the participants
+    AlgorithmsProposed { hash: String },
+    /// The algorithms that are run on the inputs changed
+    AlgorithmsActivated { hash: String },
+    /// The inputs were zeroized after the results were assembled
+    InputsWiped { companies: usize },
+    /// The sealed results of the company can be retrieved
+    ResultsReady,
+    /// Results that were not downloaded within the retention time got deleted
+    ResultsExpired { company: CompanyID },
+    /// The input of a company lacks variables of the algorithms and was discarded
+    InputInvalid { company: CompanyID, missing: Vec<String> },
+    /// The computation failed, the benchmark returns to the collection
+    ComputationFailed { reason: String },
+}
+
+impl ServerEvent {
+    /// Whether the event is kept for clients that reconnect
+    ///
+    /// Measurements are not, they would push the lifecycle events out.
+    pub fn is_replayed(&self) -> bool {
+        !matches!(self, ServerEvent::Evaluation { .. })
+    }
+
+    pub fn event_type(&self) -> EventType {
+        match self {
+            ServerEvent::Progress { .. } | ServerEvent::BenchmarkingSuccess | ServerEvent::Evaluation { .. } => EventType::Progress,
+            ServerEvent::ParticipantEnrolled { .. } | ServerEvent::AllParticipantsEnrolled | ServerEvent::CompanyRevoked { .. } => EventType::Enrollment,
+            ServerEvent::AnalystMessage { .. } => EventType::AnalystMessage,
+            ServerEvent::PhaseChanged { .. }
+            | ServerEvent::AlgorithmsProposed { .. }
+            | ServerEvent::AlgorithmsActivated { .. }
+            | ServerEvent::InputsWiped { .. }
+            | ServerEvent::ResultsReady
+            | ServerEvent::ResultsExpired { .. } => EventType::Lifecycle,
+            ServerEvent::InputInvalid { .. } | ServerEvent::ComputationFailed { .. } => EventType::Error,
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod request;
 pub mod response;
+pub mod event;
+pub mod error;
 pub mod io;
\ No newline at end of file
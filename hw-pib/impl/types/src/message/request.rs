@@ -6,14 +6,22 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::entity::BenchmarkPhase;
+
 ///
 /// On Instance Verification
 /// 
 
 /// In order to be assured that the server is running in a trusted
 /// environment, all clients can issue a get request to get information
-/// on the enclave that hosts the server. This is done using a GET,
-/// therefore it is not shown here.
+/// on the enclave that hosts the server. The query may hold a nonce
+/// that the server includes in its attestation evidence.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AttestMsg {
+    /// Hex encoded random value of the client (at most 64 bytes)
+    #[serde(default)]
+    pub nonce: String,
+}
 
 
 ///
@@ -27,8 +35,6 @@ use serde::{Deserialize, Serialize};
 ///     - The CA root certificate for accepting incomming
 ///       communication from all clients that have their
 ///       certificate signed by this root certificate.
-///     - The symmetric HMAC key that is encrypted with the
-///       servers public key.
 ///     - The configuratin yaml file that sets up basic config
 ///       for the server (i.e. the k_anonymity, name,...)
 /// 
@@ -43,17 +49,22 @@ use serde::{Deserialize, Serialize};
 ///       and use the service to register multiple times.
 ///     - Thus only the analyst performs registration of clients.
 /// 
-/// 2. The analyst registers companies. He then gets server 
+/// 2. The analyst connects over HTTPS and exchanges the symmetric
+///    HMAC key, which is encrypted to an ephemeral key of the server
+///    (see [`AnalystSetupMsg`]). From now on all his requests carry
+///    an HMAC (see [`request_mac_data`]).
+/// 
+/// 3. The analyst registers companies. He then gets server 
 ///    generated Company-IDs that get passed along to the 
 ///    individual companies (by using their public key to encrypt
 ///    the received ID). Using this scheme each company may only
 ///    register once. (This is a GET-type request, i.e. empty!) 
 /// 
-/// 2. The analyst uploads the Algorithms for KPI computation
+/// 4. The analyst uploads the Algorithms for KPI computation
 ///     - This includes one yaml file containing all computing
 ///       schemes used for computation.
 /// 
-/// 3. The analyst finally starts the benchmarking process by
+/// 5. The analyst finally starts the benchmarking process by
 ///    sending a message to the server in which he specifies 
 ///    which of the provided KPIs should be used for computat 
 /// 
@@ -61,16 +72,22 @@ use serde::{Deserialize, Serialize};
 ///   event messages. These messages are broadcasted to all clients
 ///   that listen on the event channel.
 
-#[derive(Deserialize)]
-/// First message from analyst which holds information
-/// on the ca_certificate
-/// It is accompanied by a yaml and a certificate file 
-/// in a form-data request
+#[derive(Deserialize, Serialize)]
+/// Key exchange of the analyst after the setup
+/// 
+/// The HMAC key is encrypted with AES-256-GCM under a key that is
+/// derived (HKDF-SHA256) from the X25519 agreement of the offered
+/// server key and the ephemeral key of the analyst.
 pub struct AnalystSetupMsg {
+    /// Offered key of the server (hex)
+    pub exchange_key: String,
+    /// Ephemeral public key of the analyst (hex)
+    pub public_key: String,
+    /// Nonce and ciphertext of the HMAC key (hex)
     pub encrypted_analyst_hmac: String,
 }
 
-/// The second request is a GET-Request.
+/// The enrollment of companies is a POST-Request without body.
 
 /// The third message is only a form-data request and thus not
 /// seen here. The format of the config is in file is in 
@@ -91,6 +108,14 @@ pub struct AnalystEventMsg {
     pub event: String,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+/// The analyst moves a benchmark into its next phase. Only the
+/// transitions that are not performed by the server itself are
+/// allowed (i.e. not into computing or results).
+pub struct AnalystPhaseMsg {
+    pub phase: BenchmarkPhase,
+}
+
 
 ///
 /// Company Messages
@@ -106,25 +131,61 @@ pub struct AnalystEventMsg {
 /// communication with the server. This key is for integrity checking.
 /// 
 /// Since every company has its own ID, the HMAC key (that is again 
-/// encrypted with the servers public key) is now bound to its ID. The
+/// encrypted to a key of the server) is now bound to its ID. The
 /// server allows setting the key only once, thus forging messages is
 /// not possible, even if the ID of one company gets public afterwards.
 /// If it gets public before, the company cannot register and thus no 
 /// data is further shared.
 /// 
-/// 1. The company sends the encrypted HMAC key.
+/// 1. The company attests the server and then registers its certificate together with the 
+///    encrypted HMAC key and the key for its results (see [`CompanySetupMsg`]).
+///    From now on all its requests carry an HMAC (see [`request_mac_data`]).
 /// 
 /// 2. The company uploads the data to the server (multipated) 
 /// 
 /// - The company may also register for publicly (meaning only to 
 ///   all participants) dispatched messages from the analyst (GET).
 /// 
-/// 3. The company can request the results (again GET).
+/// 3. The company can request the results (again GET), which are encrypted
+///    to its result key, and confirms their download (DELETE).
 /// 
 /// 
 /// TODO: EVAL MESSAGES FOR DIRECT COMPARISON TO OFFLOADING APPROACH.
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
+/// Registration of the company with its HMAC key (encrypted as for
+/// the [`AnalystSetupMsg`]) and the key that its results are sealed to
 pub struct CompanySetupMsg {
+    /// Offered key of the server (hex)
+    pub exchange_key: String,
+    /// Ephemeral public key of the company (hex)
+    pub public_key: String,
+    /// Nonce and ciphertext of the HMAC key (hex)
     pub encrypted_company_hmac: String,
+    /// X25519 key to which the results are encrypted (hex)
+    pub result_public_key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+/// A company consents to the algorithms with the given hash, i.e.
+/// to a modification that awaits the confirmation of all participants.
+pub struct CompanyConfirmMsg {
+    pub algorithm_hash: String,
+}
+
+/// Data that is authenticated by the HMAC of a request
+/// 
+/// The HMAC (header `X-Teebench-Hmac`) covers the method, the path
+/// with query, the nonce (header `X-Teebench-Counter`) and the body.
+/// The nonce is a counter that has to increase with every request of
+/// a participant, thus recorded requests cannot be replayed.
+pub fn request_mac_data(method: &str, path_and_query: &str, counter: u64, body: &[u8]) -> Vec<u8> {
+    let mut data = request_mac_header(method, path_and_query, counter);
+    data.extend_from_slice(body);
+    data
+}
+
+/// Data of [`request_mac_data`] that precedes the body
+pub fn request_mac_header(method: &str, path_and_query: &str, counter: u64) -> Vec<u8> {
+    format!("{}\n{}\n{}\n", method, path_and_query, counter).into_bytes()
 }
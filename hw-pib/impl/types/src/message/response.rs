@@ -5,6 +5,8 @@
 //! same message types.
 
 
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 use crate::entity::BenchmarkingConfig;
 
@@ -26,21 +28,117 @@ impl<T> RspMsg<T> {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 /// Server status message
 /// This is receivable by any (non) participant
 /// and shows that the enclave is trusted!
 pub struct ServerStatus {
     is_setup: bool,
-    enclave_certificate: String,
-    server_info: BenchmarkingConfig,
+    evidence: AttestationEvidence,
+    benchmarks: HashMap<BenchmarkID, BenchmarkingConfig>,
 }
 
 impl ServerStatus {
-    pub fn new(is_setup: bool, enclave_certificate: String, server_info: BenchmarkingConfig) -> Self {
-        ServerStatus {is_setup,enclave_certificate, server_info}
+    pub fn new(is_setup: bool, evidence: AttestationEvidence, benchmarks: HashMap<BenchmarkID, BenchmarkingConfig>) -> Self {
+        ServerStatus {is_setup, evidence, benchmarks}
+    }
+
+    pub fn is_setup(&self) -> bool {
+        self.is_setup
+    }
+
+    pub fn evidence(&self) -> &AttestationEvidence {
+        &self.evidence
+    }
+
+    pub fn benchmarks(&self) -> &HashMap<BenchmarkID, BenchmarkingConfig> {
+        &self.benchmarks
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Attestation evidence of the server (quote-like report)
+///
+/// The report binds the measurement of the running code to the TLS
+/// certificate of the server and the algorithms of all benchmarks.
+/// All binary values are hex encoded.
+pub struct AttestationEvidence {
+    /// Attester that produced the evidence (e.g. `simulated`)
+    pub attester: String,
+    /// Measurement of the running server code
+    pub measurement: String,
+    /// SHA-256 of the DER encoded TLS certificate (holds the public key)
+    pub tls_certificate_hash: String,
+    /// SHA-256 of the algorithms of all benchmarks that have algorithms
+    pub algorithm_hashes: BTreeMap<BenchmarkID, String>,
+    /// Nonce of the verifier for freshness
+    pub nonce: String,
+    /// Key to verify the signature (DER encoded RSA public key)
+    pub attestation_key: String,
+    /// RSA PKCS#1 SHA-256 signature over the [`report_data`](Self::report_data)
+    pub signature: String,
+}
+
+impl AttestationEvidence {
+    /// Canonical encoding of all reported values that is signed by the attester
+    pub fn report_data(&self) -> Vec<u8> {
+        let mut data = format!("teebench-attestation-v1\n{}\n{}\n{}\n{}\n", self.attester, self.measurement, self.tls_certificate_hash, self.nonce);
+        for (benchmark_id, algorithm_hash) in &self.algorithm_hashes {
+            data += &format!("{}={}\n", benchmark_id, algorithm_hash);
+        }
+        data.into_bytes()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Algorithms of a benchmark as shown to the companies
+pub struct AlgorithmsMsg<T> {
+    /// Algorithms that are run on the inputs
+    pub active: Option<AlgorithmInfo<T>>,
+    /// Modification that awaits the confirmation of all participants
+    pub pending: Option<AlgorithmInfo<T>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// One version of the algorithms with its hash (as in the attestation evidence)
+pub struct AlgorithmInfo<T> {
+    pub version: u64,
+    pub hash: String,
+    pub algorithm: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Ephemeral X25519 key of the server (hex) to which clients encrypt their HMAC key.
+/// Every offered key can only be used for one exchange.
+pub struct KeyExchangeOffer {
+    pub exchange_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Results of a company encrypted to its registered result key
+///
+/// The AES-256-GCM key is derived (HKDF-SHA256) from the X25519 agreement
+/// of the ephemeral `public_key` of the server and the company's key.
+pub struct SealedResults {
+    /// Ephemeral public key of the server (hex)
+    pub public_key: String,
+    /// Nonce and ciphertext of the JSON encoded output (hex)
+    pub sealed_output: String,
+}
+
+/// Associated data of the sealed results of a company
+///
+/// Binds the results to the benchmark and the company, thus sealed
+/// results cannot be handed out for another benchmark or company.
+pub fn result_sealing_aad(benchmark_id: BenchmarkID, company_id: CompanyID) -> Vec<u8> {
+    format!("{}\n{}", benchmark_id, company_id).into_bytes()
+}
+
 /// Company IDs are UUID
 pub type CompanyID = u128;
+
+/// Benchmark IDs are assigned in order of creation
+pub type BenchmarkID = u64;
+
+/// The benchmark that is created during the setup of the server
+pub const DEFAULT_BENCHMARK_ID: BenchmarkID = 0;